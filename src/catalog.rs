@@ -0,0 +1,318 @@
+//! The system catalog, which records the tables of a database in a tree of its own (like SQLite's
+//! `sqlite_schema`), so the `Schema` the planner uses survives the database being closed.
+//!
+//! Each row is a record of the object's type, its name and the SQL that creates it:
+//!   ['table', 'users', 'CREATE TABLE users (id integer, name)']
+//! The SQL is parsed again to rebuild the schema when the database is opened. Every table's rows
+//! are in the tree with the table's name.
+
+use std::io::Read;
+
+use crate::frontend::{ast, parse};
+use crate::planner::schema::{Column, Schema, Table};
+use crate::record::{self, Record};
+use crate::scalarvalue::ScalarValue;
+use crate::storage::{BTree, PageError};
+
+/// The tree the catalog is kept in, no table can have this name
+pub const CATALOG_TREE: &str = "schema";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CatalogError {
+    /// A table, or a tree, with this name already exists
+    TableExists(String),
+    DuplicateColumn { table: String, column: String },
+    /// A row of the catalog, with this key, which can't be read back as a table
+    Corrupt(u64),
+    /// The catalog has used up every rowid, so no more tables can be recorded
+    Full,
+    /// The database can't be written to, as SQLite databases can't
+    ReadOnly,
+    /// A page of the catalog can't be read
+    Page(PageError),
+}
+
+impl std::fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CatalogError::TableExists(name) => write!(f, "table {name} already exists"),
+            CatalogError::DuplicateColumn { table, column } => {
+                write!(f, "table {table} has more than one column named {column}")
+            }
+            CatalogError::Corrupt(key) => write!(f, "catalog entry {key} is corrupt"),
+            CatalogError::Full => write!(f, "the catalog has no rowids left"),
+            CatalogError::ReadOnly => write!(f, "the database is read-only"),
+            CatalogError::Page(e) => write!(f, "the catalog can't be read: {e}"),
+        }
+    }
+}
+
+/// Read the schema of every table in the catalog, a database without one has no tables.
+/// A SQLite database's tables are those of its own catalog, `sqlite_schema`.
+pub fn load(btree: &BTree) -> Result<Schema, CatalogError> {
+    if let Some(tables) = btree.sqlite_tables() {
+        let tables = tables
+            .into_iter()
+            .map(|(name, columns)| Table {
+                name,
+                columns: columns.into_iter().map(|name| Column { name }).collect(),
+            })
+            .collect();
+        return Ok(Schema { tables });
+    }
+
+    let mut schema = Schema { tables: vec![] };
+    let Some(mut handle) = btree.open(CATALOG_TREE) else {
+        return Ok(schema);
+    };
+
+    let mut cursor = handle.open_readonly();
+    cursor.first().map_err(CatalogError::Page)?;
+    while let Some(mut entry) = cursor.get_entry().map_err(CatalogError::Page)? {
+        let key = entry.key();
+        let mut row = vec![];
+        entry.read_to_end(&mut row).map_err(|_| CatalogError::Corrupt(key))?;
+
+        schema.tables.push(read_entry(&row).ok_or(CatalogError::Corrupt(key))?);
+        cursor.next().map_err(CatalogError::Page)?;
+    }
+
+    Ok(schema)
+}
+
+/// The table a catalog row creates
+fn read_entry(row: &[u8]) -> Option<Table> {
+    let record = Record::parse(row).ok()?;
+    let (Ok(ScalarValue::String(tipe)), Ok(ScalarValue::String(sql))) = (record.column(0), record.column(2)) else {
+        return None;
+    };
+    if tipe != "table" {
+        return None;
+    }
+
+    match parse(&sql).ok()? {
+        ast::Statement::CreateTable(create) => Some(table(&create)),
+        _ => None,
+    }
+}
+
+/// Create the table's tree and record it in the catalog, adding it to `schema`
+pub fn create_table(
+    btree: &mut BTree,
+    schema: &mut Schema,
+    create: &ast::CreateTableStatement,
+) -> Result<(), CatalogError> {
+    if btree.is_read_only() {
+        return Err(CatalogError::ReadOnly);
+    }
+    let name = &create.name;
+    if name == CATALOG_TREE || schema.get_table(name).is_some() || btree.open(name).is_some() {
+        return Err(CatalogError::TableExists(name.clone()));
+    }
+    for (idx, column) in create.columns.iter().enumerate() {
+        if create.columns[..idx].iter().any(|earlier| earlier.name == column.name) {
+            return Err(CatalogError::DuplicateColumn {
+                table: name.clone(),
+                column: column.name.clone(),
+            });
+        }
+    }
+
+    // A tree without its catalog row would keep the name from ever being used, so they are made together
+    let transaction = btree.begin();
+    if btree.open(CATALOG_TREE).is_none() {
+        btree.create_tree(CATALOG_TREE);
+    }
+    btree.create_tree(name);
+
+    let entry = [
+        ScalarValue::String("table".to_string()),
+        ScalarValue::String(name.clone()),
+        ScalarValue::String(sql(create)),
+    ];
+    let mut handle = btree.open(CATALOG_TREE).unwrap();
+    handle
+        .open_readwrite()
+        .insert_next_rowid(record::encode(&entry))
+        .map_err(|_| CatalogError::Full)?;
+    transaction.commit();
+
+    schema.tables.push(table(create));
+    Ok(())
+}
+
+fn table(create: &ast::CreateTableStatement) -> Table {
+    Table {
+        name: create.name.clone(),
+        columns: create
+            .columns
+            .iter()
+            .map(|column| Column {
+                name: column.name.clone(),
+            })
+            .collect(),
+    }
+}
+
+/// The SQL recorded for a table, which the parser lowercases and so needs no quoting
+fn sql(create: &ast::CreateTableStatement) -> String {
+    let columns: Vec<String> = create
+        .columns
+        .iter()
+        .map(|column| match &column.data_type {
+            Some(data_type) => format!("{} {}", column.name, data_type),
+            None => column.name.clone(),
+        })
+        .collect();
+
+    format!("CREATE TABLE {} ({})", create.name, columns.join(", "))
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::NamedTempFile;
+
+    use super::*;
+    use crate::storage::{MemoryFile, PagerOptions};
+
+    fn create(btree: &mut BTree, schema: &mut Schema, sql: &str) -> Result<(), CatalogError> {
+        let ast::Statement::CreateTable(create) = parse(sql).unwrap() else {
+            panic!("expected a CREATE TABLE");
+        };
+        create_table(btree, schema, &create)
+    }
+
+    fn table_columns(schema: &Schema) -> Vec<(String, Vec<String>)> {
+        schema
+            .tables
+            .iter()
+            .map(|table| {
+                let columns = table.columns.iter().map(|column| column.name.clone()).collect();
+                (table.name.clone(), columns)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tables_are_loaded_when_reopened() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let mut btree = BTree::new(path);
+        let mut schema = load(&btree).unwrap();
+        assert!(schema.tables.is_empty());
+
+        create(&mut btree, &mut schema, "CREATE TABLE users (id integer, name text, age)").unwrap();
+        create(&mut btree, &mut schema, "create table Orders (id, user_id INTEGER)").unwrap();
+        assert!(btree.open("users").is_some());
+        assert!(btree.open("orders").is_some());
+
+        let created = table_columns(&schema);
+        assert_eq!(
+            created,
+            [
+                ("users".to_string(), vec!["id".to_string(), "name".to_string(), "age".to_string()]),
+                ("orders".to_string(), vec!["id".to_string(), "user_id".to_string()]),
+            ]
+        );
+        drop(btree);
+
+        let btree = BTree::new(path);
+        assert_eq!(table_columns(&load(&btree).unwrap()), created);
+
+        // The SQL kept is the statement as it was parsed
+        let mut handle = btree.open(CATALOG_TREE).unwrap();
+        let mut cursor = handle.open_readonly();
+        cursor.first().unwrap();
+        let mut row = vec![];
+        cursor.get_entry().unwrap().unwrap().read_to_end(&mut row).unwrap();
+        let record = Record::parse(&row).unwrap();
+        assert_eq!(
+            record.column(2).unwrap(),
+            ScalarValue::String("CREATE TABLE users (id integer, name text, age)".to_string())
+        );
+    }
+
+    #[test]
+    fn create_table_errors() {
+        let file = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(file.path().to_str().unwrap());
+        let mut schema = load(&btree).unwrap();
+
+        create(&mut btree, &mut schema, "CREATE TABLE users (id)").unwrap();
+        assert_eq!(
+            create(&mut btree, &mut schema, "CREATE TABLE users (name)"),
+            Err(CatalogError::TableExists("users".to_string()))
+        );
+        assert_eq!(
+            create(&mut btree, &mut schema, "CREATE TABLE schema (name)"),
+            Err(CatalogError::TableExists("schema".to_string()))
+        );
+
+        // A tree made without the catalog, which no table can share
+        btree.create_tree("raw");
+        assert_eq!(
+            create(&mut btree, &mut schema, "CREATE TABLE raw (name)"),
+            Err(CatalogError::TableExists("raw".to_string()))
+        );
+
+        assert_eq!(
+            create(&mut btree, &mut schema, "CREATE TABLE t (a, b, a)"),
+            Err(CatalogError::DuplicateColumn {
+                table: "t".to_string(),
+                column: "a".to_string(),
+            })
+        );
+        assert!(btree.open("t").is_none());
+
+        assert_eq!(table_columns(&load(&btree).unwrap()), table_columns(&schema));
+        assert_eq!(schema.tables.len(), 1);
+    }
+
+    #[test]
+    fn interrupted_create_leaves_no_tree() {
+        let file = MemoryFile::named("db");
+        let journal = file.sharing_log("journal");
+        let open = || {
+            BTree::with_files(Box::new(file.clone()), Box::new(journal.clone()), PagerOptions::default()).unwrap()
+        };
+
+        let mut btree = open();
+        let mut schema = load(&btree).unwrap();
+        create(&mut btree, &mut schema, "CREATE TABLE users (id)").unwrap();
+
+        // Crash after the first couple of writes, which without a transaction give the tree a root page
+        // before the catalog has a row for it
+        file.crash_after_writes(Some(2));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            create(&mut btree, &mut schema, "CREATE TABLE orders (id)")
+        }));
+        assert!(result.is_err());
+        drop(btree);
+        file.crash_after_writes(None);
+
+        let mut btree = open();
+        assert!(btree.open("orders").is_none());
+        let mut schema = load(&btree).unwrap();
+        assert_eq!(schema.tables.len(), 1);
+        create(&mut btree, &mut schema, "CREATE TABLE orders (id)").unwrap();
+        assert_eq!(table_columns(&load(&btree).unwrap()), table_columns(&schema));
+    }
+
+    #[test]
+    fn corrupt_entries_are_reported() {
+        let file = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(file.path().to_str().unwrap());
+        btree.create_tree(CATALOG_TREE);
+
+        let entry = [
+            ScalarValue::String("table".to_string()),
+            ScalarValue::String("t".to_string()),
+            ScalarValue::String("SELECT a FROM t".to_string()),
+        ];
+        let mut handle = btree.open(CATALOG_TREE).unwrap();
+        handle.open_readwrite().insert(7, record::encode(&entry));
+
+        assert_eq!(load(&btree).unwrap_err(), CatalogError::Corrupt(7));
+    }
+}
@@ -71,12 +71,30 @@ impl BytecodeEmitter {
         self.operations.push(Operation::GoToIfFalse(target, reg));
     }
 
+    /// Emit a GoToIfTrue instruction: jump to label if register is true.
+    pub fn emit_goto_if_true(&mut self, label: Label, reg: Reg) {
+        let target = self.resolve_label(label);
+        self.operations.push(Operation::GoToIfTrue(target, reg));
+    }
+
     /// Emit a GoToIfEqualValue instruction: jump to label if lhs == rhs.
     pub fn emit_goto_if_equal(&mut self, label: Label, lhs: Reg, rhs: Reg) {
         let target = self.resolve_label(label);
         self.operations.push(Operation::GoToIfEqualValue(target, lhs, rhs));
     }
 
+    /// Emit a GoToIfDistinct instruction: jump to label unless lhs and rhs are equal, or both NULL.
+    pub fn emit_goto_if_distinct(&mut self, label: Label, lhs: Reg, rhs: Reg) {
+        let target = self.resolve_label(label);
+        self.operations.push(Operation::GoToIfDistinct(target, lhs, rhs));
+    }
+
+    /// Emit a Once instruction: jump to label every time but the first.
+    pub fn emit_once(&mut self, label: Label, reg: Reg) {
+        let target = self.resolve_label(label);
+        self.operations.push(Operation::Once(target, reg));
+    }
+
     /// Finalize the bytecode by resolving all jump targets.
     /// Returns the final list of operations.
     /// Panics if any label was never bound.
@@ -85,17 +103,8 @@ impl BytecodeEmitter {
 
         // Resolve all unresolved jump targets
         for op in &mut self.operations {
-            match op {
-                Operation::GoTo(ref mut target) => {
-                    *target = resolve_target(target, label_positions);
-                }
-                Operation::GoToIfFalse(ref mut target, _) => {
-                    *target = resolve_target(target, label_positions);
-                }
-                Operation::GoToIfEqualValue(ref mut target, _, _) => {
-                    *target = resolve_target(target, label_positions);
-                }
-                _ => {}
+            if let Some(target) = op.jump_target_mut() {
+                *target = resolve_target(target, label_positions);
             }
         }
         self.operations
@@ -123,7 +132,7 @@ impl Default for BytecodeEmitter {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::engine::scalarvalue::ScalarValue;
+    use crate::scalarvalue::ScalarValue;
 
     #[test]
     fn test_emit_sequence() {
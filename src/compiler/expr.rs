@@ -1,14 +1,17 @@
 use crate::engine::program::{Operation, Reg};
-use crate::engine::scalarvalue::ScalarValue;
-use crate::planner::{BinaryOp, ColumnRef, Literal, PlanExpr, UnaryOp};
+use crate::scalarvalue::ScalarValue;
+use crate::planner::{BinaryOp, ColumnRef, Literal, LogicalPlan, PlanExpr, SubqueryKind, UnaryOp};
 
 use super::emitter::BytecodeEmitter;
 use super::registers::RegisterAllocator;
+use super::{codegen, CodegenContext, NodeContinuation};
 
 /// Context for expression compilation, providing access to shared state.
 pub struct ExprContext<'a> {
     pub emitter: &'a mut BytecodeEmitter,
     pub registers: &'a mut RegisterAllocator,
+    /// The rows of the queries a subquery is in, innermost last, see `ColumnRef::Outer`
+    pub outer_regs: &'a [Vec<Reg>],
 }
 
 /// Compile a PlanExpr to bytecode.
@@ -29,12 +32,18 @@ pub fn compile_expr(
         PlanExpr::Literal(lit) => {
             compile_literal(lit, ctx)
         }
+        PlanExpr::BinaryOp { op: op @ (BinaryOp::And | BinaryOp::Or), left, right } => {
+            compile_logical_op(op, left, right, input_regs, ctx)
+        }
         PlanExpr::BinaryOp { op, left, right } => {
             compile_binary_op(op, left, right, input_regs, ctx)
         }
         PlanExpr::UnaryOp { op, operand } => {
             compile_unary_op(op, operand, input_regs, ctx)
         }
+        PlanExpr::Subquery { kind, plan, correlated } => {
+            compile_subquery(kind, plan, *correlated, input_regs, ctx)
+        }
     }
 }
 
@@ -51,6 +60,12 @@ fn compile_column_ref(
             ctx.emitter.emit(Operation::CopyValue(dest, src));
             dest
         }
+        ColumnRef::Outer { depth, column_idx } => {
+            let src = ctx.outer_regs[ctx.outer_regs.len() - depth][*column_idx];
+            let dest = ctx.registers.alloc();
+            ctx.emitter.emit(Operation::CopyValue(dest, src));
+            dest
+        }
     }
 }
 
@@ -61,10 +76,7 @@ fn compile_literal(lit: &Literal, ctx: &mut ExprContext) -> Reg {
         Literal::Float(f) => ScalarValue::Floating(*f),
         Literal::Bool(b) => ScalarValue::Boolean(*b),
         Literal::String(s) => ScalarValue::String(s.clone()),
-        Literal::Null => {
-            // TODO: Add proper NULL support to ScalarValue and VM
-            panic!("NULL literals not yet supported")
-        }
+        Literal::Null => ScalarValue::Null,
     };
     ctx.emitter.emit(Operation::StoreValue(dest, scalar));
     dest
@@ -97,24 +109,62 @@ fn compile_binary_op(
         BinaryOp::LessThan => Operation::LessThanValue(dest, left_reg, right_reg),
         BinaryOp::LessThanOrEqual => Operation::LessThanOrEqualValue(dest, left_reg, right_reg),
 
-        // Logical
-        BinaryOp::And => Operation::AndValue(dest, left_reg, right_reg),
-        BinaryOp::Or => Operation::OrValue(dest, left_reg, right_reg),
-
-        // TODO: Add bitwise operations to VM (LeftShiftValue, RightShiftValue, etc.)
-        BinaryOp::LeftShift
-        | BinaryOp::RightShift
-        | BinaryOp::BitOr
-        | BinaryOp::BitXor
-        | BinaryOp::BitAnd => {
-            panic!("Bitwise operations not yet implemented")
-        }
+        BinaryOp::And | BinaryOp::Or => unreachable!("AND and OR are compiled by compile_logical_op"),
+
+        // Bitwise
+        BinaryOp::LeftShift => Operation::LeftShiftValue(dest, left_reg, right_reg),
+        BinaryOp::RightShift => Operation::RightShiftValue(dest, left_reg, right_reg),
+        BinaryOp::BitOr => Operation::BitOrValue(dest, left_reg, right_reg),
+        BinaryOp::BitXor => Operation::BitXorValue(dest, left_reg, right_reg),
+        BinaryOp::BitAnd => Operation::BitAndValue(dest, left_reg, right_reg),
     };
 
     ctx.emitter.emit(operation);
     dest
 }
 
+/// AND and OR only evaluate their right side when the left side doesn't decide the result.
+///
+/// ```text
+///   left = <left>
+///   CopyValue(dest, left)
+///   AND: NotValue(skip, left); GoToIfTrue(done, skip)   // false AND x = false
+///   OR:  GoToIfTrue(done, left)                         // true OR x = true
+///   right = <right>
+///   AndValue/OrValue(dest, left, right)                 // left is true or NULL (AND), false or NULL (OR)
+/// done:
+/// ```
+fn compile_logical_op(
+    op: &BinaryOp,
+    left: &PlanExpr,
+    right: &PlanExpr,
+    input_regs: &[Reg],
+    ctx: &mut ExprContext,
+) -> Reg {
+    let left_reg = compile_expr(left, input_regs, ctx);
+    let dest = ctx.registers.alloc();
+    let done = ctx.emitter.create_label();
+
+    ctx.emitter.emit(Operation::CopyValue(dest, left_reg));
+    if *op == BinaryOp::And {
+        let skip = ctx.registers.alloc();
+        ctx.emitter.emit(Operation::NotValue(skip, left_reg));
+        ctx.emitter.emit_goto_if_true(done, skip);
+    } else {
+        ctx.emitter.emit_goto_if_true(done, left_reg);
+    }
+
+    let right_reg = compile_expr(right, input_regs, ctx);
+    let operation = match op {
+        BinaryOp::And => Operation::AndValue(dest, left_reg, right_reg),
+        _ => Operation::OrValue(dest, left_reg, right_reg),
+    };
+    ctx.emitter.emit(operation);
+    ctx.emitter.bind_label(done);
+
+    dest
+}
+
 fn compile_unary_op(
     op: &UnaryOp,
     operand: &PlanExpr,
@@ -127,6 +177,9 @@ fn compile_unary_op(
     let operation = match op {
         UnaryOp::Negate => Operation::NegateValue(dest, operand_reg),
         UnaryOp::Not => Operation::NotValue(dest, operand_reg),
+        UnaryOp::BitNot => Operation::BitNotValue(dest, operand_reg),
+        UnaryOp::IsNull => Operation::IsNullValue(dest, operand_reg),
+        UnaryOp::IsNotNull => Operation::IsNotNullValue(dest, operand_reg),
         UnaryOp::Plus => {
             // Plus is a no-op, just copy the value
             Operation::CopyValue(dest, operand_reg)
@@ -137,9 +190,160 @@ fn compile_unary_op(
     dest
 }
 
+/// A subquery runs its plan in place, starting it over every time the expression is evaluated,
+/// so a correlated one sees the current row. Otherwise the result is the same every time, so the
+/// plan only runs the first time, and the result is kept for the times after.
+///
+/// IN puts the first column of every row in a hash table, then looks the value up in it.
+///
+/// ```text
+///   Once(finish, ran)                         (uncorrelated only)
+///   Scalar: dest = NULL  Exists: dest = false  In: HashTableOpen(table); has_rows = false; has_null = false
+///   GoTo(start)
+/// body:     <plan body with our handlers>
+/// on_tuple: Scalar: CopyValue(dest, row[0]); GoTo(finish)
+///           Exists: dest = true; GoTo(finish)
+///           In:     HashTableInsert(table, [row[0]], []); has_rows = true
+///                   IsNullValue(null, row[0]); GoToIfFalse(plan.next, null); has_null = true; GoTo(plan.next)
+/// start:    <plan init>; GoTo(body)
+/// finish:   In only:
+///           value = <value>; dest = false; GoToIfFalse(done, has_rows)
+///           dest = NULL; IsNullValue(null, value); GoToIfTrue(done, null)
+///           HashTableFind(table, [value]); CanReadHashTable(dest, table); GoToIfTrue(done, dest)
+///           GoToIfFalse(done, has_null); dest = NULL
+/// done:
+/// ```
+fn compile_subquery(
+    kind: &SubqueryKind,
+    plan: &LogicalPlan,
+    correlated: bool,
+    input_regs: &[Reg],
+    ctx: &mut ExprContext,
+) -> Reg {
+    let dest = ctx.registers.alloc();
+    let start = ctx.emitter.create_label();
+    let on_tuple = ctx.emitter.create_label();
+    let finish = ctx.emitter.create_label();
+
+    if !correlated {
+        let ran = ctx.registers.alloc();
+        ctx.emitter.emit_once(finish, ran);
+    }
+
+    // The hash table IN looks the value up in, whether it has rows, and whether any of them is NULL
+    let in_regs = matches!(kind, SubqueryKind::In(_))
+        .then(|| (ctx.registers.alloc(), ctx.registers.alloc(), ctx.registers.alloc()));
+    match in_regs {
+        Some((table, has_rows, has_null)) => {
+            ctx.emitter.emit(Operation::HashTableOpen(table));
+            ctx.emitter.emit(Operation::StoreValue(has_rows, ScalarValue::Boolean(false)));
+            ctx.emitter.emit(Operation::StoreValue(has_null, ScalarValue::Boolean(false)));
+        }
+        None => {
+            let empty = match kind {
+                SubqueryKind::Exists => ScalarValue::Boolean(false),
+                _ => ScalarValue::Null,
+            };
+            ctx.emitter.emit(Operation::StoreValue(dest, empty));
+        }
+    }
+    ctx.emitter.emit_goto(start);
+
+    // Compile the plan in a context of its own, sharing our emitter and registers, where the row
+    // the expression is evaluated over is the innermost outer row
+    let mut outer_regs = ctx.outer_regs.to_vec();
+    outer_regs.push(input_regs.to_vec());
+    let mut plan_ctx = CodegenContext {
+        init_emitter: BytecodeEmitter::new(),
+        body_emitter: std::mem::take(ctx.emitter),
+        registers: std::mem::take(ctx.registers),
+        outer_regs,
+        scan_cursors: Vec::new(),
+    };
+    let body = plan_ctx.body_emitter.create_label();
+    plan_ctx.body_emitter.bind_label(body);
+    let cont = NodeContinuation {
+        on_tuple,
+        on_done: finish,
+    };
+    let output = codegen(plan, &cont, &mut plan_ctx);
+    let CodegenContext {
+        init_emitter,
+        body_emitter,
+        registers,
+        ..
+    } = plan_ctx;
+    *ctx.emitter = body_emitter;
+    *ctx.registers = registers;
+
+    // on_tuple: take what we need from the row
+    ctx.emitter.bind_label(on_tuple);
+    let column = output.output_regs.first().copied();
+    match (kind, in_regs) {
+        (SubqueryKind::Scalar, _) => {
+            let column = column.expect("a scalar subquery to have a column");
+            ctx.emitter.emit(Operation::CopyValue(dest, column));
+            ctx.emitter.emit_goto(finish);
+        }
+        (SubqueryKind::Exists, _) => {
+            ctx.emitter.emit(Operation::StoreValue(dest, ScalarValue::Boolean(true)));
+            ctx.emitter.emit_goto(finish);
+        }
+        (SubqueryKind::In(_), Some((table, has_rows, has_null))) => {
+            let column = column.expect("an IN subquery to have a column");
+            let is_null = ctx.registers.alloc();
+            ctx.emitter.emit(Operation::HashTableInsert(table, vec![column], vec![]));
+            ctx.emitter.emit(Operation::StoreValue(has_rows, ScalarValue::Boolean(true)));
+            ctx.emitter.emit(Operation::IsNullValue(is_null, column));
+            ctx.emitter.emit_goto_if_false(output.next, is_null);
+            ctx.emitter.emit(Operation::StoreValue(has_null, ScalarValue::Boolean(true)));
+            ctx.emitter.emit_goto(output.next);
+        }
+        (SubqueryKind::In(_), None) => unreachable!("IN to have its registers"),
+    }
+
+    // start: run the plan's init code here, so it starts over every time
+    ctx.emitter.bind_label(start);
+    for mut op in init_emitter.finalize() {
+        // Init code is straight-line, a jump would point into init_emitter's code
+        debug_assert!(op.jump_target_mut().is_none(), "init code to have no jumps");
+        ctx.emitter.emit(op);
+    }
+    ctx.emitter.emit_goto(body);
+
+    // finish: IN looks up the value, now that every row is in the table
+    ctx.emitter.bind_label(finish);
+    if let (SubqueryKind::In(value), Some((table, has_rows, has_null))) = (kind, in_regs) {
+        let value_reg = compile_expr(value, input_regs, ctx);
+        let is_null = ctx.registers.alloc();
+        let done = ctx.emitter.create_label();
+
+        // No rows, so nothing to be equal to
+        ctx.emitter.emit(Operation::StoreValue(dest, ScalarValue::Boolean(false)));
+        ctx.emitter.emit_goto_if_false(done, has_rows);
+
+        // A NULL value is unknown to equal any row
+        ctx.emitter.emit(Operation::StoreValue(dest, ScalarValue::Null));
+        ctx.emitter.emit(Operation::IsNullValue(is_null, value_reg));
+        ctx.emitter.emit_goto_if_true(done, is_null);
+
+        ctx.emitter.emit(Operation::HashTableFind(table, vec![value_reg]));
+        ctx.emitter.emit(Operation::CanReadHashTable(dest, table));
+        ctx.emitter.emit_goto_if_true(done, dest);
+
+        // Not found, but it might equal a NULL row
+        ctx.emitter.emit_goto_if_false(done, has_null);
+        ctx.emitter.emit(Operation::StoreValue(dest, ScalarValue::Null));
+        ctx.emitter.bind_label(done);
+    }
+
+    dest
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test::{b, i, s, TestDb};
 
     #[test]
     fn test_compile_integer_literal() {
@@ -151,6 +355,7 @@ mod tests {
             let mut ctx = ExprContext {
                 emitter: &mut emitter,
                 registers: &mut registers,
+                outer_regs: &[],
             };
             compile_expr(&expr, &[], &mut ctx)
         };
@@ -176,6 +381,7 @@ mod tests {
             let mut ctx = ExprContext {
                 emitter: &mut emitter,
                 registers: &mut registers,
+                outer_regs: &[],
             };
             compile_expr(&expr, &[], &mut ctx)
         };
@@ -198,6 +404,7 @@ mod tests {
             let mut ctx = ExprContext {
                 emitter: &mut emitter,
                 registers: &mut registers,
+                outer_regs: &[],
             };
             compile_expr(&expr, &input_regs, &mut ctx)
         };
@@ -229,6 +436,7 @@ mod tests {
             let mut ctx = ExprContext {
                 emitter: &mut emitter,
                 registers: &mut registers,
+                outer_regs: &[],
             };
             compile_expr(&expr, &[], &mut ctx)
         };
@@ -259,6 +467,7 @@ mod tests {
             let mut ctx = ExprContext {
                 emitter: &mut emitter,
                 registers: &mut registers,
+                outer_regs: &[],
             };
             compile_expr(&expr, &[], &mut ctx)
         };
@@ -288,6 +497,7 @@ mod tests {
             let mut ctx = ExprContext {
                 emitter: &mut emitter,
                 registers: &mut registers,
+                outer_regs: &[],
             };
             compile_expr(&expr, &[], &mut ctx)
         };
@@ -324,6 +534,7 @@ mod tests {
             let mut ctx = ExprContext {
                 emitter: &mut emitter,
                 registers: &mut registers,
+                outer_regs: &[],
             };
             compile_expr(&expr, &input_regs, &mut ctx)
         };
@@ -355,15 +566,224 @@ mod tests {
             let mut ctx = ExprContext {
                 emitter: &mut emitter,
                 registers: &mut registers,
+                outer_regs: &[],
             };
             compile_expr(&expr, &[], &mut ctx);
         }
 
+        // StoreValue(true), CopyValue, NotValue, GoToIfTrue, StoreValue(false), AndValue
         let ops = emitter.finalize();
-        assert_eq!(ops.len(), 3);
-        match &ops[2] {
+        assert_eq!(ops.len(), 6);
+        match &ops[3] {
+            Operation::GoToIfTrue(target, _) => assert_eq!(target.unwrap_resolved(), 6),
+            _ => panic!("Expected GoToIfTrue"),
+        }
+        match &ops[5] {
             Operation::AndValue(_, _, _) => {}
             _ => panic!("Expected AndValue"),
         }
     }
+
+    #[test]
+    fn test_compile_null() {
+        let mut emitter = BytecodeEmitter::new();
+        let mut registers = RegisterAllocator::new();
+
+        // NULL IS NOT NULL
+        let expr = PlanExpr::UnaryOp {
+            op: UnaryOp::IsNotNull,
+            operand: Box::new(PlanExpr::Literal(Literal::Null)),
+        };
+
+        {
+            let mut ctx = ExprContext {
+                emitter: &mut emitter,
+                registers: &mut registers,
+                outer_regs: &[],
+            };
+            compile_expr(&expr, &[], &mut ctx);
+        }
+
+        let ops = emitter.finalize();
+        assert_eq!(ops.len(), 2);
+        assert!(matches!(ops[0], Operation::StoreValue(_, ScalarValue::Null)));
+        assert!(matches!(ops[1], Operation::IsNotNullValue(_, _)));
+    }
+
+    #[test]
+    fn test_bitwise_sql() {
+        use crate::engine::ErrorKind;
+        use crate::scalarvalue::ValueError;
+
+        let mut db = TestDb::default();
+        db.create_table(
+            "t",
+            &["a", "b"],
+            &[vec![i(12), i(2)], vec![i(-8), i(-1)], vec![ScalarValue::Null, i(1)]],
+        );
+
+        let rows = db.query("select a << b, a >> b, a & b, a | b, a ^ b, ~a from t").unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![i(48), i(3), i(0), i(14), i(14), i(-13)],
+                vec![i(-4), i(-16), i(-8), i(-1), i(7), i(7)],
+                vec![ScalarValue::Null; 6],
+            ]
+        );
+
+        let rows = db.query("select a from t where (a & 4) = 4").unwrap();
+        assert_eq!(rows, vec![vec![i(12)]]);
+
+        db.create_table("f", &["x"], &[vec![ScalarValue::Floating(1.5)]]);
+        let error = db.query("select x | 1 from f").unwrap_err();
+        assert!(matches!(error.operation, Some(Operation::BitOrValue(..))));
+        assert!(matches!(error.kind, ErrorKind::ValueError(ValueError::TypeMismatch("|", _))));
+    }
+
+    #[test]
+    fn test_short_circuit_sql() {
+        let mut db = TestDb::default();
+        db.create_table("t", &["a"], &[vec![i(0)], vec![i(5)], vec![i(20)], vec![ScalarValue::Null]]);
+
+        // 10 / 0 would fail, but is never evaluated
+        let rows = db.query("select a from t where a != 0 and 10 / a > 1").unwrap();
+        assert_eq!(rows, vec![vec![i(5)]]);
+        let rows = db.query("select a from t where a = 0 or 10 / a > 1").unwrap();
+        assert_eq!(rows, vec![vec![i(0)], vec![i(5)]]);
+
+        // Without the guard the division is reached
+        let error = db.query("select a from t where 10 / a > 1 and a != 0").unwrap_err();
+        assert!(matches!(error.operation, Some(Operation::DivideValue(..))));
+    }
+
+    #[test]
+    fn test_numeric_comparison_sql() {
+        let mut db = TestDb::default();
+        db.create_table("t", &["a"], &[vec![i(11)], vec![ScalarValue::Floating(11.0)], vec![i(12)]]);
+
+        // Integers equal the floats with the same value, whichever side they are on
+        let rows = db.query("select a = 11.0, a != 11.0, a = 11, 11 = a from t").unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![b(true), b(false), b(true), b(true)],
+                vec![b(true), b(false), b(true), b(true)],
+                vec![b(false), b(true), b(false), b(false)],
+            ]
+        );
+        let rows = db.query("select a from t where a <= 11.0 and a >= 11.0").unwrap();
+        assert_eq!(rows, db.query("select a from t where a = 11.0").unwrap());
+
+        // Floats compare exactly
+        let rows = db.query("select 0.1 + 0.2 = 0.3, 0.1 + 0.2 != 0.3, 0.5 + 0.25 = 0.75 from t limit 1").unwrap();
+        assert_eq!(rows, vec![vec![b(false), b(true), b(true)]]);
+    }
+
+    #[test]
+    fn test_subquery_sql() {
+        let db = TestDb::users_and_orders();
+
+        let rows = db.query("select name from users where id = (select max(user_id) from orders)").unwrap();
+        assert_eq!(rows, vec![vec![s("bob")]]);
+
+        // No rows is NULL
+        let rows = db.query("select (select total from orders where total > 100) from users limit 1").unwrap();
+        assert_eq!(rows, vec![vec![ScalarValue::Null]]);
+
+        let rows = db.query("select name from users where id in (select user_id from orders)").unwrap();
+        assert_eq!(rows, vec![vec![s("ann")], vec![s("bob")]]);
+
+        // cid isn't in a list with a NULL, which might be anything
+        let rows = db
+            .query("select id in (select user_id from orders), id not in (select user_id from orders where user_id is not null) from users")
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![b(true), b(false)],
+                vec![b(true), b(false)],
+                vec![ScalarValue::Null, b(true)],
+            ]
+        );
+        let rows = db.query("select name from users where id not in (select user_id from orders)").unwrap();
+        assert!(rows.is_empty());
+
+        // NULL is in nothing, but unknown to be in anything else
+        let rows = db
+            .query("select null in (select total from orders where total > 100), null in (select total from orders) from users limit 1")
+            .unwrap();
+        assert_eq!(rows, vec![vec![b(false), ScalarValue::Null]]);
+
+        let rows = db.query("select exists (select 1 from orders where total > 8), exists (select 1 from orders where total > 9) from users limit 1").unwrap();
+        assert_eq!(rows, vec![vec![b(true), b(false)]]);
+
+        // The subquery runs once, though the hash join looks up its result for every user
+        let rows = db.query("select name, total from users join orders on total = (select max(total) from orders)").unwrap();
+        assert_eq!(rows, vec![vec![s("ann"), i(9)], vec![s("bob"), i(9)], vec![s("cid"), i(9)]]);
+    }
+
+    #[test]
+    fn test_correlated_subquery_sql() {
+        let db = TestDb::users_and_orders();
+
+        let rows = db.query("select name, (select sum(total) from orders where user_id = users.id) from users").unwrap();
+        assert_eq!(rows, vec![vec![s("ann"), i(7)], vec![s("bob"), i(14)], vec![s("cid"), ScalarValue::Null]]);
+
+        let rows = db.query("select name from users where exists (select 1 from orders where user_id = users.id)").unwrap();
+        assert_eq!(rows, vec![vec![s("ann")], vec![s("bob")]]);
+        let rows = db.query("select name from users where not exists (select 1 from orders where user_id = users.id)").unwrap();
+        assert_eq!(rows, vec![vec![s("cid")]]);
+
+        let rows = db.query("select name from users where 9 in (select total from orders where user_id = users.id)").unwrap();
+        assert_eq!(rows, vec![vec![s("bob")]]);
+
+        // Two levels in, a subquery can read the rows of both queries it is in
+        let rows = db
+            .query(
+                "select name from users where exists (select 1 from orders where user_id = users.id \
+                 and exists (select 1 from orders as o where o.total > orders.total and o.user_id = users.id))",
+            )
+            .unwrap();
+        assert_eq!(rows, vec![vec![s("bob")]]);
+
+        // A subquery in a grouped query reads the groups
+        let rows = db
+            .query("select user_id, (select name from users where id = user_id) from orders where user_id is not null group by user_id")
+            .unwrap();
+        assert_eq!(rows, vec![vec![i(1), s("ann")], vec![i(2), s("bob")]]);
+        let rows = db
+            .query("select name from users where id in (select user_id from orders group by user_id having count(*) > 1)")
+            .unwrap();
+        assert_eq!(rows, vec![vec![s("bob")]]);
+    }
+
+    #[test]
+    fn test_logical_sql_with_null() {
+        let n = ScalarValue::Null;
+        let mut db = TestDb::default();
+        db.create_table(
+            "t",
+            &["x", "y"],
+            &[
+                vec![b(true), n.clone()],
+                vec![b(false), n.clone()],
+                vec![n.clone(), b(true)],
+                vec![n.clone(), b(false)],
+                vec![n.clone(), n.clone()],
+            ],
+        );
+
+        let rows = db.query("select x and y, x or y from t").unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![n.clone(), b(true)],
+                vec![b(false), n.clone()],
+                vec![n.clone(), b(true)],
+                vec![b(false), n.clone()],
+                vec![n.clone(), n.clone()],
+            ]
+        );
+    }
 }
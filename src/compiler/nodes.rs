@@ -1,6 +1,9 @@
+use crate::engine::aggregate;
 use crate::engine::program::{JumpTarget, Label, MoveOperation, Operation, Reg};
-use crate::engine::scalarvalue::ScalarValue;
-use crate::planner::{Literal, LogicalPlan, PlanExpr};
+use crate::scalarvalue::ScalarValue;
+use crate::planner::{
+    AggregateExpr, AggregateFunction, JoinKind, JoinStrategy, Literal, LogicalPlan, PlanExpr, SortKey, ROWID_COLUMN,
+};
 
 use super::{compile_expr, BytecodeEmitter, ExprContext, RegisterAllocator};
 
@@ -11,10 +14,18 @@ fn literal_to_scalar(lit: &Literal) -> ScalarValue {
         Literal::Float(f) => ScalarValue::Floating(*f),
         Literal::String(s) => ScalarValue::String(s.clone()),
         Literal::Bool(b) => ScalarValue::Boolean(*b),
-        Literal::Null => {
-            // TODO: Add proper NULL support to ScalarValue
-            panic!("NULL literals not yet supported")
-        }
+        Literal::Null => ScalarValue::Null,
+    }
+}
+
+/// Convert a planner AggregateFunction to the engine's.
+fn aggregate_function(function: AggregateFunction) -> aggregate::AggregateFunction {
+    match function {
+        AggregateFunction::Count => aggregate::AggregateFunction::Count,
+        AggregateFunction::Sum => aggregate::AggregateFunction::Sum,
+        AggregateFunction::Avg => aggregate::AggregateFunction::Avg,
+        AggregateFunction::Min => aggregate::AggregateFunction::Min,
+        AggregateFunction::Max => aggregate::AggregateFunction::Max,
     }
 }
 
@@ -27,6 +38,10 @@ pub struct CodegenContext {
     pub body_emitter: BytecodeEmitter,
     /// Register allocator shared across all nodes
     pub registers: RegisterAllocator,
+    /// The rows of the queries a subquery is in, innermost last, see `ColumnRef::Outer`
+    pub outer_regs: Vec<Vec<Reg>>,
+    /// The cursor of each table scanned, for nodes that write to the row a scan emitted
+    pub scan_cursors: Vec<(String, Reg)>,
 }
 
 impl CodegenContext {
@@ -35,6 +50,8 @@ impl CodegenContext {
             init_emitter: BytecodeEmitter::new(),
             body_emitter: BytecodeEmitter::new(),
             registers: RegisterAllocator::new(),
+            outer_regs: Vec::new(),
+            scan_cursors: Vec::new(),
         }
     }
 
@@ -64,26 +81,15 @@ impl CodegenContext {
 }
 
 /// Adjust jump targets in an operation by adding an offset.
-fn adjust_jump_targets(op: Operation, offset: usize) -> Operation {
-    match op {
-        Operation::GoTo(JumpTarget::Resolved(addr)) => {
-            Operation::GoTo(JumpTarget::Resolved(addr + offset))
-        }
-        Operation::GoToIfFalse(JumpTarget::Resolved(addr), reg) => {
-            Operation::GoToIfFalse(JumpTarget::Resolved(addr + offset), reg)
-        }
-        Operation::GoToIfEqualValue(JumpTarget::Resolved(addr), lhs, rhs) => {
-            Operation::GoToIfEqualValue(JumpTarget::Resolved(addr + offset), lhs, rhs)
-        }
+fn adjust_jump_targets(mut op: Operation, offset: usize) -> Operation {
+    match op.jump_target_mut() {
+        Some(JumpTarget::Resolved(addr)) => *addr += offset,
         // Unresolved labels should have been resolved by finalize()
-        Operation::GoTo(JumpTarget::Unresolved(_))
-        | Operation::GoToIfFalse(JumpTarget::Unresolved(_), _)
-        | Operation::GoToIfEqualValue(JumpTarget::Unresolved(_), _, _) => {
-            panic!("Unresolved jump target after finalize")
-        }
+        Some(JumpTarget::Unresolved(_)) => panic!("Unresolved jump target after finalize"),
         // All other operations pass through unchanged
-        other => other,
+        None => {}
     }
+    op
 }
 
 /// Continuation labels that a node needs to know where to jump
@@ -110,22 +116,24 @@ pub struct NodeOutput {
 ///   Open(cursor, table)
 ///   MoveCursor(cursor, First)
 ///
-/// BODY (body_emitter, next_label = CHECK):
+/// BODY (body_emitter, next_label = ADVANCE):
 ///   CHECK:   CanReadCursor(flag, cursor); GoToIfFalse(on_done, flag)
-///   READ:    ReadCursor(output_regs, cursor)
-///   ADVANCE: MoveCursor(cursor, Next)
+///   READ:    ReadCursor(columns -> output_regs, cursor)
 ///   EMIT:    GoTo(on_tuple)
+///   ADVANCE: MoveCursor(cursor, Next); GoTo(CHECK)
 /// ```
+///
+/// The cursor stays on the emitted row until the next is asked for, so it can be written through.
 pub fn codegen_scan(
     table: &str,
-    num_columns: usize,
+    columns: &[usize],
     cont: &NodeContinuation,
     ctx: &mut CodegenContext,
 ) -> NodeOutput {
     // Allocate registers for cursor, flag, and output columns
     let cursor_reg = ctx.registers.alloc();
     let flag_reg = ctx.registers.alloc();
-    let output_regs = ctx.registers.alloc_block(num_columns);
+    let output_regs = ctx.registers.alloc_block(columns.len());
 
     // INIT (init_emitter): Open cursor and move to first row
     ctx.init_emitter
@@ -141,23 +149,44 @@ pub fn codegen_scan(
         .emit(Operation::CanReadCursor(flag_reg, cursor_reg));
     ctx.body_emitter.emit_goto_if_false(cont.on_done, flag_reg);
 
-    // READ: Read current row into output registers
-    ctx.body_emitter
-        .emit(Operation::ReadCursor(output_regs.clone(), cursor_reg));
+    // READ: Read the scanned columns of the current row into output registers
+    emit_read_row(cursor_reg, columns, &output_regs, &mut ctx.body_emitter);
+
+    // EMIT: Jump to tuple handler
+    ctx.body_emitter.emit_goto(cont.on_tuple);
 
-    // ADVANCE: Move cursor to next row (makes next row "pending")
+    // ADVANCE: Move cursor to next row, then check it
+    let advance_label = ctx.body_emitter.create_label();
+    ctx.body_emitter.bind_label(advance_label);
     ctx.body_emitter
         .emit(Operation::MoveCursor(cursor_reg, MoveOperation::Next));
+    ctx.body_emitter.emit_goto(check_label);
 
-    // EMIT: Jump to tuple handler
-    ctx.body_emitter.emit_goto(cont.on_tuple);
+    ctx.scan_cursors.push((table.to_string(), cursor_reg));
 
     NodeOutput {
-        next: check_label,
+        next: advance_label,
         output_regs,
     }
 }
 
+/// Read the columns of the row at the cursor into registers, ROWID_COLUMN reading its rowid
+fn emit_read_row(cursor_reg: Reg, columns: &[usize], output_regs: &[Reg], emitter: &mut BytecodeEmitter) {
+    let reads = columns
+        .iter()
+        .copied()
+        .zip(output_regs.iter().copied())
+        .filter(|(column, _)| *column != ROWID_COLUMN)
+        .collect();
+    emitter.emit(Operation::ReadCursor(reads, cursor_reg));
+
+    for (column, reg) in columns.iter().zip(output_regs) {
+        if *column == ROWID_COLUMN {
+            emitter.emit(Operation::ReadRowid(*reg, cursor_reg));
+        }
+    }
+}
+
 /// Generate bytecode for a Count node.
 ///
 /// Count consumes all rows from its child and outputs a single row
@@ -217,6 +246,755 @@ pub fn codegen_count(
     }
 }
 
+/// Generate bytecode for an Insert node.
+///
+/// Insert consumes all rows from its child, adding each to the table, and outputs none.
+///
+/// ```text
+/// INIT (init_emitter):
+///   Open(cursor, table)
+///   <child init>
+///
+/// BODY (body_emitter):
+///   <child body with our handlers>
+///   child_on_tuple: MakeRecord(record, child row)
+///                   InsertNextRowid(cursor, record); GoTo(child.next)
+///   child's on_done is ours, as there is nothing to emit
+/// ```
+pub fn codegen_insert(
+    table: &str,
+    input: &LogicalPlan,
+    cont: &NodeContinuation,
+    ctx: &mut CodegenContext,
+) -> NodeOutput {
+    let cursor_reg = ctx.registers.alloc();
+    let record_reg = ctx.registers.alloc();
+
+    // INIT: open the table to insert into
+    ctx.init_emitter
+        .emit(Operation::Open(cursor_reg, table.to_string()));
+
+    let child_on_tuple = ctx.body_emitter.create_label();
+    let child_cont = NodeContinuation {
+        on_tuple: child_on_tuple,
+        on_done: cont.on_done,
+    };
+
+    let child_output = codegen(input, &child_cont, ctx);
+
+    // child_on_tuple: insert the row, get next from child
+    ctx.body_emitter.bind_label(child_on_tuple);
+    ctx.body_emitter
+        .emit(Operation::MakeRecord(record_reg, child_output.output_regs));
+    ctx.body_emitter
+        .emit(Operation::InsertNextRowid(cursor_reg, record_reg));
+    ctx.body_emitter.emit_goto(child_output.next);
+
+    // No tuple is ever emitted, so next is never asked for
+    NodeOutput {
+        next: child_output.next,
+        output_regs: vec![],
+    }
+}
+
+/// Generate bytecode for an Update node.
+///
+/// Update consumes all rows from its child, writing each over the row the scan of the table is
+/// on, and outputs none. The scan's cursor is still on the row it emitted.
+///
+/// When the child is a Materialize each row ends with the rowid of the row to write over, which is
+/// sought with a cursor of Update's own, as the scan is over before the first.
+///
+/// ```text
+/// INIT (init_emitter, materialized only):
+///   Open(cursor, table)
+///
+/// BODY (body_emitter):
+///   <child body with our handlers>
+///   child_on_tuple: SeekRowid(found, cursor, rowid); GoToIfFalse(child.next, found) (materialized only)
+///                   MakeRecord(record, child row)
+///                   ReplaceRecord(cursor, record); GoTo(child.next)
+///   child's on_done is ours, as there is nothing to emit
+/// ```
+pub fn codegen_update(
+    table: &str,
+    input: &LogicalPlan,
+    cont: &NodeContinuation,
+    ctx: &mut CodegenContext,
+) -> NodeOutput {
+    let record_reg = ctx.registers.alloc();
+
+    let child_on_tuple = ctx.body_emitter.create_label();
+    let child_cont = NodeContinuation {
+        on_tuple: child_on_tuple,
+        on_done: cont.on_done,
+    };
+
+    let mut child_output = codegen(input, &child_cont, ctx);
+
+    // child_on_tuple: write the row back, get next from child
+    ctx.body_emitter.bind_label(child_on_tuple);
+    let cursor_reg = match input {
+        LogicalPlan::Materialize { .. } => {
+            let rowid_reg = child_output.output_regs.pop().expect("a materialized row to end with its rowid");
+            emit_seek_materialized(table, rowid_reg, child_output.next, ctx)
+        }
+        _ => scan_cursor(table, ctx),
+    };
+    ctx.body_emitter
+        .emit(Operation::MakeRecord(record_reg, child_output.output_regs));
+    ctx.body_emitter
+        .emit(Operation::ReplaceRecord(cursor_reg, record_reg));
+    ctx.body_emitter.emit_goto(child_output.next);
+
+    // No tuple is ever emitted, so next is never asked for
+    NodeOutput {
+        next: child_output.next,
+        output_regs: vec![],
+    }
+}
+
+/// Generate bytecode for a Delete node.
+///
+/// Delete consumes all rows from its child, removing the row the scan of the table is on, and
+/// outputs none. The cursor is left where the scan's next moves on to the row after.
+///
+/// When the child is a Materialize its rows are rowids, and the scan is over before the first, so
+/// the row with each rowid is sought with a cursor of Delete's own.
+///
+/// ```text
+/// INIT (init_emitter, materialized only):
+///   Open(cursor, table)
+///
+/// BODY (body_emitter):
+///   <child body with our handlers>
+///   child_on_tuple: SeekRowid(found, cursor, rowid); GoToIfFalse(child.next, found) (materialized only)
+///                   DeleteRecord(cursor); GoTo(child.next)
+///   child's on_done is ours, as there is nothing to emit
+/// ```
+pub fn codegen_delete(
+    table: &str,
+    input: &LogicalPlan,
+    cont: &NodeContinuation,
+    ctx: &mut CodegenContext,
+) -> NodeOutput {
+    let child_on_tuple = ctx.body_emitter.create_label();
+    let child_cont = NodeContinuation {
+        on_tuple: child_on_tuple,
+        on_done: cont.on_done,
+    };
+
+    let child_output = codegen(input, &child_cont, ctx);
+
+    // child_on_tuple: remove the row, get next from child
+    ctx.body_emitter.bind_label(child_on_tuple);
+    let cursor_reg = match input {
+        LogicalPlan::Materialize { .. } => {
+            emit_seek_materialized(table, child_output.output_regs[0], child_output.next, ctx)
+        }
+        _ => scan_cursor(table, ctx),
+    };
+    ctx.body_emitter.emit(Operation::DeleteRecord(cursor_reg));
+    ctx.body_emitter.emit_goto(child_output.next);
+
+    // No tuple is ever emitted, so next is never asked for
+    NodeOutput {
+        next: child_output.next,
+        output_regs: vec![],
+    }
+}
+
+/// Open a cursor on the table, and move it to the row with the rowid, going to `next` without one.
+/// An Update or Delete of materialized rows writes through it instead of the scan's cursor.
+fn emit_seek_materialized(table: &str, rowid_reg: Reg, next: Label, ctx: &mut CodegenContext) -> Reg {
+    let cursor_reg = ctx.registers.alloc();
+    let found_reg = ctx.registers.alloc();
+
+    ctx.init_emitter
+        .emit(Operation::Open(cursor_reg, table.to_string()));
+
+    ctx.body_emitter
+        .emit(Operation::SeekRowid(found_reg, cursor_reg, rowid_reg));
+    ctx.body_emitter.emit_goto_if_false(next, found_reg);
+
+    cursor_reg
+}
+
+/// The cursor of the scan of a table, which an Update or Delete writes to once it is compiled
+fn scan_cursor(table: &str, ctx: &CodegenContext) -> Reg {
+    ctx.scan_cursors
+        .iter()
+        .find(|(scanned, _)| scanned == table)
+        .map(|(_, cursor_reg)| *cursor_reg)
+        .expect("the input of an Update or Delete to scan its table")
+}
+
+/// Generate bytecode for an Aggregate node.
+///
+/// Without groups every input row is added to the accumulators as it arrives, and a single row is
+/// emitted once the child is done, like Count.
+///
+/// With groups the rows are sorted by their groups first, each row going into a sorter as its
+/// group values followed by the aggregate arguments. Rows of a group are then next to each other,
+/// so a group ends when the next row's group values differ.
+///
+/// ```text
+/// INIT (init_emitter):
+///   SorterOpen(sorter, [asc for each group])
+///
+/// BODY (body_emitter):
+///   <child body with our handlers>
+///   child_on_tuple: <compile groups and arguments>
+///                   SorterInsert(sorter, group_regs + arg_regs); GoTo(child.next)
+///   child_on_done:  SorterSort(sorter); CanReadSorter(more, sorter); GoToIfFalse(on_done, more)
+///   GROUP:          ReadSorter(groups -> output group_regs, sorter); AggregateReset(acc) for each
+///   STEP:           ReadSorter(arguments -> arg_regs, sorter); AggregateStep(acc, arg) for each
+///                   SorterNext(sorter); CanReadSorter(more, sorter); GoToIfFalse(FINISH, more)
+///                   ReadSorter(groups -> peek_regs, sorter)
+///                   GoToIfDistinct(FINISH, group, peek) for each group
+///                   GoTo(STEP)
+///   FINISH:         AggregateFinal(result, acc) for each; GoTo(on_tuple)
+///   aggregate_next: GoToIfFalse(on_done, more); GoTo(GROUP)
+/// ```
+pub fn codegen_aggregate(
+    group_by: &[PlanExpr],
+    aggregates: &[AggregateExpr],
+    input: &LogicalPlan,
+    cont: &NodeContinuation,
+    ctx: &mut CodegenContext,
+) -> NodeOutput {
+    let accumulators = ctx.registers.alloc_block(aggregates.len());
+    let results = ctx.registers.alloc_block(aggregates.len());
+
+    // Create labels for child's continuations
+    let child_on_tuple = ctx.body_emitter.create_label();
+    let child_on_done = ctx.body_emitter.create_label();
+    let child_cont = NodeContinuation {
+        on_tuple: child_on_tuple,
+        on_done: child_on_done,
+    };
+
+    let reset = |emitter: &mut BytecodeEmitter| {
+        for (accumulator, aggregate) in accumulators.iter().zip(aggregates) {
+            emitter.emit(Operation::AggregateReset(*accumulator, aggregate_function(aggregate.function)));
+        }
+    };
+    // Emit the aggregates once the accumulators hold a whole group
+    let finish = |emitter: &mut BytecodeEmitter| {
+        for (result, accumulator) in results.iter().zip(&accumulators) {
+            emitter.emit(Operation::AggregateFinal(*result, *accumulator));
+        }
+        emitter.emit_goto(cont.on_tuple);
+    };
+
+    if group_by.is_empty() {
+        // INIT: start the accumulators empty
+        reset(&mut ctx.init_emitter);
+
+        let child_output = codegen(input, &child_cont, ctx);
+
+        // child_on_tuple: add the row to every accumulator, get next from child
+        ctx.body_emitter.bind_label(child_on_tuple);
+        for (accumulator, aggregate) in accumulators.iter().zip(aggregates) {
+            let arg = aggregate.arg.as_ref().map(|arg| {
+                let mut expr_ctx = ExprContext {
+                    emitter: &mut ctx.body_emitter,
+                    registers: &mut ctx.registers,
+                    outer_regs: &ctx.outer_regs,
+                };
+                compile_expr(arg, &child_output.output_regs, &mut expr_ctx)
+            });
+            ctx.body_emitter.emit(Operation::AggregateStep(*accumulator, arg));
+        }
+        ctx.body_emitter.emit_goto(child_output.next);
+
+        // child_on_done: the single row is ready
+        ctx.body_emitter.bind_label(child_on_done);
+        finish(&mut ctx.body_emitter);
+
+        // aggregate_next: after yielding once, we're done
+        let aggregate_next = ctx.body_emitter.create_label();
+        ctx.body_emitter.bind_label(aggregate_next);
+        ctx.body_emitter.emit_goto(cont.on_done);
+
+        return NodeOutput {
+            next: aggregate_next,
+            output_regs: results,
+        };
+    }
+
+    let sorter_reg = ctx.registers.alloc();
+    let more_reg = ctx.registers.alloc();
+    let group_regs = ctx.registers.alloc_block(group_by.len());
+    let peek_regs = ctx.registers.alloc_block(group_by.len());
+
+    // INIT: open the sorter, every group sorts ascending
+    ctx.init_emitter
+        .emit(Operation::SorterOpen(sorter_reg, vec![false; group_by.len()]));
+
+    let child_output = codegen(input, &child_cont, ctx);
+
+    // child_on_tuple: compute the groups and arguments, insert them into the sorter
+    ctx.body_emitter.bind_label(child_on_tuple);
+    let arg_exprs: Vec<&PlanExpr> = group_by
+        .iter()
+        .chain(aggregates.iter().filter_map(|aggregate| aggregate.arg.as_ref()))
+        .collect();
+    let row_regs = arg_exprs
+        .iter()
+        .map(|expr| {
+            let mut expr_ctx = ExprContext {
+                emitter: &mut ctx.body_emitter,
+                registers: &mut ctx.registers,
+                outer_regs: &ctx.outer_regs,
+            };
+            compile_expr(expr, &child_output.output_regs, &mut expr_ctx)
+        })
+        .collect();
+    ctx.body_emitter.emit(Operation::SorterInsert(sorter_reg, row_regs));
+    ctx.body_emitter.emit_goto(child_output.next);
+
+    // child_on_done: sort the rows, there are no groups if there are no rows
+    ctx.body_emitter.bind_label(child_on_done);
+    ctx.body_emitter.emit(Operation::SorterSort(sorter_reg));
+    ctx.body_emitter.emit(Operation::CanReadSorter(more_reg, sorter_reg));
+    ctx.body_emitter.emit_goto_if_false(cont.on_done, more_reg);
+
+    // GROUP: the current row starts a new group
+    let group_label = ctx.body_emitter.create_label();
+    ctx.body_emitter.bind_label(group_label);
+    let group_reads: Vec<(usize, Reg)> = group_regs.iter().copied().enumerate().collect();
+    ctx.body_emitter.emit(Operation::ReadSorter(group_reads, sorter_reg));
+    reset(&mut ctx.body_emitter);
+
+    // STEP: add the current row to the group, and move on
+    let step_label = ctx.body_emitter.create_label();
+    let finish_label = ctx.body_emitter.create_label();
+    ctx.body_emitter.bind_label(step_label);
+    let mut column = group_by.len();
+    let mut arg_reads = vec![];
+    let args: Vec<Option<Reg>> = aggregates
+        .iter()
+        .map(|aggregate| {
+            aggregate.arg.as_ref().map(|_| {
+                let reg = ctx.registers.alloc();
+                arg_reads.push((column, reg));
+                column += 1;
+                reg
+            })
+        })
+        .collect();
+    if !arg_reads.is_empty() {
+        ctx.body_emitter.emit(Operation::ReadSorter(arg_reads, sorter_reg));
+    }
+    for (accumulator, arg) in accumulators.iter().zip(args) {
+        ctx.body_emitter.emit(Operation::AggregateStep(*accumulator, arg));
+    }
+    ctx.body_emitter.emit(Operation::SorterNext(sorter_reg));
+    ctx.body_emitter.emit(Operation::CanReadSorter(more_reg, sorter_reg));
+    ctx.body_emitter.emit_goto_if_false(finish_label, more_reg);
+
+    // The group goes on while the next row has the same groups
+    let peek_reads: Vec<(usize, Reg)> = peek_regs.iter().copied().enumerate().collect();
+    ctx.body_emitter.emit(Operation::ReadSorter(peek_reads, sorter_reg));
+    for (group, peek) in group_regs.iter().zip(&peek_regs) {
+        ctx.body_emitter.emit_goto_if_distinct(finish_label, *group, *peek);
+    }
+    ctx.body_emitter.emit_goto(step_label);
+
+    // FINISH: the group is complete
+    ctx.body_emitter.bind_label(finish_label);
+    finish(&mut ctx.body_emitter);
+
+    // aggregate_next: start the next group, if there are rows left
+    let aggregate_next = ctx.body_emitter.create_label();
+    ctx.body_emitter.bind_label(aggregate_next);
+    ctx.body_emitter.emit_goto_if_false(cont.on_done, more_reg);
+    ctx.body_emitter.emit_goto(group_label);
+
+    let mut output_regs = group_regs;
+    output_regs.extend(results.iter().copied());
+
+    NodeOutput {
+        next: aggregate_next,
+        output_regs,
+    }
+}
+
+/// Generate bytecode for a Join node, finding the right rows for each left row as the strategy says.
+///
+/// A LEFT join remembers whether the left row matched, and emits it once with NULL right columns if not.
+pub fn codegen_join(
+    left: &LogicalPlan,
+    right: &LogicalPlan,
+    kind: JoinKind,
+    condition: Option<&PlanExpr>,
+    strategy: &JoinStrategy,
+    cont: &NodeContinuation,
+    ctx: &mut CodegenContext,
+) -> NodeOutput {
+    let join = Join {
+        left,
+        right,
+        kind,
+        condition,
+    };
+    match strategy {
+        JoinStrategy::NestedLoop => codegen_nested_loop_join(join, cont, ctx),
+        JoinStrategy::RowidSeek { key } => codegen_rowid_join(join, key, cont, ctx),
+        JoinStrategy::Hash { left_keys, right_keys } => codegen_hash_join(join, left_keys, right_keys, cont, ctx),
+    }
+}
+
+/// The parts of a Join node every strategy uses
+#[derive(Clone, Copy)]
+struct Join<'a> {
+    left: &'a LogicalPlan,
+    right: &'a LogicalPlan,
+    kind: JoinKind,
+    condition: Option<&'a PlanExpr>,
+}
+
+/// Join as a nested loop.
+///
+/// The right input is run from the start for every left row. Its init code is emitted where a
+/// left row arrives instead of in init_emitter, so running it again restarts the right input.
+/// The right input is entered at its first op, as its `next` only moves on from a row it emitted.
+///
+/// ```text
+/// BODY (body_emitter):
+///   <left body with our handlers>
+///   right_start:    <right body with our handlers>
+///   left_on_tuple:  matched = false; padded = false (LEFT only); <right init>; GoTo(right_start)
+///   right_on_tuple: <compile condition>; GoToIfFalse(right.next, condition)
+///                   matched = true (LEFT only); GoTo(on_tuple)
+///   right_on_done:  INNER: GoTo(left.next)
+///                   LEFT:  GoToIfTrue(left.next, matched)
+///                          StoreValue(right output_regs, NULL); padded = true; GoTo(on_tuple)
+///   join_next:      GoToIfTrue(left.next, padded); GoTo(right.next)   (LEFT only, INNER uses right.next)
+/// ```
+fn codegen_nested_loop_join(join: Join, cont: &NodeContinuation, ctx: &mut CodegenContext) -> NodeOutput {
+    let Join {
+        left,
+        right,
+        kind,
+        condition,
+    } = join;
+    let matched_reg = ctx.registers.alloc();
+    let padded_reg = ctx.registers.alloc();
+
+    // Create labels for the children's continuations
+    let left_on_tuple = ctx.body_emitter.create_label();
+    let left_cont = NodeContinuation {
+        on_tuple: left_on_tuple,
+        on_done: cont.on_done,
+    };
+    let right_on_tuple = ctx.body_emitter.create_label();
+    let right_on_done = ctx.body_emitter.create_label();
+    let right_cont = NodeContinuation {
+        on_tuple: right_on_tuple,
+        on_done: right_on_done,
+    };
+
+    // Compile children, keeping the right child's init code apart
+    let left_output = codegen(left, &left_cont, ctx);
+    let outer_init = std::mem::take(&mut ctx.init_emitter);
+    let right_start = ctx.body_emitter.create_label();
+    ctx.body_emitter.bind_label(right_start);
+    let right_output = codegen(right, &right_cont, ctx);
+    let right_init = std::mem::replace(&mut ctx.init_emitter, outer_init).finalize();
+
+    let mut output_regs = left_output.output_regs.clone();
+    output_regs.extend(right_output.output_regs.iter().copied());
+
+    // left_on_tuple: start the right input over
+    ctx.body_emitter.bind_label(left_on_tuple);
+    if kind == JoinKind::Left {
+        ctx.body_emitter
+            .emit(Operation::StoreValue(matched_reg, ScalarValue::Boolean(false)));
+        ctx.body_emitter
+            .emit(Operation::StoreValue(padded_reg, ScalarValue::Boolean(false)));
+    }
+    for mut op in right_init {
+        // Init code is straight-line, a jump would point into init_emitter's code
+        debug_assert!(op.jump_target_mut().is_none(), "init code to have no jumps");
+        ctx.body_emitter.emit(op);
+    }
+    ctx.body_emitter.emit_goto(right_start);
+
+    // right_on_tuple: emit the pair if it meets the condition
+    ctx.body_emitter.bind_label(right_on_tuple);
+    emit_join_condition(condition, &output_regs, right_output.next, ctx);
+    if kind == JoinKind::Left {
+        ctx.body_emitter
+            .emit(Operation::StoreValue(matched_reg, ScalarValue::Boolean(true)));
+    }
+    ctx.body_emitter.emit_goto(cont.on_tuple);
+
+    // right_on_done: move on to the next left row, unless it has to be emitted unmatched
+    ctx.body_emitter.bind_label(right_on_done);
+    let next = match kind {
+        JoinKind::Inner => {
+            ctx.body_emitter.emit_goto(left_output.next);
+            right_output.next
+        }
+        JoinKind::Left => {
+            ctx.body_emitter.emit_goto_if_true(left_output.next, matched_reg);
+            for reg in &right_output.output_regs {
+                ctx.body_emitter.emit(Operation::StoreValue(*reg, ScalarValue::Null));
+            }
+            ctx.body_emitter
+                .emit(Operation::StoreValue(padded_reg, ScalarValue::Boolean(true)));
+            ctx.body_emitter.emit_goto(cont.on_tuple);
+
+            // join_next: the right input is done once the unmatched row was emitted
+            let join_next = ctx.body_emitter.create_label();
+            ctx.body_emitter.bind_label(join_next);
+            ctx.body_emitter.emit_goto_if_true(left_output.next, padded_reg);
+            ctx.body_emitter.emit_goto(right_output.next);
+            join_next
+        }
+    };
+
+    NodeOutput { next, output_regs }
+}
+
+/// Join by seeking the right table to the row whose rowid is the key, there is at most one.
+///
+/// ```text
+/// INIT (init_emitter):
+///   Open(cursor, table)
+///
+/// BODY (body_emitter):
+///   <left body with our handlers>
+///   left_on_tuple: matched = false (LEFT only); <compile key>
+///                  SeekRowid(found, cursor, key); GoToIfFalse(UNMATCHED, found)
+///                  ReadCursor(columns -> right_regs, cursor)
+///                  <compile condition>; GoToIfFalse(UNMATCHED, condition)
+///                  matched = true (LEFT only); GoTo(on_tuple)
+///   UNMATCHED:     see emit_join_unmatched
+/// ```
+fn codegen_rowid_join(join: Join, key: &PlanExpr, cont: &NodeContinuation, ctx: &mut CodegenContext) -> NodeOutput {
+    let Join {
+        left,
+        right,
+        kind,
+        condition,
+    } = join;
+    let LogicalPlan::Scan { table, columns } = right else {
+        panic!("a rowid seek to be into a scanned table");
+    };
+    let cursor_reg = ctx.registers.alloc();
+    let found_reg = ctx.registers.alloc();
+    let matched_reg = ctx.registers.alloc();
+    let right_regs = ctx.registers.alloc_block(columns.len());
+
+    // INIT: open the right table, it is only ever sought
+    ctx.init_emitter
+        .emit(Operation::Open(cursor_reg, table.clone()));
+
+    let left_on_tuple = ctx.body_emitter.create_label();
+    let left_cont = NodeContinuation {
+        on_tuple: left_on_tuple,
+        on_done: cont.on_done,
+    };
+    let left_output = codegen(left, &left_cont, ctx);
+
+    let mut output_regs = left_output.output_regs.clone();
+    output_regs.extend(right_regs.iter().copied());
+
+    // left_on_tuple: seek the row with the key, and emit the pair if it meets the condition
+    let unmatched = ctx.body_emitter.create_label();
+    ctx.body_emitter.bind_label(left_on_tuple);
+    if kind == JoinKind::Left {
+        ctx.body_emitter
+            .emit(Operation::StoreValue(matched_reg, ScalarValue::Boolean(false)));
+    }
+    let mut expr_ctx = ExprContext {
+        emitter: &mut ctx.body_emitter,
+        registers: &mut ctx.registers,
+        outer_regs: &ctx.outer_regs,
+    };
+    let key_reg = compile_expr(key, &left_output.output_regs, &mut expr_ctx);
+    ctx.body_emitter
+        .emit(Operation::SeekRowid(found_reg, cursor_reg, key_reg));
+    ctx.body_emitter.emit_goto_if_false(unmatched, found_reg);
+    emit_read_row(cursor_reg, columns, &right_regs, &mut ctx.body_emitter);
+    emit_join_condition(condition, &output_regs, unmatched, ctx);
+    if kind == JoinKind::Left {
+        ctx.body_emitter
+            .emit(Operation::StoreValue(matched_reg, ScalarValue::Boolean(true)));
+    }
+    ctx.body_emitter.emit_goto(cont.on_tuple);
+
+    // UNMATCHED: there is no other row, so this is also where the next tuple is asked for
+    ctx.body_emitter.bind_label(unmatched);
+    emit_join_unmatched(kind, matched_reg, &right_regs, left_output.next, cont, ctx);
+
+    NodeOutput {
+        next: unmatched,
+        output_regs,
+    }
+}
+
+/// Join by putting every right row into a hash table under its keys, then looking up each
+/// left row's keys.
+///
+/// The right input is compiled first, so it runs through once before the first left row.
+///
+/// ```text
+/// INIT (init_emitter):
+///   HashTableOpen(table)
+///
+/// BODY (body_emitter):
+///   <right body with our handlers>
+///   right_on_tuple: <compile right keys>; HashTableInsert(table, keys, right output_regs)
+///                   GoTo(right.next)
+///   right_on_done:  (falls through to the left input)
+///   <left body with our handlers>
+///   left_on_tuple:  matched = false (LEFT only); <compile left keys>; HashTableFind(table, keys)
+///   PROBE:          CanReadHashTable(more, table); GoToIfFalse(UNMATCHED, more)
+///                   ReadHashTable(right_regs, table); HashTableNext(table)
+///                   <compile condition>; GoToIfFalse(PROBE, condition)
+///                   matched = true (LEFT only); GoTo(on_tuple)
+///   UNMATCHED:      see emit_join_unmatched
+/// ```
+fn codegen_hash_join(
+    join: Join,
+    left_keys: &[PlanExpr],
+    right_keys: &[PlanExpr],
+    cont: &NodeContinuation,
+    ctx: &mut CodegenContext,
+) -> NodeOutput {
+    let Join {
+        left,
+        right,
+        kind,
+        condition,
+    } = join;
+    let table_reg = ctx.registers.alloc();
+    let more_reg = ctx.registers.alloc();
+    let matched_reg = ctx.registers.alloc();
+
+    // INIT: start with an empty table
+    ctx.init_emitter.emit(Operation::HashTableOpen(table_reg));
+
+    // Build the table from the right input
+    let right_on_tuple = ctx.body_emitter.create_label();
+    let right_on_done = ctx.body_emitter.create_label();
+    let right_cont = NodeContinuation {
+        on_tuple: right_on_tuple,
+        on_done: right_on_done,
+    };
+    let right_output = codegen(right, &right_cont, ctx);
+
+    ctx.body_emitter.bind_label(right_on_tuple);
+    let key_regs = compile_exprs(right_keys, &right_output.output_regs, ctx);
+    ctx.body_emitter.emit(Operation::HashTableInsert(
+        table_reg,
+        key_regs,
+        right_output.output_regs.clone(),
+    ));
+    ctx.body_emitter.emit_goto(right_output.next);
+
+    // right_on_done: the left input's code comes next, starting it
+    ctx.body_emitter.bind_label(right_on_done);
+    let left_on_tuple = ctx.body_emitter.create_label();
+    let left_cont = NodeContinuation {
+        on_tuple: left_on_tuple,
+        on_done: cont.on_done,
+    };
+    let left_output = codegen(left, &left_cont, ctx);
+
+    let right_regs = ctx.registers.alloc_block(right_output.output_regs.len());
+    let mut output_regs = left_output.output_regs.clone();
+    output_regs.extend(right_regs.iter().copied());
+
+    // left_on_tuple: look up the right rows with the same keys
+    ctx.body_emitter.bind_label(left_on_tuple);
+    if kind == JoinKind::Left {
+        ctx.body_emitter
+            .emit(Operation::StoreValue(matched_reg, ScalarValue::Boolean(false)));
+    }
+    let key_regs = compile_exprs(left_keys, &left_output.output_regs, ctx);
+    ctx.body_emitter.emit(Operation::HashTableFind(table_reg, key_regs));
+
+    // PROBE: emit the next of those rows that meets the condition
+    let probe = ctx.body_emitter.create_label();
+    let unmatched = ctx.body_emitter.create_label();
+    ctx.body_emitter.bind_label(probe);
+    ctx.body_emitter.emit(Operation::CanReadHashTable(more_reg, table_reg));
+    ctx.body_emitter.emit_goto_if_false(unmatched, more_reg);
+    let reads = right_regs.iter().copied().enumerate().collect();
+    ctx.body_emitter.emit(Operation::ReadHashTable(reads, table_reg));
+    ctx.body_emitter.emit(Operation::HashTableNext(table_reg));
+    emit_join_condition(condition, &output_regs, probe, ctx);
+    if kind == JoinKind::Left {
+        ctx.body_emitter
+            .emit(Operation::StoreValue(matched_reg, ScalarValue::Boolean(true)));
+    }
+    ctx.body_emitter.emit_goto(cont.on_tuple);
+
+    // UNMATCHED: no rows are left for this left row
+    ctx.body_emitter.bind_label(unmatched);
+    emit_join_unmatched(kind, matched_reg, &right_regs, left_output.next, cont, ctx);
+
+    NodeOutput {
+        next: probe,
+        output_regs,
+    }
+}
+
+/// Jump to `on_false` unless the pair in output_regs meets the join condition
+fn emit_join_condition(condition: Option<&PlanExpr>, output_regs: &[Reg], on_false: Label, ctx: &mut CodegenContext) {
+    if let Some(condition) = condition {
+        let mut expr_ctx = ExprContext {
+            emitter: &mut ctx.body_emitter,
+            registers: &mut ctx.registers,
+            outer_regs: &ctx.outer_regs,
+        };
+        let condition_reg = compile_expr(condition, output_regs, &mut expr_ctx);
+        ctx.body_emitter.emit_goto_if_false(on_false, condition_reg);
+    }
+}
+
+/// Move on to the next left row once it has no right rows left. A LEFT join first emits it with
+/// NULL right columns if nothing matched, and marks it matched so asking for the next tuple moves on.
+fn emit_join_unmatched(
+    kind: JoinKind,
+    matched_reg: Reg,
+    right_regs: &[Reg],
+    left_next: Label,
+    cont: &NodeContinuation,
+    ctx: &mut CodegenContext,
+) {
+    if kind == JoinKind::Left {
+        ctx.body_emitter.emit_goto_if_true(left_next, matched_reg);
+        for reg in right_regs {
+            ctx.body_emitter.emit(Operation::StoreValue(*reg, ScalarValue::Null));
+        }
+        ctx.body_emitter
+            .emit(Operation::StoreValue(matched_reg, ScalarValue::Boolean(true)));
+        ctx.body_emitter.emit_goto(cont.on_tuple);
+    } else {
+        ctx.body_emitter.emit_goto(left_next);
+    }
+}
+
+fn compile_exprs(exprs: &[PlanExpr], input_regs: &[Reg], ctx: &mut CodegenContext) -> Vec<Reg> {
+    exprs
+        .iter()
+        .map(|expr| {
+            let mut expr_ctx = ExprContext {
+                emitter: &mut ctx.body_emitter,
+                registers: &mut ctx.registers,
+                outer_regs: &ctx.outer_regs,
+            };
+            compile_expr(expr, input_regs, &mut expr_ctx)
+        })
+        .collect()
+}
+
 /// Generate bytecode for a Values node.
 ///
 /// Values emits a fixed set of rows (useful for testing and VALUES clauses).
@@ -230,13 +1008,13 @@ pub fn codegen_count(
 /// BODY (body_emitter):
 ///   CHECK:    LessThan(flag, index, num_rows); GoToIfFalse(on_done, flag)
 ///   DISPATCH: GoToIfEqual(ROW_i, index, i) for each row
-///   ROW_0:    store row 0 values; goto EMIT
-///   ROW_1:    store row 1 values; goto EMIT
+///   ROW_0:    compute row 0 values; goto EMIT
+///   ROW_1:    compute row 1 values; goto EMIT
 ///   ...
 ///   EMIT:     index++; goto on_tuple
 /// ```
 pub fn codegen_values(
-    rows: &[Vec<Literal>],
+    rows: &[Vec<PlanExpr>],
     cont: &NodeContinuation,
     ctx: &mut CodegenContext,
 ) -> NodeOutput {
@@ -304,10 +1082,20 @@ pub fn codegen_values(
     // Emit each row's code
     for (i, row) in rows.iter().enumerate() {
         ctx.body_emitter.bind_label(row_labels[i]);
-        for (j, lit) in row.iter().enumerate() {
-            let sv = literal_to_scalar(lit);
-            ctx.body_emitter
-                .emit(Operation::StoreValue(output_regs[j], sv.clone()));
+        for (j, expr) in row.iter().enumerate() {
+            if let PlanExpr::Literal(lit) = expr {
+                ctx.body_emitter
+                    .emit(Operation::StoreValue(output_regs[j], literal_to_scalar(lit)));
+                continue;
+            }
+
+            let mut expr_ctx = ExprContext {
+                emitter: &mut ctx.body_emitter,
+                registers: &mut ctx.registers,
+                outer_regs: &ctx.outer_regs,
+            };
+            let value = compile_expr(expr, &[], &mut expr_ctx);
+            ctx.body_emitter.emit(Operation::CopyValue(output_regs[j], value));
         }
         ctx.body_emitter.emit_goto(emit_label);
     }
@@ -418,6 +1206,7 @@ pub fn codegen_filter(
         let mut expr_ctx = ExprContext {
             emitter: &mut ctx.body_emitter,
             registers: &mut ctx.registers,
+            outer_regs: &ctx.outer_regs,
         };
         compile_expr(predicate, &child_output.output_regs, &mut expr_ctx)
     };
@@ -480,6 +1269,7 @@ pub fn codegen_project(
             let mut expr_ctx = ExprContext {
                 emitter: &mut ctx.body_emitter,
                 registers: &mut ctx.registers,
+                outer_regs: &ctx.outer_regs,
             };
             compile_expr(expr, &child_output.output_regs, &mut expr_ctx)
         })
@@ -495,6 +1285,95 @@ pub fn codegen_project(
     }
 }
 
+/// Generate bytecode for a Sort node.
+///
+/// Sort consumes all rows from its child into a sorter, then emits them in order.
+/// Each row goes into the sorter as its keys followed by the child's columns.
+///
+/// ```text
+/// INIT (init_emitter):
+///   SorterOpen(sorter, descending)
+///
+/// BODY (body_emitter):
+///   <child body with our handlers>
+///   child_on_tuple: <compile keys into key_regs>
+///                   SorterInsert(sorter, key_regs + child.output_regs); GoTo(child.next)
+///   child_on_done:  SorterSort(sorter); GoTo(CHECK)
+///   sort_next:      SorterNext(sorter)
+///   CHECK:          CanReadSorter(flag, sorter); GoToIfFalse(on_done, flag)
+///                   ReadSorter(columns -> output_regs, sorter); GoTo(on_tuple)
+/// ```
+pub fn codegen_sort(
+    keys: &[SortKey],
+    input: &LogicalPlan,
+    cont: &NodeContinuation,
+    ctx: &mut CodegenContext,
+) -> NodeOutput {
+    let sorter_reg = ctx.registers.alloc();
+    let flag_reg = ctx.registers.alloc();
+
+    // INIT: open the sorter
+    let descending = keys.iter().map(|key| key.descending).collect();
+    ctx.init_emitter.emit(Operation::SorterOpen(sorter_reg, descending));
+
+    // Create labels for child's continuations
+    let child_on_tuple = ctx.body_emitter.create_label();
+    let child_on_done = ctx.body_emitter.create_label();
+    let child_cont = NodeContinuation {
+        on_tuple: child_on_tuple,
+        on_done: child_on_done,
+    };
+
+    // Compile child
+    let child_output = codegen(input, &child_cont, ctx);
+
+    // child_on_tuple: compute the keys and insert them with the row, get next from child
+    ctx.body_emitter.bind_label(child_on_tuple);
+    let mut row_regs: Vec<Reg> = keys
+        .iter()
+        .map(|key| {
+            let mut expr_ctx = ExprContext {
+                emitter: &mut ctx.body_emitter,
+                registers: &mut ctx.registers,
+                outer_regs: &ctx.outer_regs,
+            };
+            compile_expr(&key.expr, &child_output.output_regs, &mut expr_ctx)
+        })
+        .collect();
+    row_regs.extend(child_output.output_regs.iter().copied());
+    ctx.body_emitter.emit(Operation::SorterInsert(sorter_reg, row_regs));
+    ctx.body_emitter.emit_goto(child_output.next);
+
+    // child_on_done: every row is in, sort them and emit the first
+    let check_label = ctx.body_emitter.create_label();
+    ctx.body_emitter.bind_label(child_on_done);
+    ctx.body_emitter.emit(Operation::SorterSort(sorter_reg));
+    ctx.body_emitter.emit_goto(check_label);
+
+    // sort_next: move to the next row in order
+    let sort_next = ctx.body_emitter.create_label();
+    ctx.body_emitter.bind_label(sort_next);
+    ctx.body_emitter.emit(Operation::SorterNext(sorter_reg));
+
+    // CHECK: emit the current row, skipping over the keys
+    let output_regs = ctx.registers.alloc_block(child_output.output_regs.len());
+    ctx.body_emitter.bind_label(check_label);
+    ctx.body_emitter.emit(Operation::CanReadSorter(flag_reg, sorter_reg));
+    ctx.body_emitter.emit_goto_if_false(cont.on_done, flag_reg);
+    let reads = output_regs
+        .iter()
+        .enumerate()
+        .map(|(column, reg)| (keys.len() + column, *reg))
+        .collect();
+    ctx.body_emitter.emit(Operation::ReadSorter(reads, sorter_reg));
+    ctx.body_emitter.emit_goto(cont.on_tuple);
+
+    NodeOutput {
+        next: sort_next,
+        output_regs,
+    }
+}
+
 /// Generate bytecode for a Limit node.
 ///
 /// Limit restricts the number of rows emitted to at most `count`.
@@ -568,7 +1447,7 @@ pub fn codegen_limit(
 pub fn codegen(plan: &LogicalPlan, cont: &NodeContinuation, ctx: &mut CodegenContext) -> NodeOutput {
     match plan {
         LogicalPlan::Scan { table, columns } => {
-            codegen_scan(table, columns.len(), cont, ctx)
+            codegen_scan(table, columns, cont, ctx)
         }
         LogicalPlan::Count { input } => {
             codegen_count(input, cont, ctx)
@@ -576,6 +1455,15 @@ pub fn codegen(plan: &LogicalPlan, cont: &NodeContinuation, ctx: &mut CodegenCon
         LogicalPlan::Values { rows } => {
             codegen_values(rows, cont, ctx)
         }
+        LogicalPlan::Insert { input, table } => {
+            codegen_insert(table, input, cont, ctx)
+        }
+        LogicalPlan::Update { input, table } => {
+            codegen_update(table, input, cont, ctx)
+        }
+        LogicalPlan::Delete { input, table } => {
+            codegen_delete(table, input, cont, ctx)
+        }
         LogicalPlan::Filter { predicate, input } => {
             codegen_filter(predicate, input, cont, ctx)
         }
@@ -585,6 +1473,19 @@ pub fn codegen(plan: &LogicalPlan, cont: &NodeContinuation, ctx: &mut CodegenCon
         LogicalPlan::Sequence { start, end } => {
             codegen_sequence(*start, *end, cont, ctx)
         }
+        LogicalPlan::Aggregate { group_by, aggregates, input } => {
+            codegen_aggregate(group_by, aggregates, input, cont, ctx)
+        }
+        LogicalPlan::Sort { keys, input } => {
+            codegen_sort(keys, input, cont, ctx)
+        }
+        LogicalPlan::Materialize { input } => {
+            // Without keys every row sorts equal, and the sorter keeps those in the order they came in
+            codegen_sort(&[], input, cont, ctx)
+        }
+        LogicalPlan::Join { left, right, kind, condition, strategy } => {
+            codegen_join(left, right, *kind, condition.as_ref(), strategy, cont, ctx)
+        }
         LogicalPlan::Limit { count, input } => {
             codegen_limit(*count, input, cont, ctx)
         }
@@ -623,10 +1524,18 @@ pub fn compile_plan(plan: &LogicalPlan) -> (Vec<Operation>, usize) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::engine::scalarvalue::ScalarValue;
+    use crate::scalarvalue::ScalarValue;
     use crate::engine::Engine;
+    use crate::record;
     use crate::planner::{BinaryOp, ColumnRef, PlanExpr};
-    use crate::test::TestDb;
+    use crate::test::{f, i, s, TestDb};
+
+    /// Rows of Values that are all literals
+    fn literal_rows(rows: Vec<Vec<Literal>>) -> Vec<Vec<PlanExpr>> {
+        rows.into_iter()
+            .map(|row| row.into_iter().map(PlanExpr::Literal).collect())
+            .collect()
+    }
 
     /// Test that codegen_scan produces correct bytecode structure
     #[test]
@@ -638,7 +1547,7 @@ mod tests {
         let on_done = ctx.body_emitter.create_label();
         let cont = NodeContinuation { on_tuple, on_done };
 
-        let output = codegen_scan("test_table", 2, &cont, &mut ctx);
+        let output = codegen_scan("test_table", &[0, 1], &cont, &mut ctx);
 
         // Check that we got 2 output registers
         assert_eq!(output.output_regs.len(), 2);
@@ -667,14 +1576,14 @@ mod tests {
 
         let mut cursor = btree.open("test").unwrap();
         let mut cursor = cursor.open_readwrite();
-        cursor.insert(0, b"[1, 100]".to_vec());
-        cursor.insert(1, b"[2, 200]".to_vec());
-        cursor.insert(2, b"[3, 300]".to_vec());
+        cursor.insert(0, record::encode(&[ScalarValue::Integer(1), ScalarValue::Integer(100)]));
+        cursor.insert(1, record::encode(&[ScalarValue::Integer(2), ScalarValue::Integer(200)]));
+        cursor.insert(2, record::encode(&[ScalarValue::Integer(3), ScalarValue::Integer(300)]));
         drop(cursor);
 
         // Run through engine
         let mut engine = Engine::with_program(&ops, num_registers, btree);
-        let yields = engine.run();
+        let yields = engine.run().unwrap();
 
         // Count should yield single row with value 3
         assert_eq!(yields.len(), 1);
@@ -700,7 +1609,7 @@ mod tests {
 
         // Run through engine
         let mut engine = Engine::with_program(&ops, num_registers, btree);
-        let yields = engine.run();
+        let yields = engine.run().unwrap();
 
         // Count should yield 0 for empty table
         assert_eq!(yields.len(), 1);
@@ -724,13 +1633,13 @@ mod tests {
 
         let mut cursor = btree.open("test").unwrap();
         let mut cursor = cursor.open_readwrite();
-        cursor.insert(0, b"[10, 20]".to_vec());
-        cursor.insert(1, b"[30, 40]".to_vec());
+        cursor.insert(0, record::encode(&[ScalarValue::Integer(10), ScalarValue::Integer(20)]));
+        cursor.insert(1, record::encode(&[ScalarValue::Integer(30), ScalarValue::Integer(40)]));
         drop(cursor);
 
         // Run through engine
         let mut engine = Engine::with_program(&ops, num_registers, btree);
-        let yields = engine.run();
+        let yields = engine.run().unwrap();
 
         // Should have 2 rows
         assert_eq!(yields.len(), 2);
@@ -750,11 +1659,11 @@ mod tests {
     #[test]
     fn test_values_basic() {
         let plan = LogicalPlan::Values {
-            rows: vec![
+            rows: literal_rows(vec![
                 vec![Literal::Integer(1), Literal::Integer(10)],
                 vec![Literal::Integer(2), Literal::Integer(20)],
                 vec![Literal::Integer(3), Literal::Integer(30)],
-            ],
+            ]),
         };
 
         let (ops, num_registers) = compile_plan(&plan);
@@ -764,7 +1673,7 @@ mod tests {
         let btree = test.btree;
 
         let mut engine = Engine::with_program(&ops, num_registers, btree);
-        let yields = engine.run();
+        let yields = engine.run().unwrap();
 
         assert_eq!(yields.len(), 3);
         assert_eq!(yields[0], vec![ScalarValue::Integer(1), ScalarValue::Integer(10)]);
@@ -783,7 +1692,7 @@ mod tests {
         let btree = test.btree;
 
         let mut engine = Engine::with_program(&ops, num_registers, btree);
-        let yields = engine.run();
+        let yields = engine.run().unwrap();
 
         assert_eq!(yields.len(), 0);
     }
@@ -793,13 +1702,13 @@ mod tests {
     fn test_count_values() {
         let plan = LogicalPlan::Count {
             input: Box::new(LogicalPlan::Values {
-                rows: vec![
+                rows: literal_rows(vec![
                     vec![Literal::Integer(1)],
                     vec![Literal::Integer(2)],
                     vec![Literal::Integer(3)],
                     vec![Literal::Integer(4)],
                     vec![Literal::Integer(5)],
-                ],
+                ]),
             }),
         };
 
@@ -809,7 +1718,7 @@ mod tests {
         let btree = test.btree;
 
         let mut engine = Engine::with_program(&ops, num_registers, btree);
-        let yields = engine.run();
+        let yields = engine.run().unwrap();
 
         assert_eq!(yields.len(), 1);
         assert_eq!(yields[0][0], ScalarValue::Integer(5));
@@ -819,14 +1728,14 @@ mod tests {
     #[test]
     fn test_values_mixed_types() {
         let plan = LogicalPlan::Values {
-            rows: vec![
+            rows: literal_rows(vec![
                 vec![
                     Literal::Integer(42),
                     Literal::Float(3.14),
                     Literal::Bool(true),
                     Literal::String("hello".to_string()),
                 ],
-            ],
+            ]),
         };
 
         let (ops, num_registers) = compile_plan(&plan);
@@ -835,7 +1744,7 @@ mod tests {
         let btree = test.btree;
 
         let mut engine = Engine::with_program(&ops, num_registers, btree);
-        let yields = engine.run();
+        let yields = engine.run().unwrap();
 
         assert_eq!(yields.len(), 1);
         assert_eq!(yields[0][0], ScalarValue::Integer(42));
@@ -859,7 +1768,7 @@ mod tests {
         let btree = test.btree;
 
         let mut engine = Engine::with_program(&ops, num_registers, btree);
-        let yields = engine.run();
+        let yields = engine.run().unwrap();
 
         assert_eq!(yields.len(), 3);
         assert_eq!(yields[0][0], ScalarValue::Integer(1));
@@ -878,7 +1787,7 @@ mod tests {
         let btree = test.btree;
 
         let mut engine = Engine::with_program(&ops, num_registers, btree);
-        let yields = engine.run();
+        let yields = engine.run().unwrap();
 
         assert_eq!(yields.len(), 0);
     }
@@ -896,7 +1805,7 @@ mod tests {
         let btree = test.btree;
 
         let mut engine = Engine::with_program(&ops, num_registers, btree);
-        let yields = engine.run();
+        let yields = engine.run().unwrap();
 
         assert_eq!(yields.len(), 1);
         assert_eq!(yields[0][0], ScalarValue::Integer(100));
@@ -924,7 +1833,7 @@ mod tests {
         let test = TestDb::default();
         let btree = test.btree;
         let mut engine = Engine::with_program(&ops, num_registers, btree);
-        engine.run()
+        engine.run().unwrap()
     }
 
     /// Test Filter with equality predicate
@@ -999,11 +1908,11 @@ mod tests {
                 right: Box::new(PlanExpr::Literal(Literal::Integer(20))),
             },
             input: Box::new(LogicalPlan::Values {
-                rows: vec![
+                rows: literal_rows(vec![
                     vec![Literal::Integer(1), Literal::Integer(10)],
                     vec![Literal::Integer(2), Literal::Integer(20)],
                     vec![Literal::Integer(3), Literal::Integer(30)],
-                ],
+                ]),
             }),
         };
 
@@ -1154,10 +2063,10 @@ mod tests {
                 PlanExpr::ColumnRef(ColumnRef::Single { column_idx: 0 }),
             ],
             input: Box::new(LogicalPlan::Values {
-                rows: vec![
+                rows: literal_rows(vec![
                     vec![Literal::Integer(1), Literal::Integer(10)],
                     vec![Literal::Integer(2), Literal::Integer(20)],
-                ],
+                ]),
             }),
         };
 
@@ -1259,6 +2168,184 @@ mod tests {
         assert_eq!(yields[2][0], ScalarValue::Integer(3));
     }
 
+    /// Test Sort orders by each key in turn, keeping equal rows in input order
+    #[test]
+    fn test_sort_values() {
+        // Sort [col[0] DESC, col[1]] from Values
+        let plan = LogicalPlan::Sort {
+            keys: vec![
+                SortKey {
+                    expr: PlanExpr::ColumnRef(ColumnRef::Single { column_idx: 0 }),
+                    descending: true,
+                },
+                SortKey {
+                    expr: PlanExpr::ColumnRef(ColumnRef::Single { column_idx: 1 }),
+                    descending: false,
+                },
+            ],
+            input: Box::new(LogicalPlan::Values {
+                rows: literal_rows(vec![
+                    vec![Literal::Integer(1), Literal::String("b".to_string()), Literal::Integer(0)],
+                    vec![Literal::Integer(2), Literal::String("z".to_string()), Literal::Integer(1)],
+                    vec![Literal::Integer(1), Literal::String("a".to_string()), Literal::Integer(2)],
+                    vec![Literal::Integer(1), Literal::String("b".to_string()), Literal::Integer(3)],
+                ]),
+            }),
+        };
+
+        let yields = run_plan(&plan);
+
+        let order: Vec<ScalarValue> = yields.iter().map(|row| row[2].clone()).collect();
+        assert_eq!(order, [1, 2, 0, 3].map(ScalarValue::Integer));
+        assert_eq!(yields[0].len(), 3);
+    }
+
+    /// Test Sort of an empty input emits nothing
+    #[test]
+    fn test_sort_empty() {
+        let plan = LogicalPlan::Sort {
+            keys: vec![SortKey {
+                expr: PlanExpr::ColumnRef(ColumnRef::Single { column_idx: 0 }),
+                descending: false,
+            }],
+            input: Box::new(LogicalPlan::Sequence { start: 5, end: 5 }),
+        };
+
+        assert!(run_plan(&plan).is_empty());
+    }
+
+    #[test]
+    fn test_order_by_sql() {
+        let mut db = TestDb::default();
+        db.create_table(
+            "t",
+            &["a", "b"],
+            &[
+                vec![i(3), s("c")],
+                vec![ScalarValue::Null, s("n")],
+                vec![i(1), s("a")],
+                vec![i(2), s("b")],
+            ],
+        );
+
+        // NULLs sort first, and last when descending
+        let rows = db.query("select b from t order by a").unwrap();
+        assert_eq!(rows, vec![vec![s("n")], vec![s("a")], vec![s("b")], vec![s("c")]]);
+        let rows = db.query("select b from t order by a desc limit 2").unwrap();
+        assert_eq!(rows, vec![vec![s("c")], vec![s("b")]]);
+
+        // Keys can be expressions, and are applied before the limit
+        let rows = db.query("select a from t where a is not null order by a % 3 asc, b desc limit 2").unwrap();
+        assert_eq!(rows, vec![vec![i(3)], vec![i(1)]]);
+    }
+
+    #[test]
+    fn test_order_by_sql_spills() {
+        let mut db = TestDb::default();
+        let keys: Vec<i64> = (0..1000).map(|n| (n * 7919) % 250).collect();
+        let rows: Vec<_> = keys.iter().enumerate().map(|(n, key)| vec![i(*key), i(n as i64)]).collect();
+        db.create_table("t", &["key", "n"], &rows);
+
+        // Far less than the rows take up, so the sorter merges many runs
+        db.sort_budget = 256;
+        let sorted = db.query("select key, n from t order by key desc").unwrap();
+
+        let mut expected = rows.clone();
+        expected.sort_by(|lhs, rhs| rhs[0].sort_cmp(&lhs[0]));
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn test_aggregate_sql() {
+        let db = TestDb::groups();
+
+        let rows = db.query("select count(*), count(v), sum(v), avg(v), min(v), max(v) from t").unwrap();
+        assert_eq!(rows, vec![vec![i(7), i(6), i(45), f(7.5), i(1), i(20)]]);
+
+        // One row even with nothing to aggregate
+        let rows = db.query("select count(*), sum(v), max(g) from t where v > 100").unwrap();
+        assert_eq!(rows, vec![vec![i(0), ScalarValue::Null, ScalarValue::Null]]);
+    }
+
+    #[test]
+    fn test_group_by_sql() {
+        let db = TestDb::groups();
+
+        // Groups come out in order, NULLs grouped together first
+        let rows = db.query("select g, count(*), count(v), sum(v) from t group by g").unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![ScalarValue::Null, i(2), i(2), i(10)],
+                vec![s("a"), i(2), i(2), i(5)],
+                vec![s("b"), i(3), i(2), i(30)],
+            ]
+        );
+
+        let rows = db.query("select g, max(v) - min(v) from t group by g having count(v) > 1 and g is not null order by sum(v) desc").unwrap();
+        assert_eq!(rows, vec![vec![s("b"), i(10)], vec![s("a"), i(3)]]);
+
+        // Grouping by an expression, which the select list can use
+        let rows = db.query("select v % 2, count(*) from t where v is not null group by v % 2").unwrap();
+        assert_eq!(rows, vec![vec![i(0), i(3)], vec![i(1), i(3)]]);
+
+        // No rows, no groups
+        let rows = db.query("select g, count(*) from t where v > 100 group by g").unwrap();
+        assert!(rows.is_empty());
+    }
+
+    #[test]
+    fn test_group_by_mixed_numbers_sql() {
+        let mut db = TestDb::default();
+        db.create_table(
+            "t",
+            &["g", "v"],
+            &[
+                vec![i(1), i(1)],
+                vec![f(2.5), i(2)],
+                vec![f(1.0), i(4)],
+                vec![f(-0.0), i(8)],
+                vec![ScalarValue::Null, i(16)],
+                vec![i(0), i(32)],
+                vec![i(2), i(64)],
+                vec![ScalarValue::Null, i(128)],
+            ],
+        );
+
+        // Values `=` finds equal share a group, as do NULLs
+        let rows = db.query("select count(*), sum(v) from t group by g").unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![i(2), i(144)],
+                vec![i(2), i(40)],
+                vec![i(2), i(5)],
+                vec![i(1), i(64)],
+                vec![i(1), i(2)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_by_sql_spills() {
+        let mut db = TestDb::default();
+        let rows: Vec<_> = (0..1000).map(|n| vec![i((n * 7919) % 50), i(n)]).collect();
+        db.create_table("t", &["g", "v"], &rows);
+
+        db.sort_budget = 256;
+        let groups = db.query("select g, count(*), sum(v) from t group by g").unwrap();
+
+        assert_eq!(groups.len(), 50);
+        for (g, row) in groups.iter().enumerate() {
+            let values = rows.iter().filter(|row| row[0] == i(g as i64));
+            let sum = values.clone().map(|row| match row[1] {
+                ScalarValue::Integer(v) => v,
+                _ => unreachable!(),
+            });
+            assert_eq!(row, &vec![i(g as i64), i(values.count() as i64), i(sum.sum())]);
+        }
+    }
+
     /// Test Limit 0 returns no rows
     #[test]
     fn test_limit_zero() {
@@ -1414,4 +2501,349 @@ mod tests {
         assert_eq!(yields[0][0], ScalarValue::Integer(60));
         assert_eq!(yields[1][0], ScalarValue::Integer(70));
     }
+
+    #[test]
+    fn test_join_sql() {
+        let db = TestDb::users_and_orders();
+
+        // Left rows in order, each with its matches in order
+        let rows = db.query("select name, orders.id from users join orders on user_id = users.id").unwrap();
+        assert_eq!(
+            rows,
+            vec![vec![s("ann"), i(11)], vec![s("bob"), i(10)], vec![s("bob"), i(12)]]
+        );
+
+        // Unmatched users get NULLs, a NULL user_id matches nobody
+        let rows = db
+            .query("select u.name, o.total from users as u left join orders as o on o.user_id = u.id and o.total > 5")
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![s("ann"), i(7)],
+                vec![s("bob"), i(9)],
+                vec![s("cid"), ScalarValue::Null],
+            ]
+        );
+
+        // WHERE applies after the join, so it can drop the padded rows
+        let rows = db
+            .query("select name from users left join orders on user_id = users.id where total is null")
+            .unwrap();
+        assert_eq!(rows, vec![vec![s("cid")]]);
+
+        // A comma join pairs every row
+        let rows = db.query("select count(*), sum(total) from users, orders").unwrap();
+        assert_eq!(rows, vec![vec![i(12), i(66)]]);
+
+        // Three tables, grouped and ordered over the joined rows
+        let rows = db
+            .query("select a.name, count(*) from users as a join orders on user_id = a.id join users as b on b.id >= a.id group by a.name order by a.name desc")
+            .unwrap();
+        assert_eq!(rows, vec![vec![s("bob"), i(4)], vec![s("ann"), i(3)]]);
+    }
+
+    #[test]
+    fn test_join_empty_sides() {
+        let mut db = TestDb::users_and_orders();
+        db.create_table("nothing", &["x"], &[]);
+
+        let rows = db.query("select id, x from users left join nothing on x = id").unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![i(1), ScalarValue::Null],
+                vec![i(2), ScalarValue::Null],
+                vec![i(3), ScalarValue::Null],
+            ]
+        );
+
+        assert!(db.query("select id from users join nothing").unwrap().is_empty());
+        assert!(db.query("select id from nothing left join users on 1").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_join_strategies_sql() {
+        let db = TestDb::users_and_orders();
+        let uses = |sql, op: fn(&Operation) -> bool| db.compile(sql).operations().iter().any(op);
+
+        // Rows are keyed by their position, so the users are rowid 0, 1 and 2
+        let rows = db.query("select rowid, name from users where rowid > 0").unwrap();
+        assert_eq!(rows, vec![vec![i(1), s("bob")], vec![i(2), s("cid")]]);
+
+        let sql = "select orders.id, name from orders join users on users.rowid = user_id";
+        assert!(uses(sql, |op| matches!(op, Operation::SeekRowid(..))));
+        let rows = db.query(sql).unwrap();
+        assert_eq!(
+            rows,
+            vec![vec![i(10), s("cid")], vec![i(11), s("bob")], vec![i(12), s("cid")]]
+        );
+
+        // The rest of the condition still has to hold, and a NULL key finds nothing
+        let rows = db
+            .query("select orders.id, name from orders left join users on users.rowid = user_id - 1 and total > 5")
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![i(10), ScalarValue::Null],
+                vec![i(11), s("ann")],
+                vec![i(12), s("bob")],
+                vec![i(13), ScalarValue::Null],
+            ]
+        );
+
+        // A hash join whose left input is another hash join
+        let sql = "select a.name, b.name, total from users as a join orders on user_id = a.id join users as b on b.id = orders.user_id";
+        let probes = db
+            .compile(sql)
+            .operations()
+            .iter()
+            .filter(|op| matches!(op, Operation::HashTableFind(..)))
+            .count();
+        assert_eq!(probes, 2);
+        let rows = db.query(sql).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![s("ann"), s("ann"), i(7)],
+                vec![s("bob"), s("bob"), i(5)],
+                vec![s("bob"), s("bob"), i(9)],
+            ]
+        );
+
+        // The hash join keeps its left row's order, and pads the unmatched ones
+        let rows = db
+            .query("select total, name from orders left join users on users.id = orders.user_id and name != 'bob'")
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![i(5), ScalarValue::Null],
+                vec![i(7), s("ann")],
+                vec![i(9), ScalarValue::Null],
+                vec![i(1), ScalarValue::Null],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_join_strategies_agree_on_numbers() {
+        let mut db = TestDb::default();
+        let column = |values: Vec<ScalarValue>| values.into_iter().map(|value| vec![value]).collect::<Vec<_>>();
+        db.create_table("l", &["x"], &column(vec![i(1), f(1.0), f(0.0), f(2.5), i((1 << 53) + 1), f(f64::NAN)]));
+        db.create_table("r", &["y"], &column(vec![f(1.0), i(1), f(-0.0), f(2.5), f(2f64.powi(53)), f(f64::NAN)]));
+        let uses_hash = |sql| {
+            db.compile(sql)
+                .operations()
+                .iter()
+                .any(|op| matches!(op, Operation::HashTableFind(..)))
+        };
+
+        let hashed = "select x, y from l join r on x = y";
+        let nested = "select x, y from l join r on x <= y and x >= y";
+        assert!(uses_hash(hashed));
+        assert!(!uses_hash(nested));
+        let rows = db.query(hashed).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![i(1), f(1.0)],
+                vec![i(1), i(1)],
+                vec![f(1.0), f(1.0)],
+                vec![f(1.0), i(1)],
+                vec![f(0.0), f(-0.0)],
+                vec![f(2.5), f(2.5)],
+            ]
+        );
+        assert_eq!(rows, db.query(nested).unwrap());
+
+        // An IN subquery looks its values up in a hash table too
+        let rows = db.query("select x from l where x in (select y from r)").unwrap();
+        assert_eq!(rows, db.query("select x from l where exists (select 1 from r where y = x)").unwrap());
+        assert_eq!(rows.len(), 4);
+    }
+
+    #[test]
+    fn test_derived_table_sql() {
+        let db = TestDb::users_and_orders();
+
+        let rows = db
+            .query("select d.spent, name from (select user_id, sum(total) as spent from orders group by user_id) as d join users on id = d.user_id order by spent")
+            .unwrap();
+        assert_eq!(rows, vec![vec![i(7), s("ann")], vec![i(14), s("bob")]]);
+
+        // Derived tables nest, and a limit applies within its own query
+        let rows = db
+            .query("select x * 10 from (select total + 1 as x from (select total from orders order by total desc limit 3) where total != 7)")
+            .unwrap();
+        assert_eq!(rows, vec![vec![i(100)], vec![i(60)]]);
+
+        // On the right of a nested loop the derived table is run again for every left row,
+        // sorting and limiting each time
+        let rows = db
+            .query("select name, t.total from users left join (select user_id, total from orders order by total desc limit 2) as t on t.user_id > users.id")
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![s("ann"), i(9)],
+                vec![s("bob"), ScalarValue::Null],
+                vec![s("cid"), ScalarValue::Null],
+            ]
+        );
+
+        // A derived table without a FROM of its own columns still counts its rows
+        let rows = db
+            .query("select count(*) from (select 1 from orders where total > 1)")
+            .unwrap();
+        assert_eq!(rows, vec![vec![i(3)]]);
+    }
+
+    #[test]
+    fn test_insert_sql() {
+        let mut db = TestDb::default();
+        db.create_table("t", &["a", "b", "c"], &[vec![i(1), s("x"), i(0)]]);
+
+        // An insert returns no rows
+        let rows = db.query("insert into t values (2, 'y', 1 + 1), (3, 'z', -3)").unwrap();
+        assert!(rows.is_empty());
+
+        // Columns it doesn't name are NULL, and values can be any expression without columns
+        db.query("insert into t (c, a) values (10, (select max(a) from t) * 2)").unwrap();
+
+        // Each row gets the next rowid
+        let rows = db.query("select rowid, a, b, c from t").unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![i(0), i(1), s("x"), i(0)],
+                vec![i(1), i(2), s("y"), i(2)],
+                vec![i(2), i(3), s("z"), i(-3)],
+                vec![i(3), i(6), ScalarValue::Null, i(10)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_insert_select_sql() {
+        let mut db = TestDb::default();
+        db.create_table("t", &["a", "b"], &[vec![i(1), s("x")], vec![i(2), s("y")]]);
+        db.create_table("u", &["name", "n", "m"], &[]);
+
+        // Selected columns go to the named columns in order, the rest are NULL
+        let rows = db.query("insert into u (n, name) select a * 10, b from t where a > 1").unwrap();
+        assert!(rows.is_empty());
+        assert_eq!(db.query("select name, n, m from u").unwrap(), vec![vec![s("y"), i(20), ScalarValue::Null]]);
+
+        // Reading the table it inserts into sees only the rows there were to begin with
+        db.query("insert into t select a + 2, b from t").unwrap();
+        db.query("insert into t select (select count(*) from t) + a, 'z' from t where a = 1").unwrap();
+        assert_eq!(
+            db.query("select rowid, a, b from t").unwrap(),
+            vec![
+                vec![i(0), i(1), s("x")],
+                vec![i(1), i(2), s("y")],
+                vec![i(2), i(3), s("x")],
+                vec![i(3), i(4), s("y")],
+                vec![i(4), i(5), s("z")],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_sql() {
+        let mut db = TestDb::default();
+        let rows: Vec<_> = (0..300).map(|n| vec![i(n), s("x")]).collect();
+        db.create_table("t", &["a", "b"], &rows);
+
+        // An update returns no rows, and columns it doesn't set keep their value
+        let rows = db.query("update t set b = 'even' where a % 2 = 0").unwrap();
+        assert!(rows.is_empty());
+        let rows = db.query("select a, b from t where a < 4").unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![i(0), s("even")],
+                vec![i(1), s("x")],
+                vec![i(2), s("even")],
+                vec![i(3), s("x")],
+            ]
+        );
+
+        // Values are computed from the row before it is changed, and rows growing enough to split
+        // the pages being scanned are each updated exactly once
+        db.query("update t set a = a + 1000, b = 'a much longer value than any row had before'").unwrap();
+        assert_eq!(
+            db.query("select count(*), min(a), max(a) from t").unwrap(),
+            vec![vec![i(300), i(1000), i(1299)]]
+        );
+        assert_eq!(
+            db.query("select rowid, a, b from t where rowid = 299").unwrap(),
+            vec![vec![i(299), i(1299), s("a much longer value than any row had before")]]
+        );
+        db.btree.verify().unwrap();
+
+        // Subqueries can read other tables
+        db.create_table("u", &["n"], &[vec![i(7)]]);
+        db.query("update t set a = (select n from u) where rowid = 0").unwrap();
+        assert_eq!(db.query("select a from t where rowid < 2").unwrap(), vec![vec![i(7)], vec![i(1001)]]);
+
+        // Subqueries reading the table see every row as it was before any is changed
+        db.query("delete from t").unwrap();
+        db.query("insert into t values (1, 'x'), (2, 'x'), (3, 'x')").unwrap();
+        db.query("update t set a = a + (select count(*) from t as t2 where t2.a > t.a)").unwrap();
+        assert_eq!(db.query("select a from t").unwrap(), vec![vec![i(3)], vec![i(3)], vec![i(3)]]);
+        db.query("update t set b = 'y' where a = (select max(a) from t as t2 where t2.rowid < t.rowid)").unwrap();
+        assert_eq!(
+            db.query("select a, b from t").unwrap(),
+            vec![vec![i(3), s("x")], vec![i(3), s("y")], vec![i(3), s("y")]]
+        );
+        db.btree.verify().unwrap();
+    }
+
+    #[test]
+    fn test_delete_sql() {
+        let mut db = TestDb::default();
+        let rows: Vec<_> = (0..300).map(|n| vec![i(n)]).collect();
+        db.create_table("t", &["a"], &rows);
+        db.create_table("u", &["n"], &[vec![i(5)], vec![i(7)]]);
+
+        // A delete returns no rows, and the scan it removes rows from carries on past each of them
+        let rows = db.query("delete from t where a % 3 = 0 or (a > 100 and a < 250)").unwrap();
+        assert!(rows.is_empty());
+        assert_eq!(
+            db.query("select count(*), min(a), max(a) from t").unwrap(),
+            vec![vec![i(101), i(1), i(299)]]
+        );
+        assert_eq!(
+            db.query("select a from t where a < 12").unwrap(),
+            vec![vec![i(1)], vec![i(2)], vec![i(4)], vec![i(5)], vec![i(7)], vec![i(8)], vec![i(10)], vec![i(11)]]
+        );
+
+        // The filter can use the rowid and subqueries on other tables
+        db.query("delete from t where rowid < 5 or a in (select n from u)").unwrap();
+        assert_eq!(
+            db.query("select a from t where a < 12").unwrap(),
+            vec![vec![i(8)], vec![i(10)], vec![i(11)]]
+        );
+
+        // Removing the last rows lets their rowids be used again, as the table isn't autoincrement
+        db.query("delete from t where a > 290").unwrap();
+        db.query("insert into t values (-1)").unwrap();
+        assert_eq!(
+            db.query("select rowid, a from t where a < 0 or a > 285").unwrap(),
+            vec![vec![i(286), i(286)], vec![i(287), i(287)], vec![i(289), i(289)], vec![i(290), i(290)], vec![i(291), i(-1)]]
+        );
+
+        db.query("delete from t").unwrap();
+        assert_eq!(db.query("select count(*) from t").unwrap(), vec![vec![i(0)]]);
+        db.btree.verify().unwrap();
+
+        // A filter reading the table sees every row there was to begin with
+        db.query("insert into t values (1), (2), (3), (4), (5)").unwrap();
+        db.query("delete from t where exists (select a from t as t2 where t2.a = t.a - 1)").unwrap();
+        assert_eq!(db.query("select a from t").unwrap(), vec![vec![i(1)]]);
+        db.btree.verify().unwrap();
+    }
 }
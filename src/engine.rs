@@ -1,14 +1,25 @@
-use crate::{engine::registers::RegisterValue, storage};
+use std::{cmp::Ordering, io::Read};
+
+use crate::{
+    engine::registers::RegisterValue,
+    record::{self, Record, RecordError},
+    scalarvalue::{ScalarValue, ValueError},
+    storage,
+};
 
 use self::{
-    program::{Operation, ProgramCode, Reg},
+    aggregate::Accumulator,
+    hashtable::HashTable,
+    program::{JumpTarget, Label, Operation, ProgramCode, Reg},
     registers::Registers,
-    scalarvalue::ScalarValue,
+    sorter::{Sorter, SorterError},
 };
 
+pub mod aggregate;
+pub mod hashtable;
 pub mod program;
 pub(crate) mod registers;
-pub mod scalarvalue;
+pub mod sorter;
 
 type StepResult = std::result::Result<StepSuccess, EngineError>;
 
@@ -19,15 +30,114 @@ pub(crate) enum StepSuccess {
     Continue,
 }
 
+/// A failure while running a program, and where in the program it happened
 #[derive(Debug)]
-enum EngineError {
-    RegisterTypeError(Reg, &'static str, RegisterValue),
+pub(crate) struct EngineError {
+    pub pc: usize,
+    /// The operation that failed, None if the program counter was out of range
+    pub operation: Option<Operation>,
+    pub kind: ErrorKind,
+}
+
+#[derive(Debug)]
+pub(crate) enum ErrorKind {
+    /// The register doesn't hold the expected kind of value
+    RegisterTypeError(Reg, &'static str, Box<RegisterValue>),
+    InvalidRegister(Reg),
+    InvalidProgramCounter,
+    UnresolvedJump(Label),
+    ValueError(ValueError),
+    RecordError(Reg, RecordError),
+    NoStorage,
+    TableNotFound(String),
+    /// The cursor isn't positioned on an entry
+    CursorExhausted(Reg),
+    CursorReadError(Reg, std::io::Error),
+    SorterError(Reg, SorterError),
+    /// The table in the cursor has used up every rowid
+    RowidsExhausted(Reg),
+    /// The table in the cursor can't be written to
+    ReadOnly(Reg),
+    /// The row in the cursor has a rowid too large for an integer
+    RowidOverflow(Reg, u64),
+}
+
+impl From<ValueError> for ErrorKind {
+    fn from(e: ValueError) -> Self {
+        ErrorKind::ValueError(e)
+    }
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKind::RegisterTypeError(reg, expected, found) => {
+                write!(f, "expected {reg} to hold a {expected}, found {found:?}")
+            }
+            ErrorKind::InvalidRegister(reg) => write!(f, "{reg} doesn't exist"),
+            ErrorKind::InvalidProgramCounter => write!(f, "ran past the end of the program"),
+            ErrorKind::UnresolvedJump(label) => write!(f, "jump to unresolved label {}", label.0),
+            ErrorKind::ValueError(e) => write!(f, "{e}"),
+            ErrorKind::RecordError(reg, e) => write!(f, "couldn't decode the row at {reg}: {e:?}"),
+            ErrorKind::NoStorage => write!(f, "no database is open"),
+            ErrorKind::TableNotFound(name) => write!(f, "no such table: {name}"),
+            ErrorKind::CursorExhausted(reg) => write!(f, "the cursor in {reg} has no current row"),
+            ErrorKind::CursorReadError(reg, e) => write!(f, "reading the cursor in {reg} failed: {e}"),
+            ErrorKind::SorterError(reg, e) => write!(f, "the sorter in {reg} failed: {e}"),
+            ErrorKind::RowidsExhausted(reg) => write!(f, "the table in {reg} has no rowids left"),
+            ErrorKind::ReadOnly(reg) => write!(f, "the table in {reg} is read-only"),
+            ErrorKind::RowidOverflow(reg, key) => write!(f, "the rowid {key} in {reg} is too large for an integer"),
+        }
+    }
+}
+
+impl std::fmt::Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.operation {
+            Some(operation) => write!(f, "{} at {} ({})", self.kind, self.pc, operation),
+            None => write!(f, "{} at {}", self.kind, self.pc),
+        }
+    }
+}
+
+/// The rows yielded by a program, see `Engine::rows`
+pub(crate) struct Rows<'a> {
+    engine: &'a mut Engine,
+    done: bool,
+}
+
+impl Iterator for Rows<'_> {
+    type Item = Result<Vec<ScalarValue>, EngineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match self.engine.step() {
+                Ok(StepSuccess::Continue) => continue,
+                Ok(StepSuccess::Yield(values)) => return Some(Ok(values)),
+                Ok(StepSuccess::Halt) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl Drop for Rows<'_> {
+    fn drop(&mut self) {
+        self.engine.registers.close_cursors();
+    }
 }
 
 pub(crate) struct Engine {
     btree: Option<storage::BTree>,
     registers: Registers,
     program: ProgramCode,
+    /// Bytes of rows each sorter holds in memory before spilling
+    sort_budget: usize,
 }
 
 impl Engine {
@@ -36,6 +146,7 @@ impl Engine {
             btree: None,
             registers,
             program,
+            sort_budget: sorter::DEFAULT_BUDGET,
         }
     }
 
@@ -51,244 +162,474 @@ impl Engine {
             btree: Some(btree),
             registers,
             program,
+            sort_budget: sorter::DEFAULT_BUDGET,
         }
     }
 
+    /// How many bytes of rows a sorter may hold in memory, past this it spills them to a temporary btree
+    pub(crate) fn set_sort_budget(&mut self, budget: usize) {
+        self.sort_budget = budget;
+    }
+
     /// Run the program to completion, returning all yielded rows.
-    pub(crate) fn run(&mut self) -> Vec<Vec<ScalarValue>> {
-        let mut yields = Vec::new();
-        loop {
-            match self.step() {
-                Ok(StepSuccess::Continue) => continue,
-                Ok(StepSuccess::Halt) => break,
-                Ok(StepSuccess::Yield(values)) => yields.push(values),
-                Err(e) => panic!("Engine error: {:?}", e),
-            }
+    pub(crate) fn run(&mut self) -> Result<Vec<Vec<ScalarValue>>, EngineError> {
+        self.rows().collect()
+    }
+
+    /// Run the program a row at a time, the program only advances as rows are taken.
+    /// Dropping the iterator closes any cursors the program opened, `reset` before running it again.
+    pub(crate) fn rows(&mut self) -> Rows<'_> {
+        Rows {
+            engine: self,
+            done: false,
         }
-        yields
+    }
+
+    /// Rewind the program to the start and clear the registers, so it can be run again
+    pub(crate) fn reset(&mut self) {
+        self.program.set_next_operation_index(0);
+        self.registers.clear();
     }
 
     pub fn step(&mut self) -> StepResult {
+        let pc = self.program.curent_operation_index();
+        let Some(operation) = self.program.advance() else {
+            return Err(EngineError {
+                pc,
+                operation: None,
+                kind: ErrorKind::InvalidProgramCounter,
+            });
+        };
+
+        self.execute(operation).map_err(|kind| EngineError {
+            pc,
+            operation: self.program.operation(pc).cloned(),
+            kind,
+        })
+    }
+
+    fn execute(&mut self, operation: Operation) -> Result<StepSuccess, ErrorKind> {
         use program::Operation::*;
 
-        match self.program.advance() {
+        match operation {
             StoreValue(reg, scalar) => {
-                *self.registers.get_mut(reg) = RegisterValue::ScalarValue(scalar);
+                self.set_scalar(reg, scalar)?;
             }
             Yield(regs) => {
-                let values = self.registers.get_range(&regs);
-                let values = values
-                    .map(RegisterValue::scalar)
-                    .map(Option::unwrap)
-                    .cloned()
-                    .collect();
+                let values = regs
+                    .iter()
+                    .map(|reg| self.scalar(*reg).cloned())
+                    .collect::<Result<_, _>>()?;
 
-                return StepResult::Ok(StepSuccess::Yield(values));
+                return Ok(StepSuccess::Yield(values));
             }
             IncrementValue(dest) => {
-                let lhs = self.registers.get(dest).scalar().unwrap();
-                let rhs = ScalarValue::Integer(1);
-                let value = RegisterValue::ScalarValue(lhs.clone() + rhs);
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
+                self.unary(dest, dest, |value| value.checked_add(&ScalarValue::Integer(1)))?;
             }
             DecrementValue(dest) => {
-                let lhs = self.registers.get(dest).scalar().unwrap();
-                let rhs = ScalarValue::Integer(1);
-                let value = RegisterValue::ScalarValue(lhs.clone() - rhs);
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
-            }
-            AddValue(dest, lhs, rhs) => {
-                let lhs = self.registers.get(lhs).scalar().unwrap().clone();
-                let rhs = self.registers.get(rhs).scalar().unwrap().clone();
-                let value = RegisterValue::ScalarValue(lhs + rhs);
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
-            }
-            SubtractValue(dest, lhs, rhs) => {
-                let lhs = self.registers.get(lhs).scalar().unwrap().clone();
-                let rhs = self.registers.get(rhs).scalar().unwrap().clone();
-                let value = RegisterValue::ScalarValue(lhs - rhs);
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
-            }
-            MultiplyValue(dest, lhs, rhs) => {
-                let lhs = self.registers.get(lhs).scalar().unwrap().clone();
-                let rhs = self.registers.get(rhs).scalar().unwrap().clone();
-                let value = RegisterValue::ScalarValue(lhs * rhs);
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
-            }
-            DivideValue(dest, lhs, rhs) => {
-                let lhs = self.registers.get(lhs).scalar().unwrap().clone();
-                let rhs = self.registers.get(rhs).scalar().unwrap().clone();
-                let value = RegisterValue::ScalarValue(lhs / rhs);
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
-            }
-            RemainderValue(dest, lhs, rhs) => {
-                let lhs = self.registers.get(lhs).scalar().unwrap().clone();
-                let rhs = self.registers.get(rhs).scalar().unwrap().clone();
-                let value = RegisterValue::ScalarValue(lhs % rhs);
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
+                self.unary(dest, dest, |value| value.checked_sub(&ScalarValue::Integer(1)))?;
             }
+            AddValue(dest, lhs, rhs) => self.binary(dest, lhs, rhs, ScalarValue::checked_add)?,
+            SubtractValue(dest, lhs, rhs) => self.binary(dest, lhs, rhs, ScalarValue::checked_sub)?,
+            MultiplyValue(dest, lhs, rhs) => self.binary(dest, lhs, rhs, ScalarValue::checked_mul)?,
+            DivideValue(dest, lhs, rhs) => self.binary(dest, lhs, rhs, ScalarValue::checked_div)?,
+            RemainderValue(dest, lhs, rhs) => self.binary(dest, lhs, rhs, ScalarValue::checked_rem)?,
             LessThanValue(dest, lhs, rhs) => {
-                let lhs = self.registers.get(lhs).scalar().unwrap();
-                let rhs = self.registers.get(rhs).scalar().unwrap();
-                let value = RegisterValue::ScalarValue(ScalarValue::Boolean(*lhs < *rhs));
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
+                self.binary(dest, lhs, rhs, |lhs, rhs| lhs.compare(rhs, Ordering::is_lt))?
             }
             LessThanOrEqualValue(dest, lhs, rhs) => {
-                let lhs = self.registers.get(lhs).scalar().unwrap();
-                let rhs = self.registers.get(rhs).scalar().unwrap();
-                let value = RegisterValue::ScalarValue(ScalarValue::Boolean(*lhs <= *rhs));
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
+                self.binary(dest, lhs, rhs, |lhs, rhs| lhs.compare(rhs, Ordering::is_le))?
             }
             GreaterThanValue(dest, lhs, rhs) => {
-                let lhs = self.registers.get(lhs).scalar().unwrap();
-                let rhs = self.registers.get(rhs).scalar().unwrap();
-                let value = RegisterValue::ScalarValue(ScalarValue::Boolean(*lhs > *rhs));
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
+                self.binary(dest, lhs, rhs, |lhs, rhs| lhs.compare(rhs, Ordering::is_gt))?
             }
             GreaterThanOrEqualValue(dest, lhs, rhs) => {
-                let lhs = self.registers.get(lhs).scalar().unwrap();
-                let rhs = self.registers.get(rhs).scalar().unwrap();
-                let value = RegisterValue::ScalarValue(ScalarValue::Boolean(*lhs >= *rhs));
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
+                self.binary(dest, lhs, rhs, |lhs, rhs| lhs.compare(rhs, Ordering::is_ge))?
             }
             EqualsValue(dest, lhs, rhs) => {
-                let lhs = self.registers.get(lhs).scalar().unwrap();
-                let rhs = self.registers.get(rhs).scalar().unwrap();
-                let value = RegisterValue::ScalarValue(ScalarValue::Boolean(*lhs == *rhs));
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
+                self.binary(dest, lhs, rhs, |lhs, rhs| lhs.compare(rhs, Ordering::is_eq))?
             }
             NotEqualsValue(dest, lhs, rhs) => {
-                let lhs = self.registers.get(lhs).scalar().unwrap();
-                let rhs = self.registers.get(rhs).scalar().unwrap();
-                let value = RegisterValue::ScalarValue(ScalarValue::Boolean(*lhs != *rhs));
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
-            }
-            AndValue(dest, lhs, rhs) => {
-                let lhs = self.registers.get(lhs).boolean().unwrap();
-                let rhs = self.registers.get(rhs).boolean().unwrap();
-                let value = RegisterValue::ScalarValue(ScalarValue::Boolean(lhs && rhs));
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
-            }
-            OrValue(dest, lhs, rhs) => {
-                let lhs = self.registers.get(lhs).boolean().unwrap();
-                let rhs = self.registers.get(rhs).boolean().unwrap();
-                let value = RegisterValue::ScalarValue(ScalarValue::Boolean(lhs || rhs));
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
-            }
-            NotValue(dest, src) => {
-                let src = self.registers.get(src).boolean().unwrap();
-                let value = RegisterValue::ScalarValue(ScalarValue::Boolean(!src));
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
-            }
-            NegateValue(dest, src) => {
-                let src = self.registers.get(src).scalar().unwrap().clone();
-                let value = RegisterValue::ScalarValue(-src);
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
+                self.binary(dest, lhs, rhs, |lhs, rhs| lhs.compare(rhs, Ordering::is_ne))?
+            }
+            AndValue(dest, lhs, rhs) => self.binary(dest, lhs, rhs, ScalarValue::and)?,
+            OrValue(dest, lhs, rhs) => self.binary(dest, lhs, rhs, ScalarValue::or)?,
+            NotValue(dest, src) => self.unary(dest, src, |src| src.not())?,
+            LeftShiftValue(dest, lhs, rhs) => self.binary(dest, lhs, rhs, ScalarValue::shift_left)?,
+            RightShiftValue(dest, lhs, rhs) => self.binary(dest, lhs, rhs, ScalarValue::shift_right)?,
+            BitAndValue(dest, lhs, rhs) => self.binary(dest, lhs, rhs, ScalarValue::bit_and)?,
+            BitOrValue(dest, lhs, rhs) => self.binary(dest, lhs, rhs, ScalarValue::bit_or)?,
+            BitXorValue(dest, lhs, rhs) => self.binary(dest, lhs, rhs, ScalarValue::bit_xor)?,
+            BitNotValue(dest, src) => self.unary(dest, src, ScalarValue::bit_not)?,
+            IsNullValue(dest, src) => {
+                self.unary(dest, src, |src| Ok(ScalarValue::Boolean(src.is_null())))?
+            }
+            IsNotNullValue(dest, src) => {
+                self.unary(dest, src, |src| Ok(ScalarValue::Boolean(!src.is_null())))?
             }
+            NegateValue(dest, src) => self.unary(dest, src, |src| src.checked_neg())?,
             CopyValue(dest, src) => {
-                let src = self.registers.get(src).scalar().unwrap().clone();
-                let value = RegisterValue::ScalarValue(src);
-                let dest = self.registers.get_mut(dest);
-                *dest = value;
+                let src = self.scalar(src)?.clone();
+                self.set_scalar(dest, src)?;
             }
             GoTo(target) => {
-                self.program.set_next_operation_index(target.unwrap_resolved());
+                self.jump(&target)?;
             }
             GoToIfEqualValue(target, lhs, rhs) => {
-                let lhs = self.registers.get(lhs).scalar().unwrap();
-                let rhs = self.registers.get(rhs).scalar().unwrap();
+                let lhs = self.scalar(lhs)?;
+                let rhs = self.scalar(rhs)?;
                 if *lhs == *rhs {
-                    self.program.set_next_operation_index(target.unwrap_resolved());
+                    self.jump(&target)?;
                 } else {
                     // branch not taken
                 }
             }
             GoToIfFalse(target, reg) => {
-                let reg = self.registers.get(reg).boolean().unwrap();
-                if !reg {
-                    self.program.set_next_operation_index(target.unwrap_resolved());
+                // NULL isn't true, so a NULL predicate takes the branch just like false
+                if !self.scalar(reg)?.is_true()? {
+                    self.jump(&target)?;
+                } else {
+                    // branch not taken
+                }
+            }
+            GoToIfTrue(target, reg) => {
+                if self.scalar(reg)?.is_true()? {
+                    self.jump(&target)?;
                 } else {
                     // branch not taken
                 }
             }
+            GoToIfDistinct(target, lhs, rhs) => {
+                let lhs = self.scalar(lhs)?;
+                let rhs = self.scalar(rhs)?;
+                if lhs.is_distinct(rhs) {
+                    self.jump(&target)?;
+                } else {
+                    // branch not taken
+                }
+            }
+            Once(target, reg) => {
+                // Registers start out as None, so only the first Once to reach here falls through
+                if let RegisterValue::None = self.register(reg)? {
+                    self.set_scalar(reg, ScalarValue::Boolean(true))?;
+                } else {
+                    self.jump(&target)?;
+                }
+            }
             Halt => {
-                return StepResult::Ok(StepSuccess::Halt);
+                return Ok(StepSuccess::Halt);
             }
             Open(reg, name) => {
-                let btree = self.btree.as_ref().unwrap();
-                let cursor = btree.open(&name).unwrap();
-                *self.registers.get_mut(reg) = RegisterValue::CursorHandle(cursor);
+                let btree = self.btree.as_ref().ok_or(ErrorKind::NoStorage)?;
+                let cursor = btree.open(&name).ok_or(ErrorKind::TableNotFound(name))?;
+                self.set(reg, RegisterValue::CursorHandle(cursor))?;
             }
             MoveCursor(reg, operation) => {
-                let cursor = self.registers.get_mut(reg).cursor_mut().unwrap();
+                let cursor = self.cursor(reg)?;
                 let mut cursor = cursor.open_readwrite();
                 match operation {
-                    program::MoveOperation::First => {
-                        cursor.first();
-                    }
-                    program::MoveOperation::Next => {
-                        cursor.next();
-                    }
-                };
+                    program::MoveOperation::First => cursor.first(),
+                    program::MoveOperation::Next => cursor.next(),
+                }
+                .map_err(|e| ErrorKind::CursorReadError(reg, e.into()))?;
             }
             CanReadCursor(dest, reg) => {
-                let cursor = self.registers.get_mut(reg).cursor_mut().unwrap();
+                let cursor = self.cursor(reg)?;
+                let cursor = cursor.open_readonly();
+                let value = cursor
+                    .get_entry()
+                    .map_err(|e| ErrorKind::CursorReadError(reg, e.into()))?
+                    .is_some();
+                // we must drop cursror before we can mutate registers
+                drop(cursor);
+                self.set_scalar(dest, ScalarValue::Boolean(value))?;
+            }
+            ReadCursor(columns, cursor_reg) => {
+                let cursor = self.cursor(cursor_reg)?;
                 let cursor = cursor.open_readonly();
-                let value = cursor.get_entry().is_some();
+                let mut row = vec![];
+                cursor
+                    .get_entry()
+                    .map_err(|e| ErrorKind::CursorReadError(cursor_reg, e.into()))?
+                    .ok_or(ErrorKind::CursorExhausted(cursor_reg))?
+                    .read_to_end(&mut row)
+                    .map_err(|e| ErrorKind::CursorReadError(cursor_reg, e))?;
                 // we must drop cursror before we can mutate registers
                 drop(cursor);
-                let value = ScalarValue::Boolean(value);
-                *self.registers.get_mut(dest) = RegisterValue::ScalarValue(value);
-            }
-            ReadCursor(regs, cursor_reg) => {
-                let cursor = self.registers.get_mut(cursor_reg).cursor_mut().unwrap();
-                let cursor = cursor.open_readwrite();
-                let mut value = cursor.get_entry().unwrap();
-                let values = value.decode_as_json_array();
+
+                let record = Record::parse(&row).map_err(|e| ErrorKind::RecordError(cursor_reg, e))?;
+                for (column, reg) in columns {
+                    let value = record
+                        .column(column)
+                        .map_err(|e| ErrorKind::RecordError(cursor_reg, e))?;
+                    self.set_scalar(reg, value)?;
+                }
+            }
+            ReadRowid(dest, cursor_reg) => {
+                let cursor = self.cursor(cursor_reg)?;
+                let cursor = cursor.open_readonly();
+                let key = cursor
+                    .get_entry()
+                    .map_err(|e| ErrorKind::CursorReadError(cursor_reg, e.into()))?
+                    .ok_or(ErrorKind::CursorExhausted(cursor_reg))?
+                    .key();
                 // we must drop cursror before we can mutate registers
                 drop(cursor);
+                let rowid = i64::try_from(key).map_err(|_| ErrorKind::RowidOverflow(cursor_reg, key))?;
+                self.set_scalar(dest, ScalarValue::Integer(rowid))?;
+            }
+            SeekRowid(dest, cursor_reg, key_reg) => {
+                // Only a non-negative integer can be a rowid, anything else finds nothing
+                let key = match self.scalar(key_reg)? {
+                    ScalarValue::Integer(key) => u64::try_from(*key).ok(),
+                    _ => None,
+                };
 
-                for (reg, value) in regs.iter().zip(values) {
-                    match value {
-                        serde_json::Value::Number(n) => {
-                            if n.is_i64() {
-                                let value = ScalarValue::Integer(n.as_i64().unwrap());
-                                *self.registers.get_mut(*reg) = RegisterValue::ScalarValue(value);
-                            } else if n.is_f64() {
-                                let value = ScalarValue::Floating(n.as_f64().unwrap());
-                                *self.registers.get_mut(*reg) = RegisterValue::ScalarValue(value);
-                            } else {
-                                todo!()
-                            }
-                        }
-                        serde_json::Value::Bool(b) => {
-                            let value = ScalarValue::Boolean(b);
-                            *self.registers.get_mut(*reg) = RegisterValue::ScalarValue(value);
-                        }
-                        _ => todo!(),
+                let cursor = self.cursor(cursor_reg)?;
+                let found = match key {
+                    Some(key) => {
+                        let mut cursor = cursor.open_readwrite();
+                        let read_error = |e: storage::PageError| ErrorKind::CursorReadError(cursor_reg, e.into());
+                        cursor.find(key).map_err(read_error)?;
+                        cursor
+                            .get_entry()
+                            .map_err(read_error)?
+                            .is_some_and(|entry| entry.key() == key)
                     }
+                    None => false,
+                };
+                self.set_scalar(dest, ScalarValue::Boolean(found))?;
+            }
+            MakeRecord(dest, regs) => {
+                let values: Vec<_> = regs
+                    .iter()
+                    .map(|reg| self.scalar(*reg).cloned())
+                    .collect::<Result<_, _>>()?;
+                self.set(dest, RegisterValue::Record(record::encode(&values)))?;
+            }
+            InsertNextRowid(cursor_reg, record_reg) => {
+                let record = self.record(record_reg)?.to_vec();
+                let cursor = self.writable_cursor(cursor_reg)?;
+                cursor
+                    .open_readwrite()
+                    .insert_next_rowid(record)
+                    .map_err(|_| ErrorKind::RowidsExhausted(cursor_reg))?;
+            }
+            ReplaceRecord(cursor_reg, record_reg) => {
+                let record = self.record(record_reg)?.to_vec();
+                let cursor = self.writable_cursor(cursor_reg)?;
+                if !cursor.open_readwrite().replace(record) {
+                    return Err(ErrorKind::CursorExhausted(cursor_reg));
+                }
+            }
+            DeleteRecord(cursor_reg) => {
+                let cursor = self.writable_cursor(cursor_reg)?;
+                if !cursor.open_readwrite().delete() {
+                    return Err(ErrorKind::CursorExhausted(cursor_reg));
                 }
             }
+            HashTableOpen(reg) => {
+                self.set(reg, RegisterValue::HashTable(Box::default()))?;
+            }
+            HashTableInsert(reg, keys, regs) => {
+                let keys: Vec<_> = keys
+                    .iter()
+                    .map(|reg| self.scalar(*reg).cloned())
+                    .collect::<Result<_, _>>()?;
+                let row = regs
+                    .iter()
+                    .map(|reg| self.scalar(*reg).cloned())
+                    .collect::<Result<_, _>>()?;
+                self.hash_table(reg)?.insert(&keys, row);
+            }
+            HashTableFind(reg, keys) => {
+                let keys: Vec<_> = keys
+                    .iter()
+                    .map(|reg| self.scalar(*reg).cloned())
+                    .collect::<Result<_, _>>()?;
+                self.hash_table(reg)?.find(&keys);
+            }
+            HashTableNext(reg) => {
+                self.hash_table(reg)?.next();
+            }
+            CanReadHashTable(dest, reg) => {
+                let value = self.hash_table(reg)?.current().is_some();
+                self.set_scalar(dest, ScalarValue::Boolean(value))?;
+            }
+            ReadHashTable(columns, table_reg) => {
+                let row = self
+                    .hash_table(table_reg)?
+                    .current()
+                    .ok_or(ErrorKind::CursorExhausted(table_reg))?;
+                let values: Vec<_> = columns
+                    .iter()
+                    .map(|(column, reg)| (*reg, row.get(*column).cloned().unwrap_or(ScalarValue::Null)))
+                    .collect();
+
+                for (reg, value) in values {
+                    self.set_scalar(reg, value)?;
+                }
+            }
+            SorterOpen(reg, descending) => {
+                let encrypted = self.btree.as_ref().is_some_and(storage::BTree::is_encrypted);
+                let sorter = Sorter::new(descending, self.sort_budget, encrypted);
+                self.set(reg, RegisterValue::Sorter(Box::new(sorter)))?;
+            }
+            SorterInsert(reg, regs) => {
+                let row = regs
+                    .iter()
+                    .map(|reg| self.scalar(*reg).cloned())
+                    .collect::<Result<_, _>>()?;
+                self.sorter(reg)?
+                    .insert(row)
+                    .map_err(|e| ErrorKind::SorterError(reg, e))?;
+            }
+            SorterSort(reg) => {
+                self.sorter(reg)?.sort().map_err(|e| ErrorKind::SorterError(reg, e))?;
+            }
+            SorterNext(reg) => {
+                self.sorter(reg)?.next().map_err(|e| ErrorKind::SorterError(reg, e))?;
+            }
+            CanReadSorter(dest, reg) => {
+                let value = self.sorter(reg)?.current().is_some();
+                self.set_scalar(dest, ScalarValue::Boolean(value))?;
+            }
+            ReadSorter(columns, sorter_reg) => {
+                let row = self
+                    .sorter(sorter_reg)?
+                    .current()
+                    .ok_or(ErrorKind::CursorExhausted(sorter_reg))?;
+                let values: Vec<_> = columns
+                    .iter()
+                    .map(|(column, reg)| (*reg, row.get(*column).cloned().unwrap_or(ScalarValue::Null)))
+                    .collect();
+
+                for (reg, value) in values {
+                    self.set_scalar(reg, value)?;
+                }
+            }
+            AggregateReset(reg, function) => {
+                self.set(reg, RegisterValue::Accumulator(Accumulator::new(function)))?;
+            }
+            AggregateStep(reg, value) => {
+                let value = value.map(|value| self.scalar(value).cloned()).transpose()?;
+                self.accumulator(reg)?.step(value.as_ref())?;
+            }
+            AggregateFinal(dest, reg) => {
+                let value = self.accumulator(reg)?.finish()?;
+                self.set_scalar(dest, value)?;
+            }
         };
 
-        StepResult::Ok(StepSuccess::Continue)
+        Ok(StepSuccess::Continue)
+    }
+
+    fn register(&self, reg: Reg) -> Result<&RegisterValue, ErrorKind> {
+        self.registers.get(reg).ok_or(ErrorKind::InvalidRegister(reg))
+    }
+
+    fn scalar(&self, reg: Reg) -> Result<&ScalarValue, ErrorKind> {
+        let value = self.register(reg)?;
+        value
+            .scalar()
+            .ok_or_else(|| ErrorKind::RegisterTypeError(reg, "scalar", Box::new(value.clone())))
+    }
+
+    fn record(&self, reg: Reg) -> Result<&[u8], ErrorKind> {
+        let value = self.register(reg)?;
+        value
+            .record()
+            .ok_or_else(|| ErrorKind::RegisterTypeError(reg, "record", Box::new(value.clone())))
+    }
+
+    fn cursor(&mut self, reg: Reg) -> Result<&mut storage::CursorHandle, ErrorKind> {
+        let value = self.registers.get_mut(reg).ok_or(ErrorKind::InvalidRegister(reg))?;
+        if value.cursor().is_none() {
+            return Err(ErrorKind::RegisterTypeError(reg, "cursor", Box::new(value.clone())));
+        }
+
+        Ok(value.cursor_mut().unwrap())
+    }
+
+    /// The cursor in `reg`, for an operation which changes its table
+    fn writable_cursor(&mut self, reg: Reg) -> Result<&mut storage::CursorHandle, ErrorKind> {
+        let cursor = self.cursor(reg)?;
+        if cursor.is_read_only() {
+            return Err(ErrorKind::ReadOnly(reg));
+        }
+
+        Ok(cursor)
+    }
+
+    fn sorter(&mut self, reg: Reg) -> Result<&mut Sorter, ErrorKind> {
+        let value = self.registers.get_mut(reg).ok_or(ErrorKind::InvalidRegister(reg))?;
+        if value.sorter().is_none() {
+            return Err(ErrorKind::RegisterTypeError(reg, "sorter", Box::new(value.clone())));
+        }
+
+        Ok(value.sorter_mut().unwrap())
+    }
+
+    fn hash_table(&mut self, reg: Reg) -> Result<&mut HashTable, ErrorKind> {
+        let value = self.registers.get_mut(reg).ok_or(ErrorKind::InvalidRegister(reg))?;
+        if value.hash_table_mut().is_none() {
+            return Err(ErrorKind::RegisterTypeError(reg, "hash table", Box::new(value.clone())));
+        }
+
+        Ok(value.hash_table_mut().unwrap())
+    }
+
+    fn accumulator(&mut self, reg: Reg) -> Result<&mut Accumulator, ErrorKind> {
+        let value = self.registers.get_mut(reg).ok_or(ErrorKind::InvalidRegister(reg))?;
+        if value.accumulator_mut().is_none() {
+            return Err(ErrorKind::RegisterTypeError(reg, "accumulator", Box::new(value.clone())));
+        }
+
+        Ok(value.accumulator_mut().unwrap())
+    }
+
+    fn set(&mut self, reg: Reg, value: RegisterValue) -> Result<(), ErrorKind> {
+        *self.registers.get_mut(reg).ok_or(ErrorKind::InvalidRegister(reg))? = value;
+        Ok(())
+    }
+
+    fn set_scalar(&mut self, reg: Reg, value: ScalarValue) -> Result<(), ErrorKind> {
+        self.set(reg, RegisterValue::ScalarValue(value))
+    }
+
+    /// dest = op(src)
+    fn unary(
+        &mut self,
+        dest: Reg,
+        src: Reg,
+        op: impl FnOnce(&ScalarValue) -> Result<ScalarValue, ValueError>,
+    ) -> Result<(), ErrorKind> {
+        let value = op(self.scalar(src)?)?;
+
+        self.set_scalar(dest, value)
+    }
+
+    /// dest = op(lhs, rhs)
+    fn binary(
+        &mut self,
+        dest: Reg,
+        lhs: Reg,
+        rhs: Reg,
+        op: impl FnOnce(&ScalarValue, &ScalarValue) -> Result<ScalarValue, ValueError>,
+    ) -> Result<(), ErrorKind> {
+        let value = op(self.scalar(lhs)?, self.scalar(rhs)?)?;
+
+        self.set_scalar(dest, value)
+    }
+
+    fn jump(&mut self, target: &JumpTarget) -> Result<(), ErrorKind> {
+        match target {
+            JumpTarget::Resolved(addr) => {
+                self.program.set_next_operation_index(*addr);
+                Ok(())
+            }
+            JumpTarget::Unresolved(label) => Err(ErrorKind::UnresolvedJump(*label)),
+        }
     }
 }
 
@@ -297,16 +638,19 @@ mod test {
     use crate::{
         engine::{
             program::{JumpTarget, MoveOperation, Operation, ProgramCode},
-            scalarvalue::ScalarValue, StepSuccess,
+            StepSuccess,
         },
-        storage::BTree,
-        test::TestDb,
+        record,
+        scalarvalue::{ScalarValue, ValueError},
+        storage::{BTree, PagerOptions},
+        test::{b, TestDb},
     };
+    use tempfile::NamedTempFile;
 
     use super::{
-        program::Reg,
+        program::{Label, Reg},
         registers::Registers,
-        Engine,
+        Engine, EngineError, ErrorKind,
     };
 
     struct TestHarness {
@@ -342,20 +686,27 @@ mod test {
         }
 
         fn run(&mut self) {
+            if let Err(e) = self.try_run() {
+                panic!("{e}");
+            }
+        }
+
+        fn try_run(&mut self) -> Result<(), EngineError> {
             loop {
-                match self.engine.step() {
-                    Ok(StepSuccess::Continue) => {
+                match self.engine.step()? {
+                    StepSuccess::Continue => {
                         continue;
                     }
-                    Ok(StepSuccess::Halt) => {
+                    StepSuccess::Halt => {
                         break;
                     }
-                    Ok(StepSuccess::Yield(values)) => {
+                    StepSuccess::Yield(values) => {
                         self.yields.push(values);
                     }
-                    Err(_) => todo!(),
                 };
             }
+
+            Ok(())
         }
 
         fn num_yields(&self) -> usize {
@@ -809,6 +1160,330 @@ mod test {
         assert_eq!(harness.value(0, 3), ScalarValue::Boolean(false));
     }
 
+    #[test]
+    fn test_null_arithmetic_and_comparison() {
+        let r0 = Reg::new(0);
+        let r1 = Reg::new(1);
+        let regs: Vec<_> = (2..14).map(Reg::new).collect();
+
+        let mut harness = TestHarness::new(
+            &[
+                Operation::StoreValue(r0, ScalarValue::Integer(5)),
+                Operation::StoreValue(r1, ScalarValue::Null),
+                Operation::AddValue(regs[0], r0, r1),
+                Operation::SubtractValue(regs[1], r1, r0),
+                Operation::MultiplyValue(regs[2], r0, r1),
+                Operation::DivideValue(regs[3], r1, r0),
+                Operation::RemainderValue(regs[4], r0, r1),
+                Operation::NegateValue(regs[5], r1),
+                Operation::LessThanValue(regs[6], r0, r1),
+                Operation::LessThanOrEqualValue(regs[7], r1, r0),
+                Operation::GreaterThanValue(regs[8], r0, r1),
+                Operation::GreaterThanOrEqualValue(regs[9], r1, r0),
+                // NULL = NULL is NULL, not true
+                Operation::EqualsValue(regs[10], r1, r1),
+                Operation::NotEqualsValue(regs[11], r0, r1),
+                Operation::Yield(regs.clone()),
+                Operation::Halt,
+            ],
+            14,
+        );
+
+        harness.run();
+
+        assert_eq!(harness.num_yields(), 1);
+        for column in 0..regs.len() {
+            assert_eq!(harness.value(0, column), ScalarValue::Null, "column {column}");
+        }
+    }
+
+    #[test]
+    fn test_null_logical_operations() {
+        let t = Reg::new(0);
+        let f = Reg::new(1);
+        let n = Reg::new(2);
+        let regs: Vec<_> = (3..15).map(Reg::new).collect();
+
+        let mut harness = TestHarness::new(
+            &[
+                Operation::StoreValue(t, ScalarValue::Boolean(true)),
+                Operation::StoreValue(f, ScalarValue::Boolean(false)),
+                Operation::StoreValue(n, ScalarValue::Null),
+                // AND with NULL
+                Operation::AndValue(regs[0], t, n),
+                Operation::AndValue(regs[1], n, t),
+                Operation::AndValue(regs[2], f, n),
+                Operation::AndValue(regs[3], n, f),
+                Operation::AndValue(regs[4], n, n),
+                // OR with NULL
+                Operation::OrValue(regs[5], t, n),
+                Operation::OrValue(regs[6], n, t),
+                Operation::OrValue(regs[7], f, n),
+                Operation::OrValue(regs[8], n, f),
+                Operation::OrValue(regs[9], n, n),
+                // NOT NULL
+                Operation::NotValue(regs[10], n),
+                Operation::NotValue(regs[11], f),
+                Operation::Yield(regs.clone()),
+                Operation::Halt,
+            ],
+            15,
+        );
+
+        harness.run();
+
+        let t = ScalarValue::Boolean(true);
+        let f = ScalarValue::Boolean(false);
+        let n = ScalarValue::Null;
+        let expected = [
+            n.clone(), n.clone(), f.clone(), f.clone(), n.clone(),
+            t.clone(), t.clone(), n.clone(), n.clone(), n.clone(),
+            n.clone(), t.clone(),
+        ];
+
+        assert_eq!(harness.num_yields(), 1);
+        for (column, expected) in expected.into_iter().enumerate() {
+            assert_eq!(harness.value(0, column), expected, "column {column}");
+        }
+    }
+
+    #[test]
+    fn test_is_null() {
+        let r0 = Reg::new(0);
+        let r1 = Reg::new(1);
+        let r2 = Reg::new(2);
+        let r3 = Reg::new(3);
+        let r4 = Reg::new(4);
+        let r5 = Reg::new(5);
+
+        let mut harness = TestHarness::new(
+            &[
+                Operation::StoreValue(r0, ScalarValue::Integer(0)),
+                Operation::StoreValue(r1, ScalarValue::Null),
+                Operation::IsNullValue(r2, r0),
+                Operation::IsNullValue(r3, r1),
+                Operation::IsNotNullValue(r4, r0),
+                Operation::IsNotNullValue(r5, r1),
+                Operation::Yield(vec![r2, r3, r4, r5]),
+                Operation::Halt,
+            ],
+            6,
+        );
+
+        harness.run();
+
+        assert_eq!(harness.num_yields(), 1);
+        assert_eq!(harness.value(0, 0), ScalarValue::Boolean(false));
+        assert_eq!(harness.value(0, 1), ScalarValue::Boolean(true));
+        assert_eq!(harness.value(0, 2), ScalarValue::Boolean(true));
+        assert_eq!(harness.value(0, 3), ScalarValue::Boolean(false));
+    }
+
+    #[test]
+    fn test_goto_if_false_on_null() {
+        let r0 = Reg::new(0);
+        let r1 = Reg::new(1);
+
+        let mut harness = TestHarness::new(
+            &[
+                Operation::StoreValue(r0, ScalarValue::Null),
+                Operation::StoreValue(r1, ScalarValue::Integer(1)),
+                // A NULL condition isn't true, so the yield is skipped
+                Operation::GoToIfFalse(JumpTarget::addr(4), r0),
+                Operation::Yield(vec![r1]),
+                Operation::Halt,
+            ],
+            2,
+        );
+
+        harness.run();
+
+        assert_eq!(harness.num_yields(), 0);
+    }
+
+    #[test]
+    fn test_once() {
+        let r0 = Reg::new(0);
+        let r1 = Reg::new(1);
+        let r2 = Reg::new(2);
+
+        let mut harness = TestHarness::new(
+            &[
+                Operation::StoreValue(r1, ScalarValue::Integer(0)),
+                Operation::StoreValue(r2, ScalarValue::Integer(3)),
+                Operation::IncrementValue(r1),
+                // Yields the first time round the loop, then jumps over the yield
+                Operation::Once(JumpTarget::addr(5), r0),
+                Operation::Yield(vec![r1]),
+                Operation::GoToIfDistinct(JumpTarget::addr(2), r1, r2),
+                Operation::Halt,
+            ],
+            3,
+        );
+
+        harness.run();
+
+        assert_eq!(harness.num_yields(), 1);
+        assert_eq!(harness.value(0, 0), ScalarValue::Integer(1));
+    }
+
+    #[test]
+    fn test_value_errors() {
+        let r0 = Reg::new(0);
+        let r1 = Reg::new(1);
+        let r2 = Reg::new(2);
+
+        let divide = Operation::DivideValue(r2, r0, r1);
+        let mut harness = TestHarness::new(
+            &[
+                Operation::StoreValue(r0, ScalarValue::Integer(1)),
+                Operation::StoreValue(r1, ScalarValue::Integer(0)),
+                divide.clone(),
+                Operation::Halt,
+            ],
+            3,
+        );
+        let error = harness.try_run().unwrap_err();
+        assert_eq!(2, error.pc);
+        assert!(matches!(error.operation, Some(Operation::DivideValue(..))));
+        assert!(matches!(error.kind, ErrorKind::ValueError(ValueError::DivisionByZero)));
+        assert!(error.to_string().starts_with("division by zero at 2"));
+
+        let mut harness = TestHarness::new(
+            &[
+                Operation::StoreValue(r0, ScalarValue::Integer(i64::MAX)),
+                Operation::IncrementValue(r0),
+                Operation::Halt,
+            ],
+            1,
+        );
+        let error = harness.try_run().unwrap_err();
+        assert_eq!(1, error.pc);
+        assert!(matches!(error.kind, ErrorKind::ValueError(ValueError::Overflow)));
+
+        let mut harness = TestHarness::new(
+            &[
+                Operation::StoreValue(r0, ScalarValue::Integer(1)),
+                Operation::StoreValue(r1, ScalarValue::String("one".to_string())),
+                Operation::LessThanValue(r2, r0, r1),
+                Operation::Halt,
+            ],
+            3,
+        );
+        let error = harness.try_run().unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::ValueError(ValueError::TypeMismatch("comparison", _))));
+
+        // Branching on something other than a boolean
+        let mut harness = TestHarness::new(
+            &[
+                Operation::StoreValue(r0, ScalarValue::Integer(1)),
+                Operation::GoToIfFalse(JumpTarget::addr(0), r0),
+                Operation::Halt,
+            ],
+            1,
+        );
+        let error = harness.try_run().unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::ValueError(ValueError::TypeMismatch("condition", _))));
+    }
+
+    #[test]
+    fn test_program_errors() {
+        let r0 = Reg::new(0);
+        let r1 = Reg::new(1);
+
+        // Reading a register that was never written
+        let mut harness = TestHarness::new(&[Operation::CopyValue(r1, r0), Operation::Halt], 2);
+        let error = harness.try_run().unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::RegisterTypeError(reg, "scalar", _) if reg == r0));
+
+        // A register past the end of the register file
+        let mut harness = TestHarness::new(
+            &[Operation::StoreValue(Reg::new(5), ScalarValue::Integer(1)), Operation::Halt],
+            2,
+        );
+        let error = harness.try_run().unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::InvalidRegister(_)));
+
+        // Running off the end of the program
+        let mut harness = TestHarness::new(&[Operation::StoreValue(r0, ScalarValue::Integer(1))], 1);
+        let error = harness.try_run().unwrap_err();
+        assert_eq!(1, error.pc);
+        assert!(error.operation.is_none());
+        assert!(matches!(error.kind, ErrorKind::InvalidProgramCounter));
+
+        let mut harness = TestHarness::new(&[Operation::GoTo(JumpTarget::label(Label(3)))], 1);
+        let error = harness.try_run().unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::UnresolvedJump(Label(3))));
+
+        // Opening a table without a database
+        let mut harness = TestHarness::new(&[Operation::Open(r0, "test".to_string())], 1);
+        let error = harness.try_run().unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::NoStorage));
+    }
+
+    #[test]
+    fn test_cursor_errors() {
+        let empty_table = || {
+            let mut test = TestDb::default();
+            test.btree.create_tree("test");
+            test
+        };
+
+        let r0 = Reg::new(0);
+        let r1 = Reg::new(1);
+
+        let test = empty_table();
+        let mut harness = TestHarness::new_with_btree(
+            &[Operation::Open(r0, "missing".to_string()), Operation::Halt],
+            2,
+            test.btree,
+        );
+        let error = harness.try_run().unwrap_err();
+        assert_eq!(0, error.pc);
+        assert!(matches!(error.kind, ErrorKind::TableNotFound(ref name) if name == "missing"));
+
+        // The table is empty, so there is no row to read
+        let test = empty_table();
+        let mut harness = TestHarness::new_with_btree(
+            &[
+                Operation::Open(r0, "test".to_string()),
+                Operation::MoveCursor(r0, MoveOperation::First),
+                Operation::ReadCursor(vec![(0, r1)], r0),
+                Operation::Halt,
+            ],
+            2,
+            test.btree,
+        );
+        let error = harness.try_run().unwrap_err();
+        assert_eq!(2, error.pc);
+        assert!(matches!(error.kind, ErrorKind::CursorExhausted(reg) if reg == r0));
+
+        // A scalar isn't a cursor
+        let test = empty_table();
+        let mut harness = TestHarness::new_with_btree(
+            &[
+                Operation::StoreValue(r0, ScalarValue::Integer(1)),
+                Operation::MoveCursor(r0, MoveOperation::First),
+                Operation::Halt,
+            ],
+            2,
+            test.btree,
+        );
+        let error = harness.try_run().unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::RegisterTypeError(_, "cursor", _)));
+    }
+
+    #[test]
+    fn test_run_returns_errors() {
+        let test = TestDb::default();
+        let r0 = Reg::new(0);
+
+        let mut engine = Engine::with_program(&[Operation::Open(r0, "missing".to_string())], 1, test.btree);
+        let error = engine.run().unwrap_err();
+
+        assert!(matches!(error.kind, ErrorKind::TableNotFound(_)));
+    }
+
     #[test]
     fn test_btree_open() {
         let test = TestDb::default();
@@ -817,10 +1492,10 @@ mod test {
 
         let mut cursor = btree.open("test").unwrap();
         let mut cursor = cursor.open_readwrite();
-        cursor.insert(0, b"[12345,6789]".to_vec());
-        cursor.insert(1, b"[12345]".to_vec());
-        cursor.insert(2, b"[12345]".to_vec());
-        cursor.insert(3, b"[12345]".to_vec());
+        cursor.insert(0, record::encode(&[ScalarValue::Integer(12345), ScalarValue::Integer(6789)]));
+        cursor.insert(1, record::encode(&[ScalarValue::Integer(12345)]));
+        cursor.insert(2, record::encode(&[ScalarValue::Integer(12345)]));
+        cursor.insert(3, record::encode(&[ScalarValue::Integer(12345)]));
         drop(cursor);
 
         let r0 = Reg::new(0);
@@ -834,7 +1509,7 @@ mod test {
                 // Move Cursor to first record
                 Operation::MoveCursor(r0, MoveOperation::First),
                 // Read Record Key
-                Operation::ReadCursor(vec![r1, r2], r0),
+                Operation::ReadCursor(vec![(0, r1), (1, r2)], r0),
                 // Yield Record Key
                 Operation::Yield(vec![r1, r2]),
                 Operation::Halt,
@@ -850,6 +1525,171 @@ mod test {
         assert_eq!(harness.value(0, 1), ScalarValue::Integer(6789));
     }
 
+    #[test]
+    fn test_read_selected_columns() {
+        let test = TestDb::default();
+        let mut btree = test.btree;
+        btree.create_tree("test");
+
+        let mut cursor = btree.open("test").unwrap();
+        let mut cursor = cursor.open_readwrite();
+        cursor.insert(
+            0,
+            record::encode(&[
+                ScalarValue::Integer(7),
+                ScalarValue::Floating(2.5),
+                ScalarValue::String("seven".to_string()),
+                ScalarValue::Boolean(true),
+                ScalarValue::Null,
+            ]),
+        );
+        drop(cursor);
+
+        let r0 = Reg::new(0);
+        let r1 = Reg::new(1);
+        let r2 = Reg::new(2);
+        let r3 = Reg::new(3);
+        let r4 = Reg::new(4);
+
+        let mut harness = TestHarness::new_with_btree(
+            &[
+                Operation::Open(r0, "test".to_string()),
+                Operation::MoveCursor(r0, MoveOperation::First),
+                // Columns in any order, not all of them
+                Operation::ReadCursor(vec![(2, r1), (3, r2), (0, r3), (4, r4)], r0),
+                Operation::Yield(vec![r1, r2, r3, r4]),
+                Operation::Halt,
+            ],
+            5,
+            btree,
+        );
+
+        harness.run();
+
+        assert_eq!(harness.num_yields(), 1);
+        assert_eq!(harness.value(0, 0), ScalarValue::String("seven".to_string()));
+        assert_eq!(harness.value(0, 1), ScalarValue::Boolean(true));
+        assert_eq!(harness.value(0, 2), ScalarValue::Integer(7));
+        assert_eq!(harness.value(0, 3), ScalarValue::Null);
+    }
+
+    #[test]
+    fn test_seek_rowid() {
+        let test = TestDb::default();
+        let mut btree = test.btree;
+        btree.create_tree("test");
+
+        let mut cursor = btree.open("test").unwrap();
+        let mut cursor = cursor.open_readwrite();
+        for key in [2, 4, 6] {
+            cursor.insert(key, record::encode(&[ScalarValue::Integer(key as i64 * 10)]));
+        }
+        drop(cursor);
+
+        let r0 = Reg::new(0);
+        let found = Reg::new(1);
+        let key = Reg::new(2);
+        let rowid = Reg::new(3);
+        let value = Reg::new(4);
+
+        let mut ops = vec![Operation::Open(r0, "test".to_string())];
+        for seek in [
+            ScalarValue::Integer(4),
+            ScalarValue::Integer(3),
+            ScalarValue::Integer(7),
+            ScalarValue::Integer(-1),
+            ScalarValue::Floating(2.0),
+            ScalarValue::Null,
+        ] {
+            ops.push(Operation::StoreValue(key, seek));
+            ops.push(Operation::SeekRowid(found, r0, key));
+            ops.push(Operation::Yield(vec![found]));
+        }
+        // The cursor is left on the row that was found
+        ops.push(Operation::StoreValue(key, ScalarValue::Integer(6)));
+        ops.push(Operation::SeekRowid(found, r0, key));
+        ops.push(Operation::ReadRowid(rowid, r0));
+        ops.push(Operation::ReadCursor(vec![(0, value)], r0));
+        ops.push(Operation::Yield(vec![rowid, value]));
+        ops.push(Operation::Halt);
+
+        let mut harness = TestHarness::new_with_btree(&ops, 5, btree);
+        harness.run();
+
+        let found: Vec<_> = (0..6).map(|row| harness.value(row, 0)).collect();
+        assert_eq!(found, vec![b(true), b(false), b(false), b(false), b(false), b(false)]);
+        assert_eq!(harness.value(6, 0), ScalarValue::Integer(6));
+        assert_eq!(harness.value(6, 1), ScalarValue::Integer(60));
+    }
+
+    #[test]
+    fn test_read_rowid_overflow() {
+        let test = TestDb::default();
+        let mut btree = test.btree;
+        btree.create_tree("test");
+
+        let mut cursor = btree.open("test").unwrap();
+        let mut cursor = cursor.open_readwrite();
+        cursor.insert(i64::MAX as u64, record::encode(&[ScalarValue::Integer(1)]));
+        cursor.insert(i64::MAX as u64 + 1, record::encode(&[ScalarValue::Integer(2)]));
+        drop(cursor);
+
+        let r0 = Reg::new(0);
+        let rowid = Reg::new(1);
+        let ops = [
+            Operation::Open(r0, "test".to_string()),
+            Operation::MoveCursor(r0, MoveOperation::First),
+            Operation::ReadRowid(rowid, r0),
+            Operation::Yield(vec![rowid]),
+            Operation::MoveCursor(r0, MoveOperation::Next),
+            Operation::ReadRowid(rowid, r0),
+            Operation::Yield(vec![rowid]),
+            Operation::Halt,
+        ];
+
+        // The largest rowid an integer holds is read, the one after it is an error rather than wrapping
+        let mut harness = TestHarness::new_with_btree(&ops, 2, btree);
+        let error = harness.try_run().unwrap_err();
+        assert_eq!(harness.value(0, 0), ScalarValue::Integer(i64::MAX));
+        assert_eq!(5, error.pc);
+        assert!(matches!(error.kind, ErrorKind::RowidOverflow(_, key) if key == i64::MAX as u64 + 1));
+    }
+
+    #[test]
+    fn test_tampered_page() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        let options = || PagerOptions {
+            passphrase: Some("hunter2".to_string()),
+            ..Default::default()
+        };
+
+        let mut btree = BTree::with_options(path, options()).unwrap();
+        btree.create_tree("test");
+        let mut cursor = btree.open("test").unwrap();
+        cursor.open_readwrite().insert(0, record::encode(&[ScalarValue::Integer(1)]));
+        drop(cursor);
+        drop(btree);
+
+        // The only row is in the last page of the file
+        let mut raw = std::fs::read(path).unwrap();
+        *raw.last_mut().unwrap() ^= 1;
+        std::fs::write(path, raw).unwrap();
+
+        let r0 = Reg::new(0);
+        let ops = [
+            Operation::Open(r0, "test".to_string()),
+            Operation::MoveCursor(r0, MoveOperation::First),
+            Operation::Halt,
+        ];
+
+        let btree = BTree::with_options(path, options()).unwrap();
+        let mut harness = TestHarness::new_with_btree(&ops, 1, btree);
+        let error = harness.try_run().unwrap_err();
+        assert_eq!(1, error.pc);
+        assert!(matches!(error.kind, ErrorKind::CursorReadError(reg, _) if reg == r0));
+    }
+
     #[test]
     fn test_read_all_data() {
         let test = TestDb::default();
@@ -858,10 +1698,10 @@ mod test {
 
         let mut cursor = btree.open("test").unwrap();
         let mut cursor = cursor.open_readwrite();
-        cursor.insert(0, b"[12345,6789]".to_vec());
-        cursor.insert(1, b"[12345,0]".to_vec());
-        cursor.insert(2, b"[12345,0]".to_vec());
-        cursor.insert(3, b"[12345,0]".to_vec());
+        cursor.insert(0, record::encode(&[ScalarValue::Integer(12345), ScalarValue::Integer(6789)]));
+        cursor.insert(1, record::encode(&[ScalarValue::Integer(12345), ScalarValue::Integer(0)]));
+        cursor.insert(2, record::encode(&[ScalarValue::Integer(12345), ScalarValue::Integer(0)]));
+        cursor.insert(3, record::encode(&[ScalarValue::Integer(12345), ScalarValue::Integer(0)]));
         drop(cursor);
 
         let r0 = Reg::new(0);
@@ -875,7 +1715,7 @@ mod test {
                 Operation::MoveCursor(r0, MoveOperation::First),
                 Operation::CanReadCursor(r1, r0),  // Next
                 Operation::GoToIfFalse(JumpTarget::addr(8), r1), // Goto End
-                Operation::ReadCursor(vec![r2, r3], r0),
+                Operation::ReadCursor(vec![(0, r2), (1, r3)], r0),
                 Operation::Yield(vec![r2, r3]),
                 Operation::MoveCursor(r0, MoveOperation::Next),
                 Operation::GoTo(JumpTarget::addr(2)), // Goto Next
@@ -913,7 +1753,7 @@ mod test {
         ];
 
         let mut engine = Engine::with_program(&ops, 1, btree);
-        let yields = engine.run();
+        let yields = engine.run().unwrap();
 
         assert_eq!(yields.len(), 1);
         assert_eq!(yields[0][0], ScalarValue::Integer(42));
@@ -928,8 +1768,8 @@ mod test {
         // Insert test data
         let mut cursor = btree.open("test").unwrap();
         let mut cursor = cursor.open_readwrite();
-        cursor.insert(0, b"[100, 200]".to_vec());
-        cursor.insert(1, b"[300, 400]".to_vec());
+        cursor.insert(0, record::encode(&[ScalarValue::Integer(100), ScalarValue::Integer(200)]));
+        cursor.insert(1, record::encode(&[ScalarValue::Integer(300), ScalarValue::Integer(400)]));
         drop(cursor);
 
         let r0 = Reg::new(0);
@@ -942,7 +1782,7 @@ mod test {
             Operation::MoveCursor(r0, MoveOperation::First),
             Operation::CanReadCursor(r1, r0),
             Operation::GoToIfFalse(JumpTarget::addr(8), r1),
-            Operation::ReadCursor(vec![r2, r3], r0),
+            Operation::ReadCursor(vec![(0, r2), (1, r3)], r0),
             Operation::Yield(vec![r2, r3]),
             Operation::MoveCursor(r0, MoveOperation::Next),
             Operation::GoTo(JumpTarget::addr(2)),
@@ -950,7 +1790,7 @@ mod test {
         ];
 
         let mut engine = Engine::with_program(&ops, 4, btree);
-        let yields = engine.run();
+        let yields = engine.run().unwrap();
 
         assert_eq!(yields.len(), 2);
         assert_eq!(yields[0][0], ScalarValue::Integer(100));
@@ -975,14 +1815,14 @@ mod test {
             Operation::MoveCursor(r0, MoveOperation::First),
             Operation::CanReadCursor(r1, r0),
             Operation::GoToIfFalse(JumpTarget::addr(7), r1),
-            Operation::ReadCursor(vec![r2], r0),
+            Operation::ReadCursor(vec![(0, r2)], r0),
             Operation::Yield(vec![r2]),
             Operation::GoTo(JumpTarget::addr(2)),
             Operation::Halt,
         ];
 
         let mut engine = Engine::with_program(&ops, 3, btree);
-        let yields = engine.run();
+        let yields = engine.run().unwrap();
 
         assert_eq!(yields.len(), 0);
     }
@@ -1004,11 +1844,83 @@ mod test {
         ];
 
         let mut engine = Engine::with_program(&ops, 1, btree);
-        let yields = engine.run();
+        let yields = engine.run().unwrap();
 
         assert_eq!(yields.len(), 3);
         assert_eq!(yields[0][0], ScalarValue::Integer(1));
         assert_eq!(yields[1][0], ScalarValue::Integer(2));
         assert_eq!(yields[2][0], ScalarValue::Integer(3));
     }
+
+    fn scan_program() -> [Operation; 9] {
+        let r0 = Reg::new(0);
+        let r1 = Reg::new(1);
+        let r2 = Reg::new(2);
+
+        [
+            Operation::Open(r0, "test".to_string()),
+            Operation::MoveCursor(r0, MoveOperation::First),
+            Operation::CanReadCursor(r1, r0),
+            Operation::GoToIfFalse(JumpTarget::addr(8), r1),
+            Operation::ReadCursor(vec![(0, r2)], r0),
+            Operation::Yield(vec![r2]),
+            Operation::MoveCursor(r0, MoveOperation::Next),
+            Operation::GoTo(JumpTarget::addr(2)),
+            Operation::Halt,
+        ]
+    }
+
+    #[test]
+    fn test_rows_streamed() {
+        let test = TestDb::default();
+        let mut btree = test.btree;
+        btree.create_tree("test");
+
+        let mut cursor = btree.open("test").unwrap();
+        let mut cursor = cursor.open_readwrite();
+        for i in 0..100 {
+            cursor.insert(i, record::encode(&[ScalarValue::Integer(i as i64)]));
+        }
+        drop(cursor);
+
+        let mut engine = Engine::with_program(&scan_program(), 3, btree);
+
+        let mut rows = engine.rows();
+        assert_eq!(vec![ScalarValue::Integer(0)], rows.next().unwrap().unwrap());
+        assert_eq!(vec![ScalarValue::Integer(1)], rows.next().unwrap().unwrap());
+        drop(rows);
+
+        // Only the first two rows were read, and dropping the rows closed the cursor
+        assert_eq!(6, engine.program.curent_operation_index());
+        assert!(engine.registers.get(Reg::new(0)).unwrap().cursor().is_none());
+
+        // Reset runs the program from the start again
+        engine.reset();
+        let rows: Vec<_> = engine.rows().map(Result::unwrap).collect();
+        assert_eq!(100, rows.len());
+        assert_eq!(vec![ScalarValue::Integer(99)], rows[99]);
+
+        engine.reset();
+        assert_eq!(rows, engine.run().unwrap());
+    }
+
+    #[test]
+    fn test_rows_stop_after_error() {
+        let r0 = Reg::new(0);
+        let r1 = Reg::new(1);
+        let ops = [
+            Operation::StoreValue(r0, ScalarValue::Integer(1)),
+            Operation::Yield(vec![r0]),
+            Operation::StoreValue(r1, ScalarValue::Integer(0)),
+            Operation::DivideValue(r0, r0, r1),
+            Operation::Yield(vec![r0]),
+            Operation::Halt,
+        ];
+        let mut engine = Engine::new(Registers::new(2), ops.as_slice().into());
+
+        let mut rows = engine.rows();
+        assert_eq!(vec![ScalarValue::Integer(1)], rows.next().unwrap().unwrap());
+        assert_eq!(3, rows.next().unwrap().unwrap_err().pc);
+        assert!(rows.next().is_none());
+    }
 }
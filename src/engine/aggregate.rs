@@ -0,0 +1,158 @@
+use crate::scalarvalue::{ScalarValue, ValueError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+/// The running state of an aggregate over the rows of one group.
+/// NULLs are skipped, so an aggregate of nothing but NULLs is NULL, except COUNT which is 0.
+#[derive(Clone, Debug)]
+pub struct Accumulator {
+    function: AggregateFunction,
+    /// Values seen so far, not counting NULLs
+    count: i64,
+    /// The sum, minimum or maximum so far, NULL until a value is seen
+    value: ScalarValue,
+}
+
+impl Accumulator {
+    pub fn new(function: AggregateFunction) -> Accumulator {
+        Accumulator {
+            function,
+            count: 0,
+            value: ScalarValue::Null,
+        }
+    }
+
+    /// Add a value, or with None count a row, as `count(*)` does
+    pub fn step(&mut self, value: Option<&ScalarValue>) -> Result<(), ValueError> {
+        let Some(value) = value else {
+            self.count += 1;
+            return Ok(());
+        };
+        if value.is_null() {
+            return Ok(());
+        }
+
+        match self.function {
+            AggregateFunction::Count => {}
+            AggregateFunction::Sum | AggregateFunction::Avg => {
+                let name = if self.function == AggregateFunction::Sum { "SUM" } else { "AVG" };
+                if !matches!(value, ScalarValue::Integer(_) | ScalarValue::Floating(_)) {
+                    return Err(ValueError::TypeMismatch(name, vec![value.clone()]));
+                }
+
+                self.value = if self.value.is_null() {
+                    value.clone()
+                } else {
+                    self.value.checked_add(value)?
+                };
+            }
+            AggregateFunction::Min => {
+                if self.value.is_null() || value.sort_cmp(&self.value).is_lt() {
+                    self.value = value.clone();
+                }
+            }
+            AggregateFunction::Max => {
+                if self.value.is_null() || value.sort_cmp(&self.value).is_gt() {
+                    self.value = value.clone();
+                }
+            }
+        }
+        self.count += 1;
+
+        Ok(())
+    }
+
+    /// The result over every value so far, an average is always a float
+    pub fn finish(&self) -> Result<ScalarValue, ValueError> {
+        match self.function {
+            AggregateFunction::Count => Ok(ScalarValue::Integer(self.count)),
+            AggregateFunction::Avg if self.count == 0 => Ok(ScalarValue::Null),
+            AggregateFunction::Avg => self.value.checked_div(&ScalarValue::Floating(self.count as f64)),
+            AggregateFunction::Sum | AggregateFunction::Min | AggregateFunction::Max => Ok(self.value.clone()),
+        }
+    }
+}
+
+impl std::fmt::Display for AggregateFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AggregateFunction::Count => write!(f, "count"),
+            AggregateFunction::Sum => write!(f, "sum"),
+            AggregateFunction::Avg => write!(f, "avg"),
+            AggregateFunction::Min => write!(f, "min"),
+            AggregateFunction::Max => write!(f, "max"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Accumulator, AggregateFunction};
+    use crate::scalarvalue::{ScalarValue, ValueError};
+    use crate::test::{i, s};
+
+    fn aggregate(function: AggregateFunction, values: &[ScalarValue]) -> Result<ScalarValue, ValueError> {
+        let mut accumulator = Accumulator::new(function);
+        for value in values {
+            accumulator.step(Some(value))?;
+        }
+
+        accumulator.finish()
+    }
+
+    #[test]
+    fn skips_nulls() {
+        let values = [i(4), ScalarValue::Null, i(-2), i(7)];
+
+        assert_eq!(Ok(i(3)), aggregate(AggregateFunction::Count, &values));
+        assert_eq!(Ok(i(9)), aggregate(AggregateFunction::Sum, &values));
+        assert_eq!(Ok(ScalarValue::Floating(3.0)), aggregate(AggregateFunction::Avg, &values));
+        assert_eq!(Ok(i(-2)), aggregate(AggregateFunction::Min, &values));
+        assert_eq!(Ok(i(7)), aggregate(AggregateFunction::Max, &values));
+    }
+
+    #[test]
+    fn only_nulls() {
+        let values = [ScalarValue::Null, ScalarValue::Null];
+
+        assert_eq!(Ok(ScalarValue::Integer(0)), aggregate(AggregateFunction::Count, &values));
+        for function in [AggregateFunction::Sum, AggregateFunction::Avg, AggregateFunction::Min, AggregateFunction::Max] {
+            assert_eq!(Ok(ScalarValue::Null), aggregate(function, &values));
+            assert_eq!(Ok(ScalarValue::Null), aggregate(function, &[]));
+        }
+    }
+
+    #[test]
+    fn count_rows() {
+        let mut accumulator = Accumulator::new(AggregateFunction::Count);
+        accumulator.step(None).unwrap();
+        accumulator.step(None).unwrap();
+        accumulator.step(Some(&ScalarValue::Null)).unwrap();
+
+        assert_eq!(Ok(ScalarValue::Integer(2)), accumulator.finish());
+    }
+
+    #[test]
+    fn mixed_values() {
+        assert_eq!(
+            Ok(ScalarValue::Floating(3.5)),
+            aggregate(AggregateFunction::Sum, &[ScalarValue::Integer(1), ScalarValue::Floating(2.5)])
+        );
+        assert_eq!(Ok(s("b")), aggregate(AggregateFunction::Max, &[s("a"), s("b"), ScalarValue::Integer(9)]));
+        assert_eq!(
+            Err(ValueError::TypeMismatch("SUM", vec![s("a")])),
+            aggregate(AggregateFunction::Sum, &[ScalarValue::Integer(1), s("a")])
+        );
+        assert_eq!(
+            Err(ValueError::Overflow),
+            aggregate(AggregateFunction::Sum, &[ScalarValue::Integer(i64::MAX), ScalarValue::Integer(1)])
+        );
+    }
+}
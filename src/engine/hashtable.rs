@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use crate::record;
+use crate::scalarvalue::ScalarValue;
+
+/// Rows looked up by the values of their keys, the build side of a hash join.
+///
+/// Keys are compared by their encoding as a record, once whole floats are made integers, so keys are the
+/// same exactly when `=` finds them equal (1 and 1.0, or 0.0 and -0.0). A key holding a NULL or NaN never
+/// matches, so rows with one aren't kept.
+#[derive(Clone, Debug, Default)]
+pub struct HashTable {
+    rows: HashMap<Vec<u8>, Vec<Vec<ScalarValue>>>,
+    /// The key last looked up and the index of the current row among its rows
+    found: Option<(Vec<u8>, usize)>,
+}
+
+impl HashTable {
+    pub fn insert(&mut self, keys: &[ScalarValue], row: Vec<ScalarValue>) {
+        if let Some(key) = encode_keys(keys) {
+            self.rows.entry(key).or_default().push(row);
+        }
+    }
+
+    /// Move to the first row with these keys
+    pub fn find(&mut self, keys: &[ScalarValue]) {
+        self.found = encode_keys(keys).map(|key| (key, 0));
+    }
+
+    /// The row the table is on, None once every row with the keys has been read
+    pub fn current(&self) -> Option<&[ScalarValue]> {
+        let (key, index) = self.found.as_ref()?;
+        self.rows.get(key)?.get(*index).map(Vec::as_slice)
+    }
+
+    /// Move on to the next row with the same keys
+    pub fn next(&mut self) {
+        if let Some((_, index)) = &mut self.found {
+            *index += 1;
+        }
+    }
+}
+
+/// The keys as they are hashed, None if one can't equal anything
+fn encode_keys(keys: &[ScalarValue]) -> Option<Vec<u8>> {
+    // Just past the largest integer, every float below it with no fraction is one
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+
+    let keys = keys
+        .iter()
+        .map(|key| match key {
+            ScalarValue::Null => None,
+            ScalarValue::Floating(f) if f.is_nan() => None,
+            ScalarValue::Floating(f) if f.fract() == 0.0 && (-LIMIT..LIMIT).contains(f) => {
+                Some(ScalarValue::Integer(*f as i64))
+            }
+            key => Some(key.clone()),
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(record::encode(&keys))
+}
+
+#[cfg(test)]
+mod test {
+    use super::HashTable;
+    use crate::scalarvalue::ScalarValue;
+    use crate::test::{f, i, s};
+
+    fn found(table: &mut HashTable, keys: &[ScalarValue]) -> Vec<Vec<ScalarValue>> {
+        table.find(keys);
+
+        let mut rows = vec![];
+        while let Some(row) = table.current() {
+            rows.push(row.to_vec());
+            table.next();
+        }
+
+        rows
+    }
+
+    #[test]
+    fn finds_rows_in_order() {
+        let mut table = HashTable::default();
+        table.insert(&[i(1)], vec![s("a")]);
+        table.insert(&[i(2)], vec![s("b")]);
+        table.insert(&[i(1)], vec![s("c")]);
+
+        assert_eq!(vec![vec![s("a")], vec![s("c")]], found(&mut table, &[i(1)]));
+        assert_eq!(vec![vec![s("b")]], found(&mut table, &[i(2)]));
+        assert!(found(&mut table, &[i(3)]).is_empty());
+    }
+
+    #[test]
+    fn numbers_match_as_equals_does() {
+        let mut table = HashTable::default();
+        table.insert(&[i(1)], vec![i(1)]);
+        table.insert(&[f(2.0)], vec![i(2)]);
+        table.insert(&[f(-0.0)], vec![i(3)]);
+        table.insert(&[f(2.5)], vec![i(4)]);
+        table.insert(&[f(f64::NAN)], vec![i(5)]);
+        table.insert(&[i(1 << 53)], vec![i(6)]);
+
+        assert_eq!(vec![vec![i(1)]], found(&mut table, &[f(1.0)]));
+        assert_eq!(vec![vec![i(2)]], found(&mut table, &[i(2)]));
+        assert_eq!(vec![vec![i(3)]], found(&mut table, &[f(0.0)]));
+        assert_eq!(vec![vec![i(3)]], found(&mut table, &[i(0)]));
+        assert_eq!(vec![vec![i(4)]], found(&mut table, &[f(2.5)]));
+        assert!(found(&mut table, &[f(f64::NAN)]).is_empty());
+        assert_eq!(vec![vec![i(6)]], found(&mut table, &[f(2f64.powi(53))]));
+        assert!(found(&mut table, &[i((1 << 53) + 1)]).is_empty());
+    }
+
+    #[test]
+    fn nulls_never_match() {
+        let mut table = HashTable::default();
+        table.insert(&[ScalarValue::Null, i(1)], vec![i(1)]);
+        table.insert(&[i(1), i(1)], vec![i(2)]);
+
+        assert!(found(&mut table, &[ScalarValue::Null, i(1)]).is_empty());
+        assert_eq!(vec![vec![i(2)]], found(&mut table, &[i(1), i(1)]));
+    }
+}
@@ -1,4 +1,5 @@
-use super::scalarvalue::ScalarValue;
+use super::aggregate::AggregateFunction;
+use crate::scalarvalue::ScalarValue;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Reg(usize);
@@ -65,23 +66,76 @@ pub enum Operation {
     AndValue(Reg, Reg, Reg),             // Reg = Reg && Reg
     OrValue(Reg, Reg, Reg),              // Reg = Reg || Reg
     NotValue(Reg, Reg),                  // Reg = !Reg
+    LeftShiftValue(Reg, Reg, Reg),       // Reg = Reg << Reg
+    RightShiftValue(Reg, Reg, Reg),      // Reg = Reg >> Reg
+    BitAndValue(Reg, Reg, Reg),          // Reg = Reg & Reg
+    BitOrValue(Reg, Reg, Reg),           // Reg = Reg | Reg
+    BitXorValue(Reg, Reg, Reg),          // Reg = Reg ^ Reg
+    BitNotValue(Reg, Reg),               // Reg = ~Reg
+    IsNullValue(Reg, Reg),               // Reg = Reg IS NULL
+    IsNotNullValue(Reg, Reg),            // Reg = Reg IS NOT NULL
     NegateValue(Reg, Reg),               // Reg = -Reg (arithmetic negation)
     CopyValue(Reg, Reg),                 // Reg = Reg (copy value)
 
     // Db
     Open(Reg, String),
     MoveCursor(Reg, MoveOperation),
-    ReadCursor(Vec<Reg>, Reg), // TODO: allow program to select which columns to read and type check
+    ReadCursor(Vec<(usize, Reg)>, Reg), // (column, Reg) = column of the row at the cursor
     CanReadCursor(Reg, Reg),   // Reg = CanReadCursor(Reg)
+    ReadRowid(Reg, Reg),       // Reg = rowid of the row at the cursor
+    SeekRowid(Reg, Reg, Reg),  // move the cursor to the row with rowid Reg, Reg = whether there is one
+    MakeRecord(Reg, Vec<Reg>), // Reg = record of the values
+    InsertNextRowid(Reg, Reg), // insert the record Reg with the next rowid of the cursor's table
+    ReplaceRecord(Reg, Reg),   // replace the row at the cursor with the record Reg
+    DeleteRecord(Reg),         // remove the row at the cursor, its next is the row after
+
+    // Hash table
+    HashTableOpen(Reg),                     // Reg = new hash table
+    HashTableInsert(Reg, Vec<Reg>, Vec<Reg>), // insert the row under the keys
+    HashTableFind(Reg, Vec<Reg>),           // move to the first row with the keys
+    HashTableNext(Reg),
+    CanReadHashTable(Reg, Reg),             // Reg = the hash table has a current row
+    ReadHashTable(Vec<(usize, Reg)>, Reg),  // (column, Reg) = column of the current row
+
+    // Sorter
+    SorterOpen(Reg, Vec<bool>),          // Reg = new sorter, with whether each key sorts descending
+    SorterInsert(Reg, Vec<Reg>),         // insert the keys followed by the rest of the row
+    SorterSort(Reg),                     // finish inserting, move to the first row in order
+    SorterNext(Reg),
+    CanReadSorter(Reg, Reg),             // Reg = the sorter has a current row
+    ReadSorter(Vec<(usize, Reg)>, Reg),  // (column, Reg) = column of the current row
+
+    // Aggregate
+    AggregateReset(Reg, AggregateFunction), // Reg = new accumulator
+    AggregateStep(Reg, Option<Reg>),        // add Reg to the accumulator, None counts the row
+    AggregateFinal(Reg, Reg),               // Reg = result of the accumulator
 
     // Control Flow
     Yield(Vec<Reg>),
     GoTo(JumpTarget),
     GoToIfEqualValue(JumpTarget, Reg, Reg),
-    GoToIfFalse(JumpTarget, Reg),
+    GoToIfFalse(JumpTarget, Reg), // jumps if Reg is false or NULL
+    GoToIfTrue(JumpTarget, Reg),
+    GoToIfDistinct(JumpTarget, Reg, Reg), // jumps unless `=` finds them equal, NULL isn't distinct from NULL
+    Once(JumpTarget, Reg),        // jumps if an earlier Once set Reg, otherwise sets it
     Halt,
 }
 
+impl Operation {
+    /// Where a jump goes, so targets can be resolved and relocated without listing every jump
+    pub fn jump_target_mut(&mut self) -> Option<&mut JumpTarget> {
+        match self {
+            Operation::GoTo(target)
+            | Operation::GoToIfEqualValue(target, _, _)
+            | Operation::GoToIfFalse(target, _)
+            | Operation::GoToIfTrue(target, _)
+            | Operation::GoToIfDistinct(target, _, _)
+            | Operation::Once(target, _) => Some(target),
+            _ => None,
+        }
+    }
+}
+
 pub(crate) struct ProgramCode {
     operations: Vec<Operation>,
     curent_operation_index: usize,
@@ -97,22 +151,28 @@ impl From<&[Operation]> for ProgramCode {
 }
 
 impl ProgramCode {
-    pub fn advance(&mut self) -> Operation {
-        let op = self.curent();
+    /// The next operation to run, or None if the program counter has run off the end
+    pub fn advance(&mut self) -> Option<Operation> {
+        let op = self.curent()?;
 
         match op {
             Operation::Halt => {}
             _ => self.curent_operation_index += 1,
         };
 
-        op
+        Some(op)
     }
 
-    fn curent(&self) -> Operation {
-        self.operations
-            .get(self.curent_operation_index)
-            .unwrap()
-            .clone()
+    fn curent(&self) -> Option<Operation> {
+        self.operations.get(self.curent_operation_index).cloned()
+    }
+
+    pub(crate) fn curent_operation_index(&self) -> usize {
+        self.curent_operation_index
+    }
+
+    pub(crate) fn operation(&self, index: usize) -> Option<&Operation> {
+        self.operations.get(index)
     }
 
     pub(crate) fn set_next_operation_index(&mut self, index: usize) {
@@ -182,6 +242,14 @@ impl std::fmt::Display for Operation {
             AndValue(d, a, b) => write!(f, "{:10} {}, {}, {}", "And".cyan().bold(), d, a, b),
             OrValue(d, a, b) => write!(f, "{:10} {}, {}, {}", "Or".cyan().bold(), d, a, b),
             NotValue(d, s) => write!(f, "{:10} {}, {}", "Not".cyan().bold(), d, s),
+            LeftShiftValue(d, a, b) => write!(f, "{:10} {}, {}, {}", "Shl".cyan().bold(), d, a, b),
+            RightShiftValue(d, a, b) => write!(f, "{:10} {}, {}, {}", "Shr".cyan().bold(), d, a, b),
+            BitAndValue(d, a, b) => write!(f, "{:10} {}, {}, {}", "BitAnd".cyan().bold(), d, a, b),
+            BitOrValue(d, a, b) => write!(f, "{:10} {}, {}, {}", "BitOr".cyan().bold(), d, a, b),
+            BitXorValue(d, a, b) => write!(f, "{:10} {}, {}, {}", "BitXor".cyan().bold(), d, a, b),
+            BitNotValue(d, s) => write!(f, "{:10} {}, {}", "BitNot".cyan().bold(), d, s),
+            IsNullValue(d, s) => write!(f, "{:10} {}, {}", "IsNull".cyan().bold(), d, s),
+            IsNotNullValue(d, s) => write!(f, "{:10} {}, {}", "NotNull".cyan().bold(), d, s),
             NegateValue(d, s) => write!(f, "{:10} {}, {}", "Neg".cyan().bold(), d, s),
             CopyValue(d, s) => write!(f, "{:10} {}, {}", "Copy".cyan().bold(), d, s),
 
@@ -191,11 +259,74 @@ impl std::fmt::Display for Operation {
                 write!(f, "{:10} {}, {}", "Open".cyan().bold(), r, format!("\"{}\"", table).green())
             }
             MoveCursor(r, op) => write!(f, "{:10} {}, {}", "MoveCursor".cyan().bold(), r, op),
-            ReadCursor(regs, cursor) => {
-                let regs_str: Vec<String> = regs.iter().map(|r| format!("{}", r)).collect();
+            ReadCursor(columns, cursor) => {
+                let regs_str: Vec<String> = columns.iter().map(|(c, r)| format!("{}={}", r, c)).collect();
                 write!(f, "{:10} [{}], {}", "ReadCursor".cyan().bold(), regs_str.join(", "), cursor)
             }
             CanReadCursor(dest, cursor) => write!(f, "{:10} {}, {}", "CanRead".cyan().bold(), dest, cursor),
+            ReadRowid(dest, cursor) => write!(f, "{:10} {}, {}", "ReadRowid".cyan().bold(), dest, cursor),
+            SeekRowid(dest, cursor, key) => {
+                write!(f, "{:10} {}, {}, {}", "SeekRowid".cyan().bold(), dest, cursor, key)
+            }
+            MakeRecord(dest, regs) => {
+                let regs_str: Vec<String> = regs.iter().map(|r| format!("{}", r)).collect();
+                write!(f, "{:10} {}, [{}]", "MakeRecord".cyan().bold(), dest, regs_str.join(", "))
+            }
+            InsertNextRowid(cursor, record) => {
+                write!(f, "{:10} {}, {}", "InsertNext".cyan().bold(), cursor, record)
+            }
+            ReplaceRecord(cursor, record) => {
+                write!(f, "{:10} {}, {}", "Replace".cyan().bold(), cursor, record)
+            }
+            DeleteRecord(cursor) => write!(f, "{:10} {}", "Delete".cyan().bold(), cursor),
+
+            // Hash table operations
+            HashTableOpen(r) => write!(f, "{:10} {}", "HashOpen".cyan().bold(), r),
+            HashTableInsert(table, keys, row) => {
+                let keys_str: Vec<String> = keys.iter().map(|r| format!("{}", r)).collect();
+                let row_str: Vec<String> = row.iter().map(|r| format!("{}", r)).collect();
+                write!(
+                    f,
+                    "{:10} {}, [{}], [{}]",
+                    "HashInsert".cyan().bold(),
+                    table,
+                    keys_str.join(", "),
+                    row_str.join(", ")
+                )
+            }
+            HashTableFind(table, keys) => {
+                let keys_str: Vec<String> = keys.iter().map(|r| format!("{}", r)).collect();
+                write!(f, "{:10} {}, [{}]", "HashFind".cyan().bold(), table, keys_str.join(", "))
+            }
+            HashTableNext(r) => write!(f, "{:10} {}", "HashNext".cyan().bold(), r),
+            CanReadHashTable(dest, table) => write!(f, "{:10} {}, {}", "CanReadHash".cyan().bold(), dest, table),
+            ReadHashTable(columns, table) => {
+                let regs_str: Vec<String> = columns.iter().map(|(c, r)| format!("{}={}", r, c)).collect();
+                write!(f, "{:10} [{}], {}", "ReadHash".cyan().bold(), regs_str.join(", "), table)
+            }
+
+            // Sorter operations
+            SorterOpen(r, descending) => {
+                let keys: Vec<&str> = descending.iter().map(|d| if *d { "desc" } else { "asc" }).collect();
+                write!(f, "{:10} {}, [{}]", "SorterOpen".cyan().bold(), r, keys.join(", "))
+            }
+            SorterInsert(sorter, regs) => {
+                let regs_str: Vec<String> = regs.iter().map(|r| format!("{}", r)).collect();
+                write!(f, "{:10} {}, [{}]", "SorterInsert".cyan().bold(), sorter, regs_str.join(", "))
+            }
+            SorterSort(r) => write!(f, "{:10} {}", "SorterSort".cyan().bold(), r),
+            SorterNext(r) => write!(f, "{:10} {}", "SorterNext".cyan().bold(), r),
+            CanReadSorter(dest, sorter) => write!(f, "{:10} {}, {}", "CanReadSorter".cyan().bold(), dest, sorter),
+            ReadSorter(columns, sorter) => {
+                let regs_str: Vec<String> = columns.iter().map(|(c, r)| format!("{}={}", r, c)).collect();
+                write!(f, "{:10} [{}], {}", "ReadSorter".cyan().bold(), regs_str.join(", "), sorter)
+            }
+
+            // Aggregate operations
+            AggregateReset(r, function) => write!(f, "{:10} {}, {}", "AggReset".cyan().bold(), r, function),
+            AggregateStep(r, Some(value)) => write!(f, "{:10} {}, {}", "AggStep".cyan().bold(), r, value),
+            AggregateStep(r, None) => write!(f, "{:10} {}, *", "AggStep".cyan().bold(), r),
+            AggregateFinal(d, r) => write!(f, "{:10} {}, {}", "AggFinal".cyan().bold(), d, r),
 
             // Control flow
             Yield(regs) => {
@@ -207,6 +338,11 @@ impl std::fmt::Display for Operation {
                 write!(f, "{:10} {}, {}, {}", "GoToIfEq".cyan().bold(), target, a, b)
             }
             GoToIfFalse(target, r) => write!(f, "{:10} {}, {}", "GoToIfNot".cyan().bold(), target, r),
+            GoToIfTrue(target, r) => write!(f, "{:10} {}, {}", "GoToIf".cyan().bold(), target, r),
+            GoToIfDistinct(target, a, b) => {
+                write!(f, "{:10} {}, {}, {}", "GoToIfDistinct".cyan().bold(), target, a, b)
+            }
+            Once(target, r) => write!(f, "{:10} {}, {}", "Once".cyan().bold(), target, r),
             Halt => write!(f, "{}", "Halt".cyan().bold()),
         }
     }
@@ -1,15 +1,18 @@
+use crate::scalarvalue::ScalarValue;
 use crate::storage::CursorHandle;
 
-use super::{
-    program::Reg,
-    scalarvalue::ScalarValue,
-};
+use super::{aggregate::Accumulator, hashtable::HashTable, program::Reg, sorter::Sorter};
 
 #[derive(Clone, Debug)]
 pub enum RegisterValue {
     None,
     ScalarValue(ScalarValue),
     CursorHandle(CursorHandle),
+    Sorter(Box<Sorter>),
+    Accumulator(Accumulator),
+    HashTable(Box<HashTable>),
+    /// A row encoded as a record, ready to be stored
+    Record(Vec<u8>),
 }
 
 #[derive(Clone, Debug)]
@@ -17,11 +20,6 @@ pub struct Registers {
     file: Vec<RegisterValue>,
 }
 
-pub struct RegisterIterator<'a, RegIter: Iterator<Item = &'a Reg>> {
-    values: &'a [RegisterValue],
-    regs: RegIter,
-}
-
 impl Default for RegisterValue {
     fn default() -> Self {
         Self::None
@@ -36,94 +34,79 @@ impl RegisterValue {
         }
     }
 
-    pub(crate) fn scalar_mut(&mut self) -> Option<&mut ScalarValue> {
-        if let RegisterValue::ScalarValue(ref mut s) = self {
-            Some(s)
-        } else {
-            None
+    pub(crate) fn cursor(&self) -> Option<&CursorHandle> {
+        match self {
+            RegisterValue::CursorHandle(c) => Some(c),
+            _ => None,
         }
     }
 
-    pub fn integer(&self) -> Option<i64> {
-        if let RegisterValue::ScalarValue(scalar_value) = self {
-            if let ScalarValue::Integer(x) = scalar_value {
-                Some(*x)
-            } else {
-                None
-            }
+    pub(crate) fn cursor_mut(&mut self) -> Option<&mut CursorHandle> {
+        if let RegisterValue::CursorHandle(ref mut c) = self {
+            Some(c)
         } else {
             None
         }
     }
 
-    pub fn integer_mut(&mut self) -> Option<&mut i64> {
-        if let RegisterValue::ScalarValue(ref mut scalar_value) = self {
-            if let ScalarValue::Integer(ref mut x) = scalar_value {
-                Some(x)
-            } else {
-                None
-            }
-        } else {
-            None
+    pub(crate) fn sorter(&self) -> Option<&Sorter> {
+        match self {
+            RegisterValue::Sorter(s) => Some(s),
+            _ => None,
         }
     }
 
-    pub fn boolean(&self) -> Option<bool> {
-        if let RegisterValue::ScalarValue(scalar_value) = self {
-            if let ScalarValue::Boolean(x) = scalar_value {
-                Some(*x)
-            } else {
-                None
-            }
-        } else {
-            None
+    pub(crate) fn sorter_mut(&mut self) -> Option<&mut Sorter> {
+        match self {
+            RegisterValue::Sorter(s) => Some(s),
+            _ => None,
         }
     }
 
-    pub(crate) fn cursor(&self) -> Option<&CursorHandle> {
+    pub(crate) fn hash_table_mut(&mut self) -> Option<&mut HashTable> {
         match self {
-            RegisterValue::CursorHandle(c) => Some(c),
+            RegisterValue::HashTable(h) => Some(h),
             _ => None,
         }
     }
 
-    pub(crate) fn cursor_mut(&mut self) -> Option<&mut CursorHandle> {
-        if let RegisterValue::CursorHandle(ref mut c) = self {
-            Some(c)
-        } else {
-            None
+    pub(crate) fn record(&self) -> Option<&[u8]> {
+        match self {
+            RegisterValue::Record(r) => Some(r),
+            _ => None,
         }
     }
-}
-
-impl<'a, RegIter: Iterator<Item = &'a Reg>> Iterator for RegisterIterator<'a, RegIter> {
-    type Item = &'a RegisterValue;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let r = self.regs.next()?;
-        Some(self.values.get(r.index()).unwrap())
+    pub(crate) fn accumulator_mut(&mut self) -> Option<&mut Accumulator> {
+        match self {
+            RegisterValue::Accumulator(a) => Some(a),
+            _ => None,
+        }
     }
 }
 
 impl Registers {
-    pub fn get_mut(&mut self, reg: Reg) -> &mut RegisterValue {
-        self.file.get_mut(reg.index()).unwrap()
+    pub fn get_mut(&mut self, reg: Reg) -> Option<&mut RegisterValue> {
+        self.file.get_mut(reg.index())
     }
 
-    pub fn get(&self, reg: Reg) -> &RegisterValue {
-        self.file.get(reg.index()).unwrap()
+    pub fn get(&self, reg: Reg) -> Option<&RegisterValue> {
+        self.file.get(reg.index())
     }
 
-    pub fn get_range<'a>(
-        &'a self,
-        regs: &'a [Reg],
-    ) -> RegisterIterator<'a, core::slice::Iter<'a, Reg>> {
-        RegisterIterator {
-            values: &self.file,
-            regs: regs.iter(),
+    /// Drop every cursor held in a register, and every sorter along with any rows it spilled
+    pub(crate) fn close_cursors(&mut self) {
+        for value in self.file.iter_mut() {
+            if value.cursor().is_some() || value.sorter().is_some() {
+                *value = RegisterValue::None;
+            }
         }
     }
 
+    pub(crate) fn clear(&mut self) {
+        self.file.fill(RegisterValue::None);
+    }
+
     pub(crate) fn new(size: usize) -> Registers {
         let mut file = Vec::with_capacity(size);
 
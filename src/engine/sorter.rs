@@ -0,0 +1,313 @@
+use std::cmp::Ordering;
+use std::io::Read;
+
+use crate::storage::{BTree, CursorHandle};
+
+use crate::record::{self, Record, RecordError};
+use crate::scalarvalue::ScalarValue;
+
+/// How many bytes of rows a sorter keeps in memory before it spills them, unless the engine is told otherwise
+pub const DEFAULT_BUDGET: usize = 4 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum SorterError {
+    /// The temporary btree for spilled rows couldn't be created
+    Spill(std::io::Error),
+    Read(std::io::Error),
+    Record(RecordError),
+}
+
+impl std::fmt::Display for SorterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SorterError::Spill(e) => write!(f, "couldn't spill rows to a temporary file: {e}"),
+            SorterError::Read(e) => write!(f, "couldn't read back spilled rows: {e}"),
+            SorterError::Record(e) => write!(f, "couldn't decode a spilled row: {e:?}"),
+        }
+    }
+}
+
+/// Sorts rows by their leading columns, the keys.
+///
+/// Rows are kept in memory until they take up more than the budget, then they are sorted and written
+/// out as a run to a temporary btree. Once every row is in, the runs are merged as the rows are read.
+/// The sort is stable, rows with equal keys come out in the order they went in.
+///
+/// Rows of an encrypted database are spilled to an encrypted btree, see `BTree::temporary`.
+#[derive(Clone, Debug)]
+pub struct Sorter {
+    /// Whether each key sorts descending, there is one entry per key
+    descending: Vec<bool>,
+    budget: usize,
+    encrypted: bool,
+    rows: Vec<Vec<ScalarValue>>,
+    /// Size of the rows in memory, encoded as records
+    size: usize,
+    spill: Option<BTree>,
+    runs: usize,
+    output: Output,
+}
+
+#[derive(Clone, Debug)]
+enum Output {
+    /// Still taking rows
+    Unsorted,
+    /// Every row fit in memory, reading the row at this index
+    Memory(usize),
+    /// Reading from spilled runs, the current row is the head of the run at this index
+    Merge(Vec<Run>, Option<usize>),
+}
+
+#[derive(Clone, Debug)]
+struct Run {
+    cursor: CursorHandle,
+    /// The row at the cursor, None once the run is used up
+    head: Option<Vec<ScalarValue>>,
+}
+
+impl Sorter {
+    pub fn new(descending: Vec<bool>, budget: usize, encrypted: bool) -> Sorter {
+        Sorter {
+            descending,
+            budget,
+            encrypted,
+            rows: vec![],
+            size: 0,
+            spill: None,
+            runs: 0,
+            output: Output::Unsorted,
+        }
+    }
+
+    pub fn insert(&mut self, row: Vec<ScalarValue>) -> Result<(), SorterError> {
+        self.size += record::encode(&row).len();
+        self.rows.push(row);
+
+        if self.size > self.budget {
+            self.spill_run()?;
+        }
+
+        Ok(())
+    }
+
+    /// Finish taking rows, and move to the first row in order
+    pub fn sort(&mut self) -> Result<(), SorterError> {
+        if self.runs == 0 {
+            sort_rows(&mut self.rows, &self.descending);
+            self.output = Output::Memory(0);
+            return Ok(());
+        }
+
+        if !self.rows.is_empty() {
+            self.spill_run()?;
+        }
+
+        let spill = self.spill.as_ref().expect("spilled runs to have a btree");
+        let mut runs = vec![];
+        for run in 0..self.runs {
+            let mut cursor = spill.open(&run_name(run)).expect("spilled run to exist");
+            cursor
+                .open_readwrite()
+                .first()
+                .map_err(|e| SorterError::Read(e.into()))?;
+            let head = read_row(&mut cursor)?;
+            runs.push(Run { cursor, head });
+        }
+
+        let current = self.smallest(&runs);
+        self.output = Output::Merge(runs, current);
+
+        Ok(())
+    }
+
+    /// The row the sorter is on, None before `sort` or once every row has been read
+    pub fn current(&self) -> Option<&[ScalarValue]> {
+        match &self.output {
+            Output::Unsorted => None,
+            Output::Memory(index) => self.rows.get(*index).map(Vec::as_slice),
+            Output::Merge(runs, current) => current.and_then(|run| runs[run].head.as_deref()),
+        }
+    }
+
+    /// Move on to the next row in order
+    pub fn next(&mut self) -> Result<(), SorterError> {
+        let mut output = std::mem::replace(&mut self.output, Output::Unsorted);
+
+        match &mut output {
+            Output::Unsorted => {}
+            Output::Memory(index) => *index += 1,
+            Output::Merge(runs, current) => {
+                if let Some(run) = *current {
+                    let run = &mut runs[run];
+                    run.cursor
+                        .open_readwrite()
+                        .next()
+                        .map_err(|e| SorterError::Read(e.into()))?;
+                    run.head = read_row(&mut run.cursor)?;
+                    *current = self.smallest(runs);
+                }
+            }
+        }
+
+        self.output = output;
+        Ok(())
+    }
+
+    /// The run whose head comes first, the earliest run wins a tie to keep the sort stable
+    fn smallest(&self, runs: &[Run]) -> Option<usize> {
+        let mut smallest: Option<(usize, &[ScalarValue])> = None;
+
+        for (index, run) in runs.iter().enumerate() {
+            let Some(head) = &run.head else {
+                continue;
+            };
+            match smallest {
+                Some((_, row)) if compare(head, row, &self.descending) != Ordering::Less => {}
+                _ => smallest = Some((index, head)),
+            }
+        }
+
+        smallest.map(|(index, _)| index)
+    }
+
+    /// Sort the rows in memory and write them out as a new run
+    fn spill_run(&mut self) -> Result<(), SorterError> {
+        sort_rows(&mut self.rows, &self.descending);
+
+        if self.spill.is_none() {
+            self.spill = Some(BTree::temporary(self.encrypted).map_err(SorterError::Spill)?);
+        }
+        let spill = self.spill.as_mut().unwrap();
+
+        let name = run_name(self.runs);
+        spill.create_tree(&name);
+        let mut cursor = spill.open(&name).expect("run to have been created");
+        let mut cursor = cursor.open_readwrite();
+        for (key, row) in self.rows.drain(..).enumerate() {
+            cursor.insert(key as u64, record::encode(&row));
+        }
+
+        self.runs += 1;
+        self.size = 0;
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn runs(&self) -> usize {
+        self.runs
+    }
+}
+
+fn run_name(run: usize) -> String {
+    format!("run{run}")
+}
+
+fn compare(lhs: &[ScalarValue], rhs: &[ScalarValue], descending: &[bool]) -> Ordering {
+    for (key, descending) in descending.iter().enumerate() {
+        let ordering = lhs[key].sort_cmp(&rhs[key]);
+        let ordering = if *descending { ordering.reverse() } else { ordering };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    Ordering::Equal
+}
+
+fn sort_rows(rows: &mut [Vec<ScalarValue>], descending: &[bool]) {
+    rows.sort_by(|lhs, rhs| compare(lhs, rhs, descending));
+}
+
+fn read_row(cursor: &mut CursorHandle) -> Result<Option<Vec<ScalarValue>>, SorterError> {
+    let cursor = cursor.open_readonly();
+    let Some(mut entry) = cursor.get_entry().map_err(|e| SorterError::Read(e.into()))? else {
+        return Ok(None);
+    };
+
+    let mut data = vec![];
+    entry.read_to_end(&mut data).map_err(SorterError::Read)?;
+
+    let record = Record::parse(&data).map_err(SorterError::Record)?;
+    let row = (0..record.num_columns())
+        .map(|column| record.column(column))
+        .collect::<Result<_, _>>()
+        .map_err(SorterError::Record)?;
+
+    Ok(Some(row))
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sorter;
+    use crate::scalarvalue::ScalarValue;
+
+    fn sorted(sorter: &mut Sorter) -> Vec<Vec<ScalarValue>> {
+        sorter.sort().unwrap();
+
+        let mut rows = vec![];
+        while let Some(row) = sorter.current() {
+            rows.push(row.to_vec());
+            sorter.next().unwrap();
+        }
+
+        rows
+    }
+
+    fn row(key: i64, value: &str) -> Vec<ScalarValue> {
+        vec![ScalarValue::Integer(key), ScalarValue::String(value.to_string())]
+    }
+
+    #[test]
+    fn sorts_in_memory() {
+        let mut sorter = Sorter::new(vec![false], super::DEFAULT_BUDGET, false);
+        for (key, value) in [(3, "c"), (1, "a"), (2, "b"), (1, "a2")] {
+            sorter.insert(row(key, value)).unwrap();
+        }
+
+        assert_eq!(vec![row(1, "a"), row(1, "a2"), row(2, "b"), row(3, "c")], sorted(&mut sorter));
+        assert_eq!(0, sorter.runs());
+    }
+
+    #[test]
+    fn descending_keys() {
+        let mut sorter = Sorter::new(vec![true, false], super::DEFAULT_BUDGET, false);
+        for (key, value) in [(1, "b"), (2, "z"), (1, "a"), (3, "c")] {
+            sorter.insert(row(key, value)).unwrap();
+        }
+
+        assert_eq!(vec![row(3, "c"), row(2, "z"), row(1, "a"), row(1, "b")], sorted(&mut sorter));
+    }
+
+    #[test]
+    fn spills_over_budget() {
+        // Each row is a handful of bytes, so this spills every few rows
+        for encrypted in [false, true] {
+            let mut sorter = Sorter::new(vec![false], 64, encrypted);
+            let keys: Vec<i64> = (0..500).map(|i| (i * 7919) % 100).collect();
+            for (i, key) in keys.iter().enumerate() {
+                sorter.insert(row(*key, &i.to_string())).unwrap();
+            }
+
+            let rows = sorted(&mut sorter);
+            assert!(sorter.runs() > 10);
+            assert_eq!(encrypted, sorter.spill.as_ref().unwrap().is_encrypted());
+
+            // Same rows as a stable in-memory sort
+            let mut expected: Vec<_> = keys.iter().enumerate().map(|(i, key)| row(*key, &i.to_string())).collect();
+            expected.sort_by_key(|row| match row[0] {
+                ScalarValue::Integer(key) => key,
+                _ => unreachable!(),
+            });
+            assert_eq!(expected, rows);
+        }
+    }
+
+    #[test]
+    fn empty() {
+        let mut sorter = Sorter::new(vec![false], super::DEFAULT_BUDGET, false);
+        assert!(sorter.current().is_none());
+        assert!(sorted(&mut sorter).is_empty());
+    }
+}
@@ -1,16 +1,92 @@
 #[derive(Debug)]
 pub enum Statement {
-    Select(SelectStatement),
+    Select(Box<SelectStatement>),
+    CreateTable(CreateTableStatement),
+    Insert(InsertStatement),
+    Update(UpdateStatement),
+    Delete(DeleteStatement),
+}
+
+/// `INSERT INTO table [(column, ...)] VALUES (expression, ...), ...` or `... SELECT ...`
+#[derive(Debug)]
+pub struct InsertStatement {
+    pub table: String,
+    /// The columns each row gives values for, in order, None for every column of the table
+    pub columns: Option<Vec<String>>,
+    pub source: InsertSource,
+}
+
+/// Where the rows of an INSERT come from
+#[derive(Debug)]
+pub enum InsertSource {
+    Values(Vec<Vec<Expression>>),
+    Select(Box<SelectStatement>),
+}
+
+/// `UPDATE table SET column = expression, ... [WHERE filter]`
+#[derive(Debug)]
+pub struct UpdateStatement {
+    pub table: String,
+    /// Each column set, with the expression giving its new value
+    pub assignments: Vec<(String, Expression)>,
+    pub filter: Option<Expression>,
+}
+
+/// `DELETE FROM table [WHERE filter]`
+#[derive(Debug)]
+pub struct DeleteStatement {
+    pub table: String,
+    pub filter: Option<Expression>,
+}
+
+/// `CREATE TABLE name (column type, ...)`
+#[derive(Debug)]
+pub struct CreateTableStatement {
+    pub name: String,
+    pub columns: Vec<ColumnDefinition>,
+}
+
+#[derive(Debug)]
+pub struct ColumnDefinition {
+    pub name: String,
+    /// The type name as written, if any, it isn't checked
+    pub data_type: Option<String>,
 }
 
 #[derive(Debug)]
 pub struct SelectStatement {
     pub columns: Vec<ColumnExpression>,
     pub from: NamedTupleSource,
+    /// Sources joined onto `from`, left to right
+    pub joins: Vec<Join>,
     pub filter: Option<Expression>,
+    pub group_by: Vec<Expression>,
+    pub having: Option<Expression>,
+    pub order_by: Vec<OrderingTerm>,
     pub limit: Option<Expression>,
 }
 
+#[derive(Debug)]
+pub struct Join {
+    pub kind: JoinKind,
+    pub source: NamedTupleSource,
+    /// The `ON` condition, None for a comma join or a join without one, which pairs every row
+    pub constraint: Option<Expression>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinKind {
+    Inner,
+    /// `LEFT [OUTER] JOIN`, keeps rows of the left side that nothing matched
+    Left,
+}
+
+#[derive(Debug)]
+pub struct OrderingTerm {
+    pub expression: Expression,
+    pub descending: bool,
+}
+
 #[derive(Debug)]
 pub enum ColumnExpression {
     Named {
@@ -29,6 +105,9 @@ pub struct ColumnReference {
 pub enum ScalarValue {
     IntegerNumber(i64),
     FloatingNumber(f64),
+    String(String),
+    Boolean(bool),
+    Null,
     Identifier(String),
     MultiPartIdentifier(Box<Expression>, String),
 }
@@ -37,6 +116,10 @@ pub enum ScalarValue {
 pub enum UnaryOp {
     Plus,
     Negate,
+    Not,
+    BitNot,
+    IsNull,
+    IsNotNull,
 }
 
 #[derive(Debug)]
@@ -73,6 +156,27 @@ pub enum Expression {
         rhs: Box<Expression>,
     },
     Value(ScalarValue),
+    /// A call such as `max(a)`, the name is lowercase
+    Function {
+        name: String,
+        arguments: FunctionArguments,
+    },
+    /// `(SELECT ...)`, the value in the first row it returns
+    Subquery(Box<SelectStatement>),
+    /// `expression IN (SELECT ...)`, `NOT IN` is this under a NOT
+    In {
+        expression: Box<Expression>,
+        subquery: Box<SelectStatement>,
+    },
+    /// `EXISTS (SELECT ...)`
+    Exists(Box<SelectStatement>),
+}
+
+#[derive(Debug)]
+pub enum FunctionArguments {
+    /// `count(*)`
+    Star,
+    List(Vec<Expression>),
 }
 
 #[derive(Debug)]
@@ -117,7 +221,20 @@ impl Expression {
             }
             Expression::Value(ScalarValue::FloatingNumber(_)) => vec![],
             Expression::Value(ScalarValue::IntegerNumber(_)) => vec![],
+            Expression::Value(ScalarValue::String(_)) => vec![],
+            Expression::Value(ScalarValue::Boolean(_)) => vec![],
+            Expression::Value(ScalarValue::Null) => vec![],
             Expression::UnaryOp { expression, .. } => expression.get_column_references(),
+            Expression::Function { arguments, .. } => match arguments {
+                FunctionArguments::Star => vec![],
+                FunctionArguments::List(arguments) => arguments
+                    .iter()
+                    .flat_map(|argument| argument.get_column_references())
+                    .collect(),
+            },
+            // A subquery's columns are resolved against its own FROM clause first
+            Expression::Subquery(_) | Expression::Exists(_) => vec![],
+            Expression::In { expression, .. } => expression.get_column_references(),
             Expression::BinaryOp { lhs, rhs, .. } => {
                 let mut lhs = lhs.get_column_references();
                 let mut rhs = rhs.get_column_references();
@@ -59,6 +59,29 @@ pub enum Type {
     False,
     True,
     Null,
+    Not,
+    Is,
+    Order,
+    By,
+    Asc,
+    Desc,
+    Group,
+    Having,
+    Join,
+    Inner,
+    Left,
+    Outer,
+    On,
+    In,
+    Exists,
+    Create,
+    Table,
+    Insert,
+    Into,
+    Values,
+    Update,
+    Set,
+    Delete,
 
     Error(Error),
 
@@ -71,6 +94,7 @@ pub enum Type {
     Pipe,
     Caret,
     Amp,
+    Tilde,
 }
 
 #[derive(Debug, Clone)]
@@ -151,7 +175,7 @@ impl<'a> Lexer<'a> {
     }
 
     fn peek_next(&mut self) -> char {
-        match self.input.peek_nth(2) {
+        match self.input.peek_nth(1) {
             Some(c) => *c,
             None => '\0',
         }
@@ -199,6 +223,7 @@ impl<'a> Lexer<'a> {
             '|' => self.make_token(Type::Pipe),
             '^' => self.make_token(Type::Caret),
             '&' => self.make_token(Type::Amp),
+            '~' => self.make_token(Type::Tilde),
             '!' => {
                 let next = self.check_next('=');
                 self.make_token(if next { Type::BangEqual } else { Type::Bang })
@@ -242,13 +267,11 @@ impl<'a> Lexer<'a> {
             match self.peek() {
                 ' ' | '\r' | '\t' => {
                     self.advance();
-                    break;
                 }
                 '\n' => {
                     self.advance();
                     self.line += 1;
                     self.column = 0;
-                    break;
                 }
                 '-' => {
                     if self.peek_next() == '-' {
@@ -261,6 +284,9 @@ impl<'a> Lexer<'a> {
                                 break;
                             }
                         }
+                    } else {
+                        // A minus, for the next token
+                        break;
                     }
                 }
                 _ => break,
@@ -416,9 +442,14 @@ impl<'a> Lexer<'a> {
         let ident = ident.as_str();
 
         let tipe = match ident.chars().next().unwrap() {
-            's' => match_reserved(ident, "select", Type::Select),
+            's' => match ident.chars().nth(2) {
+                Some('l') => match_reserved(ident, "select", Type::Select),
+                Some('t') => match_reserved(ident, "set", Type::Set),
+                _ => Type::Identifier(ident.to_owned()),
+            },
             'a' => match ident.chars().nth(1) {
-                Some('s') => match_reserved(ident, "as", Type::As),
+                Some('s') if ident.len() == 2 => match_reserved(ident, "as", Type::As),
+                Some('s') => match_reserved(ident, "asc", Type::Asc),
                 Some('n') => match_reserved(ident, "and", Type::And),
                 _ => Type::Identifier(ident.to_owned()),
             },
@@ -428,10 +459,52 @@ impl<'a> Lexer<'a> {
                 _ => Type::Identifier(ident.to_owned()),
             },
             'w' => match_reserved(ident, "where", Type::Where),
-            'o' => match_reserved(ident, "or", Type::Or),
-            'l' => match_reserved(ident, "limit", Type::Limit),
-            't' => match_reserved(ident, "true", Type::True),
-            'n' => match_reserved(ident, "null", Type::Null),
+            'o' => match ident.chars().nth(1) {
+                Some('r') if ident.len() == 2 => match_reserved(ident, "or", Type::Or),
+                Some('r') => match_reserved(ident, "order", Type::Order),
+                Some('n') => match_reserved(ident, "on", Type::On),
+                Some('u') => match_reserved(ident, "outer", Type::Outer),
+                _ => Type::Identifier(ident.to_owned()),
+            },
+            'b' => match_reserved(ident, "by", Type::By),
+            'd' => match ident {
+                "desc" => Type::Desc,
+                "delete" => Type::Delete,
+                _ => Type::Identifier(ident.to_owned()),
+            },
+            'g' => match_reserved(ident, "group", Type::Group),
+            'h' => match_reserved(ident, "having", Type::Having),
+            'l' => match ident.chars().nth(1) {
+                Some('i') => match_reserved(ident, "limit", Type::Limit),
+                Some('e') => match_reserved(ident, "left", Type::Left),
+                _ => Type::Identifier(ident.to_owned()),
+            },
+            'j' => match_reserved(ident, "join", Type::Join),
+            'e' => match_reserved(ident, "exists", Type::Exists),
+            't' => match ident.chars().nth(1) {
+                Some('r') => match_reserved(ident, "true", Type::True),
+                Some('a') => match_reserved(ident, "table", Type::Table),
+                _ => Type::Identifier(ident.to_owned()),
+            },
+            'c' => match_reserved(ident, "create", Type::Create),
+            'v' => match_reserved(ident, "values", Type::Values),
+            'u' => match_reserved(ident, "update", Type::Update),
+            'n' => match ident.chars().nth(1) {
+                Some('u') => match_reserved(ident, "null", Type::Null),
+                Some('o') => match_reserved(ident, "not", Type::Not),
+                _ => Type::Identifier(ident.to_owned()),
+            },
+            'i' => match ident.chars().nth(1) {
+                Some('s') => match_reserved(ident, "is", Type::Is),
+                Some('n') => match ident {
+                    "in" => Type::In,
+                    "inner" => Type::Inner,
+                    "insert" => Type::Insert,
+                    "into" => Type::Into,
+                    _ => Type::Identifier(ident.to_owned()),
+                },
+                _ => Type::Identifier(ident.to_owned()),
+            },
             _ => Type::Identifier(ident.to_owned()),
         };
 
@@ -467,4 +540,108 @@ mod test {
         println!("{:?}", input);
         println!("{:?}", output);
     }
+
+    #[test]
+    fn minus_whitespace_and_comments() {
+        use super::Type;
+
+        let output = lex("(1-1.5)  -- a comment\n\t -x");
+        let types: Vec<_> = output.iter().map(|t| t.tipe()).collect();
+
+        assert!(matches!(
+            types.as_slice(),
+            [
+                Type::LeftParen,
+                Type::IntegerNumber(1),
+                Type::Minus,
+                Type::FloatingPointNumber(f),
+                Type::RightParen,
+                Type::Minus,
+                Type::Identifier(x),
+                Type::Eof,
+            ] if *f == 1.5 && x == "x"
+        ));
+    }
+
+    #[test]
+    fn keywords() {
+        use super::Type;
+
+        let output = lex("null not is nothing island");
+        let types: Vec<_> = output.iter().map(|t| t.tipe()).collect();
+
+        assert!(matches!(
+            types.as_slice(),
+            [Type::Null, Type::Not, Type::Is, Type::Identifier(a), Type::Identifier(b), Type::Eof]
+                if a == "nothing" && b == "island"
+        ));
+
+        let output = lex("order by asc desc as or ordered");
+        let types: Vec<_> = output.iter().map(|t| t.tipe()).collect();
+
+        assert!(matches!(
+            types.as_slice(),
+            [Type::Order, Type::By, Type::Asc, Type::Desc, Type::As, Type::Or, Type::Identifier(a), Type::Eof]
+                if a == "ordered"
+        ));
+
+        let output = lex("group having groups");
+        let types: Vec<_> = output.iter().map(|t| t.tipe()).collect();
+
+        assert!(matches!(
+            types.as_slice(),
+            [Type::Group, Type::Having, Type::Identifier(a), Type::Eof] if a == "groups"
+        ));
+
+        let output = lex("join inner left outer on one lefty");
+        let types: Vec<_> = output.iter().map(|t| t.tipe()).collect();
+
+        assert!(matches!(
+            types.as_slice(),
+            [Type::Join, Type::Inner, Type::Left, Type::Outer, Type::On, Type::Identifier(a), Type::Identifier(b), Type::Eof]
+                if a == "one" && b == "lefty"
+        ));
+
+        let output = lex("in exists inner index");
+        let types: Vec<_> = output.iter().map(|t| t.tipe()).collect();
+
+        assert!(matches!(
+            types.as_slice(),
+            [Type::In, Type::Exists, Type::Inner, Type::Identifier(a), Type::Eof] if a == "index"
+        ));
+
+        let output = lex("CREATE table true tables");
+        let types: Vec<_> = output.iter().map(|t| t.tipe()).collect();
+
+        assert!(matches!(
+            types.as_slice(),
+            [Type::Create, Type::Table, Type::True, Type::Identifier(a), Type::Eof] if a == "tables"
+        ));
+
+        let output = lex("insert into values int inserts");
+        let types: Vec<_> = output.iter().map(|t| t.tipe()).collect();
+
+        assert!(matches!(
+            types.as_slice(),
+            [Type::Insert, Type::Into, Type::Values, Type::Identifier(a), Type::Identifier(b), Type::Eof]
+                if a == "int" && b == "inserts"
+        ));
+
+        let output = lex("update set select sets seat");
+        let types: Vec<_> = output.iter().map(|t| t.tipe()).collect();
+
+        assert!(matches!(
+            types.as_slice(),
+            [Type::Update, Type::Set, Type::Select, Type::Identifier(a), Type::Identifier(b), Type::Eof]
+                if a == "sets" && b == "seat"
+        ));
+
+        let output = lex("delete desc deleted");
+        let types: Vec<_> = output.iter().map(|t| t.tipe()).collect();
+
+        assert!(matches!(
+            types.as_slice(),
+            [Type::Delete, Type::Desc, Type::Identifier(a), Type::Eof] if a == "deleted"
+        ));
+    }
 }
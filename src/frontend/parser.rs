@@ -61,6 +61,62 @@ impl ParserInput {
                 self.advance();
                 Ok(())
             }
+            (Expect::Null, lexer::Type::Null) => {
+                self.advance();
+                Ok(())
+            }
+            (Expect::By, lexer::Type::By) => {
+                self.advance();
+                Ok(())
+            }
+            (Expect::Join, lexer::Type::Join) => {
+                self.advance();
+                Ok(())
+            }
+            (Expect::LeftParen, lexer::Type::LeftParen) => {
+                self.advance();
+                Ok(())
+            }
+            (Expect::In, lexer::Type::In) => {
+                self.advance();
+                Ok(())
+            }
+            (Expect::Create, lexer::Type::Create) => {
+                self.advance();
+                Ok(())
+            }
+            (Expect::Table, lexer::Type::Table) => {
+                self.advance();
+                Ok(())
+            }
+            (Expect::Insert, lexer::Type::Insert) => {
+                self.advance();
+                Ok(())
+            }
+            (Expect::Into, lexer::Type::Into) => {
+                self.advance();
+                Ok(())
+            }
+            (Expect::Values, lexer::Type::Values) => {
+                self.advance();
+                Ok(())
+            }
+            (Expect::Update, lexer::Type::Update) => {
+                self.advance();
+                Ok(())
+            }
+            (Expect::Set, lexer::Type::Set) => {
+                self.advance();
+                Ok(())
+            }
+            (Expect::Equal, lexer::Type::Equal) => {
+                self.advance();
+                Ok(())
+            }
+            (Expect::Delete, lexer::Type::Delete) => {
+                self.advance();
+                Ok(())
+            }
             // These expectations are not used with `.expect`
             (Expect::PrimaryExpression, _) => panic!("Not implemented"),
             (Expect::Identifier, _) => panic!("Not implemented"),
@@ -86,11 +142,25 @@ enum BinaryCategory {
 
 #[derive(Debug)]
 pub enum Expect {
+    LeftParen,
     RightParen,
     PrimaryExpression,
     Identifier,
     From,
     Select,
+    Null,
+    By,
+    Join,
+    In,
+    Create,
+    Table,
+    Insert,
+    Into,
+    Values,
+    Update,
+    Set,
+    Equal,
+    Delete,
 }
 
 impl lexer::Type {
@@ -123,7 +193,9 @@ impl lexer::Type {
     fn as_unary(self) -> Option<ast::UnaryOp> {
         match self {
             lexer::Type::Plus => Some(ast::UnaryOp::Plus),
-            lexer::Type::Bang => Some(ast::UnaryOp::Negate),
+            lexer::Type::Minus => Some(ast::UnaryOp::Negate),
+            lexer::Type::Bang => Some(ast::UnaryOp::Not),
+            lexer::Type::Tilde => Some(ast::UnaryOp::BitNot),
             _ => None,
         }
     }
@@ -133,11 +205,133 @@ impl lexer::Type {
 impl Parser {
     pub(crate) fn parse_statement(&mut self) -> ParseResult<ast::Statement> {
         match self.input.peek() {
-            lexer::Type::Select => Ok(ast::Statement::Select(self.parse_select_statement()?)),
+            lexer::Type::Select => Ok(ast::Statement::Select(Box::new(self.parse_select_statement()?))),
+            lexer::Type::Create => Ok(ast::Statement::CreateTable(self.parse_create_table_statement()?)),
+            lexer::Type::Insert => Ok(ast::Statement::Insert(self.parse_insert_statement()?)),
+            lexer::Type::Update => Ok(ast::Statement::Update(self.parse_update_statement()?)),
+            lexer::Type::Delete => Ok(ast::Statement::Delete(self.parse_delete_statement()?)),
             _ => todo!(),
         }
     }
 
+    fn parse_create_table_statement(&mut self) -> ParseResult<ast::CreateTableStatement> {
+        self.input.expect(Expect::Create)?;
+        self.input.expect(Expect::Table)?;
+        let name = self.parse_table_name()?;
+
+        self.input.expect(Expect::LeftParen)?;
+        let mut columns = vec![self.parse_column_definition()?];
+        while let lexer::Type::Comma = self.input.peek() {
+            self.input.advance();
+            columns.push(self.parse_column_definition()?);
+        }
+        self.input.expect(Expect::RightParen)?;
+
+        Ok(ast::CreateTableStatement { name, columns })
+    }
+
+    fn parse_insert_statement(&mut self) -> ParseResult<ast::InsertStatement> {
+        self.input.expect(Expect::Insert)?;
+        self.input.expect(Expect::Into)?;
+        let table = self.parse_table_name()?;
+
+        let columns = match self.input.peek() {
+            lexer::Type::LeftParen => {
+                self.input.advance();
+                let mut columns = vec![self.parse_identifier()?];
+                while let lexer::Type::Comma = self.input.peek() {
+                    self.input.advance();
+                    columns.push(self.parse_identifier()?);
+                }
+                self.input.expect(Expect::RightParen)?;
+                Some(columns)
+            }
+            _ => None,
+        };
+
+        let source = match self.input.peek() {
+            lexer::Type::Select => ast::InsertSource::Select(Box::new(self.parse_select_statement()?)),
+            _ => {
+                self.input.expect(Expect::Values)?;
+                let mut rows = vec![self.parse_values_row()?];
+                while let lexer::Type::Comma = self.input.peek() {
+                    self.input.advance();
+                    rows.push(self.parse_values_row()?);
+                }
+                ast::InsertSource::Values(rows)
+            }
+        };
+
+        Ok(ast::InsertStatement { table, columns, source })
+    }
+
+    fn parse_update_statement(&mut self) -> ParseResult<ast::UpdateStatement> {
+        self.input.expect(Expect::Update)?;
+        let table = self.parse_table_name()?;
+
+        self.input.expect(Expect::Set)?;
+        let mut assignments = vec![self.parse_assignment()?];
+        while let lexer::Type::Comma = self.input.peek() {
+            self.input.advance();
+            assignments.push(self.parse_assignment()?);
+        }
+
+        let filter = self.parse_where_clause()?;
+
+        Ok(ast::UpdateStatement {
+            table,
+            assignments,
+            filter,
+        })
+    }
+
+    fn parse_delete_statement(&mut self) -> ParseResult<ast::DeleteStatement> {
+        self.input.expect(Expect::Delete)?;
+        self.input.expect(Expect::From)?;
+        let table = self.parse_table_name()?;
+        let filter = self.parse_where_clause()?;
+
+        Ok(ast::DeleteStatement { table, filter })
+    }
+
+    /// `WHERE filter`, if there is one, of an UPDATE or DELETE
+    fn parse_where_clause(&mut self) -> ParseResult<Option<ast::Expression>> {
+        match self.input.peek() {
+            lexer::Type::Where => {
+                self.input.advance();
+                Ok(Some(self.parse_filter_expression()?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// `column = expression`, as in an UPDATE's SET
+    fn parse_assignment(&mut self) -> ParseResult<(String, ast::Expression)> {
+        let column = self.parse_identifier()?;
+        self.input.expect(Expect::Equal)?;
+        let expression = self.parse_expression()?;
+
+        Ok((column, expression))
+    }
+
+    fn parse_values_row(&mut self) -> ParseResult<Vec<ast::Expression>> {
+        self.input.expect(Expect::LeftParen)?;
+        let row = self.parse_expressions()?;
+        self.input.expect(Expect::RightParen)?;
+
+        Ok(row)
+    }
+
+    fn parse_column_definition(&mut self) -> ParseResult<ast::ColumnDefinition> {
+        let name = self.parse_identifier()?;
+        let data_type = match self.input.peek() {
+            lexer::Type::Identifier(_) => Some(self.parse_identifier()?),
+            _ => None,
+        };
+
+        Ok(ast::ColumnDefinition { name, data_type })
+    }
+
     fn parse_column_expressions(&mut self) -> ParseResult<Vec<ast::ColumnExpression>> {
         let mut exprs = Vec::new();
 
@@ -193,6 +387,7 @@ impl Parser {
             lexer::Type::LeftParen => {
                 self.input.advance();
                 let statement = self.parse_select_statement()?;
+                self.input.expect(Expect::RightParen)?;
                 Ok(ast::TupleSource::Subquery(Box::new(statement)))
             }
             _ => {
@@ -213,6 +408,7 @@ impl Parser {
         self.input.expect(Expect::From)?;
 
         let from = self.parse_named_tuple_source()?;
+        let joins = self.parse_joins()?;
 
         let filter = match self.input.peek() {
             lexer::Type::Where => {
@@ -222,6 +418,32 @@ impl Parser {
             _ => None,
         };
 
+        let group_by = match self.input.peek() {
+            lexer::Type::Group => {
+                self.input.advance();
+                self.input.expect(Expect::By)?;
+                self.parse_expressions()?
+            }
+            _ => vec![],
+        };
+
+        let having = match self.input.peek() {
+            lexer::Type::Having => {
+                self.input.advance();
+                Some(self.parse_filter_expression()?)
+            }
+            _ => None,
+        };
+
+        let order_by = match self.input.peek() {
+            lexer::Type::Order => {
+                self.input.advance();
+                self.input.expect(Expect::By)?;
+                self.parse_ordering_terms()?
+            }
+            _ => vec![],
+        };
+
         let limit = match self.input.peek() {
             lexer::Type::Limit => {
                 self.input.advance();
@@ -233,10 +455,98 @@ impl Parser {
         Ok(ast::SelectStatement {
             columns,
             from,
+            joins,
             filter,
+            group_by,
+            having,
+            order_by,
             limit,
         })
     }
+
+    fn parse_joins(&mut self) -> ParseResult<Vec<ast::Join>> {
+        let mut joins = vec![];
+
+        loop {
+            let kind = match self.input.peek() {
+                lexer::Type::Comma => {
+                    self.input.advance();
+                    let source = self.parse_named_tuple_source()?;
+                    joins.push(ast::Join {
+                        kind: ast::JoinKind::Inner,
+                        source,
+                        constraint: None,
+                    });
+                    continue;
+                }
+                lexer::Type::Join => ast::JoinKind::Inner,
+                lexer::Type::Inner => {
+                    self.input.advance();
+                    ast::JoinKind::Inner
+                }
+                lexer::Type::Left => {
+                    self.input.advance();
+                    if let lexer::Type::Outer = self.input.peek() {
+                        self.input.advance();
+                    }
+                    ast::JoinKind::Left
+                }
+                _ => return Ok(joins),
+            };
+            self.input.expect(Expect::Join)?;
+
+            let source = self.parse_named_tuple_source()?;
+            let constraint = match self.input.peek() {
+                lexer::Type::On => {
+                    self.input.advance();
+                    Some(self.parse_expression()?)
+                }
+                _ => None,
+            };
+
+            joins.push(ast::Join { kind, source, constraint });
+        }
+    }
+
+    fn parse_expressions(&mut self) -> ParseResult<Vec<ast::Expression>> {
+        let mut exprs = vec![self.parse_expression()?];
+
+        while let lexer::Type::Comma = self.input.peek() {
+            self.input.advance();
+            exprs.push(self.parse_expression()?);
+        }
+
+        Ok(exprs)
+    }
+
+    fn parse_ordering_terms(&mut self) -> ParseResult<Vec<ast::OrderingTerm>> {
+        let mut terms = vec![self.parse_ordering_term()?];
+
+        while let lexer::Type::Comma = self.input.peek() {
+            self.input.advance();
+            terms.push(self.parse_ordering_term()?);
+        }
+
+        Ok(terms)
+    }
+
+    fn parse_ordering_term(&mut self) -> ParseResult<ast::OrderingTerm> {
+        let expression = self.parse_expression()?;
+
+        let descending = match self.input.peek() {
+            lexer::Type::Asc => {
+                self.input.advance();
+                false
+            }
+            lexer::Type::Desc => {
+                self.input.advance();
+                true
+            }
+            _ => false,
+        };
+
+        Ok(ast::OrderingTerm { expression, descending })
+    }
 }
 
 /// Parser for expression types
@@ -274,11 +584,11 @@ impl Parser {
     }
 
     fn parse_logical_and(&mut self) -> ParseResult<ast::Expression> {
-        let mut expr = self.parse_inclusive_or()?;
+        let mut expr = self.parse_logical_not()?;
 
         while let Some(op) = self.input.peek().as_binary(BinaryCategory::LogicalAnd) {
             self.input.advance();
-            let right = self.parse_inclusive_or()?;
+            let right = self.parse_logical_not()?;
             expr = ast::Expression::BinaryOp {
                 op,
                 lhs: Box::new(expr),
@@ -289,6 +599,21 @@ impl Parser {
         Ok(expr)
     }
 
+    // NOT binds looser than comparisons, so `not a = b` is `not (a = b)`
+    fn parse_logical_not(&mut self) -> ParseResult<ast::Expression> {
+        match self.input.peek() {
+            lexer::Type::Not => {
+                self.input.advance();
+                let expr = self.parse_logical_not()?;
+                Ok(ast::Expression::UnaryOp {
+                    op: ast::UnaryOp::Not,
+                    expression: Box::new(expr),
+                })
+            }
+            _ => self.parse_inclusive_or(),
+        }
+    }
+
     fn parse_inclusive_or(&mut self) -> ParseResult<ast::Expression> {
         let mut expr = self.parse_exclusive_or()?;
 
@@ -338,17 +663,51 @@ impl Parser {
     fn parse_equality(&mut self) -> ParseResult<ast::Expression> {
         let mut expr = self.parse_relational()?;
 
-        while let Some(op) = self.input.peek().as_binary(BinaryCategory::Equality) {
-            self.input.advance();
-            let right = self.parse_relational()?;
-            expr = ast::Expression::BinaryOp {
-                op,
-                lhs: Box::new(expr),
-                rhs: Box::new(right),
+        loop {
+            if let Some(op) = self.input.peek().as_binary(BinaryCategory::Equality) {
+                self.input.advance();
+                let right = self.parse_relational()?;
+                expr = ast::Expression::BinaryOp {
+                    op,
+                    lhs: Box::new(expr),
+                    rhs: Box::new(right),
+                }
+            } else if let lexer::Type::Is = self.input.peek() {
+                // expr IS [NOT] NULL
+                self.input.advance();
+                let op = match self.input.peek() {
+                    lexer::Type::Not => {
+                        self.input.advance();
+                        ast::UnaryOp::IsNotNull
+                    }
+                    _ => ast::UnaryOp::IsNull,
+                };
+                self.input.expect(Expect::Null)?;
+                expr = ast::Expression::UnaryOp {
+                    op,
+                    expression: Box::new(expr),
+                }
+            } else if let lexer::Type::In | lexer::Type::Not = self.input.peek() {
+                // expr [NOT] IN (SELECT ...)
+                let negated = matches!(self.input.advance().tipe(), lexer::Type::Not);
+                if negated {
+                    self.input.expect(Expect::In)?;
+                }
+                let subquery = self.parse_subquery()?;
+                expr = ast::Expression::In {
+                    expression: Box::new(expr),
+                    subquery: Box::new(subquery),
+                };
+                if negated {
+                    expr = ast::Expression::UnaryOp {
+                        op: ast::UnaryOp::Not,
+                        expression: Box::new(expr),
+                    };
+                }
+            } else {
+                return Ok(expr);
             }
         }
-
-        Ok(expr)
     }
 
     fn parse_relational(&mut self) -> ParseResult<ast::Expression> {
@@ -457,13 +816,34 @@ impl Parser {
                         identifier,
                     ));
                 }
-                lexer::Type::LeftParen => todo!(),
+                lexer::Type::LeftParen => {
+                    let ast::Expression::Value(ast::ScalarValue::Identifier(name)) = expr else {
+                        return Err(ParseError::UnexpectedToken(Expect::Identifier, lexer::Type::LeftParen));
+                    };
+                    self.input.advance();
+                    let arguments = self.parse_function_arguments()?;
+                    self.input.expect(Expect::RightParen)?;
+
+                    expr = ast::Expression::Function { name, arguments };
+                }
                 _ => {
                     return Ok(expr);
                 }
             }
         }
     }
+
+    fn parse_function_arguments(&mut self) -> ParseResult<ast::FunctionArguments> {
+        match self.input.peek() {
+            lexer::Type::Star => {
+                self.input.advance();
+                Ok(ast::FunctionArguments::Star)
+            }
+            lexer::Type::RightParen => Ok(ast::FunctionArguments::List(vec![])),
+            _ => Ok(ast::FunctionArguments::List(self.parse_expressions()?)),
+        }
+    }
+
     fn parse_identifier(&mut self) -> ParseResult<String> {
         match self.input.peek() {
             lexer::Type::Identifier(id) => {
@@ -492,21 +872,53 @@ impl Parser {
                     value,
                 )))
             }
+            lexer::Type::String(value) => {
+                self.input.advance();
+                Ok(ast::Expression::Value(ast::ScalarValue::String(value)))
+            }
+            lexer::Type::True => {
+                self.input.advance();
+                Ok(ast::Expression::Value(ast::ScalarValue::Boolean(true)))
+            }
+            lexer::Type::False => {
+                self.input.advance();
+                Ok(ast::Expression::Value(ast::ScalarValue::Boolean(false)))
+            }
+            lexer::Type::Null => {
+                self.input.advance();
+                Ok(ast::Expression::Value(ast::ScalarValue::Null))
+            }
             lexer::Type::LeftParen => {
                 self.input.advance();
-                let expr = self.parse_expression()?;
+                let expr = match self.input.peek() {
+                    lexer::Type::Select => ast::Expression::Subquery(Box::new(self.parse_select_statement()?)),
+                    _ => self.parse_expression()?,
+                };
                 self.input.expect(Expect::RightParen)?;
 
                 Ok(expr)
             }
+            lexer::Type::Exists => {
+                self.input.advance();
+                let subquery = self.parse_subquery()?;
+                Ok(ast::Expression::Exists(Box::new(subquery)))
+            }
             t => Err(ParseError::UnexpectedToken(Expect::PrimaryExpression, t)),
         }
     }
+
+    fn parse_subquery(&mut self) -> ParseResult<ast::SelectStatement> {
+        self.input.expect(Expect::LeftParen)?;
+        let statement = self.parse_select_statement()?;
+        self.input.expect(Expect::RightParen)?;
+        Ok(statement)
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::parse;
+    use crate::frontend::ast::{self, Statement};
 
     #[test]
     fn test_parse_select() {
@@ -514,4 +926,309 @@ mod test {
         let statement = parse(input).unwrap();
         println!("Statement: {:#?}", statement);
     }
+
+    fn select(sql: &str) -> ast::SelectStatement {
+        match parse(sql).unwrap() {
+            Statement::Select(select) => *select,
+            statement => panic!("expected a SELECT, got {statement:?}"),
+        }
+    }
+
+    fn filter(sql: &str) -> String {
+        format!("{:?}", select(sql).filter.unwrap())
+    }
+
+    #[test]
+    fn test_parse_literals() {
+        assert_eq!(
+            filter("select a from t where null"),
+            "Value(Null)"
+        );
+        assert_eq!(
+            filter("select a from t where true"),
+            "Value(Boolean(true))"
+        );
+        assert_eq!(
+            filter("select a from t where 'hi'"),
+            "Value(String(\"hi\"))"
+        );
+    }
+
+    #[test]
+    fn test_parse_is_null() {
+        assert_eq!(
+            filter("select a from t where a is null"),
+            "UnaryOp { op: IsNull, expression: Value(Identifier(\"a\")) }"
+        );
+        assert_eq!(
+            filter("select a from t where a+1 is not null"),
+            "UnaryOp { op: IsNotNull, expression: BinaryOp { op: Sum, lhs: Value(Identifier(\"a\")), rhs: Value(IntegerNumber(1)) } }"
+        );
+        assert!(parse("select a from t where a is 1").is_err());
+    }
+
+    #[test]
+    fn test_parse_not() {
+        // NOT binds looser than comparisons but tighter than AND
+        assert_eq!(
+            filter("select a from t where not a = 1 and b"),
+            "BinaryOp { op: And, lhs: UnaryOp { op: Not, expression: BinaryOp { op: Equals, lhs: Value(Identifier(\"a\")), rhs: Value(IntegerNumber(1)) } }, rhs: Value(Identifier(\"b\")) }"
+        );
+        assert_eq!(
+            filter("select a from t where -a"),
+            "UnaryOp { op: Negate, expression: Value(Identifier(\"a\")) }"
+        );
+    }
+
+    #[test]
+    fn test_parse_order_by() {
+        let select = select("select a from t where a order by a desc, b+1, c asc limit 2");
+
+        let terms: Vec<String> = select.order_by.iter().map(|term| format!("{term:?}")).collect();
+        assert_eq!(
+            terms,
+            [
+                "OrderingTerm { expression: Value(Identifier(\"a\")), descending: true }",
+                "OrderingTerm { expression: BinaryOp { op: Sum, lhs: Value(Identifier(\"b\")), rhs: Value(IntegerNumber(1)) }, descending: false }",
+                "OrderingTerm { expression: Value(Identifier(\"c\")), descending: false }",
+            ]
+        );
+        assert!(select.limit.is_some());
+
+        assert!(parse("select a from t order a").is_err());
+    }
+
+    #[test]
+    fn test_parse_group_by() {
+        let select = select("select a, count(*), max(b+1) from t group by a, c having count(b) > 1 order by a");
+
+        let columns: Vec<String> = select.columns.iter().map(|column| format!("{column:?}")).collect();
+        assert_eq!(
+            columns[1..],
+            [
+                "Anonyomous(Function { name: \"count\", arguments: Star })",
+                "Anonyomous(Function { name: \"max\", arguments: List([BinaryOp { op: Sum, lhs: Value(Identifier(\"b\")), rhs: Value(IntegerNumber(1)) }]) })",
+            ]
+        );
+        assert_eq!(select.group_by.len(), 2);
+        assert_eq!(
+            format!("{:?}", select.having.unwrap()),
+            "BinaryOp { op: GreaterThan, lhs: Function { name: \"count\", arguments: List([Value(Identifier(\"b\"))]) }, rhs: Value(IntegerNumber(1)) }"
+        );
+        assert_eq!(select.order_by.len(), 1);
+
+        // Only a name can be called
+        assert!(parse("select 1(a) from t").is_err());
+    }
+
+    #[test]
+    fn test_parse_derived_table() {
+        let select = select("select d.a from (select a, b + 1 as c from t where b) as d join (select e from u) on d.a = e");
+
+        let ast::NamedTupleSource::Named {
+            alias,
+            source: ast::TupleSource::Subquery(subquery),
+        } = &select.from
+        else {
+            panic!("expected an aliased subquery, got {:?}", select.from);
+        };
+        assert_eq!(alias, "d");
+        assert_eq!(subquery.columns.len(), 2);
+        assert!(subquery.filter.is_some());
+        assert!(matches!(
+            select.joins[0].source,
+            ast::NamedTupleSource::Anonyomous(ast::TupleSource::Subquery(_))
+        ));
+        assert!(select.joins[0].constraint.is_some());
+    }
+
+    #[test]
+    fn test_parse_joins() {
+        let select = select(
+            "select a.x from a join b on a.id = b.id left outer join c as d on d.id = b.id, e left join f inner join g on 1 where a.x",
+        );
+
+        let joins: Vec<String> = select
+            .joins
+            .iter()
+            .map(|join| format!("{:?} {:?} {}", join.kind, join.source, join.constraint.is_some()))
+            .collect();
+        assert_eq!(
+            joins,
+            [
+                "Inner Anonyomous(Table(\"b\")) true",
+                "Left Named { alias: \"d\", source: Table(\"c\") } true",
+                "Inner Anonyomous(Table(\"e\")) false",
+                "Left Anonyomous(Table(\"f\")) false",
+                "Inner Anonyomous(Table(\"g\")) true",
+            ]
+        );
+        assert!(select.filter.is_some());
+
+        assert!(parse("select a from t left b").is_err());
+        assert!(parse("select a from t inner join (select b from u").is_err());
+        assert!(parse("select a from t inner join").is_err());
+    }
+
+    #[test]
+    fn test_parse_subquery_expressions() {
+        let select = select(
+            "select (select max(b) from u), a from t where a not in (select c from v) and exists (select 1 from w) or (a)",
+        );
+
+        let ast::ColumnExpression::Anonyomous(column) = &select.columns[0] else {
+            panic!("expected an unnamed column, got {:?}", select.columns[0]);
+        };
+        assert!(matches!(column.as_ref(), ast::Expression::Subquery(subquery) if subquery.columns.len() == 1));
+
+        // (a NOT IN (...) AND EXISTS (...)) OR a
+        let Some(ast::Expression::BinaryOp { lhs, rhs, .. }) = &select.filter else {
+            panic!("expected an OR, got {:?}", select.filter);
+        };
+        assert!(matches!(rhs.as_ref(), ast::Expression::Value(ast::ScalarValue::Identifier(_))));
+        let ast::Expression::BinaryOp { lhs: not_in, rhs: exists, .. } = lhs.as_ref() else {
+            panic!("expected an AND, got {lhs:?}");
+        };
+        assert!(matches!(
+            not_in.as_ref(),
+            ast::Expression::UnaryOp {
+                op: ast::UnaryOp::Not,
+                expression,
+            } if matches!(expression.as_ref(), ast::Expression::In { .. })
+        ));
+        assert!(matches!(exists.as_ref(), ast::Expression::Exists(_)));
+
+        assert!(parse("select a from t where a not (select b from u)").is_err());
+        assert!(parse("select a from t where a in select b from u").is_err());
+        assert!(parse("select a from t where exists (select b from u").is_err());
+    }
+
+    #[test]
+    fn test_parse_create_table() {
+        let Statement::CreateTable(create) = parse("CREATE TABLE users (id integer, name, age INT)").unwrap() else {
+            panic!("expected a CREATE TABLE");
+        };
+
+        assert_eq!(create.name, "users");
+        let columns: Vec<String> = create.columns.iter().map(|column| format!("{column:?}")).collect();
+        assert_eq!(
+            columns,
+            [
+                "ColumnDefinition { name: \"id\", data_type: Some(\"integer\") }",
+                "ColumnDefinition { name: \"name\", data_type: None }",
+                "ColumnDefinition { name: \"age\", data_type: Some(\"int\") }",
+            ]
+        );
+
+        assert!(parse("create table t ()").is_err());
+        assert!(parse("create table t (a, )").is_err());
+        assert!(parse("create table (a)").is_err());
+        assert!(parse("create t (a)").is_err());
+        assert!(parse("create table t (a b c)").is_err());
+    }
+
+    #[test]
+    fn test_parse_insert() {
+        let Statement::Insert(insert) = parse("INSERT INTO users (name, id) VALUES ('a', 1), ('b', -2 + 1)").unwrap() else {
+            panic!("expected an INSERT");
+        };
+
+        assert_eq!(insert.table, "users");
+        assert_eq!(insert.columns, Some(vec!["name".to_string(), "id".to_string()]));
+        let ast::InsertSource::Values(rows) = insert.source else {
+            panic!("expected VALUES, got {:?}", insert.source);
+        };
+        let rows: Vec<String> = rows.iter().map(|row| format!("{row:?}")).collect();
+        assert_eq!(
+            rows,
+            [
+                "[Value(String(\"a\")), Value(IntegerNumber(1))]",
+                "[Value(String(\"b\")), BinaryOp { op: Sum, lhs: UnaryOp { op: Negate, expression: Value(IntegerNumber(2)) }, rhs: Value(IntegerNumber(1)) }]",
+            ]
+        );
+
+        let Statement::Insert(insert) = parse("insert into t values (1)").unwrap() else {
+            panic!("expected an INSERT");
+        };
+        assert_eq!(insert.columns, None);
+        assert!(matches!(insert.source, ast::InsertSource::Values(rows) if rows.len() == 1));
+
+        let Statement::Insert(insert) = parse("insert into t (b, a) select x, y + 1 from u where x").unwrap() else {
+            panic!("expected an INSERT");
+        };
+        assert_eq!(insert.columns, Some(vec!["b".to_string(), "a".to_string()]));
+        assert!(matches!(
+            insert.source,
+            ast::InsertSource::Select(select) if select.columns.len() == 2 && select.filter.is_some()
+        ));
+
+        assert!(parse("insert t values (1)").is_err());
+        assert!(parse("insert into t (a) (1)").is_err());
+        assert!(parse("insert into t () values (1)").is_err());
+        assert!(parse("insert into t values ()").is_err());
+        assert!(parse("insert into t values (1), ").is_err());
+        assert!(parse("insert into t values 1").is_err());
+        assert!(parse("insert into t (select x from u)").is_err());
+        assert!(parse("insert into t select x").is_err());
+    }
+
+    #[test]
+    fn test_parse_update() {
+        let Statement::Update(update) = parse("UPDATE users SET name = 'b', age = age + 1 WHERE id = 2").unwrap() else {
+            panic!("expected an UPDATE");
+        };
+
+        assert_eq!(update.table, "users");
+        let assignments: Vec<String> = update
+            .assignments
+            .iter()
+            .map(|(column, expression)| format!("{column} = {expression:?}"))
+            .collect();
+        assert_eq!(
+            assignments,
+            [
+                "name = Value(String(\"b\"))",
+                "age = BinaryOp { op: Sum, lhs: Value(Identifier(\"age\")), rhs: Value(IntegerNumber(1)) }",
+            ]
+        );
+        assert_eq!(
+            format!("{:?}", update.filter),
+            "Some(BinaryOp { op: Equals, lhs: Value(Identifier(\"id\")), rhs: Value(IntegerNumber(2)) })"
+        );
+
+        let Statement::Update(update) = parse("update t set a = (select max(b) from u)").unwrap() else {
+            panic!("expected an UPDATE");
+        };
+        assert_eq!(update.assignments.len(), 1);
+        assert!(update.filter.is_none());
+
+        assert!(parse("update t a = 1").is_err());
+        assert!(parse("update t set").is_err());
+        assert!(parse("update t set a").is_err());
+        assert!(parse("update t set a == 1").is_err());
+        assert!(parse("update t set a = 1,").is_err());
+        assert!(parse("update set a = 1").is_err());
+    }
+
+    #[test]
+    fn test_parse_delete() {
+        let Statement::Delete(delete) = parse("DELETE FROM users WHERE age < 18 AND name IS NULL").unwrap() else {
+            panic!("expected a DELETE");
+        };
+        assert_eq!(delete.table, "users");
+        assert!(matches!(
+            delete.filter,
+            Some(ast::Expression::BinaryOp { op: ast::BinaryOp::And, .. })
+        ));
+
+        let Statement::Delete(delete) = parse("delete from t").unwrap() else {
+            panic!("expected a DELETE");
+        };
+        assert_eq!(delete.table, "t");
+        assert!(delete.filter.is_none());
+
+        assert!(parse("delete t").is_err());
+        assert!(parse("delete from").is_err());
+        assert!(parse("delete from t where").is_err());
+    }
 }
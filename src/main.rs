@@ -1,21 +1,41 @@
+mod catalog;
 mod compiler;
 mod engine;
 mod frontend;
 mod planner;
+mod record;
 mod repl;
+mod scalarvalue;
 mod storage;
 
 #[cfg(test)]
 mod test;
 
 use repl::{Repl, SharedState};
-use storage::BTree;
+use storage::{BTree, Compression, PagerOptions, SyncMode};
 
 pub(crate) fn main() {
     let mut args = std::env::args().skip(1);
 
     let db_name = args.next().expect("first arg should be database name");
 
+    let mut options = PagerOptions::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--compress" => options.compression = Compression::Lz4,
+            "--key" => options.passphrase = Some(args.next().expect("--key should be followed by a passphrase")),
+            "--sync" => {
+                options.sync_mode = match args.next().as_deref() {
+                    Some("off") => SyncMode::Off,
+                    Some("normal") => SyncMode::Normal,
+                    Some("full") => SyncMode::Full,
+                    other => panic!("--sync should be followed by off, normal or full, not {other:?}"),
+                }
+            }
+            other => panic!("Unknown argument {other:?}"),
+        }
+    }
+
     let db_path = std::path::Path::new(&db_name);
 
     if db_path.exists() {
@@ -35,8 +55,21 @@ pub(crate) fn main() {
 
     let db_path = db_path.canonicalize().unwrap();
 
-    let btree = BTree::new(db_path.to_str().unwrap());
-    let shared = SharedState::new(db_path.clone(), btree);
+    let btree = match BTree::with_options(db_path.to_str().unwrap(), options) {
+        Ok(btree) => btree,
+        Err(e) => {
+            eprintln!("Unable to open {db_path:?}: {e}");
+            std::process::exit(1);
+        }
+    };
+    let schema = match catalog::load(&btree) {
+        Ok(schema) => schema,
+        Err(e) => {
+            eprintln!("Unable to read the schema of {db_path:?}: {e}");
+            std::process::exit(1);
+        }
+    };
+    let shared = SharedState::new(db_path.clone(), btree, schema);
 
     let mut repl = Repl::new(shared);
     repl.run();
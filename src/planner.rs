@@ -14,6 +14,9 @@ pub enum UnaryOp {
     Plus,
     Negate,
     Not,
+    BitNot,
+    IsNull,
+    IsNotNull,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -53,10 +56,12 @@ pub enum BinaryOp {
 #[derive(Debug, Clone, PartialEq)]
 pub enum ColumnRef {
     /// Column from a single-input node (Filter, Project, etc.)
-    /// column_idx is the index into the input node's output columns
+    /// column_idx is the index into the input node's output columns.
+    /// Below a Join this is its combined output, the left columns followed by the right.
     Single { column_idx: usize },
-
-    // Future: Multi { node_idx: usize, column_idx: usize } for JOINs
+    /// Column of the row a subquery is run for, in the query `depth` queries out: 1 is the query
+    /// the subquery is in. column_idx is as for Single in that query.
+    Outer { depth: usize, column_idx: usize },
 }
 
 /// Literal values in expressions
@@ -83,13 +88,79 @@ pub enum PlanExpr {
         op: UnaryOp,
         operand: Box<PlanExpr>,
     },
+    /// A subquery, run when the expression is evaluated. It is correlated if its plan refers to
+    /// the row the expression is evaluated over, with ColumnRef::Outer.
+    Subquery {
+        kind: SubqueryKind,
+        plan: Box<LogicalPlan>,
+        correlated: bool,
+    },
+}
+
+/// What a subquery in an expression evaluates to, see `PlanExpr::Subquery`
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubqueryKind {
+    /// The first column of its first row, NULL if it returns none
+    Scalar,
+    /// Whether it returns any rows
+    Exists,
+    /// Whether the value equals the first column of a row. If none does it is NULL when the value or
+    /// a row's column is NULL, unless there are no rows, as `=` would be for one of them.
+    In(Box<PlanExpr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+/// An aggregate computed over the rows of each group, see `LogicalPlan::Aggregate`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateExpr {
+    pub function: AggregateFunction,
+    /// None for `count(*)`, which counts rows rather than values
+    pub arg: Option<PlanExpr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinKind {
+    Inner,
+    /// Also keeps left rows that match no right row, with NULL for every right column
+    Left,
+}
+
+/// How a Join finds the right rows for each left row, see `LogicalPlan::Join`.
+/// Whichever it is, a pair is only emitted if it meets the join condition.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JoinStrategy {
+    /// Run the right input in full for every left row
+    NestedLoop,
+    /// Seek the table the right input scans to the row whose rowid is key, computed from the left row
+    RowidSeek { key: PlanExpr },
+    /// Put every right row in a hash table under its right_keys, then look up each left row's left_keys.
+    /// ColumnRefs in left_keys refer to the left output, in right_keys to the right output.
+    Hash {
+        left_keys: Vec<PlanExpr>,
+        right_keys: Vec<PlanExpr>,
+    },
+}
+
+/// A key rows are sorted by, see `LogicalPlan::Sort`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub expr: PlanExpr,
+    pub descending: bool,
 }
 
 /// Logical plan nodes - relational algebra operators
 #[derive(Debug, Clone, PartialEq)]
 pub enum LogicalPlan {
     /// Scan rows from a table (leaf node, no inputs)
-    /// columns: indices of columns to read from the table schema
+    /// columns: indices of columns to read from the table schema, or ROWID_COLUMN for the rowid
     Scan { table: String, columns: Vec<usize> },
 
     /// Filter rows based on a predicate (1 input)
@@ -108,6 +179,37 @@ pub enum LogicalPlan {
         columns: Vec<PlanExpr>,
     },
 
+    /// Sort rows by keys (1 input)
+    /// Pass-through: outputs all columns from its child unchanged, ordered by the first key,
+    /// then the second, and so on. Rows with equal keys keep the order they came in.
+    /// Consumes all rows from its child before emitting any.
+    Sort {
+        input: Box<LogicalPlan>,
+        keys: Vec<SortKey>,
+    },
+
+    /// Group rows and aggregate each group (1 input)
+    /// Transforms output: one row per distinct combination of the group_by values, holding those values
+    /// followed by one column per aggregate. NULLs group together.
+    /// Without group_by there is exactly one row, even when the input is empty.
+    Aggregate {
+        input: Box<LogicalPlan>,
+        group_by: Vec<PlanExpr>,
+        aggregates: Vec<AggregateExpr>,
+    },
+
+    /// Join two inputs (2 inputs)
+    /// Transforms output: the left columns followed by the right columns, for every pair of rows
+    /// where condition is true, or every pair when there is no condition.
+    /// ColumnRefs in condition refer to positions in that combined output.
+    Join {
+        left: Box<LogicalPlan>,
+        right: Box<LogicalPlan>,
+        kind: JoinKind,
+        condition: Option<PlanExpr>,
+        strategy: JoinStrategy,
+    },
+
     /// Limit output rows (1 input)
     /// Pass-through: outputs all columns from its child unchanged.
     /// Only emits up to `count` rows.
@@ -124,16 +226,44 @@ pub enum LogicalPlan {
     /// Emit fixed rows (leaf node, no inputs)
     /// Useful for testing and for VALUES clauses.
     /// Each inner Vec is a row; all rows must have the same number of columns.
-    Values { rows: Vec<Vec<Literal>> },
+    /// The expressions have no columns to refer to, other than those of outer queries.
+    Values { rows: Vec<Vec<PlanExpr>> },
+
+    /// Insert every row of the input into a table (1 input)
+    /// The input has a column for each column of the table, in order, and each row is given the next rowid.
+    /// Output: no rows.
+    Insert { input: Box<LogicalPlan>, table: String },
+
+    /// Write every row of the input over the row of the table it was read from (1 input)
+    /// The input is a Scan of the table, through Filters and a Project, with a column for each
+    /// column of the table holding its new value. The scan's row keeps its rowid.
+    /// Or it is a Materialize of those rows each followed by the rowid of the row to write over, when
+    /// the filter or values read the table.
+    /// Output: no rows.
+    Update { input: Box<LogicalPlan>, table: String },
+
+    /// Remove every row of the input from the table (1 input)
+    /// The input is a Scan of the table, through Filters, and the row the scan emitted is removed.
+    /// Or it is a Materialize of the rowids of the rows to remove, when the filter reads the table.
+    /// Output: no rows.
+    Delete { input: Box<LogicalPlan>, table: String },
+
+    /// Buffer every row of the input (1 input)
+    /// Pass-through: outputs all columns from its child unchanged, in the same order.
+    /// Consumes all rows from its child before emitting any, so nothing done with them can change
+    /// what the child reads, such as inserting them into the table it scans.
+    Materialize { input: Box<LogicalPlan> },
 
     /// Generate a sequence of integers (leaf node, no inputs)
     /// Useful for testing. Generates rows [start], [start+1], ..., [end-1]
     /// Output: single integer column
     Sequence { start: i64, end: i64 },
-
-    // Future: Join { left: Box<LogicalPlan>, right: Box<LogicalPlan>, ... }
 }
 
+/// The column index a Scan reads a row's rowid with.
+/// Every table has a `rowid`, unless it has a column of its own with that name.
+pub const ROWID_COLUMN: usize = usize::MAX;
+
 // ============================================================================
 // Schema (for column resolution)
 // ============================================================================
@@ -166,6 +296,12 @@ pub mod schema {
         pub fn get_column_index(&self, name: &str) -> Option<usize> {
             self.columns.iter().position(|c| c.name == name)
         }
+
+        /// Like `get_column_index`, but also finds the `rowid`, as `ROWID_COLUMN`
+        pub fn get_scan_index(&self, name: &str) -> Option<usize> {
+            self.get_column_index(name)
+                .or_else(|| (name == "rowid").then_some(super::ROWID_COLUMN))
+        }
     }
 }
 
@@ -176,58 +312,144 @@ pub mod schema {
 /// Convert an AST Statement to a LogicalPlan
 pub fn plan(statement: Statement, schema: &schema::Schema) -> Result<LogicalPlan, PlanError> {
     match statement {
-        Statement::Select(select) => plan_select(select, schema),
+        Statement::Select(select) => plan_select(&select, &mut ColumnResolver::new(schema, None)),
+        // Creates a table in the catalog rather than running as a plan, see `catalog::create_table`
+        Statement::CreateTable(_) => Err(PlanError::UnsupportedStatement),
+        Statement::Insert(insert) => plan_insert(&insert, schema),
+        Statement::Update(update) => plan_update(&update, schema),
+        Statement::Delete(delete) => plan_delete(&delete, schema),
     }
 }
 
+/// Plan a query, ctx is the resolver its columns are added to: empty, but for a subquery with
+/// the resolver of the query it is in.
 fn plan_select(
-    select: ast::SelectStatement,
-    schema: &schema::Schema,
+    select: &ast::SelectStatement,
+    ctx: &mut ColumnResolver,
 ) -> Result<LogicalPlan, PlanError> {
-    // 1. Extract table info from FROM clause
-    let (table_name, table_ref) = extract_table_info(&select.from)?;
-
-    // 2. Look up table in schema
-    let table = schema
-        .get_table(&table_name)
-        .ok_or_else(|| PlanError::TableNotFound(table_name.clone()))?;
+    // 1. Extract table info from FROM clause, the first table and then each joined one
+    let sources = std::iter::once(&select.from).chain(select.joins.iter().map(|join| &join.source));
+
+    // 2. Look up tables in schema, and plan derived tables
+    let mut items: Vec<FromItem> = Vec::new();
+    for source in sources {
+        let item = plan_from_item(source, ctx.schema)?;
+        if let Some(table_ref) = &item.table_ref {
+            if items.iter().any(|other| other.table_ref.as_ref() == Some(table_ref)) {
+                return Err(PlanError::DuplicateTable(table_ref.clone()));
+            }
+        }
+        items.push(item);
+    }
 
-    // 3. Collect all column references from SELECT and WHERE
+    // 3. Collect all column references from every clause
     let mut columns_needed = HashSet::new();
-    for col_expr in &select.columns {
-        collect_columns_from_column_expr(col_expr, &mut columns_needed);
+    for expr in select_expressions(select) {
+        collect_columns(expr, &mut columns_needed);
+    }
+
+    // Each table reads the needed columns it has, a column no table has is an error.
+    // In a subquery it may be an outer query's, the resolver finds out.
+    for col_name in &columns_needed {
+        if ctx.outer.is_none() && !items.iter().any(|item| item.source.has_column(col_name)) {
+            let table_refs: Vec<&str> = items.iter().filter_map(|item| item.table_ref.as_deref()).collect();
+            return Err(PlanError::ColumnNotFound {
+                table: table_refs.join(", "),
+                column: col_name.clone(),
+            });
+        }
+    }
+
+    // Subqueries may refer to these tables' columns, read those of their columns the tables have too
+    for expr in select_expressions(select) {
+        collect_subquery_columns(expr, &mut columns_needed);
+    }
+
+    // 4 & 5. Build each table's column mapping and the resolver, joining the tables left to right:
+    // Scan → Join? → ... → Filter? → Aggregate? → Filter? → Sort? → Project → Limit?
+    // A join condition is resolved before the tables after it are in scope, so it can't refer to them.
+    let mut plan = None;
+    let joins = std::iter::once(None).chain(select.joins.iter().map(Some));
+    for (item, join) in items.into_iter().zip(joins) {
+        let SourcePlan {
+            plan: input,
+            columns,
+            width,
+        } = item.source.into_plan(&columns_needed)?;
+        let left_width = ctx.width;
+        ctx.add_table(item.table_ref.as_deref(), &columns, width);
+
+        plan = Some(match (plan, join) {
+            (Some(left), Some(join)) => {
+                let condition = join.constraint.as_ref().map(|constraint| convert_expr(constraint, ctx)).transpose()?;
+                LogicalPlan::Join {
+                    left: Box::new(left),
+                    strategy: join_strategy(left_width, &input, condition.as_ref()),
+                    right: Box::new(input),
+                    kind: match join.kind {
+                        ast::JoinKind::Inner => JoinKind::Inner,
+                        ast::JoinKind::Left => JoinKind::Left,
+                    },
+                    condition,
+                }
+            }
+            _ => input,
+        });
     }
+    let mut plan = plan.expect("a FROM clause to have a table");
+
+    // Add Filter if WHERE clause exists
     if let Some(ref filter) = select.filter {
-        collect_columns(filter, &mut columns_needed);
+        plan = LogicalPlan::Filter {
+            input: Box::new(plan),
+            predicate: convert_expr(filter, ctx)?,
+        };
     }
 
-    // 4. Build column mapping
-    let mapping = build_column_mapping(&columns_needed, table, &table_ref)?;
+    // 7. Convert SELECT, HAVING and ORDER BY expressions.
+    // In a grouped query they read the output of the Aggregate, which gathers the aggregates they use.
+    let (project_exprs, having, sort_keys) = if is_grouped(select) {
+        let mut grouping = Grouping::new(&select.group_by, ctx)?;
 
-    // 5. Build expression context
-    let ctx = ExprContext {
-        table_ref: &table_ref,
-        columns: &mapping.column_map,
-    };
+        let project_exprs = select
+            .columns
+            .iter()
+            .map(|col_expr| grouping.convert(column_expression(col_expr)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let having = select.having.as_ref().map(|having| grouping.convert(having)).transpose()?;
+        let sort_keys = convert_sort_keys(&select.order_by, |expr| grouping.convert(expr))?;
 
-    // 6. Convert SELECT expressions
-    let project_exprs: Vec<PlanExpr> = select
-        .columns
-        .iter()
-        .map(|col_expr| convert_column_expr(col_expr, &ctx))
-        .collect::<Result<Vec<_>, _>>()?;
+        plan = LogicalPlan::Aggregate {
+            input: Box::new(plan),
+            group_by: grouping.groups,
+            aggregates: grouping.aggregates,
+        };
 
-    // 7. Build plan bottom-up: Scan → Filter? → Project → Limit?
-    let mut plan = LogicalPlan::Scan {
-        table: table_name,
-        columns: mapping.scan_columns,
+        (project_exprs, having, sort_keys)
+    } else {
+        let project_exprs = select
+            .columns
+            .iter()
+            .map(|col_expr| convert_column_expr(col_expr, ctx))
+            .collect::<Result<Vec<_>, _>>()?;
+        let sort_keys = convert_sort_keys(&select.order_by, |expr| convert_expr(expr, ctx))?;
+
+        (project_exprs, None, sort_keys)
     };
 
-    // Add Filter if WHERE clause exists
-    if let Some(ref filter) = select.filter {
+    // Add Filter if HAVING clause exists
+    if let Some(having) = having {
         plan = LogicalPlan::Filter {
             input: Box::new(plan),
-            predicate: convert_expr(filter, &ctx)?,
+            predicate: having,
+        };
+    }
+
+    // Add Sort if ORDER BY clause exists, below Project so it can sort by columns that aren't selected
+    if !sort_keys.is_empty() {
+        plan = LogicalPlan::Sort {
+            input: Box::new(plan),
+            keys: sort_keys,
         };
     }
 
@@ -249,33 +471,408 @@ fn plan_select(
     Ok(plan)
 }
 
-/// Extract table name and reference (alias or table name) from FROM clause
-fn extract_table_info(from: &ast::NamedTupleSource) -> Result<(String, String), PlanError> {
-    match from {
-        ast::NamedTupleSource::Named { alias, source } => {
-            let table_name = extract_table_name(source)?;
-            // The alias is what we use for column references
-            Ok((table_name, alias.clone()))
+/// Plan an INSERT of the rows of its VALUES or SELECT, with NULL for the columns it doesn't name
+fn plan_insert(insert: &ast::InsertStatement, schema: &schema::Schema) -> Result<LogicalPlan, PlanError> {
+    let table = schema
+        .get_table(&insert.table)
+        .ok_or_else(|| PlanError::TableNotFound(insert.table.clone()))?;
+
+    // The table column each value of a row is for
+    let positions = match &insert.columns {
+        Some(columns) => {
+            let mut positions = vec![];
+            for column in columns {
+                let position = table.get_column_index(column).ok_or_else(|| PlanError::ColumnNotFound {
+                    table: table.name.clone(),
+                    column: column.clone(),
+                })?;
+                if positions.contains(&position) {
+                    return Err(PlanError::DuplicateColumn(column.clone()));
+                }
+                positions.push(position);
+            }
+            positions
+        }
+        None => (0..table.columns.len()).collect(),
+    };
+
+    let mut input = match &insert.source {
+        ast::InsertSource::Values(rows) => plan_insert_values(rows, &positions, table.columns.len(), schema)?,
+        ast::InsertSource::Select(select) => {
+            if select.columns.len() != positions.len() {
+                return Err(PlanError::InsertColumns {
+                    expected: positions.len(),
+                    found: select.columns.len(),
+                });
+            }
+            let plan = plan_select(select, &mut ColumnResolver::new(schema, None))?;
+            if positions.iter().copied().eq(0..table.columns.len()) {
+                plan
+            } else {
+                // Put the selected columns where they go in the table
+                let columns = (0..table.columns.len())
+                    .map(|idx| match positions.iter().position(|&position| position == idx) {
+                        Some(column_idx) => PlanExpr::ColumnRef(ColumnRef::Single { column_idx }),
+                        None => PlanExpr::Literal(Literal::Null),
+                    })
+                    .collect();
+                LogicalPlan::Project {
+                    input: Box::new(plan),
+                    columns,
+                }
+            }
+        }
+    };
+
+    // Rows inserted into a table the input is still reading would be read again
+    if reads_table(&input, &table.name) {
+        input = LogicalPlan::Materialize { input: Box::new(input) };
+    }
+
+    Ok(LogicalPlan::Insert {
+        input: Box::new(input),
+        table: table.name.clone(),
+    })
+}
+
+/// The rows of an INSERT's VALUES, each put in a row as wide as the table at `positions`
+fn plan_insert_values(
+    rows: &[Vec<ast::Expression>],
+    positions: &[usize],
+    width: usize,
+    schema: &schema::Schema,
+) -> Result<LogicalPlan, PlanError> {
+    let ctx = ColumnResolver::new(schema, None);
+    let rows = rows
+        .iter()
+        .map(|row| {
+            if row.len() != positions.len() {
+                return Err(PlanError::InsertColumns {
+                    expected: positions.len(),
+                    found: row.len(),
+                });
+            }
+
+            let mut values = vec![PlanExpr::Literal(Literal::Null); width];
+            for (&position, expr) in positions.iter().zip(row) {
+                values[position] = convert_expr(expr, &ctx)?;
+            }
+            Ok(values)
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(LogicalPlan::Values { rows })
+}
+
+/// Plan an UPDATE as a scan of the table's rows that pass the filter, projected to their new values
+fn plan_update(update: &ast::UpdateStatement, schema: &schema::Schema) -> Result<LogicalPlan, PlanError> {
+    let table = schema
+        .get_table(&update.table)
+        .ok_or_else(|| PlanError::TableNotFound(update.table.clone()))?;
+    let (plan, ctx) = plan_filtered_scan(table, update.filter.as_ref(), schema)?;
+
+    // Columns that aren't set keep their value
+    let mut values: Vec<PlanExpr> = (0..table.columns.len())
+        .map(|column_idx| PlanExpr::ColumnRef(ColumnRef::Single { column_idx }))
+        .collect();
+    let mut assigned = vec![false; table.columns.len()];
+    for (column, expr) in &update.assignments {
+        let position = table.get_column_index(column).ok_or_else(|| PlanError::ColumnNotFound {
+            table: table.name.clone(),
+            column: column.clone(),
+        })?;
+        if assigned[position] {
+            return Err(PlanError::DuplicateColumn(column.clone()));
+        }
+        assigned[position] = true;
+        values[position] = convert_expr(expr, &ctx)?;
+    }
+
+    // Reading the table would see the rows rewritten before, so compute every new row first
+    let expr_reads = |expr: &PlanExpr| expr_reads_table(expr, &table.name);
+    let filter_reads = matches!(&plan, LogicalPlan::Filter { predicate, .. } if expr_reads(predicate));
+    let input = if filter_reads || values.iter().any(expr_reads) {
+        values.push(scan_rowid(table));
+        LogicalPlan::Materialize {
+            input: Box::new(LogicalPlan::Project {
+                input: Box::new(plan),
+                columns: values,
+            }),
+        }
+    } else {
+        LogicalPlan::Project {
+            input: Box::new(plan),
+            columns: values,
+        }
+    };
+
+    Ok(LogicalPlan::Update {
+        input: Box::new(input),
+        table: table.name.clone(),
+    })
+}
+
+/// Plan a DELETE as a scan of the table's rows that pass the filter
+fn plan_delete(delete: &ast::DeleteStatement, schema: &schema::Schema) -> Result<LogicalPlan, PlanError> {
+    let table = schema
+        .get_table(&delete.table)
+        .ok_or_else(|| PlanError::TableNotFound(delete.table.clone()))?;
+    let (mut plan, _) = plan_filtered_scan(table, delete.filter.as_ref(), schema)?;
+
+    // A filter reading the table would see the rows removed before it, so find them all first
+    if let LogicalPlan::Filter { predicate, .. } = &plan {
+        if expr_reads_table(predicate, &table.name) {
+            plan = LogicalPlan::Materialize {
+                input: Box::new(LogicalPlan::Project {
+                    input: Box::new(plan),
+                    columns: vec![scan_rowid(table)],
+                }),
+            };
+        }
+    }
+
+    Ok(LogicalPlan::Delete {
+        input: Box::new(plan),
+        table: table.name.clone(),
+    })
+}
+
+/// A scan of the rows of the table that pass the filter, for an UPDATE or DELETE to change, and the
+/// resolver for expressions over them. Every column is read, followed by the rowid, see `scan_rowid`.
+fn plan_filtered_scan<'a>(
+    table: &schema::Table,
+    filter: Option<&ast::Expression>,
+    schema: &'a schema::Schema,
+) -> Result<(LogicalPlan, ColumnResolver<'a>), PlanError> {
+    let mut scan_columns: Vec<usize> = (0..table.columns.len()).collect();
+    let mut columns: Vec<(String, usize)> = table
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(idx, column)| (column.name.clone(), idx))
+        .collect();
+    // A column of the table's own named rowid hides it, but it is still read
+    if table.get_column_index("rowid").is_none() {
+        columns.push(("rowid".to_string(), scan_columns.len()));
+    }
+    scan_columns.push(ROWID_COLUMN);
+    let mut ctx = ColumnResolver::new(schema, None);
+    ctx.add_table(Some(&table.name), &columns, scan_columns.len());
+
+    let mut plan = LogicalPlan::Scan {
+        table: table.name.clone(),
+        columns: scan_columns,
+    };
+    if let Some(filter) = filter {
+        plan = LogicalPlan::Filter {
+            input: Box::new(plan),
+            predicate: convert_expr(filter, &ctx)?,
+        };
+    }
+
+    Ok((plan, ctx))
+}
+
+/// The rowid of a row of `plan_filtered_scan`
+fn scan_rowid(table: &schema::Table) -> PlanExpr {
+    PlanExpr::ColumnRef(ColumnRef::Single {
+        column_idx: table.columns.len(),
+    })
+}
+
+/// Whether the plan, or a subquery anywhere in it, scans the table
+fn reads_table(plan: &LogicalPlan, table: &str) -> bool {
+    let expr_reads = |expr: &PlanExpr| expr_reads_table(expr, table);
+
+    match plan {
+        LogicalPlan::Scan { table: scanned, .. } => scanned == table,
+        LogicalPlan::Filter { input, predicate } => {
+            reads_table(input, table) || expr_reads(predicate)
+        }
+        LogicalPlan::Project { input, columns } => {
+            reads_table(input, table) || columns.iter().any(expr_reads)
+        }
+        LogicalPlan::Sort { input, keys } => {
+            reads_table(input, table) || keys.iter().any(|key| expr_reads(&key.expr))
+        }
+        LogicalPlan::Aggregate {
+            input,
+            group_by,
+            aggregates,
+        } => {
+            reads_table(input, table)
+                || group_by.iter().any(expr_reads)
+                || aggregates.iter().filter_map(|aggregate| aggregate.arg.as_ref()).any(expr_reads)
+        }
+        LogicalPlan::Join {
+            left,
+            right,
+            condition,
+            strategy,
+            ..
+        } => {
+            let strategy_exprs: Vec<&PlanExpr> = match strategy {
+                JoinStrategy::NestedLoop => vec![],
+                JoinStrategy::RowidSeek { key } => vec![key],
+                JoinStrategy::Hash { left_keys, right_keys } => left_keys.iter().chain(right_keys).collect(),
+            };
+            reads_table(left, table)
+                || reads_table(right, table)
+                || condition.iter().any(expr_reads)
+                || strategy_exprs.into_iter().any(expr_reads)
+        }
+        LogicalPlan::Limit { input, .. }
+        | LogicalPlan::Count { input }
+        | LogicalPlan::Materialize { input }
+        | LogicalPlan::Insert { input, .. }
+        | LogicalPlan::Update { input, .. }
+        | LogicalPlan::Delete { input, .. } => reads_table(input, table),
+        LogicalPlan::Values { rows } => rows.iter().flatten().any(expr_reads),
+        LogicalPlan::Sequence { .. } => false,
+    }
+}
+
+fn expr_reads_table(expr: &PlanExpr, table: &str) -> bool {
+    match expr {
+        PlanExpr::ColumnRef(_) | PlanExpr::Literal(_) => false,
+        PlanExpr::BinaryOp { left, right, .. } => expr_reads_table(left, table) || expr_reads_table(right, table),
+        PlanExpr::UnaryOp { operand, .. } => expr_reads_table(operand, table),
+        PlanExpr::Subquery { kind, plan, .. } => {
+            reads_table(plan, table) || matches!(kind, SubqueryKind::In(value) if expr_reads_table(value, table))
+        }
+    }
+}
+
+/// A table or derived table in the FROM clause
+struct FromItem<'a> {
+    /// The name or alias its columns can be qualified with, None for a derived table without an alias
+    table_ref: Option<String>,
+    source: FromSource<'a>,
+}
+
+enum FromSource<'a> {
+    Table {
+        name: String,
+        table: &'a schema::Table,
+    },
+    /// A subquery, its columns are named after its select list
+    Derived {
+        plan: LogicalPlan,
+        columns: Vec<Option<String>>,
+    },
+}
+
+/// A FROM item's plan and where its columns are in the plan's output
+struct SourcePlan {
+    plan: LogicalPlan,
+    /// Maps column name → position in the output, for the columns that can be referenced
+    columns: Vec<(String, usize)>,
+    /// How many columns the plan outputs
+    width: usize,
+}
+
+impl FromSource<'_> {
+    fn has_column(&self, col_name: &str) -> bool {
+        match self {
+            FromSource::Table { table, .. } => table.get_scan_index(col_name).is_some(),
+            FromSource::Derived { columns, .. } => {
+                columns.iter().any(|name| name.as_deref() == Some(col_name))
+            }
         }
-        ast::NamedTupleSource::Anonyomous(source) => {
-            let table_name = extract_table_name(source)?;
-            // No alias, use table name for references
-            Ok((table_name.clone(), table_name))
+    }
+
+    /// The plan producing the needed columns it has
+    fn into_plan(self, columns_needed: &HashSet<String>) -> Result<SourcePlan, PlanError> {
+        match self {
+            FromSource::Table { name, table } => {
+                let columns: HashSet<String> = columns_needed
+                    .iter()
+                    .filter(|col_name| table.get_scan_index(col_name).is_some())
+                    .cloned()
+                    .collect();
+                let mapping = build_column_mapping(&columns, table, &name)?;
+                let width = mapping.scan_columns.len();
+
+                let scan = LogicalPlan::Scan {
+                    table: name,
+                    columns: mapping.scan_columns,
+                };
+                Ok(SourcePlan {
+                    plan: scan,
+                    columns: mapping.column_map.into_iter().collect(),
+                    width,
+                })
+            }
+            // A derived table outputs every column of its select list, named or not
+            FromSource::Derived { plan, columns } => {
+                let width = columns.len();
+                let columns = columns
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(pos, name)| Some((name?, pos)))
+                    .collect();
+                Ok(SourcePlan {
+                    plan,
+                    columns,
+                    width,
+                })
+            }
         }
     }
 }
 
-fn extract_table_name(source: &ast::TupleSource) -> Result<String, PlanError> {
+/// Look up a table in the FROM clause, or plan a derived table
+fn plan_from_item<'a>(
+    from: &ast::NamedTupleSource,
+    schema: &'a schema::Schema,
+) -> Result<FromItem<'a>, PlanError> {
+    let (source, alias) = match from {
+        ast::NamedTupleSource::Named { alias, source } => (source, Some(alias.clone())),
+        ast::NamedTupleSource::Anonyomous(source) => (source, None),
+    };
+
     match source {
-        ast::TupleSource::Table(name) => Ok(name.clone()),
-        ast::TupleSource::Subquery(_) => Err(PlanError::UnsupportedStatement),
+        ast::TupleSource::Table(name) => {
+            let table = schema
+                .get_table(name)
+                .ok_or_else(|| PlanError::TableNotFound(name.clone()))?;
+            Ok(FromItem {
+                // Without an alias, use table name for references
+                table_ref: Some(alias.unwrap_or_else(|| name.clone())),
+                source: FromSource::Table {
+                    name: name.clone(),
+                    table,
+                },
+            })
+        }
+        ast::TupleSource::Subquery(select) => Ok(FromItem {
+            table_ref: alias,
+            source: FromSource::Derived {
+                plan: plan_select(select, &mut ColumnResolver::new(schema, None))?,
+                columns: select.columns.iter().map(output_column_name).collect(),
+            },
+        }),
+    }
+}
+
+/// The name a select list column goes by in a derived table: its alias, or the column it reads
+fn output_column_name(col_expr: &ast::ColumnExpression) -> Option<String> {
+    match col_expr {
+        ast::ColumnExpression::Named { name, .. } => Some(name.clone()),
+        ast::ColumnExpression::Anonyomous(expression) => match expression.as_ref() {
+            ast::Expression::Value(ast::ScalarValue::Identifier(name))
+            | ast::Expression::Value(ast::ScalarValue::MultiPartIdentifier(_, name)) => {
+                Some(name.clone())
+            }
+            _ => None,
+        },
     }
 }
 
 /// Convert a ColumnExpression to a PlanExpr
 fn convert_column_expr(
     col_expr: &ast::ColumnExpression,
-    ctx: &ExprContext,
+    ctx: &ColumnResolver,
 ) -> Result<PlanExpr, PlanError> {
     match col_expr {
         ast::ColumnExpression::Named { expression, .. } => convert_expr(expression, ctx),
@@ -283,6 +880,28 @@ fn convert_column_expr(
     }
 }
 
+fn column_expression(col_expr: &ast::ColumnExpression) -> &ast::Expression {
+    match col_expr {
+        ast::ColumnExpression::Named { expression, .. } => expression,
+        ast::ColumnExpression::Anonyomous(expression) => expression,
+    }
+}
+
+fn convert_sort_keys(
+    terms: &[ast::OrderingTerm],
+    mut convert: impl FnMut(&ast::Expression) -> Result<PlanExpr, PlanError>,
+) -> Result<Vec<SortKey>, PlanError> {
+    terms
+        .iter()
+        .map(|term| {
+            Ok(SortKey {
+                expr: convert(&term.expression)?,
+                descending: term.descending,
+            })
+        })
+        .collect()
+}
+
 /// Extract limit count from a limit expression (must be an integer literal)
 fn extract_limit_value(expr: &ast::Expression) -> Result<u64, PlanError> {
     match expr {
@@ -301,45 +920,176 @@ fn extract_limit_value(expr: &ast::Expression) -> Result<u64, PlanError> {
 pub enum PlanError {
     TableNotFound(String),
     ColumnNotFound { table: String, column: String },
+    /// An unqualified column that more than one table in the FROM clause has
+    AmbiguousColumn(String),
+    /// The same table name or alias used twice in the FROM clause
+    DuplicateTable(String),
     UnsupportedStatement,
+    UnknownFunction(String),
+    /// A function was given the wrong number or kind of arguments
+    InvalidArguments(String),
+    /// An aggregate where it can't be computed, such as in WHERE or inside another aggregate
+    MisusedAggregate(String),
+    /// A column used outside an aggregate in a grouped query, which isn't one of the groups
+    NotGrouped(String),
+    /// A subquery used as a value that returns this many columns rather than one
+    SubqueryColumns(usize),
+    /// A column named more than once in the columns an INSERT gives values for
+    DuplicateColumn(String),
+    /// A row of an INSERT with `found` values, for `expected` columns
+    InsertColumns { expected: usize, found: usize },
 }
 
 // ============================================================================
 // Expression Conversion
 // ============================================================================
 
+use std::cell::Cell;
 use std::collections::HashMap;
 use crate::frontend::ast;
 
-// TODO: For JOIN support, replace ExprContext with a ColumnResolver that handles:
-//
-// 1. Qualified refs (table.column): lookup in specific table
-// 2. Unqualified refs (column): lookup across all tables, error if ambiguous
-//
-// Example: SELECT age, user.name FROM user JOIN relative ON relative.name = user.name
-//   - "age" is allowed if only one table has it (otherwise ambiguous error)
-//   - "user.name" must resolve to the "user" table specifically
-//
-// Data structure:
-//   struct ColumnResolver {
-//       // (table_alias, column_name) → scan output position
-//       qualified: HashMap<(String, String), usize>,
-//       // column_name → Some(position) if unique, None if ambiguous
-//       unqualified: HashMap<String, Option<usize>>,
-//   }
-//
-// Build by iterating all tables: add to qualified map, track ambiguity in unqualified map.
-
-/// Context for expression conversion (single-table queries)
-struct ExprContext<'a> {
-    /// Valid table name or alias for qualified refs (e.g., "u" for "FROM users AS u")
-    table_ref: &'a str,
-    /// Maps column name → position in scan output
-    columns: &'a HashMap<String, usize>,
+/// Resolves column references to positions in the output of the FROM clause, which is the scan output
+/// of each table in turn. In a subquery, a column its own tables don't have is looked for in the
+/// query it is in, and so on outwards.
+///
+/// Example: SELECT age, user.name FROM user JOIN relative ON relative.name = user.name
+///   - "age" is allowed if only one table has it (otherwise ambiguous error)
+///   - "user.name" must resolve to the "user" table specifically
+#[derive(Debug, Clone)]
+struct ColumnResolver<'a> {
+    /// The schema subqueries in expressions are planned with
+    schema: &'a schema::Schema,
+    /// The resolver of the query this is a subquery of
+    outer: Option<&'a ColumnResolver<'a>>,
+    /// Set once a column is found in an outer query
+    correlated: Cell<bool>,
+    /// Maps each position to the group it is, None if it isn't one. Set for the subqueries of a grouped
+    /// query that are evaluated over its groups, see `ColumnResolver::grouped`.
+    groups: Option<Vec<Option<usize>>>,
+    /// Table names or aliases in scope, for qualified refs (e.g., "u" for "FROM users AS u")
+    tables: Vec<String>,
+    /// (table_ref, column_name) → Some(position) if unique, None if ambiguous
+    qualified: HashMap<(String, String), Option<usize>>,
+    /// column_name → Some(position) if unique, None if ambiguous
+    unqualified: HashMap<String, Option<usize>>,
+    /// Number of columns output by the tables so far
+    width: usize,
+}
+
+impl<'a> ColumnResolver<'a> {
+    fn new(schema: &'a schema::Schema, outer: Option<&'a ColumnResolver<'a>>) -> ColumnResolver<'a> {
+        ColumnResolver {
+            schema,
+            outer,
+            correlated: Cell::new(false),
+            groups: None,
+            tables: Vec::new(),
+            qualified: HashMap::new(),
+            unqualified: HashMap::new(),
+            width: 0,
+        }
+    }
+
+    /// Resolves columns to their position in the output of an Aggregate grouping by `groups`,
+    /// so only a column that is a group can be used
+    fn grouped(&self, groups: &[PlanExpr]) -> ColumnResolver<'a> {
+        let groups = (0..self.width)
+            .map(|column_idx| {
+                let column = PlanExpr::ColumnRef(ColumnRef::Single { column_idx });
+                groups.iter().position(|group| *group == column)
+            })
+            .collect();
+
+        ColumnResolver {
+            groups: Some(groups),
+            ..self.clone()
+        }
+    }
+
+    /// Bring a table into scope, its `width` columns follow those of the tables already in scope.
+    /// columns maps the names it has to their position in its output, a derived table may repeat a name.
+    fn add_table(&mut self, table_ref: Option<&str>, columns: &[(String, usize)], width: usize) {
+        for (col_name, output_pos) in columns {
+            let pos = self.width + output_pos;
+            if let Some(table_ref) = table_ref {
+                self.qualified
+                    .entry((table_ref.to_string(), col_name.clone()))
+                    .and_modify(|pos| *pos = None)
+                    .or_insert(Some(pos));
+            }
+            self.unqualified
+                .entry(col_name.clone())
+                .and_modify(|pos| *pos = None)
+                .or_insert(Some(pos));
+        }
+
+        self.tables.extend(table_ref.map(str::to_string));
+        self.width += width;
+    }
+
+    fn resolve(&self, table_ref: Option<&str>, column: &str) -> Result<ColumnRef, PlanError> {
+        let not_found = |table: String| PlanError::ColumnNotFound {
+            table,
+            column: column.to_string(),
+        };
+
+        // The query's own tables hide those of the queries it is in
+        let in_scope = match table_ref {
+            Some(table_ref) => self.tables.iter().any(|table| table == table_ref),
+            None => self.unqualified.contains_key(column),
+        };
+        if let (false, Some(outer)) = (in_scope, self.outer) {
+            match outer.resolve(table_ref, column) {
+                Ok(ColumnRef::Single { column_idx }) => {
+                    self.correlated.set(true);
+                    return Ok(ColumnRef::Outer { depth: 1, column_idx });
+                }
+                Ok(ColumnRef::Outer { depth, column_idx }) => {
+                    self.correlated.set(true);
+                    return Ok(ColumnRef::Outer {
+                        depth: depth + 1,
+                        column_idx,
+                    });
+                }
+                // No outer query has it either, so report it missing from this one's tables
+                Err(PlanError::ColumnNotFound { .. }) if table_ref.is_none() => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        let pos = match table_ref {
+            Some(table_ref) => {
+                if !self.tables.iter().any(|table| table == table_ref) {
+                    return Err(PlanError::TableNotFound(table_ref.to_string()));
+                }
+                match self
+                    .qualified
+                    .get(&(table_ref.to_string(), column.to_string()))
+                {
+                    Some(Some(pos)) => *pos,
+                    Some(None) => {
+                        return Err(PlanError::AmbiguousColumn(format!("{table_ref}.{column}")))
+                    }
+                    None => return Err(not_found(table_ref.to_string())),
+                }
+            }
+            None => match self.unqualified.get(column) {
+                Some(Some(pos)) => *pos,
+                Some(None) => return Err(PlanError::AmbiguousColumn(column.to_string())),
+                None => return Err(not_found(self.tables.join(", "))),
+            },
+        };
+        let pos = match &self.groups {
+            Some(groups) => groups[pos].ok_or_else(|| PlanError::NotGrouped(column.to_string()))?,
+            None => pos,
+        };
+
+        Ok(ColumnRef::Single { column_idx: pos })
+    }
 }
 
 /// Convert an AST Expression to a PlanExpr
-fn convert_expr(expr: &ast::Expression, ctx: &ExprContext) -> Result<PlanExpr, PlanError> {
+fn convert_expr(expr: &ast::Expression, ctx: &ColumnResolver) -> Result<PlanExpr, PlanError> {
     match expr {
         ast::Expression::Value(scalar) => convert_scalar(scalar, ctx),
         ast::Expression::BinaryOp { op, lhs, rhs } => Ok(PlanExpr::BinaryOp {
@@ -351,34 +1101,54 @@ fn convert_expr(expr: &ast::Expression, ctx: &ExprContext) -> Result<PlanExpr, P
             op: convert_unary_op(op),
             operand: Box::new(convert_expr(expression, ctx)?),
         }),
+        // Aggregates are only allowed where convert_grouped_expr handles them
+        ast::Expression::Function { name, .. } if aggregate_function(name).is_some() => {
+            Err(PlanError::MisusedAggregate(name.clone()))
+        }
+        ast::Expression::Function { name, .. } => Err(PlanError::UnknownFunction(name.clone())),
+        ast::Expression::Subquery(select) => plan_subquery(select, SubqueryKind::Scalar, ctx),
+        ast::Expression::Exists(select) => plan_subquery(select, SubqueryKind::Exists, ctx),
+        ast::Expression::In { expression, subquery } => {
+            let value = convert_expr(expression, ctx)?;
+            plan_subquery(subquery, SubqueryKind::In(Box::new(value)), ctx)
+        }
+    }
+}
+
+/// Plan a subquery in an expression of the query ctx resolves the columns of
+fn plan_subquery(
+    select: &ast::SelectStatement,
+    kind: SubqueryKind,
+    ctx: &ColumnResolver,
+) -> Result<PlanExpr, PlanError> {
+    // EXISTS only looks at whether there are rows, the others at the first column
+    if kind != SubqueryKind::Exists && select.columns.len() != 1 {
+        return Err(PlanError::SubqueryColumns(select.columns.len()));
     }
+
+    let mut subquery_ctx = ColumnResolver::new(ctx.schema, Some(ctx));
+    let plan = plan_select(select, &mut subquery_ctx)?;
+
+    Ok(PlanExpr::Subquery {
+        kind,
+        plan: Box::new(plan),
+        correlated: subquery_ctx.correlated.get(),
+    })
 }
 
-fn convert_scalar(scalar: &ast::ScalarValue, ctx: &ExprContext) -> Result<PlanExpr, PlanError> {
+fn convert_scalar(scalar: &ast::ScalarValue, ctx: &ColumnResolver) -> Result<PlanExpr, PlanError> {
     match scalar {
         ast::ScalarValue::IntegerNumber(n) => Ok(PlanExpr::Literal(Literal::Integer(*n))),
         ast::ScalarValue::FloatingNumber(n) => Ok(PlanExpr::Literal(Literal::Float(*n))),
-        ast::ScalarValue::Identifier(name) => {
-            let pos = ctx.columns.get(name).ok_or_else(|| PlanError::ColumnNotFound {
-                table: ctx.table_ref.to_string(),
-                column: name.clone(),
-            })?;
-            Ok(PlanExpr::ColumnRef(ColumnRef::Single { column_idx: *pos }))
-        }
+        ast::ScalarValue::String(s) => Ok(PlanExpr::Literal(Literal::String(s.clone()))),
+        ast::ScalarValue::Boolean(b) => Ok(PlanExpr::Literal(Literal::Bool(*b))),
+        ast::ScalarValue::Null => Ok(PlanExpr::Literal(Literal::Null)),
+        ast::ScalarValue::Identifier(name) => Ok(PlanExpr::ColumnRef(ctx.resolve(None, name)?)),
         ast::ScalarValue::MultiPartIdentifier(table_expr, column_name) => {
             // Extract table name from expression (e.g., "u" from "u.name")
             let ref_table = extract_identifier(table_expr)?;
 
-            // Validate table reference matches our context
-            if ref_table != ctx.table_ref {
-                return Err(PlanError::TableNotFound(ref_table));
-            }
-
-            let pos = ctx.columns.get(column_name).ok_or_else(|| PlanError::ColumnNotFound {
-                table: ctx.table_ref.to_string(),
-                column: column_name.clone(),
-            })?;
-            Ok(PlanExpr::ColumnRef(ColumnRef::Single { column_idx: *pos }))
+            Ok(PlanExpr::ColumnRef(ctx.resolve(Some(&ref_table), column_name)?))
         }
     }
 }
@@ -392,55 +1162,350 @@ fn extract_identifier(expr: &ast::Expression) -> Result<String, PlanError> {
 }
 
 // ============================================================================
-// Column Collection
+// Aggregation
 // ============================================================================
 
-use std::collections::HashSet;
+fn aggregate_function(name: &str) -> Option<AggregateFunction> {
+    match name {
+        "count" => Some(AggregateFunction::Count),
+        "sum" => Some(AggregateFunction::Sum),
+        "avg" => Some(AggregateFunction::Avg),
+        "min" => Some(AggregateFunction::Min),
+        "max" => Some(AggregateFunction::Max),
+        _ => None,
+    }
+}
 
-/// Collect all column names referenced in an expression
-fn collect_columns(expr: &ast::Expression, columns: &mut HashSet<String>) {
+fn contains_aggregate(expr: &ast::Expression) -> bool {
     match expr {
-        ast::Expression::Value(scalar) => collect_columns_scalar(scalar, columns),
-        ast::Expression::BinaryOp { lhs, rhs, .. } => {
-            collect_columns(lhs, columns);
-            collect_columns(rhs, columns);
-        }
-        ast::Expression::UnaryOp { expression, .. } => {
-            collect_columns(expression, columns);
+        ast::Expression::Value(_) => false,
+        ast::Expression::BinaryOp { lhs, rhs, .. } => contains_aggregate(lhs) || contains_aggregate(rhs),
+        ast::Expression::UnaryOp { expression, .. } => contains_aggregate(expression),
+        ast::Expression::Function { name, arguments } => {
+            aggregate_function(name).is_some()
+                || matches!(arguments, ast::FunctionArguments::List(arguments) if arguments.iter().any(contains_aggregate))
         }
+        // An aggregate in a subquery aggregates the subquery's rows
+        ast::Expression::Subquery(_) | ast::Expression::Exists(_) => false,
+        ast::Expression::In { expression, .. } => contains_aggregate(expression),
     }
 }
 
-fn collect_columns_scalar(scalar: &ast::ScalarValue, columns: &mut HashSet<String>) {
-    match scalar {
-        ast::ScalarValue::Identifier(name) => {
-            columns.insert(name.clone());
-        }
-        ast::ScalarValue::MultiPartIdentifier(_, column_name) => {
-            // For table.column, we only need the column name
-            columns.insert(column_name.clone());
-        }
-        ast::ScalarValue::IntegerNumber(_) | ast::ScalarValue::FloatingNumber(_) => {
-            // Literals don't reference columns
-        }
-    }
+/// A query is grouped if it has GROUP BY or HAVING, or aggregates anywhere rows are output
+fn is_grouped(select: &ast::SelectStatement) -> bool {
+    !select.group_by.is_empty()
+        || select.having.is_some()
+        || select.columns.iter().any(|col_expr| contains_aggregate(column_expression(col_expr)))
+        || select.order_by.iter().any(|term| contains_aggregate(&term.expression))
 }
 
-/// Collect columns from a ColumnExpression (SELECT list item)
-fn collect_columns_from_column_expr(col_expr: &ast::ColumnExpression, columns: &mut HashSet<String>) {
-    match col_expr {
-        ast::ColumnExpression::Named { expression, .. } => {
-            collect_columns(expression, columns);
-        }
-        ast::ColumnExpression::Anonyomous(expression) => {
-            collect_columns(expression, columns);
-        }
-    }
+/// Converts expressions evaluated over the groups of an Aggregate, rather than over the rows of its input.
+///
+/// The Aggregate outputs the groups, then the aggregates, so an expression that is one of the groups
+/// becomes a reference to it, and each aggregate call a reference to its result. Aggregates are
+/// collected as they are found, the same aggregate used twice is only computed once.
+struct Grouping<'a> {
+    ctx: &'a ColumnResolver<'a>,
+    /// Resolves the columns of subqueries, which are evaluated over the groups too
+    grouped_ctx: ColumnResolver<'a>,
+    groups: Vec<PlanExpr>,
+    aggregates: Vec<AggregateExpr>,
 }
 
-// ============================================================================
-// Column Map Building
-// ============================================================================
+impl<'a> Grouping<'a> {
+    fn new(group_by: &[ast::Expression], ctx: &'a ColumnResolver<'a>) -> Result<Grouping<'a>, PlanError> {
+        let groups = group_by
+            .iter()
+            .map(|expr| convert_expr(expr, ctx))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Grouping {
+            ctx,
+            grouped_ctx: ctx.grouped(&groups),
+            groups,
+            aggregates: vec![],
+        })
+    }
+
+    fn convert(&mut self, expr: &ast::Expression) -> Result<PlanExpr, PlanError> {
+        if let ast::Expression::Function { name, arguments } = expr {
+            if let Some(function) = aggregate_function(name) {
+                let aggregate = self.convert_aggregate(name, function, arguments)?;
+                let idx = match self.aggregates.iter().position(|a| *a == aggregate) {
+                    Some(idx) => idx,
+                    None => {
+                        self.aggregates.push(aggregate);
+                        self.aggregates.len() - 1
+                    }
+                };
+
+                return Ok(PlanExpr::ColumnRef(ColumnRef::Single {
+                    column_idx: self.groups.len() + idx,
+                }));
+            }
+        }
+
+        if !contains_aggregate(expr) {
+            let converted = convert_expr(expr, self.ctx)?;
+            if let Some(idx) = self.groups.iter().position(|group| *group == converted) {
+                return Ok(PlanExpr::ColumnRef(ColumnRef::Single { column_idx: idx }));
+            }
+        }
+
+        // Not a group as a whole, so convert the parts
+        match expr {
+            ast::Expression::Value(ast::ScalarValue::Identifier(name))
+            | ast::Expression::Value(ast::ScalarValue::MultiPartIdentifier(_, name)) => {
+                Err(PlanError::NotGrouped(name.clone()))
+            }
+            ast::Expression::Value(_) | ast::Expression::Function { .. } => convert_expr(expr, self.ctx),
+            ast::Expression::BinaryOp { op, lhs, rhs } => Ok(PlanExpr::BinaryOp {
+                op: convert_binary_op(op),
+                left: Box::new(self.convert(lhs)?),
+                right: Box::new(self.convert(rhs)?),
+            }),
+            ast::Expression::UnaryOp { op, expression } => Ok(PlanExpr::UnaryOp {
+                op: convert_unary_op(op),
+                operand: Box::new(self.convert(expression)?),
+            }),
+            ast::Expression::Subquery(select) => self.convert_subquery(select, SubqueryKind::Scalar),
+            ast::Expression::Exists(select) => self.convert_subquery(select, SubqueryKind::Exists),
+            ast::Expression::In { expression, subquery } => {
+                let value = self.convert(expression)?;
+                self.convert_subquery(subquery, SubqueryKind::In(Box::new(value)))
+            }
+        }
+    }
+
+    fn convert_subquery(&self, select: &ast::SelectStatement, kind: SubqueryKind) -> Result<PlanExpr, PlanError> {
+        let subquery = plan_subquery(select, kind, &self.grouped_ctx)?;
+        // grouped_ctx is a copy, so ctx has to be told if it found a column in an outer query
+        if self.grouped_ctx.correlated.get() {
+            self.ctx.correlated.set(true);
+        }
+        Ok(subquery)
+    }
+
+    /// The argument is evaluated over the input rows, so it can't hold another aggregate
+    fn convert_aggregate(
+        &self,
+        name: &str,
+        function: AggregateFunction,
+        arguments: &ast::FunctionArguments,
+    ) -> Result<AggregateExpr, PlanError> {
+        match arguments {
+            ast::FunctionArguments::Star if function == AggregateFunction::Count => {
+                Ok(AggregateExpr { function, arg: None })
+            }
+            ast::FunctionArguments::List(arguments) if arguments.len() == 1 => Ok(AggregateExpr {
+                function,
+                arg: Some(convert_expr(&arguments[0], self.ctx)?),
+            }),
+            _ => Err(PlanError::InvalidArguments(name.to_string())),
+        }
+    }
+}
+
+// ============================================================================
+// Join Strategy
+// ============================================================================
+
+/// Pick how a join finds its right rows from the equalities ANDed together in its condition,
+/// those comparing an expression of the left columns with one of the right columns.
+/// Seeking by rowid beats building a hash table, and either beats a nested loop.
+fn join_strategy(left_width: usize, right: &LogicalPlan, condition: Option<&PlanExpr>) -> JoinStrategy {
+    let mut left_keys = Vec::new();
+    let mut right_keys = Vec::new();
+
+    let mut conjuncts = condition.into_iter().collect::<Vec<_>>();
+    while let Some(conjunct) = conjuncts.pop() {
+        let (lhs, rhs) = match conjunct {
+            PlanExpr::BinaryOp { op: BinaryOp::And, left, right } => {
+                conjuncts.push(right);
+                conjuncts.push(left);
+                continue;
+            }
+            PlanExpr::BinaryOp { op: BinaryOp::Equals, left, right } => (left.as_ref(), right.as_ref()),
+            _ => continue,
+        };
+
+        // A left key may be a constant, a right key has to depend on the right row
+        let is_left = |expr: &PlanExpr| all_columns(expr, &|idx| idx < left_width);
+        let has_columns = |expr: &PlanExpr| !all_columns(expr, &|_| false);
+        let is_right = |expr: &PlanExpr| all_columns(expr, &|idx| idx >= left_width) && has_columns(expr);
+        let (left_key, right_key) = if is_left(lhs) && is_right(rhs) {
+            (lhs, rhs)
+        } else if is_left(rhs) && is_right(lhs) {
+            (rhs, lhs)
+        } else {
+            continue;
+        };
+
+        left_keys.push(left_key.clone());
+        right_keys.push(shift_columns(right_key, left_width));
+    }
+
+    if let LogicalPlan::Scan { columns, .. } = right {
+        let rowid = columns
+            .iter()
+            .position(|column| *column == ROWID_COLUMN)
+            .map(|column_idx| PlanExpr::ColumnRef(ColumnRef::Single { column_idx }));
+        if let Some(key) = right_keys.iter().position(|key| Some(key) == rowid.as_ref()) {
+            return JoinStrategy::RowidSeek {
+                key: left_keys.swap_remove(key),
+            };
+        }
+    }
+
+    if left_keys.is_empty() {
+        JoinStrategy::NestedLoop
+    } else {
+        JoinStrategy::Hash { left_keys, right_keys }
+    }
+}
+
+/// Whether every column an expression refers to meets the predicate, true if it refers to none
+fn all_columns(expr: &PlanExpr, predicate: &impl Fn(usize) -> bool) -> bool {
+    match expr {
+        PlanExpr::ColumnRef(ColumnRef::Single { column_idx }) => predicate(*column_idx),
+        // The same for every row of this query
+        PlanExpr::ColumnRef(ColumnRef::Outer { .. }) | PlanExpr::Literal(_) => true,
+        PlanExpr::BinaryOp { left, right, .. } => all_columns(left, predicate) && all_columns(right, predicate),
+        PlanExpr::UnaryOp { operand, .. } => all_columns(operand, predicate),
+        // Which columns a correlated subquery reads isn't known without looking through its plan
+        PlanExpr::Subquery { correlated: true, .. } => false,
+        PlanExpr::Subquery { kind, .. } => match kind {
+            SubqueryKind::In(value) => all_columns(value, predicate),
+            SubqueryKind::Scalar | SubqueryKind::Exists => true,
+        },
+    }
+}
+
+/// Make an expression of the columns after the first `offset` refer to the same columns on their own
+fn shift_columns(expr: &PlanExpr, offset: usize) -> PlanExpr {
+    match expr {
+        PlanExpr::ColumnRef(ColumnRef::Single { column_idx }) => PlanExpr::ColumnRef(ColumnRef::Single {
+            column_idx: column_idx - offset,
+        }),
+        PlanExpr::ColumnRef(ColumnRef::Outer { .. }) | PlanExpr::Literal(_) => expr.clone(),
+        PlanExpr::BinaryOp { op, left, right } => PlanExpr::BinaryOp {
+            op: op.clone(),
+            left: Box::new(shift_columns(left, offset)),
+            right: Box::new(shift_columns(right, offset)),
+        },
+        PlanExpr::UnaryOp { op, operand } => PlanExpr::UnaryOp {
+            op: op.clone(),
+            operand: Box::new(shift_columns(operand, offset)),
+        },
+        // Only called for expressions all_columns looked through, so not a correlated subquery
+        PlanExpr::Subquery { kind, plan, correlated } => PlanExpr::Subquery {
+            kind: match kind {
+                SubqueryKind::In(value) => SubqueryKind::In(Box::new(shift_columns(value, offset))),
+                kind => kind.clone(),
+            },
+            plan: plan.clone(),
+            correlated: *correlated,
+        },
+    }
+}
+
+// ============================================================================
+// Column Collection
+// ============================================================================
+
+use std::collections::HashSet;
+
+/// Collect all column names referenced in an expression
+fn collect_columns(expr: &ast::Expression, columns: &mut HashSet<String>) {
+    match expr {
+        ast::Expression::Value(scalar) => collect_columns_scalar(scalar, columns),
+        ast::Expression::BinaryOp { lhs, rhs, .. } => {
+            collect_columns(lhs, columns);
+            collect_columns(rhs, columns);
+        }
+        ast::Expression::UnaryOp { expression, .. } => {
+            collect_columns(expression, columns);
+        }
+        ast::Expression::Function { arguments, .. } => {
+            if let ast::FunctionArguments::List(arguments) = arguments {
+                for argument in arguments {
+                    collect_columns(argument, columns);
+                }
+            }
+        }
+        // The columns of subqueries are collected by collect_subquery_columns
+        ast::Expression::Subquery(_) | ast::Expression::Exists(_) => {}
+        ast::Expression::In { expression, .. } => collect_columns(expression, columns),
+    }
+}
+
+fn collect_columns_scalar(scalar: &ast::ScalarValue, columns: &mut HashSet<String>) {
+    match scalar {
+        ast::ScalarValue::Identifier(name) => {
+            columns.insert(name.clone());
+        }
+        ast::ScalarValue::MultiPartIdentifier(_, column_name) => {
+            // For table.column, we only need the column name
+            columns.insert(column_name.clone());
+        }
+        ast::ScalarValue::IntegerNumber(_)
+        | ast::ScalarValue::FloatingNumber(_)
+        | ast::ScalarValue::String(_)
+        | ast::ScalarValue::Boolean(_)
+        | ast::ScalarValue::Null => {
+            // Literals don't reference columns
+        }
+    }
+}
+
+/// Collect the column names referenced in an expression's subqueries, and in theirs.
+/// Columns the subqueries' own tables have are included, it isn't known yet which query has them.
+fn collect_subquery_columns(expr: &ast::Expression, columns: &mut HashSet<String>) {
+    match expr {
+        ast::Expression::Value(_) => {}
+        ast::Expression::BinaryOp { lhs, rhs, .. } => {
+            collect_subquery_columns(lhs, columns);
+            collect_subquery_columns(rhs, columns);
+        }
+        ast::Expression::UnaryOp { expression, .. } => collect_subquery_columns(expression, columns),
+        ast::Expression::Function { arguments, .. } => {
+            if let ast::FunctionArguments::List(arguments) = arguments {
+                for argument in arguments {
+                    collect_subquery_columns(argument, columns);
+                }
+            }
+        }
+        ast::Expression::Subquery(select) | ast::Expression::Exists(select) => collect_select_columns(select, columns),
+        ast::Expression::In { expression, subquery } => {
+            collect_subquery_columns(expression, columns);
+            collect_select_columns(subquery, columns);
+        }
+    }
+}
+
+fn collect_select_columns(select: &ast::SelectStatement, columns: &mut HashSet<String>) {
+    for expr in select_expressions(select) {
+        collect_columns(expr, columns);
+        collect_subquery_columns(expr, columns);
+    }
+}
+
+/// Every expression of a SELECT that is evaluated over the rows of its FROM clause
+fn select_expressions(select: &ast::SelectStatement) -> impl Iterator<Item = &ast::Expression> {
+    select
+        .columns
+        .iter()
+        .map(column_expression)
+        .chain(select.joins.iter().filter_map(|join| join.constraint.as_ref()))
+        .chain(&select.filter)
+        .chain(&select.group_by)
+        .chain(&select.having)
+        .chain(select.order_by.iter().map(|term| &term.expression))
+}
+
+// ============================================================================
+// Column Map Building
+// ============================================================================
 
 /// Result of building the column map
 #[derive(Debug, PartialEq)]
@@ -462,7 +1527,7 @@ fn build_column_mapping(
     // Resolve each column name to its table index
     let mut table_indices: Vec<(String, usize)> = Vec::new();
     for col_name in columns {
-        let idx = table.get_column_index(col_name).ok_or_else(|| {
+        let idx = table.get_scan_index(col_name).ok_or_else(|| {
             PlanError::ColumnNotFound {
                 table: table_name.to_string(),
                 column: col_name.clone(),
@@ -471,7 +1536,7 @@ fn build_column_mapping(
         table_indices.push((col_name.clone(), idx));
     }
 
-    // Sort by table index to get consistent scan order
+    // Sort by table index to get consistent scan order, the rowid goes last
     table_indices.sort_by_key(|(_, idx)| *idx);
 
     // Build scan_columns and column_map
@@ -515,6 +1580,10 @@ fn convert_unary_op(op: &ast::UnaryOp) -> UnaryOp {
     match op {
         ast::UnaryOp::Plus => UnaryOp::Plus,
         ast::UnaryOp::Negate => UnaryOp::Negate,
+        ast::UnaryOp::Not => UnaryOp::Not,
+        ast::UnaryOp::BitNot => UnaryOp::BitNot,
+        ast::UnaryOp::IsNull => UnaryOp::IsNull,
+        ast::UnaryOp::IsNotNull => UnaryOp::IsNotNull,
     }
 }
 
@@ -541,10 +1610,17 @@ mod tests {
         map
     }
 
+    fn make_resolver() -> ColumnResolver<'static> {
+        static SCHEMA: schema::Schema = schema::Schema { tables: Vec::new() };
+        let columns: Vec<_> = make_column_map().into_iter().collect();
+        let mut ctx = ColumnResolver::new(&SCHEMA, None);
+        ctx.add_table(Some("users"), &columns, columns.len());
+        ctx
+    }
+
     #[test]
     fn test_convert_integer_literal() {
-        let columns = make_column_map();
-        let ctx = ExprContext { table_ref: "users", columns: &columns };
+        let ctx = make_resolver();
 
         let expr = ast::Expression::Value(ast::ScalarValue::IntegerNumber(42));
         let result = convert_expr(&expr, &ctx).unwrap();
@@ -554,8 +1630,7 @@ mod tests {
 
     #[test]
     fn test_convert_float_literal() {
-        let columns = make_column_map();
-        let ctx = ExprContext { table_ref: "users", columns: &columns };
+        let ctx = make_resolver();
 
         let expr = ast::Expression::Value(ast::ScalarValue::FloatingNumber(3.14));
         let result = convert_expr(&expr, &ctx).unwrap();
@@ -565,8 +1640,7 @@ mod tests {
 
     #[test]
     fn test_convert_column_ref() {
-        let columns = make_column_map();
-        let ctx = ExprContext { table_ref: "users", columns: &columns };
+        let ctx = make_resolver();
 
         let expr = ast::Expression::Value(ast::ScalarValue::Identifier("age".to_string()));
         let result = convert_expr(&expr, &ctx).unwrap();
@@ -576,8 +1650,7 @@ mod tests {
 
     #[test]
     fn test_convert_qualified_column_ref() {
-        let columns = make_column_map();
-        let ctx = ExprContext { table_ref: "users", columns: &columns };
+        let ctx = make_resolver();
 
         // users.name
         let table_expr = Box::new(ast::Expression::Value(
@@ -593,8 +1666,7 @@ mod tests {
 
     #[test]
     fn test_convert_qualified_column_wrong_table() {
-        let columns = make_column_map();
-        let ctx = ExprContext { table_ref: "users", columns: &columns };
+        let ctx = make_resolver();
 
         // other.name - should fail because "other" != "users"
         let table_expr = Box::new(ast::Expression::Value(
@@ -610,8 +1682,7 @@ mod tests {
 
     #[test]
     fn test_convert_column_not_found() {
-        let columns = make_column_map();
-        let ctx = ExprContext { table_ref: "users", columns: &columns };
+        let ctx = make_resolver();
 
         let expr = ast::Expression::Value(ast::ScalarValue::Identifier("nonexistent".to_string()));
         let result = convert_expr(&expr, &ctx);
@@ -624,8 +1695,7 @@ mod tests {
 
     #[test]
     fn test_convert_binary_comparison() {
-        let columns = make_column_map();
-        let ctx = ExprContext { table_ref: "users", columns: &columns };
+        let ctx = make_resolver();
 
         // age > 21
         let expr = ast::Expression::BinaryOp {
@@ -644,8 +1714,7 @@ mod tests {
 
     #[test]
     fn test_convert_unary_negate() {
-        let columns = make_column_map();
-        let ctx = ExprContext { table_ref: "users", columns: &columns };
+        let ctx = make_resolver();
 
         // -age
         let expr = ast::Expression::UnaryOp {
@@ -662,8 +1731,7 @@ mod tests {
 
     #[test]
     fn test_convert_nested_expression() {
-        let columns = make_column_map();
-        let ctx = ExprContext { table_ref: "users", columns: &columns };
+        let ctx = make_resolver();
 
         // (age + 1) > 21
         let age_plus_one = ast::Expression::BinaryOp {
@@ -784,7 +1852,7 @@ mod tests {
             )),
         };
         let mut columns = HashSet::new();
-        collect_columns_from_column_expr(&col_expr, &mut columns);
+        collect_columns(column_expression(&col_expr), &mut columns);
 
         assert_eq!(columns, HashSet::from(["age".to_string()]));
     }
@@ -798,7 +1866,7 @@ mod tests {
             rhs: Box::new(ast::Expression::Value(ast::ScalarValue::IntegerNumber(1))),
         }));
         let mut columns = HashSet::new();
-        collect_columns_from_column_expr(&col_expr, &mut columns);
+        collect_columns(column_expression(&col_expr), &mut columns);
 
         assert_eq!(columns, HashSet::from(["age".to_string()]));
     }
@@ -991,6 +2059,42 @@ mod tests {
         assert_eq!(plan, expected);
     }
 
+    /// SELECT name FROM users ORDER BY age DESC, id
+    /// Expected:
+    ///   Project { columns: [ColumnRef(1)] }
+    ///   └─ Sort { keys: [ColumnRef(2) DESC, ColumnRef(0)] }
+    ///        └─ Scan { table: "users", columns: [0, 1, 2] }
+    /// The sort keys don't have to be selected, so Sort sits below Project
+    #[test]
+    fn test_select_with_order_by() {
+        let schema = make_users_schema();
+        let stmt = parse_sql("SELECT name FROM users ORDER BY age DESC, id");
+
+        let plan = plan(stmt, &schema).expect("Planning failed");
+
+        let expected = LogicalPlan::Project {
+            input: Box::new(LogicalPlan::Sort {
+                input: Box::new(LogicalPlan::Scan {
+                    table: "users".to_string(),
+                    columns: vec![0, 1, 2],
+                }),
+                keys: vec![
+                    SortKey {
+                        expr: PlanExpr::ColumnRef(ColumnRef::Single { column_idx: 2 }),
+                        descending: true,
+                    },
+                    SortKey {
+                        expr: PlanExpr::ColumnRef(ColumnRef::Single { column_idx: 0 }),
+                        descending: false,
+                    },
+                ],
+            }),
+            columns: vec![PlanExpr::ColumnRef(ColumnRef::Single { column_idx: 1 })],
+        };
+
+        assert_eq!(plan, expected);
+    }
+
     /// SELECT * should expand to all columns
     /// Scan { columns: [0, 1, 2] } reads all columns
     /// Project outputs them in order
@@ -1047,4 +2151,706 @@ mod tests {
             })
         );
     }
+
+    /// SELECT age, count(*), max(id) + 1 FROM users WHERE id > 0 GROUP BY age HAVING count(*) > 1 ORDER BY max(id)
+    /// Expected:
+    ///   Project { columns: [ColumnRef(0), ColumnRef(1), ColumnRef(2) + 1] }
+    ///   └─ Sort { keys: [ColumnRef(2)] }
+    ///        └─ Filter { predicate: ColumnRef(1) > 1 }
+    ///             └─ Aggregate { group_by: [ColumnRef(1)], aggregates: [count(*), max(ColumnRef(0))] }
+    ///                  └─ Filter { predicate: ColumnRef(0) > 0 }
+    ///                       └─ Scan { table: "users", columns: [0, 2] }
+    /// Expressions above the Aggregate read its output: the group, then each distinct aggregate
+    #[test]
+    fn test_select_with_group_by() {
+        let schema = make_users_schema();
+        let stmt = parse_sql(
+            "SELECT age, count(*), max(id) + 1 FROM users WHERE id > 0 GROUP BY age HAVING count(*) > 1 ORDER BY max(id)",
+        );
+
+        let plan = plan(stmt, &schema).expect("Planning failed");
+
+        let column = |column_idx| PlanExpr::ColumnRef(ColumnRef::Single { column_idx });
+        let compare = |op, left, right| PlanExpr::BinaryOp {
+            op,
+            left: Box::new(left),
+            right: Box::new(PlanExpr::Literal(Literal::Integer(right))),
+        };
+        let expected = LogicalPlan::Project {
+            input: Box::new(LogicalPlan::Sort {
+                input: Box::new(LogicalPlan::Filter {
+                    input: Box::new(LogicalPlan::Aggregate {
+                        input: Box::new(LogicalPlan::Filter {
+                            input: Box::new(LogicalPlan::Scan {
+                                table: "users".to_string(),
+                                columns: vec![0, 2],
+                            }),
+                            predicate: compare(BinaryOp::GreaterThan, column(0), 0),
+                        }),
+                        group_by: vec![column(1)],
+                        aggregates: vec![
+                            AggregateExpr {
+                                function: AggregateFunction::Count,
+                                arg: None,
+                            },
+                            AggregateExpr {
+                                function: AggregateFunction::Max,
+                                arg: Some(column(0)),
+                            },
+                        ],
+                    }),
+                    predicate: compare(BinaryOp::GreaterThan, column(1), 1),
+                }),
+                keys: vec![SortKey {
+                    expr: column(2),
+                    descending: false,
+                }],
+            }),
+            columns: vec![column(0), column(1), compare(BinaryOp::Add, column(2), 1)],
+        };
+
+        assert_eq!(plan, expected);
+    }
+
+    /// Without GROUP BY, aggregates make the whole table one group
+    #[test]
+    fn test_select_aggregate_without_group_by() {
+        let schema = make_users_schema();
+        let stmt = parse_sql("SELECT sum(age) FROM users");
+
+        let plan = plan(stmt, &schema).expect("Planning failed");
+
+        let expected = LogicalPlan::Project {
+            input: Box::new(LogicalPlan::Aggregate {
+                input: Box::new(LogicalPlan::Scan {
+                    table: "users".to_string(),
+                    columns: vec![2],
+                }),
+                group_by: vec![],
+                aggregates: vec![AggregateExpr {
+                    function: AggregateFunction::Sum,
+                    arg: Some(PlanExpr::ColumnRef(ColumnRef::Single { column_idx: 0 })),
+                }],
+            }),
+            columns: vec![PlanExpr::ColumnRef(ColumnRef::Single { column_idx: 0 })],
+        };
+
+        assert_eq!(plan, expected);
+    }
+
+    #[test]
+    fn test_aggregate_errors() {
+        let schema = make_users_schema();
+        let error = |sql| plan(parse_sql(sql), &schema).unwrap_err();
+
+        assert_eq!(error("SELECT name FROM users GROUP BY age"), PlanError::NotGrouped("name".to_string()));
+        assert_eq!(error("SELECT age, count(*) FROM users"), PlanError::NotGrouped("age".to_string()));
+        assert_eq!(error("SELECT id FROM users WHERE count(*) > 1"), PlanError::MisusedAggregate("count".to_string()));
+        assert_eq!(error("SELECT max(min(id)) FROM users"), PlanError::MisusedAggregate("min".to_string()));
+        assert_eq!(error("SELECT sum(*) FROM users"), PlanError::InvalidArguments("sum".to_string()));
+        assert_eq!(error("SELECT max(id, age) FROM users"), PlanError::InvalidArguments("max".to_string()));
+        assert_eq!(error("SELECT lower(name) FROM users"), PlanError::UnknownFunction("lower".to_string()));
+    }
+
+    fn make_join_schema() -> schema::Schema {
+        let mut schema = make_users_schema();
+        schema.tables.push(schema::Table {
+            name: "orders".to_string(),
+            columns: ["id", "user_id", "total"]
+                .into_iter()
+                .map(|name| schema::Column { name: name.to_string() })
+                .collect(),
+        });
+        schema
+    }
+
+    /// SELECT name, total FROM users AS u LEFT JOIN orders ON orders.user_id = u.id WHERE total > 10
+    /// Expected:
+    ///   Project { columns: [ColumnRef(1), ColumnRef(4)] }
+    ///   └─ Filter { predicate: ColumnRef(4) > 10 }
+    ///        └─ Join { kind: Left, condition: ColumnRef(3) = ColumnRef(0),
+    ///                  strategy: Hash { left_keys: [ColumnRef(0)], right_keys: [ColumnRef(1)] } }
+    ///             ├─ Scan { table: "users", columns: [0, 1] }
+    ///             └─ Scan { table: "orders", columns: [0, 1, 2] }
+    /// Both tables read "id", as columns are collected by name.
+    #[test]
+    fn test_select_with_join() {
+        let schema = make_join_schema();
+        let stmt = parse_sql("SELECT name, total FROM users AS u LEFT JOIN orders ON orders.user_id = u.id WHERE total > 10");
+
+        let plan = plan(stmt, &schema).expect("Planning failed");
+
+        let column = |column_idx| Box::new(PlanExpr::ColumnRef(ColumnRef::Single { column_idx }));
+        let expected = LogicalPlan::Project {
+            input: Box::new(LogicalPlan::Filter {
+                input: Box::new(LogicalPlan::Join {
+                    left: Box::new(LogicalPlan::Scan {
+                        table: "users".to_string(),
+                        columns: vec![0, 1],
+                    }),
+                    right: Box::new(LogicalPlan::Scan {
+                        table: "orders".to_string(),
+                        columns: vec![0, 1, 2],
+                    }),
+                    kind: JoinKind::Left,
+                    condition: Some(PlanExpr::BinaryOp {
+                        op: BinaryOp::Equals,
+                        left: column(3),
+                        right: column(0),
+                    }),
+                    strategy: JoinStrategy::Hash {
+                        left_keys: vec![*column(0)],
+                        right_keys: vec![*column(1)],
+                    },
+                }),
+                predicate: PlanExpr::BinaryOp {
+                    op: BinaryOp::GreaterThan,
+                    left: column(4),
+                    right: Box::new(PlanExpr::Literal(Literal::Integer(10))),
+                },
+            }),
+            columns: vec![*column(1), *column(4)],
+        };
+
+        assert_eq!(plan, expected);
+    }
+
+    #[test]
+    fn test_join_strategy() {
+        let schema = make_join_schema();
+        let strategy = |sql| match plan(parse_sql(sql), &schema).expect("Planning failed") {
+            LogicalPlan::Project { input, .. } => match *input {
+                LogicalPlan::Join { strategy, .. } => strategy,
+                plan => panic!("expected a join, got {plan:?}"),
+            },
+            plan => panic!("expected a project, got {plan:?}"),
+        };
+        let column = |column_idx| PlanExpr::ColumnRef(ColumnRef::Single { column_idx });
+
+        // orders reads [id, user_id, rowid], after the users columns [id, age]
+        assert_eq!(
+            strategy("SELECT age FROM users JOIN orders ON orders.rowid = users.id + 1 AND orders.user_id = users.id"),
+            JoinStrategy::RowidSeek {
+                key: PlanExpr::BinaryOp {
+                    op: BinaryOp::Add,
+                    left: Box::new(column(0)),
+                    right: Box::new(PlanExpr::Literal(Literal::Integer(1))),
+                },
+            }
+        );
+
+        // Either side of the equality can be the right one, a left key can be a constant
+        assert_eq!(
+            strategy("SELECT age FROM users JOIN orders ON users.age > 1 AND 7 = orders.id AND user_id - 1 = users.id"),
+            JoinStrategy::Hash {
+                left_keys: vec![PlanExpr::Literal(Literal::Integer(7)), column(0)],
+                right_keys: vec![
+                    column(0),
+                    PlanExpr::BinaryOp {
+                        op: BinaryOp::Subtract,
+                        left: Box::new(column(1)),
+                        right: Box::new(PlanExpr::Literal(Literal::Integer(1))),
+                    },
+                ],
+            }
+        );
+
+        // Equalities under OR, within one side, or of a constant alone can't be used
+        for sql in [
+            "SELECT age FROM users JOIN orders ON orders.id = users.id OR orders.user_id = users.id",
+            "SELECT age FROM users JOIN orders ON orders.id = orders.user_id AND users.age = users.id",
+            "SELECT age FROM users JOIN orders ON orders.total > users.age AND 1 = 1",
+            "SELECT age FROM users, orders",
+        ] {
+            assert_eq!(strategy(sql), JoinStrategy::NestedLoop, "{sql}");
+        }
+    }
+
+    /// SELECT d.total FROM (SELECT age AS total, name FROM users WHERE age > 1) AS d WHERE total > 3
+    /// Expected:
+    ///   Project { columns: [ColumnRef(0)] }
+    ///   └─ Filter { predicate: ColumnRef(0) > 3 }
+    ///        └─ Project { columns: [ColumnRef(1), ColumnRef(0)] }
+    ///             └─ Filter { predicate: ColumnRef(1) > 1 }
+    ///                  └─ Scan { table: "users", columns: [1, 2] }
+    #[test]
+    fn test_select_from_derived_table() {
+        let schema = make_users_schema();
+        let stmt = parse_sql("SELECT d.total FROM (SELECT age AS total, name FROM users WHERE age > 1) AS d WHERE total > 3");
+
+        let plan = plan(stmt, &schema).expect("Planning failed");
+
+        let column = |column_idx| PlanExpr::ColumnRef(ColumnRef::Single { column_idx });
+        let greater_than = |column_idx, n| PlanExpr::BinaryOp {
+            op: BinaryOp::GreaterThan,
+            left: Box::new(column(column_idx)),
+            right: Box::new(PlanExpr::Literal(Literal::Integer(n))),
+        };
+        let expected = LogicalPlan::Project {
+            input: Box::new(LogicalPlan::Filter {
+                input: Box::new(LogicalPlan::Project {
+                    input: Box::new(LogicalPlan::Filter {
+                        input: Box::new(LogicalPlan::Scan {
+                            table: "users".to_string(),
+                            columns: vec![1, 2],
+                        }),
+                        predicate: greater_than(1, 1),
+                    }),
+                    columns: vec![column(1), column(0)],
+                }),
+                predicate: greater_than(0, 3),
+            }),
+            columns: vec![column(0)],
+        };
+
+        assert_eq!(plan, expected);
+    }
+
+    #[test]
+    fn test_derived_table_errors() {
+        let schema = make_users_schema();
+        let error = |sql| plan(parse_sql(sql), &schema).unwrap_err();
+
+        assert_eq!(
+            error("SELECT a FROM (SELECT id AS a, age AS a FROM users)"),
+            PlanError::AmbiguousColumn("a".to_string())
+        );
+        assert_eq!(
+            error("SELECT d.a FROM (SELECT id AS a, age AS a FROM users) AS d"),
+            PlanError::AmbiguousColumn("d.a".to_string())
+        );
+        // Only columns with a name can be referred to
+        assert_eq!(
+            error("SELECT age FROM (SELECT age + 1 FROM users) AS d"),
+            PlanError::ColumnNotFound {
+                table: "d".to_string(),
+                column: "age".to_string(),
+            }
+        );
+        // The tables inside aren't in scope outside
+        assert_eq!(
+            error("SELECT users.id FROM (SELECT id FROM users)"),
+            PlanError::TableNotFound("users".to_string())
+        );
+        assert_eq!(
+            error("SELECT id FROM (SELECT nothing FROM users)"),
+            PlanError::ColumnNotFound {
+                table: "users".to_string(),
+                column: "nothing".to_string(),
+            }
+        );
+        assert_eq!(
+            error("SELECT d.id FROM users AS d JOIN (SELECT id FROM users) AS d"),
+            PlanError::DuplicateTable("d".to_string())
+        );
+    }
+
+    /// SELECT name FROM users WHERE EXISTS (SELECT id FROM orders WHERE user_id = users.id)
+    /// Expected:
+    ///   Project { columns: [ColumnRef(1)] }
+    ///   └─ Filter { predicate: Subquery { kind: Exists, correlated: true } }
+    ///        │    Project { columns: [ColumnRef(0)] }
+    ///        │    └─ Filter { predicate: ColumnRef(1) = Outer(1, 0) }
+    ///        │         └─ Scan { table: "orders", columns: [0, 1] }
+    ///        └─ Scan { table: "users", columns: [0, 1] }
+    /// users reads "id" for the subquery, whose own "id" is orders'.
+    #[test]
+    fn test_select_with_correlated_subquery() {
+        let schema = make_join_schema();
+        let stmt = parse_sql("SELECT name FROM users WHERE EXISTS (SELECT id FROM orders WHERE user_id = users.id)");
+
+        let logical_plan = plan(stmt, &schema).expect("Planning failed");
+
+        let column = |column_idx| PlanExpr::ColumnRef(ColumnRef::Single { column_idx });
+        let subquery = LogicalPlan::Project {
+            input: Box::new(LogicalPlan::Filter {
+                input: Box::new(LogicalPlan::Scan {
+                    table: "orders".to_string(),
+                    columns: vec![0, 1],
+                }),
+                predicate: PlanExpr::BinaryOp {
+                    op: BinaryOp::Equals,
+                    left: Box::new(column(1)),
+                    right: Box::new(PlanExpr::ColumnRef(ColumnRef::Outer {
+                        depth: 1,
+                        column_idx: 0,
+                    })),
+                },
+            }),
+            columns: vec![column(0)],
+        };
+        let expected = LogicalPlan::Project {
+            input: Box::new(LogicalPlan::Filter {
+                input: Box::new(LogicalPlan::Scan {
+                    table: "users".to_string(),
+                    columns: vec![0, 1],
+                }),
+                predicate: PlanExpr::Subquery {
+                    kind: SubqueryKind::Exists,
+                    plan: Box::new(subquery),
+                    correlated: true,
+                },
+            }),
+            columns: vec![column(1)],
+        };
+
+        assert_eq!(logical_plan, expected);
+
+        // Only a subquery that refers to an outer query's columns is correlated
+        let predicate = |sql| match plan(parse_sql(sql), &schema).expect("Planning failed") {
+            LogicalPlan::Project { input, .. } => match *input {
+                LogicalPlan::Filter { predicate, .. } => predicate,
+                plan => panic!("expected a filter, got {plan:?}"),
+            },
+            plan => panic!("expected a project, got {plan:?}"),
+        };
+        assert!(matches!(
+            predicate("SELECT name FROM users WHERE id IN (SELECT user_id FROM orders)"),
+            PlanExpr::Subquery { kind: SubqueryKind::In(value), correlated: false, .. } if *value == column(0)
+        ));
+        assert!(matches!(
+            predicate("SELECT name FROM users WHERE age = (SELECT max(total) FROM orders WHERE id = age)"),
+            PlanExpr::BinaryOp { right, .. } if matches!(
+                *right,
+                PlanExpr::Subquery { kind: SubqueryKind::Scalar, correlated: true, .. }
+            )
+        ));
+        // Correlated through the subquery inside it
+        assert!(matches!(
+            predicate("SELECT name FROM users WHERE EXISTS (SELECT 1 FROM orders WHERE EXISTS (SELECT 1 FROM orders AS o WHERE o.id = age))"),
+            PlanExpr::Subquery { correlated: true, .. }
+        ));
+    }
+
+    #[test]
+    fn test_subquery_errors() {
+        let schema = make_join_schema();
+        let error = |sql| plan(parse_sql(sql), &schema).unwrap_err();
+
+        assert_eq!(error("SELECT (SELECT id, total FROM orders) FROM users"), PlanError::SubqueryColumns(2));
+        assert_eq!(error("SELECT id IN (SELECT id, total FROM orders) FROM users"), PlanError::SubqueryColumns(2));
+        assert!(plan(parse_sql("SELECT EXISTS (SELECT id, total FROM orders) FROM users"), &schema).is_ok());
+
+        assert_eq!(
+            error("SELECT name FROM users WHERE EXISTS (SELECT nothing FROM orders)"),
+            PlanError::ColumnNotFound {
+                table: "orders".to_string(),
+                column: "nothing".to_string(),
+            }
+        );
+        assert_eq!(
+            error("SELECT name FROM users WHERE EXISTS (SELECT 1 FROM orders WHERE users.total = 1)"),
+            PlanError::ColumnNotFound {
+                table: "users".to_string(),
+                column: "total".to_string(),
+            }
+        );
+        assert_eq!(
+            error("SELECT name FROM users WHERE EXISTS (SELECT 1 FROM orders WHERE o.id = 1)"),
+            PlanError::TableNotFound("o".to_string())
+        );
+        // Over the groups only the columns grouped by can be used
+        assert_eq!(
+            error("SELECT count(*) FROM users GROUP BY age HAVING EXISTS (SELECT 1 FROM orders WHERE user_id = name)"),
+            PlanError::NotGrouped("name".to_string())
+        );
+    }
+
+    #[test]
+    fn test_join_errors() {
+        let schema = make_join_schema();
+        let error = |sql| plan(parse_sql(sql), &schema).unwrap_err();
+
+        assert_eq!(error("SELECT id FROM users, orders"), PlanError::AmbiguousColumn("id".to_string()));
+        assert_eq!(error("SELECT users.id FROM users JOIN users"), PlanError::DuplicateTable("users".to_string()));
+        assert_eq!(
+            error("SELECT u.id FROM users AS u JOIN orders ON orders.user_id = users.id"),
+            PlanError::TableNotFound("users".to_string())
+        );
+        // A join condition can't see the tables joined after it
+        assert_eq!(
+            error("SELECT o.id FROM users JOIN orders AS o ON o.id = p.id JOIN orders AS p"),
+            PlanError::TableNotFound("p".to_string())
+        );
+        assert_eq!(
+            error("SELECT users.total FROM users, orders"),
+            PlanError::ColumnNotFound {
+                table: "users".to_string(),
+                column: "total".to_string(),
+            }
+        );
+        assert_eq!(
+            error("SELECT nothing FROM users, orders"),
+            PlanError::ColumnNotFound {
+                table: "users, orders".to_string(),
+                column: "nothing".to_string(),
+            }
+        );
+    }
+
+    /// INSERT INTO users (age, id) VALUES (30, 1), (-1, 2)
+    /// Expected:
+    ///   Insert { table: "users" }
+    ///   └─ Values { rows: [[1, NULL, 30], [2, NULL, -1]] }
+    #[test]
+    fn test_insert() {
+        let schema = make_users_schema();
+        let stmt = parse_sql("INSERT INTO users (age, id) VALUES (30, 1), (-1, 2)");
+
+        let plan = plan(stmt, &schema).expect("Planning failed");
+
+        let integer = |value| PlanExpr::Literal(Literal::Integer(value));
+        let null = PlanExpr::Literal(Literal::Null);
+        let negated = PlanExpr::UnaryOp {
+            op: UnaryOp::Negate,
+            operand: Box::new(integer(1)),
+        };
+        let expected = LogicalPlan::Insert {
+            input: Box::new(LogicalPlan::Values {
+                rows: vec![
+                    vec![integer(1), null.clone(), integer(30)],
+                    vec![integer(2), null, negated],
+                ],
+            }),
+            table: "users".to_string(),
+        };
+
+        assert_eq!(plan, expected);
+    }
+
+    #[test]
+    fn test_insert_select() {
+        let mut schema = make_users_schema();
+        schema.tables.push(schema::Table {
+            name: "people".to_string(),
+            columns: vec![schema::Column {
+                name: "years".to_string(),
+            }],
+        });
+        let stmt = parse_sql("INSERT INTO users (age, id) SELECT years, -years FROM people");
+
+        let logical_plan = plan(stmt, &schema).expect("Planning failed");
+
+        let column = |column_idx| PlanExpr::ColumnRef(ColumnRef::Single { column_idx });
+        let expected = LogicalPlan::Insert {
+            input: Box::new(LogicalPlan::Project {
+                input: Box::new(LogicalPlan::Project {
+                    input: Box::new(LogicalPlan::Scan {
+                        table: "people".to_string(),
+                        columns: vec![0],
+                    }),
+                    columns: vec![
+                        column(0),
+                        PlanExpr::UnaryOp {
+                            op: UnaryOp::Negate,
+                            operand: Box::new(column(0)),
+                        },
+                    ],
+                }),
+                columns: vec![column(1), PlanExpr::Literal(Literal::Null), column(0)],
+            }),
+            table: "users".to_string(),
+        };
+        assert_eq!(logical_plan, expected);
+
+        // Selecting from the table inserted into, even in a subquery, reads it all first
+        for sql in [
+            "INSERT INTO users SELECT id + 1, name, age FROM users",
+            "INSERT INTO users (id) SELECT (SELECT max(id) FROM users) + years FROM people",
+        ] {
+            let LogicalPlan::Insert { input, .. } = plan(parse_sql(sql), &schema).unwrap() else {
+                panic!("expected an Insert for {sql}");
+            };
+            assert!(matches!(*input, LogicalPlan::Materialize { .. }), "{sql}: {input:?}");
+        }
+    }
+
+    #[test]
+    fn test_insert_errors() {
+        let schema = make_users_schema();
+        let error = |sql| plan(parse_sql(sql), &schema).unwrap_err();
+
+        assert_eq!(
+            error("INSERT INTO nothing VALUES (1)"),
+            PlanError::TableNotFound("nothing".to_string())
+        );
+        assert_eq!(
+            error("INSERT INTO users (id, email) VALUES (1, 'a')"),
+            PlanError::ColumnNotFound {
+                table: "users".to_string(),
+                column: "email".to_string(),
+            }
+        );
+        assert_eq!(
+            error("INSERT INTO users (id, name, id) VALUES (1, 'a', 2)"),
+            PlanError::DuplicateColumn("id".to_string())
+        );
+        assert_eq!(
+            error("INSERT INTO users VALUES (1, 'a')"),
+            PlanError::InsertColumns { expected: 3, found: 2 }
+        );
+        assert_eq!(
+            error("INSERT INTO users (id) VALUES (1), (2, 3)"),
+            PlanError::InsertColumns { expected: 1, found: 2 }
+        );
+        // Values have no row to take columns from
+        assert!(matches!(
+            error("INSERT INTO users (id) VALUES (age)"),
+            PlanError::ColumnNotFound { column, .. } if column == "age"
+        ));
+        assert_eq!(
+            error("INSERT INTO users (id) VALUES (count(*))"),
+            PlanError::MisusedAggregate("count".to_string())
+        );
+        assert_eq!(
+            error("INSERT INTO users SELECT id, name FROM users"),
+            PlanError::InsertColumns { expected: 3, found: 2 }
+        );
+        assert_eq!(
+            error("INSERT INTO users (name) SELECT email FROM users"),
+            PlanError::ColumnNotFound {
+                table: "users".to_string(),
+                column: "email".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_update() {
+        let schema = make_users_schema();
+        let stmt = parse_sql("UPDATE users SET age = age + 1, name = 'x' WHERE rowid > id");
+
+        let logical_plan = plan(stmt, &schema).expect("Planning failed");
+
+        let column = |column_idx| PlanExpr::ColumnRef(ColumnRef::Single { column_idx });
+        let expected = LogicalPlan::Update {
+            input: Box::new(LogicalPlan::Project {
+                input: Box::new(LogicalPlan::Filter {
+                    input: Box::new(LogicalPlan::Scan {
+                        table: "users".to_string(),
+                        columns: vec![0, 1, 2, ROWID_COLUMN],
+                    }),
+                    predicate: PlanExpr::BinaryOp {
+                        op: BinaryOp::GreaterThan,
+                        left: Box::new(column(3)),
+                        right: Box::new(column(0)),
+                    },
+                }),
+                columns: vec![
+                    column(0),
+                    PlanExpr::Literal(Literal::String("x".to_string())),
+                    PlanExpr::BinaryOp {
+                        op: BinaryOp::Add,
+                        left: Box::new(column(2)),
+                        right: Box::new(PlanExpr::Literal(Literal::Integer(1))),
+                    },
+                ],
+            }),
+            table: "users".to_string(),
+        };
+
+        assert_eq!(logical_plan, expected);
+
+        // Values reading the table are all computed before any row is written, with its rowid
+        let logical_plan = plan(
+            parse_sql("UPDATE users SET age = (SELECT max(age) FROM users)"),
+            &schema,
+        )
+        .unwrap();
+        assert!(matches!(
+            logical_plan,
+            LogicalPlan::Update { input, .. } if matches!(
+                &*input,
+                LogicalPlan::Materialize { input } if matches!(
+                    &**input,
+                    LogicalPlan::Project { columns, .. } if columns.last() == Some(&column(3))
+                )
+            )
+        ));
+    }
+
+    #[test]
+    fn test_update_errors() {
+        let schema = make_users_schema();
+        let error = |sql| plan(parse_sql(sql), &schema).unwrap_err();
+
+        assert_eq!(
+            error("UPDATE nothing SET a = 1"),
+            PlanError::TableNotFound("nothing".to_string())
+        );
+        assert_eq!(
+            error("UPDATE users SET email = 'a'"),
+            PlanError::ColumnNotFound {
+                table: "users".to_string(),
+                column: "email".to_string(),
+            }
+        );
+        // The rowid can be read but not set
+        assert!(matches!(
+            error("UPDATE users SET rowid = 1"),
+            PlanError::ColumnNotFound { column, .. } if column == "rowid"
+        ));
+        assert_eq!(
+            error("UPDATE users SET age = 1, name = 'a', age = 2"),
+            PlanError::DuplicateColumn("age".to_string())
+        );
+        assert!(matches!(
+            error("UPDATE users SET age = 1 WHERE email = 'a'"),
+            PlanError::ColumnNotFound { column, .. } if column == "email"
+        ));
+        assert_eq!(
+            error("UPDATE users SET age = max(age)"),
+            PlanError::MisusedAggregate("max".to_string())
+        );
+    }
+
+    #[test]
+    fn test_delete() {
+        let schema = make_users_schema();
+        let stmt = parse_sql("DELETE FROM users WHERE age < 18");
+
+        let logical_plan = plan(stmt, &schema).expect("Planning failed");
+
+        let expected = LogicalPlan::Delete {
+            input: Box::new(LogicalPlan::Filter {
+                input: Box::new(LogicalPlan::Scan {
+                    table: "users".to_string(),
+                    columns: vec![0, 1, 2, ROWID_COLUMN],
+                }),
+                predicate: PlanExpr::BinaryOp {
+                    op: BinaryOp::LessThan,
+                    left: Box::new(PlanExpr::ColumnRef(ColumnRef::Single { column_idx: 2 })),
+                    right: Box::new(PlanExpr::Literal(Literal::Integer(18))),
+                },
+            }),
+            table: "users".to_string(),
+        };
+        assert_eq!(logical_plan, expected);
+
+        let logical_plan = plan(parse_sql("DELETE FROM users"), &schema).unwrap();
+        assert!(matches!(
+            logical_plan,
+            LogicalPlan::Delete { input, .. } if matches!(*input, LogicalPlan::Scan { .. })
+        ));
+
+        // A filter reading the table finds the rowids to remove before any are
+        let logical_plan = plan(
+            parse_sql("DELETE FROM users WHERE age > (SELECT min(age) FROM users)"),
+            &schema,
+        )
+        .unwrap();
+        assert!(matches!(
+            logical_plan,
+            LogicalPlan::Delete { input, .. } if matches!(*input, LogicalPlan::Materialize { .. })
+        ));
+
+        assert_eq!(
+            plan(parse_sql("DELETE FROM nothing"), &schema).unwrap_err(),
+            PlanError::TableNotFound("nothing".to_string())
+        );
+        assert!(matches!(
+            plan(parse_sql("DELETE FROM users WHERE email = 'a'"), &schema).unwrap_err(),
+            PlanError::ColumnNotFound { column, .. } if column == "email"
+        ));
+    }
 }
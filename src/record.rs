@@ -0,0 +1,299 @@
+use crate::scalarvalue::ScalarValue;
+
+// Rows are stored in the SQLite record format, see https://www.sqlite.org/fileformat.html#record_format
+//   [header size: varint][serial type: varint]...[value]...
+// the header size includes itself, each value is stored in the order of its serial type.
+// Booleans, which SQLite doesn't have, use the serial types SQLite reserves for internal use.
+const NULL: u64 = 0;
+const FLOAT: u64 = 7;
+const ZERO: u64 = 8;
+const ONE: u64 = 9;
+const FALSE: u64 = 10;
+const TRUE: u64 = 11;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordError {
+    /// The header or a value runs past the end of the record
+    Truncated,
+    InvalidUtf8,
+}
+
+/// Encode a row of values as a record
+pub fn encode(values: &[ScalarValue]) -> Vec<u8> {
+    let mut types = vec![];
+    let mut body = vec![];
+
+    for value in values {
+        let serial_type = match value {
+            ScalarValue::Null => NULL,
+            ScalarValue::Integer(0) => ZERO,
+            ScalarValue::Integer(1) => ONE,
+            ScalarValue::Integer(i) => {
+                let len = integer_size(*i);
+                body.extend_from_slice(&i.to_be_bytes()[8 - len..]);
+                integer_serial_type(len)
+            }
+            ScalarValue::Floating(f) => {
+                body.extend_from_slice(&f.to_be_bytes());
+                FLOAT
+            }
+            ScalarValue::Boolean(false) => FALSE,
+            ScalarValue::Boolean(true) => TRUE,
+            ScalarValue::String(s) => {
+                body.extend_from_slice(s.as_bytes());
+                s.len() as u64 * 2 + 13
+            }
+            ScalarValue::Blob(b) => {
+                body.extend_from_slice(b);
+                b.len() as u64 * 2 + 12
+            }
+        };
+        write_varint(&mut types, serial_type);
+    }
+
+    // The header size counts its own varint, which may make it a byte longer
+    let mut header_size = types.len() as u64 + 1;
+    if varint_size(header_size) > 1 {
+        header_size += varint_size(header_size + 1) as u64 - 1;
+    }
+
+    let mut record = Vec::with_capacity(header_size as usize + body.len());
+    write_varint(&mut record, header_size);
+    record.extend_from_slice(&types);
+    record.extend_from_slice(&body);
+
+    record
+}
+
+/// A record whose header has been read, values are only decoded when asked for
+pub struct Record<'a> {
+    data: &'a [u8],
+
+    // serial type and offset of each value
+    columns: Vec<(u64, usize)>,
+}
+
+impl<'a> Record<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Record<'a>, RecordError> {
+        let (header_size, mut header_offset) = read_varint(data).ok_or(RecordError::Truncated)?;
+        let header_size = header_size as usize;
+        let header = data.get(..header_size).ok_or(RecordError::Truncated)?;
+
+        let mut columns = vec![];
+        let mut offset = header_size;
+        while header_offset < header_size {
+            let (serial_type, n) = read_varint(&header[header_offset..]).ok_or(RecordError::Truncated)?;
+            header_offset += n;
+
+            columns.push((serial_type, offset));
+            // Records come from files, a corrupt one can claim values too large to add up
+            offset = offset.checked_add(value_size(serial_type)).ok_or(RecordError::Truncated)?;
+        }
+
+        if offset > data.len() {
+            return Err(RecordError::Truncated);
+        }
+
+        Ok(Record { data, columns })
+    }
+
+    pub fn num_columns(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// The value of column `idx`.
+    /// Columns past the end of the record are NULL, as they would be for a row written before a column was added.
+    pub fn column(&self, idx: usize) -> Result<ScalarValue, RecordError> {
+        let Some(&(serial_type, offset)) = self.columns.get(idx) else {
+            return Ok(ScalarValue::Null);
+        };
+        let bytes = &self.data[offset..offset + value_size(serial_type)];
+
+        let value = match serial_type {
+            NULL => ScalarValue::Null,
+            1..=6 => {
+                // Sign extend from the first byte
+                let initial = if bytes[0] & 0x80 != 0 { -1 } else { 0 };
+                ScalarValue::Integer(bytes.iter().fold(initial, |acc, b| (acc << 8) | *b as i64))
+            }
+            FLOAT => ScalarValue::Floating(f64::from_be_bytes(bytes.try_into().unwrap())),
+            ZERO => ScalarValue::Integer(0),
+            ONE => ScalarValue::Integer(1),
+            FALSE => ScalarValue::Boolean(false),
+            TRUE => ScalarValue::Boolean(true),
+            n if n % 2 == 1 => {
+                let s = std::str::from_utf8(bytes).map_err(|_| RecordError::InvalidUtf8)?;
+                ScalarValue::String(s.to_string())
+            }
+            _ => ScalarValue::Blob(bytes.to_vec()),
+        };
+
+        Ok(value)
+    }
+}
+
+fn integer_size(i: i64) -> usize {
+    match i {
+        -0x80..=0x7f => 1,
+        -0x8000..=0x7fff => 2,
+        -0x80_0000..=0x7f_ffff => 3,
+        -0x8000_0000..=0x7fff_ffff => 4,
+        -0x8000_0000_0000..=0x7fff_ffff_ffff => 6,
+        _ => 8,
+    }
+}
+
+fn integer_serial_type(len: usize) -> u64 {
+    match len {
+        1..=4 => len as u64,
+        6 => 5,
+        _ => 6,
+    }
+}
+
+fn value_size(serial_type: u64) -> usize {
+    match serial_type {
+        1..=4 => serial_type as usize,
+        5 => 6,
+        6 | FLOAT => 8,
+        n if n >= 12 => (n as usize - 12) / 2,
+        _ => 0,
+    }
+}
+
+/// A SQLite varint, returning the value and the number of bytes it took up
+pub fn read_varint(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value: u64 = 0;
+
+    for i in 0..9 {
+        let byte = *bytes.get(i)?;
+
+        if i == 8 {
+            // The ninth byte contributes all 8 of its bits
+            return Some(((value << 8) | byte as u64, 9));
+        }
+
+        value = (value << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+
+    unreachable!()
+}
+
+fn varint_size(value: u64) -> usize {
+    let mut buf = vec![];
+    write_varint(&mut buf, value);
+
+    buf.len()
+}
+
+pub fn write_varint(out: &mut Vec<u8>, value: u64) {
+    if value > 0x00ff_ffff_ffff_ffff {
+        // Nine bytes, the last holds a full 8 bits
+        for i in (1..9).rev() {
+            out.push(((value >> (i * 7 + 1)) as u8 & 0x7f) | 0x80);
+        }
+        out.push(value as u8);
+        return;
+    }
+
+    let mut groups = vec![(value & 0x7f) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        groups.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    out.extend(groups.iter().rev());
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::{encode, read_varint, write_varint, Record, RecordError};
+    use crate::scalarvalue::ScalarValue;
+
+    fn scalar_value() -> impl Strategy<Value = ScalarValue> {
+        prop_oneof![
+            any::<i64>().prop_map(ScalarValue::Integer),
+            (-0x10000i64..0x10000).prop_map(ScalarValue::Integer),
+            any::<f64>()
+                .prop_filter("comparable", |f| f.is_finite())
+                .prop_map(ScalarValue::Floating),
+            any::<bool>().prop_map(ScalarValue::Boolean),
+            ".*".prop_map(ScalarValue::String),
+            any::<Vec<u8>>().prop_map(ScalarValue::Blob),
+            Just(ScalarValue::Null),
+        ]
+    }
+
+    #[test]
+    fn sqlite_layout() {
+        // What sqlite writes for -2, 'hi', NULL, 256, 1
+        let record = encode(&[
+            ScalarValue::Integer(-2),
+            ScalarValue::String("hi".to_string()),
+            ScalarValue::Null,
+            ScalarValue::Integer(256),
+            ScalarValue::Integer(1),
+        ]);
+
+        assert_eq!(vec![6, 1, 17, 0, 2, 9, 0xfe, b'h', b'i', 0x01, 0x00], record);
+    }
+
+    #[test]
+    fn varints() {
+        assert_eq!(Some((0x7f, 1)), read_varint(&[0x7f]));
+        assert_eq!(Some((0x80, 2)), read_varint(&[0x81, 0x00]));
+        assert_eq!(Some((u64::MAX, 9)), read_varint(&[0xff; 9]));
+        assert_eq!(None, read_varint(&[0x81]));
+    }
+
+    #[test]
+    fn columns_decoded_lazily() {
+        // Invalid text doesn't stop the other columns being read
+        let record = [4, 14, 15, 1, 0xab, 0xff, 42];
+        let record = Record::parse(&record).unwrap();
+
+        assert_eq!(ScalarValue::Blob(vec![0xab]), record.column(0).unwrap());
+        assert_eq!(Err(RecordError::InvalidUtf8), record.column(1));
+        assert_eq!(ScalarValue::Integer(42), record.column(2).unwrap());
+        assert_eq!(ScalarValue::Null, record.column(3).unwrap());
+    }
+
+    #[test]
+    fn truncated() {
+        let record = encode(&[ScalarValue::String("hello".to_string())]);
+
+        assert!(matches!(Record::parse(&record[..4]), Err(RecordError::Truncated)));
+        assert!(matches!(Record::parse(&[]), Err(RecordError::Truncated)));
+
+        // Two values of nearly 2^63 bytes each
+        let mut huge = vec![19];
+        huge.extend([0xff; 18]);
+        assert!(matches!(Record::parse(&huge), Err(RecordError::Truncated)));
+    }
+
+    proptest! {
+        #[test]
+        fn varint_round_trip(value: u64) {
+            let mut buf = vec![];
+            write_varint(&mut buf, value);
+
+            prop_assert_eq!(Some((value, buf.len())), read_varint(&buf));
+        }
+
+        #[test]
+        fn record_round_trip(values in prop::collection::vec(scalar_value(), 0..200)) {
+            let record = encode(&values);
+            let record = Record::parse(&record).unwrap();
+
+            prop_assert_eq!(values.len(), record.num_columns());
+            for (idx, value) in values.iter().enumerate() {
+                prop_assert_eq!(value.clone(), record.column(idx).unwrap());
+            }
+        }
+    }
+}
@@ -5,12 +5,13 @@ use std::ops::ControlFlow;
 use rand::Rng;
 
 use crate::repl::{CommandResult, Mode, ModeId, SharedState};
-use crate::storage::{CellReader, CursorHandle};
+use crate::storage::{BackupStatus, CellReader, CursorHandle, DumpOptions, PageError, Transaction};
 
 /// BTree mode state - cursor is created/dropped as part of mode state
 #[derive(Debug)]
 pub struct BTreeMode {
     cursor: Option<CursorState>,
+    transaction: Option<Transaction>,
 }
 
 #[derive(Debug)]
@@ -21,7 +22,10 @@ struct CursorState {
 
 impl BTreeMode {
     pub fn new(_shared: &mut SharedState) -> Self {
-        BTreeMode { cursor: None }
+        BTreeMode {
+            cursor: None,
+            transaction: None,
+        }
     }
 }
 
@@ -49,6 +53,17 @@ impl Mode for BTreeMode {
                 CommandResult::Message(format!("Created table '{}'", name))
             }
 
+            ["create", "autoincrement", "table", rest @ ..] => {
+                let name = rest.join(" ");
+                if name.is_empty() {
+                    return CommandResult::Error(
+                        "Usage: create autoincrement table <name>".to_string(),
+                    );
+                }
+                shared.btree.create_autoincrement_tree(&name);
+                CommandResult::Message(format!("Created autoincrement table '{}'", name))
+            }
+
             // Cursor operations
             ["open", rest @ ..] | ["read", "table", rest @ ..] => {
                 let name = rest.join(" ");
@@ -82,30 +97,18 @@ impl Mode for BTreeMode {
             },
 
             // Navigation
-            ["first"] => self.with_cursor(|cursor| {
-                cursor.handle.open_readonly().first();
-                CommandResult::Ok
-            }),
+            ["first"] => self.with_cursor(|cursor| moved(cursor.handle.open_readonly().first())),
 
-            ["next"] => self.with_cursor(|cursor| {
-                cursor.handle.open_readonly().next();
-                CommandResult::Ok
-            }),
+            ["next"] => self.with_cursor(|cursor| moved(cursor.handle.open_readonly().next())),
 
-            ["prev"] => self.with_cursor(|cursor| {
-                cursor.handle.open_readonly().prev();
-                CommandResult::Ok
-            }),
+            ["prev"] => self.with_cursor(|cursor| moved(cursor.handle.open_readonly().prev())),
 
             ["find", key] => {
                 let key: u64 = match key.parse() {
                     Ok(k) => k,
                     Err(_) => return CommandResult::Error("Invalid key (must be u64)".to_string()),
                 };
-                self.with_cursor(|cursor| {
-                    cursor.handle.open_readonly().find(key);
-                    CommandResult::Ok
-                })
+                self.with_cursor(|cursor| moved(cursor.handle.open_readonly().find(key)))
             }
 
             // Read operations
@@ -118,15 +121,15 @@ impl Mode for BTreeMode {
 
             ["print", "data"] | ["scan"] => self.with_cursor(|cursor| {
                 let mut c = cursor.handle.open_readonly();
-                c.first();
-                loop {
+                let mut result = c.first();
+                while result.is_ok() {
                     let entry = c.get_entry();
                     if let ControlFlow::Break(_) = print_value(entry) {
                         break;
                     }
-                    c.next();
+                    result = c.next();
                 }
-                CommandResult::Ok
+                moved(result)
             }),
 
             // Write operations
@@ -142,6 +145,16 @@ impl Mode for BTreeMode {
                 })
             }
 
+            ["append", rest @ ..] if !rest.is_empty() => {
+                let value = rest.join(" ");
+                self.with_cursor_mut(|cursor| {
+                    match cursor.handle.open_readwrite().insert_next_rowid(value.into_bytes()) {
+                        Ok(key) => CommandResult::Message(format!("Inserted key {}", key)),
+                        Err(e) => CommandResult::Error(e.to_string()),
+                    }
+                })
+            }
+
             ["random", "insert", count, max_size] => {
                 let count: u64 = match count.parse() {
                     Ok(c) => c,
@@ -188,22 +201,63 @@ impl Mode for BTreeMode {
 
                 match result {
                     Ok(_) => CommandResult::Message("Verify success!".to_string()),
-                    Err(e) => CommandResult::Error(format!("Verify failed: {:?}", e)),
+                    Err(e) => CommandResult::Error(format!("Verify failed: {e}")),
                 }
             }
 
-            ["dump", path] => {
-                if self.cursor.is_some() {
-                    return CommandResult::Error("Close cursor before dumping".to_string());
-                }
+            ["dump", path, rest @ ..] => {
+                let options = match self.dump_options(rest) {
+                    Ok(options) => options,
+                    Err(e) => return CommandResult::Error(e),
+                };
 
                 let path = std::path::Path::new(*path);
-                match shared.btree.dump_to_file(path) {
+                match shared.btree.dump_to_file(path, &options) {
                     Ok(_) => CommandResult::Message(format!("Dumped graph to {:?}", path)),
                     Err(e) => CommandResult::Error(format!("Error dumping: {}", e)),
                 }
             }
 
+            // Transactions
+            ["begin"] => {
+                if self.transaction.is_some() {
+                    return CommandResult::Error("Transaction already in progress".to_string());
+                }
+                self.transaction = Some(shared.btree.begin());
+                CommandResult::Message("Transaction started".to_string())
+            }
+
+            ["commit"] => match self.transaction.take() {
+                None => CommandResult::Error("No transaction in progress".to_string()),
+                Some(transaction) => {
+                    transaction.commit();
+                    CommandResult::Message("Transaction committed".to_string())
+                }
+            },
+
+            ["rollback"] => match self.transaction.take() {
+                None => CommandResult::Error("No transaction in progress".to_string()),
+                Some(transaction) => {
+                    transaction.rollback();
+                    // The cursor may point at pages which no longer exist
+                    self.cursor = None;
+                    CommandResult::Message("Transaction rolled back, cursor closed".to_string())
+                }
+            },
+
+            ["backup", path] => {
+                let path = std::path::Path::new(*path);
+                let result = shared.btree.backup(path).and_then(|mut backup| {
+                    while backup.step(100)? != BackupStatus::Done {}
+                    Ok(())
+                });
+
+                match result {
+                    Ok(_) => CommandResult::Message(format!("Backed up database to {:?}", path)),
+                    Err(e) => CommandResult::Error(format!("Error backing up: {}", e)),
+                }
+            }
+
             _ => CommandResult::NotHandled,
         }
     }
@@ -212,6 +266,8 @@ impl Mode for BTreeMode {
         r#"BTree mode commands:
   Table management:
     create table <name>       Create a new B-tree table
+    create autoincrement table <name>
+                              Create a table whose rowids are never reused
     open <name>               Open a cursor on a table
     read table <name>         Alias for open
     close                     Close the current cursor
@@ -228,16 +284,58 @@ impl Mode for BTreeMode {
 
   Write operations (requires open cursor):
     insert <key> <value>      Insert a key-value pair
+    append <value>            Insert a value with the next free rowid
     random insert <n> <size>  Insert n random entries
 
+  Transactions:
+    begin                     Start a transaction
+    commit                    Write out the changes made in the transaction
+    rollback                  Discard the changes made in the transaction
+
   Debug:
     verify                    Verify B-tree integrity
-    dump <path>               Export B-tree as graphviz dot file"#
+    dump <path> [options]     Export B-tree as graphviz dot file, options are any of:
+                                tree <name>   only this table
+                                depth <n>     stop n levels below the root
+                                summary       show leaves as key range and count
+                                highlight     highlight the open cursor's path
+    backup <path>             Copy the database to another file"#
             .to_string()
     }
 }
 
 impl BTreeMode {
+    fn dump_options(&mut self, mut tokens: &[&str]) -> Result<DumpOptions, String> {
+        let mut options = DumpOptions::default();
+
+        loop {
+            tokens = match tokens {
+                [] => return Ok(options),
+                ["tree", name, rest @ ..] => {
+                    options.tree = Some(name.to_string());
+                    rest
+                }
+                ["depth", depth, rest @ ..] => {
+                    let depth = depth.parse().map_err(|_| "Invalid depth".to_string())?;
+                    options.max_depth = Some(depth);
+                    rest
+                }
+                ["summary", rest @ ..] => {
+                    options.summarise_leaves = true;
+                    rest
+                }
+                ["highlight", rest @ ..] => {
+                    let Some(cursor) = &mut self.cursor else {
+                        return Err("No cursor open to highlight".to_string());
+                    };
+                    options.highlight = cursor.handle.open_readonly().path();
+                    rest
+                }
+                [other, ..] => return Err(format!("Unknown dump option '{other}'")),
+            };
+        }
+    }
+
     fn with_cursor<F>(&mut self, f: F) -> CommandResult
     where
         F: FnOnce(&mut CursorState) -> CommandResult,
@@ -259,13 +357,25 @@ impl BTreeMode {
     }
 }
 
-fn print_value(entry: Option<CellReader<'_>>) -> ControlFlow<()> {
+// The result of a command which moves the cursor
+fn moved(result: Result<(), PageError>) -> CommandResult {
+    match result {
+        Ok(()) => CommandResult::Ok,
+        Err(e) => CommandResult::Error(e.to_string()),
+    }
+}
+
+fn print_value(entry: Result<Option<CellReader<'_>>, PageError>) -> ControlFlow<()> {
     match entry {
-        None => {
+        Err(e) => {
+            println!("Entry: {e}");
+            ControlFlow::Break(())
+        }
+        Ok(None) => {
             println!("Cursor is complete");
             ControlFlow::Break(())
         }
-        Some(mut entry) => {
+        Ok(Some(mut entry)) => {
             let key = entry.key();
             let mut value_buf = Vec::new();
             let value_size = entry.read_to_end(&mut value_buf);
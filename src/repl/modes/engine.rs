@@ -1,7 +1,9 @@
 use colored::Colorize;
 
+use crate::catalog;
 use crate::compiler::{compile, CompiledProgram};
-use crate::frontend::parse;
+use crate::engine::Engine;
+use crate::frontend::{ast, parse};
 use crate::planner::plan;
 use crate::repl::{CommandResult, Mode, ModeId, SharedState};
 
@@ -60,6 +62,58 @@ impl Mode for EngineMode {
                 }
             }
 
+            // Execution
+            ["run", rest @ ..] => {
+                let sql = rest.join(" ");
+                if sql.is_empty() {
+                    return CommandResult::Error("Usage: run <sql>".to_string());
+                }
+
+                let Some(schema) = &mut shared.schema else {
+                    return CommandResult::Error(
+                        "No schema defined. Use planner mode to 'load schema' first.".to_string(),
+                    );
+                };
+
+                match parse(&sql) {
+                    Ok(ast::Statement::CreateTable(create)) => {
+                        match catalog::create_table(&mut shared.btree, schema, &create) {
+                            Ok(()) => CommandResult::Message(format!("Created table '{}'", create.name)),
+                            Err(e) => CommandResult::Error(e.to_string()),
+                        }
+                    }
+                    Ok(stmt) => match plan(stmt, schema) {
+                        Ok(logical_plan) => {
+                            let compiled = compile(&logical_plan);
+                            let mut engine = Engine::with_program(
+                                compiled.operations(),
+                                compiled.num_registers(),
+                                shared.btree.as_ref().clone(),
+                            );
+                            // Every change the statement makes is kept, or none are
+                            let transaction = shared.btree.begin();
+                            let result = engine.run();
+                            drop(engine);
+                            match result {
+                                Ok(rows) => {
+                                    transaction.commit();
+                                    let mut output = String::new();
+                                    for row in &rows {
+                                        let values: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+                                        output += &format!("{}\n", values.join(" | "));
+                                    }
+                                    output += &format!("({} rows)", rows.len());
+                                    CommandResult::Message(output)
+                                }
+                                Err(e) => CommandResult::Error(format!("Engine error: {e}")),
+                            }
+                        }
+                        Err(e) => CommandResult::Error(format!("Plan error: {:?}", e)),
+                    },
+                    Err(e) => CommandResult::Error(format!("Parse error: {:?}", e)),
+                }
+            }
+
             // Program inspection
             ["program"] | ["show"] | ["list"] => match &self.program {
                 Some(p) => {
@@ -90,10 +144,9 @@ impl Mode for EngineMode {
     fn help(&self) -> String {
         r#"Engine/VM mode commands:
   compile <sql>       Compile SQL to bytecode (requires schema from planner mode)
+  run <sql>           Run a statement against the database, CREATE TABLE adds to the catalog
   program/show/list   Show compiled bytecode listing
-  clear/reset         Clear compiled program
-
-Note: Full VM execution requires btree integration (future work)"#
+  clear/reset         Clear compiled program"#
             .to_string()
     }
 }
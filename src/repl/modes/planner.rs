@@ -1,3 +1,4 @@
+use crate::catalog;
 use crate::frontend::parse;
 use crate::planner::{plan, schema, LogicalPlan};
 use crate::repl::{CommandResult, Mode, ModeId, SharedState};
@@ -26,10 +27,19 @@ impl Mode for PlannerMode {
             ["schema"] => match &shared.schema {
                 Some(s) => CommandResult::Message(format!("Schema:\n{:#?}", s)),
                 None => CommandResult::Message(
-                    "No schema defined. Use 'mock schema' to create a test schema.".to_string(),
+                    "No schema defined. Use 'load schema', or 'mock schema' to create a test schema.".to_string(),
                 ),
             },
 
+            ["load", "schema"] => match catalog::load(&shared.btree) {
+                Ok(schema) => {
+                    let msg = format!("Loaded schema with {} tables from the catalog", schema.tables.len());
+                    shared.schema = Some(schema);
+                    CommandResult::Message(msg)
+                }
+                Err(e) => CommandResult::Error(e.to_string()),
+            },
+
             ["mock", "schema"] => {
                 shared.schema = Some(create_mock_schema());
                 CommandResult::Message(
@@ -86,6 +96,7 @@ impl Mode for PlannerMode {
     fn help(&self) -> String {
         r#"Planner mode commands:
   schema          Show current schema
+  load schema     Load the schema of the database's tables from its catalog
   mock schema     Create a mock schema (users table with id, name, age)
   clear schema    Remove schema
   plan <sql>      Parse and plan SQL query, show logical plan
@@ -11,16 +11,16 @@ pub struct SharedState {
     /// Database file path
     pub db_path: PathBuf,
 
-    /// Schema for planner mode (can be loaded or mocked)
+    /// Schema for planner mode, loaded from the catalog (or mocked)
     pub schema: Option<Schema>,
 }
 
 impl SharedState {
-    pub fn new(db_path: PathBuf, btree: BTree) -> Self {
+    pub fn new(db_path: PathBuf, btree: BTree, schema: Schema) -> Self {
         SharedState {
             btree: Box::new(btree),
             db_path,
-            schema: None,
+            schema: Some(schema),
         }
     }
 }
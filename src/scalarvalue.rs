@@ -0,0 +1,566 @@
+use std::cmp::Ordering;
+
+//TODO: maybe consider removing boolean and making this type only handle numeric types
+#[derive(Clone, Debug)]
+pub enum ScalarValue {
+    Integer(i64),
+    Floating(f64),
+    Boolean(bool),
+    String(String),
+    Blob(Vec<u8>),
+    Null,
+}
+
+impl Eq for ScalarValue {}
+
+/// Why an operation on values failed
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueError {
+    /// The operation isn't defined for the types of these values
+    TypeMismatch(&'static str, Vec<ScalarValue>),
+    DivisionByZero,
+    /// The result doesn't fit in an integer
+    Overflow,
+}
+
+impl std::fmt::Display for ValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueError::TypeMismatch(operation, values) => {
+                let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "invalid types for {}: {}", operation, values.join(", "))
+            }
+            ValueError::DivisionByZero => write!(f, "division by zero"),
+            ValueError::Overflow => write!(f, "integer overflow"),
+        }
+    }
+}
+
+impl ScalarValue {
+    pub fn is_null(&self) -> bool {
+        matches!(self, ScalarValue::Null)
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            ScalarValue::Integer(i) => *i == 0,
+            ScalarValue::Floating(f) => *f == 0.0,
+            _ => false,
+        }
+    }
+
+    fn numeric_op(
+        &self,
+        rhs: &Self,
+        name: &'static str,
+        i_op: fn(i64, i64) -> Option<i64>,
+        f_op: fn(f64, f64) -> f64,
+    ) -> Result<ScalarValue, ValueError> {
+        match (self, rhs) {
+            (ScalarValue::Null, _) | (_, ScalarValue::Null) => Ok(ScalarValue::Null),
+            (ScalarValue::Integer(lhs), ScalarValue::Integer(rhs)) => {
+                i_op(*lhs, *rhs).map(ScalarValue::Integer).ok_or(ValueError::Overflow)
+            }
+            (ScalarValue::Integer(lhs), ScalarValue::Floating(rhs)) => {
+                Ok(ScalarValue::Floating(f_op(*lhs as f64, *rhs)))
+            }
+            (ScalarValue::Floating(lhs), ScalarValue::Integer(rhs)) => {
+                Ok(ScalarValue::Floating(f_op(*lhs, *rhs as f64)))
+            }
+            (ScalarValue::Floating(lhs), ScalarValue::Floating(rhs)) => {
+                Ok(ScalarValue::Floating(f_op(*lhs, *rhs)))
+            }
+            (lhs, rhs) => Err(ValueError::TypeMismatch(name, vec![lhs.clone(), rhs.clone()])),
+        }
+    }
+
+    /// Addition, or concatenation of two strings
+    pub fn checked_add(&self, rhs: &Self) -> Result<ScalarValue, ValueError> {
+        match (self, rhs) {
+            (ScalarValue::String(lhs), ScalarValue::String(rhs)) => {
+                Ok(ScalarValue::String(format!("{lhs}{rhs}")))
+            }
+            (lhs, rhs) => lhs.numeric_op(rhs, "+", i64::checked_add, |l, r| l + r),
+        }
+    }
+
+    pub fn checked_sub(&self, rhs: &Self) -> Result<ScalarValue, ValueError> {
+        self.numeric_op(rhs, "-", i64::checked_sub, |l, r| l - r)
+    }
+
+    pub fn checked_mul(&self, rhs: &Self) -> Result<ScalarValue, ValueError> {
+        self.numeric_op(rhs, "*", i64::checked_mul, |l, r| l * r)
+    }
+
+    pub fn checked_div(&self, rhs: &Self) -> Result<ScalarValue, ValueError> {
+        if !self.is_null() && rhs.is_zero() {
+            return Err(ValueError::DivisionByZero);
+        }
+        self.numeric_op(rhs, "/", i64::checked_div, |l, r| l / r)
+    }
+
+    pub fn checked_rem(&self, rhs: &Self) -> Result<ScalarValue, ValueError> {
+        if !self.is_null() && rhs.is_zero() {
+            return Err(ValueError::DivisionByZero);
+        }
+        self.numeric_op(rhs, "%", i64::checked_rem, |l, r| l % r)
+    }
+
+    pub fn checked_neg(&self) -> Result<ScalarValue, ValueError> {
+        match self {
+            ScalarValue::Integer(v) => v.checked_neg().map(ScalarValue::Integer).ok_or(ValueError::Overflow),
+            ScalarValue::Floating(v) => Ok(ScalarValue::Floating(-v)),
+            ScalarValue::Null => Ok(ScalarValue::Null),
+            v => Err(ValueError::TypeMismatch("-", vec![v.clone()])),
+        }
+    }
+
+    fn integer_op(&self, rhs: &Self, name: &'static str, op: fn(i64, i64) -> i64) -> Result<ScalarValue, ValueError> {
+        match (self, rhs) {
+            (ScalarValue::Null, _) | (_, ScalarValue::Null) => Ok(ScalarValue::Null),
+            (ScalarValue::Integer(lhs), ScalarValue::Integer(rhs)) => Ok(ScalarValue::Integer(op(*lhs, *rhs))),
+            (lhs, rhs) => Err(ValueError::TypeMismatch(name, vec![lhs.clone(), rhs.clone()])),
+        }
+    }
+
+    pub fn bit_and(&self, rhs: &Self) -> Result<ScalarValue, ValueError> {
+        self.integer_op(rhs, "&", |l, r| l & r)
+    }
+
+    pub fn bit_or(&self, rhs: &Self) -> Result<ScalarValue, ValueError> {
+        self.integer_op(rhs, "|", |l, r| l | r)
+    }
+
+    pub fn bit_xor(&self, rhs: &Self) -> Result<ScalarValue, ValueError> {
+        self.integer_op(rhs, "^", |l, r| l ^ r)
+    }
+
+    /// Shifting by a negative amount shifts the other way, as SQLite does
+    pub fn shift_left(&self, rhs: &Self) -> Result<ScalarValue, ValueError> {
+        self.integer_op(rhs, "<<", shift_left)
+    }
+
+    pub fn shift_right(&self, rhs: &Self) -> Result<ScalarValue, ValueError> {
+        self.integer_op(rhs, ">>", |l, r| shift_left(l, r.saturating_neg()))
+    }
+
+    pub fn bit_not(&self) -> Result<ScalarValue, ValueError> {
+        match self {
+            ScalarValue::Integer(v) => Ok(ScalarValue::Integer(!v)),
+            ScalarValue::Null => Ok(ScalarValue::Null),
+            v => Err(ValueError::TypeMismatch("~", vec![v.clone()])),
+        }
+    }
+
+    /// Whether `op` holds for the order of two values, so `=` is `Ordering::is_eq`. Integers and floats
+    /// compare by value (1 = 1.0). Comparing anything with NULL gives NULL, as does comparing NaN
+    pub fn compare(&self, rhs: &Self, op: fn(Ordering) -> bool) -> Result<ScalarValue, ValueError> {
+        match (self, rhs) {
+            (ScalarValue::Null, _) | (_, ScalarValue::Null) => Ok(ScalarValue::Null),
+            (
+                ScalarValue::Integer(_) | ScalarValue::Floating(_),
+                ScalarValue::Integer(_) | ScalarValue::Floating(_),
+            )
+            | (ScalarValue::String(_), ScalarValue::String(_))
+            | (ScalarValue::Blob(_), ScalarValue::Blob(_))
+            | (ScalarValue::Boolean(_), ScalarValue::Boolean(_)) => Ok(self
+                .partial_cmp(rhs)
+                .map_or(ScalarValue::Null, |ordering| ScalarValue::Boolean(op(ordering)))),
+            (lhs, rhs) => Err(ValueError::TypeMismatch("comparison", vec![lhs.clone(), rhs.clone()])),
+        }
+    }
+
+    /// The truth value of a boolean, NULL is unknown
+    fn truth(&self, name: &'static str) -> Result<Option<bool>, ValueError> {
+        match self {
+            ScalarValue::Boolean(b) => Ok(Some(*b)),
+            ScalarValue::Null => Ok(None),
+            v => Err(ValueError::TypeMismatch(name, vec![v.clone()])),
+        }
+    }
+
+    /// Whether a condition holds, NULL doesn't
+    pub fn is_true(&self) -> Result<bool, ValueError> {
+        Ok(self.truth("condition")? == Some(true))
+    }
+
+    /// Three valued AND, false wins over NULL
+    pub fn and(&self, rhs: &Self) -> Result<ScalarValue, ValueError> {
+        Ok(match (self.truth("AND")?, rhs.truth("AND")?) {
+            (Some(false), _) | (_, Some(false)) => ScalarValue::Boolean(false),
+            (Some(true), Some(true)) => ScalarValue::Boolean(true),
+            _ => ScalarValue::Null,
+        })
+    }
+
+    /// Three valued OR, true wins over NULL
+    pub fn or(&self, rhs: &Self) -> Result<ScalarValue, ValueError> {
+        Ok(match (self.truth("OR")?, rhs.truth("OR")?) {
+            (Some(true), _) | (_, Some(true)) => ScalarValue::Boolean(true),
+            (Some(false), Some(false)) => ScalarValue::Boolean(false),
+            _ => ScalarValue::Null,
+        })
+    }
+
+    /// Three valued NOT, NOT NULL is NULL
+    pub fn not(&self) -> Result<ScalarValue, ValueError> {
+        Ok(self.truth("NOT")?.map_or(ScalarValue::Null, |b| ScalarValue::Boolean(!b)))
+    }
+
+    /// Whether two values are in different groups, which they are unless `=` finds them equal.
+    /// Unlike `=`, NULL isn't distinct from NULL
+    pub fn is_distinct(&self, rhs: &Self) -> bool {
+        match (self, rhs) {
+            (ScalarValue::Null, ScalarValue::Null) => false,
+            (lhs, rhs) => lhs.partial_cmp(rhs) != Some(Ordering::Equal),
+        }
+    }
+
+    /// The order rows are sorted in, which unlike `compare` puts every pair of values in order.
+    /// As in SQLite NULLs come first, then booleans, numbers, strings and blobs; NaN sorts above every other float.
+    pub fn sort_cmp(&self, rhs: &Self) -> Ordering {
+        use ScalarValue::*;
+
+        fn rank(value: &ScalarValue) -> u8 {
+            match value {
+                Null => 0,
+                Boolean(_) => 1,
+                Integer(_) | Floating(_) => 2,
+                String(_) => 3,
+                Blob(_) => 4,
+            }
+        }
+
+        match (self, rhs) {
+            (Boolean(lhs), Boolean(rhs)) => lhs.cmp(rhs),
+            (Integer(lhs), Integer(rhs)) => lhs.cmp(rhs),
+            (Floating(lhs), Floating(rhs)) => lhs.partial_cmp(rhs).unwrap_or_else(|| lhs.is_nan().cmp(&rhs.is_nan())),
+            (Integer(lhs), Floating(rhs)) => cmp_integer_float(*lhs, *rhs).unwrap_or(Ordering::Less),
+            (Floating(lhs), Integer(rhs)) => cmp_integer_float(*rhs, *lhs).map_or(Ordering::Greater, Ordering::reverse),
+            (String(lhs), String(rhs)) => lhs.cmp(rhs),
+            (Blob(lhs), Blob(rhs)) => lhs.cmp(rhs),
+            (lhs, rhs) => rank(lhs).cmp(&rank(rhs)),
+        }
+    }
+}
+
+// Exactly, where converting the integer to a float would round those beyond 2^53. None if the float is NaN
+fn cmp_integer_float(integer: i64, float: f64) -> Option<Ordering> {
+    // 2^63, just past the largest integer
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+
+    if float.is_nan() {
+        None
+    } else if float >= LIMIT {
+        Some(Ordering::Less)
+    } else if float < -LIMIT {
+        Some(Ordering::Greater)
+    } else {
+        // In range, so the whole part converts exactly, and only the fraction can break a tie
+        let whole = float.trunc();
+        Some(integer.cmp(&(whole as i64)).then(0.0.partial_cmp(&(float - whole))?))
+    }
+}
+
+// Bits shifted past either end are lost, a right shift keeps the sign
+fn shift_left(value: i64, by: i64) -> i64 {
+    match by {
+        64.. => 0,
+        0..=63 => value << by,
+        -63..=-1 => value >> -by,
+        _ => value >> 63,
+    }
+}
+
+impl PartialOrd for ScalarValue {
+    fn partial_cmp(&self, rhs: &Self) -> Option<Ordering> {
+        match (self, rhs) {
+            (ScalarValue::Integer(lhs), ScalarValue::Integer(rhs)) => lhs.partial_cmp(rhs),
+            (ScalarValue::Floating(lhs), ScalarValue::Floating(rhs)) => lhs.partial_cmp(rhs),
+            (ScalarValue::Integer(lhs), ScalarValue::Floating(rhs)) => cmp_integer_float(*lhs, *rhs),
+            (ScalarValue::Floating(lhs), ScalarValue::Integer(rhs)) => cmp_integer_float(*rhs, *lhs).map(Ordering::reverse),
+            (ScalarValue::String(lhs), ScalarValue::String(rhs)) => lhs.partial_cmp(rhs),
+            (ScalarValue::Blob(lhs), ScalarValue::Blob(rhs)) => lhs.partial_cmp(rhs),
+            (ScalarValue::Boolean(lhs), ScalarValue::Boolean(rhs)) => lhs.partial_cmp(rhs),
+            (_, _) => None,
+        }
+    }
+}
+
+/// Only implemented for testing purposes, actual code shouldn't compare these types directly
+/// NULLs are equal here so tests can assert on them, use `compare` for SQL semantics
+#[cfg(test)]
+impl PartialEq for ScalarValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Null, Self::Null) => true,
+            (Self::Integer(left), Self::Integer(right)) => left == right,
+            (Self::Boolean(left), Self::Boolean(right)) => left == right,
+            (Self::Floating(left), Self::Floating(right)) => (left - right).abs() < 0.00001,
+            (Self::String(left), Self::String(right)) => left == right,
+            (Self::Blob(left), Self::Blob(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(not(test))]
+impl PartialEq for ScalarValue {
+    fn eq(&self, right: &Self) -> bool {
+        match (self, right) {
+            (Self::Integer(left), Self::Integer(right)) => left == right,
+            (Self::Boolean(left), Self::Boolean(right)) => left == right,
+            (Self::Floating(left), Self::Floating(right)) => left == right,
+            (Self::String(left), Self::String(right)) => left == right,
+            (Self::Blob(left), Self::Blob(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for ScalarValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use colored::Colorize;
+        match self {
+            ScalarValue::Integer(i) => write!(f, "{}", i.to_string().green()),
+            ScalarValue::Floating(fl) => write!(f, "{}", fl.to_string().green()),
+            ScalarValue::Boolean(b) => write!(f, "{}", b.to_string().green()),
+            ScalarValue::String(s) => write!(f, "{}", format!("\"{}\"", s).green()),
+            ScalarValue::Blob(b) => {
+                let hex: String = b.iter().map(|byte| format!("{byte:02x}")).collect();
+                write!(f, "{}", format!("x'{hex}'").green())
+            }
+            ScalarValue::Null => write!(f, "{}", "NULL".green()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_equality() {
+        let s1 = ScalarValue::String("hello".to_string());
+        let s2 = ScalarValue::String("hello".to_string());
+        let s3 = ScalarValue::String("world".to_string());
+
+        assert_eq!(s1, s2);
+        assert_ne!(s1, s3);
+    }
+
+    #[test]
+    fn test_string_ordering() {
+        let a = ScalarValue::String("apple".to_string());
+        let b = ScalarValue::String("banana".to_string());
+        let a2 = ScalarValue::String("apple".to_string());
+
+        assert!(a < b);
+        assert!(b > a);
+        assert!(!(a < a2));
+        assert!(!(a > a2));
+    }
+
+    #[test]
+    fn test_string_concatenation() {
+        let s1 = ScalarValue::String("hello".to_string());
+        let s2 = ScalarValue::String(" world".to_string());
+        let result = s1.checked_add(&s2).unwrap();
+
+        assert_eq!(result, ScalarValue::String("hello world".to_string()));
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        let s = ScalarValue::String("hello".to_string());
+        let i = ScalarValue::Integer(42);
+
+        assert_eq!(
+            Err(ValueError::TypeMismatch("+", vec![s.clone(), i.clone()])),
+            s.checked_add(&i)
+        );
+        assert_eq!(
+            Err(ValueError::TypeMismatch("*", vec![s.clone(), s.clone()])),
+            s.checked_mul(&s)
+        );
+        assert_eq!(Err(ValueError::TypeMismatch("-", vec![s.clone()])), s.checked_neg());
+        assert_eq!(
+            Err(ValueError::TypeMismatch("comparison", vec![s.clone(), i.clone()])),
+            s.compare(&i, Ordering::is_lt)
+        );
+        assert_eq!(Err(ValueError::TypeMismatch("AND", vec![i.clone()])), ScalarValue::Boolean(true).and(&i));
+        assert_eq!(Err(ValueError::TypeMismatch("condition", vec![i.clone()])), i.is_true());
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let zero = ScalarValue::Integer(0);
+
+        assert_eq!(Err(ValueError::DivisionByZero), ScalarValue::Integer(1).checked_div(&zero));
+        assert_eq!(Err(ValueError::DivisionByZero), ScalarValue::Integer(1).checked_rem(&zero));
+        assert_eq!(Err(ValueError::DivisionByZero), ScalarValue::Floating(1.0).checked_div(&ScalarValue::Floating(0.0)));
+        assert_eq!(Ok(ScalarValue::Null), ScalarValue::Null.checked_div(&zero));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let max = ScalarValue::Integer(i64::MAX);
+        let min = ScalarValue::Integer(i64::MIN);
+        let one = ScalarValue::Integer(1);
+
+        assert_eq!(Err(ValueError::Overflow), max.checked_add(&one));
+        assert_eq!(Err(ValueError::Overflow), min.checked_sub(&one));
+        assert_eq!(Err(ValueError::Overflow), max.checked_mul(&max));
+        assert_eq!(Err(ValueError::Overflow), min.checked_div(&ScalarValue::Integer(-1)));
+        assert_eq!(Err(ValueError::Overflow), min.checked_neg());
+        // Floats don't overflow
+        assert_eq!(Ok(ScalarValue::Floating(i64::MAX as f64 + 1.0)), max.checked_add(&ScalarValue::Floating(1.0)));
+    }
+
+    #[test]
+    fn test_bitwise() {
+        let i = |v| ScalarValue::Integer(v);
+
+        assert_eq!(Ok(i(0b1000)), i(0b1100).bit_and(&i(0b1010)));
+        assert_eq!(Ok(i(0b1110)), i(0b1100).bit_or(&i(0b1010)));
+        assert_eq!(Ok(i(0b0110)), i(0b1100).bit_xor(&i(0b1010)));
+        assert_eq!(Ok(i(-6)), i(5).bit_not());
+        assert_eq!(Ok(ScalarValue::Null), ScalarValue::Null.bit_and(&i(1)));
+        assert_eq!(Ok(ScalarValue::Null), ScalarValue::Null.bit_not());
+
+        assert_eq!(Ok(i(12)), i(3).shift_left(&i(2)));
+        assert_eq!(Ok(i(-2)), i(-8).shift_right(&i(2)));
+        // Negative shifts go the other way, large ones shift everything out
+        assert_eq!(Ok(i(3)), i(12).shift_left(&i(-2)));
+        assert_eq!(Ok(i(12)), i(3).shift_right(&i(-2)));
+        assert_eq!(Ok(i(0)), i(3).shift_left(&i(64)));
+        assert_eq!(Ok(i(-1)), i(-3).shift_right(&i(100)));
+        assert_eq!(Ok(i(0)), i(3).shift_right(&i(i64::MIN)));
+        assert_eq!(Ok(i(i64::MIN)), i(1).shift_left(&i(63)));
+
+        let f = ScalarValue::Floating(1.0);
+        assert_eq!(Err(ValueError::TypeMismatch("<<", vec![f.clone(), i(1)])), f.shift_left(&i(1)));
+        assert_eq!(Err(ValueError::TypeMismatch("&", vec![i(1), f.clone()])), i(1).bit_and(&f));
+        assert_eq!(Err(ValueError::TypeMismatch("~", vec![f.clone()])), f.bit_not());
+    }
+
+    #[test]
+    fn test_mixed_type_comparison_returns_none() {
+        let s = ScalarValue::String("hello".to_string());
+        let i = ScalarValue::Integer(42);
+
+        assert!(s.partial_cmp(&i).is_none());
+    }
+
+    #[test]
+    fn test_null_arithmetic() {
+        let i = ScalarValue::Integer(42);
+        let f = ScalarValue::Floating(1.5);
+
+        let n = ScalarValue::Null;
+
+        assert_eq!(Ok(ScalarValue::Null), i.checked_add(&n));
+        assert_eq!(Ok(ScalarValue::Null), n.checked_sub(&i));
+        assert_eq!(Ok(ScalarValue::Null), f.checked_mul(&n));
+        assert_eq!(Ok(ScalarValue::Null), n.checked_div(&f));
+        assert_eq!(Ok(ScalarValue::Null), i.checked_rem(&n));
+        assert_eq!(Ok(ScalarValue::Null), n.checked_neg());
+        assert_eq!(Ok(ScalarValue::Null), ScalarValue::String("a".to_string()).checked_add(&n));
+    }
+
+    #[test]
+    fn test_null_comparison() {
+        let i = ScalarValue::Integer(42);
+
+        assert_eq!(Ok(ScalarValue::Null), i.compare(&ScalarValue::Null, Ordering::is_eq));
+        assert_eq!(Ok(ScalarValue::Null), ScalarValue::Null.compare(&ScalarValue::Null, Ordering::is_eq));
+        assert_eq!(Ok(ScalarValue::Null), ScalarValue::Null.compare(&i, Ordering::is_lt));
+        assert_eq!(Ok(ScalarValue::Boolean(true)), i.compare(&i, Ordering::is_ge));
+        assert!(ScalarValue::Null.partial_cmp(&ScalarValue::Null).is_none());
+    }
+
+    #[test]
+    fn test_numeric_equality() {
+        let i = ScalarValue::Integer(11);
+        let f = ScalarValue::Floating(11.0);
+        let t = Ok(ScalarValue::Boolean(true));
+        let fls = Ok(ScalarValue::Boolean(false));
+
+        assert_eq!(t, i.compare(&f, Ordering::is_eq));
+        assert_eq!(fls, f.compare(&i, Ordering::is_ne));
+        assert_eq!(t, ScalarValue::Floating(0.0).compare(&ScalarValue::Floating(-0.0), Ordering::is_eq));
+        // Exact, unlike the tolerance `PartialEq` uses for test assertions
+        assert_eq!(fls, ScalarValue::Floating(0.1 + 0.2).compare(&ScalarValue::Floating(0.3), Ordering::is_eq));
+        assert_eq!(Ok(ScalarValue::Null), ScalarValue::Floating(f64::NAN).compare(&f, Ordering::is_ne));
+        assert_eq!(t, ScalarValue::Boolean(false).compare(&ScalarValue::Boolean(true), Ordering::is_lt));
+
+        // Integers too large for a float to hold exactly still compare exactly
+        let big = ScalarValue::Floating(2f64.powi(53));
+        assert_eq!(t, ScalarValue::Integer(1 << 53).compare(&big, Ordering::is_eq));
+        assert_eq!(t, ScalarValue::Integer((1 << 53) + 1).compare(&big, Ordering::is_gt));
+        assert_eq!(t, ScalarValue::Integer(i64::MAX).compare(&ScalarValue::Floating(2f64.powi(63)), Ordering::is_lt));
+        assert_eq!(t, ScalarValue::Integer(i64::MIN).compare(&ScalarValue::Floating(-(2f64.powi(63))), Ordering::is_eq));
+        assert_eq!(t, ScalarValue::Integer(-3).compare(&ScalarValue::Floating(-3.5), Ordering::is_gt));
+        assert_eq!(t, ScalarValue::Floating(2.5).compare(&ScalarValue::Integer(2), Ordering::is_gt));
+    }
+
+    #[test]
+    fn test_distinct() {
+        let n = ScalarValue::Null;
+
+        assert!(!ScalarValue::Integer(1).is_distinct(&ScalarValue::Floating(1.0)));
+        assert!(!ScalarValue::Floating(-0.0).is_distinct(&ScalarValue::Integer(0)));
+        assert!(!n.is_distinct(&n));
+        assert!(n.is_distinct(&ScalarValue::Integer(0)));
+        assert!(ScalarValue::Integer(1).is_distinct(&ScalarValue::String("1".to_string())));
+    }
+
+    #[test]
+    fn test_three_valued_logic() {
+        let t = ScalarValue::Boolean(true);
+        let f = ScalarValue::Boolean(false);
+        let n = ScalarValue::Null;
+
+        assert_eq!(Ok(n.clone()), t.and(&n));
+        assert_eq!(Ok(f.clone()), f.and(&n));
+        assert_eq!(Ok(f.clone()), n.and(&f));
+        assert_eq!(Ok(n.clone()), n.and(&n));
+
+        assert_eq!(Ok(t.clone()), t.or(&n));
+        assert_eq!(Ok(t.clone()), n.or(&t));
+        assert_eq!(Ok(n.clone()), f.or(&n));
+        assert_eq!(Ok(n.clone()), n.or(&n));
+
+        assert_eq!(Ok(n.clone()), n.not());
+        assert_eq!(Ok(f.clone()), t.not());
+    }
+
+    #[test]
+    fn test_sort_order() {
+        use std::cmp::Ordering::*;
+
+        let mut sorted = vec![
+            ScalarValue::Blob(vec![0]),
+            ScalarValue::String("a".to_string()),
+            ScalarValue::Floating(f64::NAN),
+            ScalarValue::Integer(2),
+            ScalarValue::Floating(1.5),
+            ScalarValue::Boolean(true),
+            ScalarValue::Integer(-3),
+            ScalarValue::Null,
+            ScalarValue::Boolean(false),
+        ];
+
+        sorted.sort_by(ScalarValue::sort_cmp);
+        assert_eq!(
+            "[Null, Boolean(false), Boolean(true), Integer(-3), Floating(1.5), Integer(2), Floating(NaN), String(\"a\"), Blob([0])]",
+            format!("{sorted:?}")
+        );
+        assert_eq!(Equal, ScalarValue::Integer(1).sort_cmp(&ScalarValue::Floating(1.0)));
+        assert_eq!(Equal, ScalarValue::Floating(-0.0).sort_cmp(&ScalarValue::Floating(0.0)));
+        assert_eq!(Less, ScalarValue::Integer(1 << 53).sort_cmp(&ScalarValue::Integer((1 << 53) + 1)));
+        assert_eq!(Greater, ScalarValue::Integer((1 << 53) + 1).sort_cmp(&ScalarValue::Floating(2f64.powi(53))));
+        assert_eq!(Less, ScalarValue::Integer(i64::MAX).sort_cmp(&ScalarValue::Floating(f64::NAN)));
+        assert_eq!(Equal, ScalarValue::Null.sort_cmp(&ScalarValue::Null));
+    }
+}
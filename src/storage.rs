@@ -1,7 +1,12 @@
+mod backup;
 mod cell;
 mod cell_reader;
+mod cipher;
+mod file;
+mod journal;
 mod node;
 mod pager;
+mod sqlite;
 
 /// Btree module heavily inspired by the fantastic article: https://cglab.ca/~abeinges/blah/rust-btree-case/
 ///
@@ -10,7 +15,15 @@ mod btree;
 
 mod btree_graph;
 mod btree_verify;
+mod transaction;
 
+pub use backup::{Backup, BackupStatus};
 pub use btree::BTree;
 pub use btree::CursorHandle;
+pub use btree_graph::DumpOptions;
 pub use cell_reader::CellReader;
+pub use file::PageFile;
+#[cfg(test)]
+pub use file::MemoryFile;
+pub use pager::{Compression, PageError, PagerOptions, SyncMode};
+pub use transaction::Transaction;
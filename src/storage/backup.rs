@@ -0,0 +1,185 @@
+use std::{
+    cell::RefCell,
+    cmp::min,
+    fs::{File, OpenOptions},
+    os::unix::fs::FileExt,
+    path::Path,
+    sync::Arc,
+};
+
+use super::pager::{Pager, PAGE_SIZE};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BackupStatus {
+    /// More pages remain to be copied, call `step` again
+    InProgress { remaining: u32 },
+    /// The destination is a complete copy of the source
+    Done,
+}
+
+/// An online backup of a database, copying pages to a destination file a few at a time.
+///
+/// The source pager is only borrowed for the duration of each `step`, so the database can keep being used
+/// in between. If a page is written through the source pager while the backup is in progress, the copy
+/// starts over from the first page on the next step. Pages are copied as stored, so the backup keeps the
+/// compression and encryption (and passphrase) of the source.
+pub struct Backup {
+    source: Arc<RefCell<Pager>>,
+    destination: File,
+
+    // next page to copy
+    next_page: u32,
+
+    // generation of the source when the current pass of copying started
+    generation: u64,
+
+    restarts: u32,
+}
+
+impl Backup {
+    pub(super) fn new(source: Arc<RefCell<Pager>>, destination: &Path) -> std::io::Result<Backup> {
+        let destination = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(destination)?;
+        let generation = source.borrow().generation();
+
+        Ok(Backup {
+            source,
+            destination,
+            next_page: 0,
+            generation,
+            restarts: 0,
+        })
+    }
+
+    /// Copy up to `max_pages` pages to the destination
+    pub fn step(&mut self, max_pages: u32) -> std::io::Result<BackupStatus> {
+        let pager = self.source.borrow();
+
+        if pager.generation() != self.generation {
+            // The pages already copied may be stale, start again
+            self.next_page = 0;
+            self.generation = pager.generation();
+            self.restarts += 1;
+        }
+
+        let total_pages = pager.get_committed_size_pages();
+        let end = min(total_pages, self.next_page.saturating_add(max_pages));
+
+        for idx in self.next_page..end {
+            let page = pager.get_committed(idx);
+            self.destination
+                .write_all_at(page.content(), PAGE_SIZE * idx as u64)?;
+        }
+        self.next_page = end;
+
+        if self.next_page < total_pages {
+            return Ok(BackupStatus::InProgress {
+                remaining: total_pages - self.next_page,
+            });
+        }
+
+        // The destination might have been a larger database, or a larger earlier pass of this backup
+        self.destination.set_len(PAGE_SIZE * total_pages as u64)?;
+        self.destination.sync_all()?;
+
+        Ok(BackupStatus::Done)
+    }
+
+    /// Number of times the copy started over because the source changed
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use tempfile::NamedTempFile;
+
+    use super::BackupStatus;
+    use crate::storage::{BTree, PagerOptions};
+
+    fn read_all(btree: &BTree, tree_name: &str) -> Vec<(u64, Vec<u8>)> {
+        let mut cursor_handle = btree.open(tree_name).unwrap();
+        let mut cursor = cursor_handle.open_readonly();
+        let mut entries = vec![];
+
+        cursor.first().unwrap();
+        while let Some(mut entry) = cursor.get_entry().unwrap() {
+            let mut buf = vec![];
+            entry.read_to_end(&mut buf).unwrap();
+            entries.push((entry.key(), buf));
+            cursor.next().unwrap();
+        }
+
+        entries
+    }
+
+    #[test]
+    fn backup_restarts_when_source_changes() {
+        let source_file = NamedTempFile::new().unwrap();
+        let backup_file = NamedTempFile::new().unwrap();
+
+        let mut btree = BTree::new(source_file.path().to_str().unwrap());
+        btree.create_tree("testing");
+        let mut cursor_handle = btree.open("testing").unwrap();
+        {
+            let mut cursor = cursor_handle.open_readwrite();
+            for i in 0..200u64 {
+                cursor.insert(i, format!("value {i}").into_bytes());
+            }
+        }
+
+        let mut backup = btree.backup(backup_file.path()).unwrap();
+        assert!(matches!(backup.step(2).unwrap(), BackupStatus::InProgress { .. }));
+
+        // Modify the source in the middle of the backup
+        {
+            let mut cursor = cursor_handle.open_readwrite();
+            for i in 200..300u64 {
+                cursor.insert(i, format!("value {i}").into_bytes());
+            }
+        }
+
+        while backup.step(2).unwrap() != BackupStatus::Done {}
+        assert_eq!(1, backup.restarts());
+
+        let copy = BTree::new(backup_file.path().to_str().unwrap());
+        copy.verify().unwrap();
+        assert_eq!(300, read_all(&copy, "testing").len());
+        assert_eq!(read_all(&btree, "testing"), read_all(&copy, "testing"));
+    }
+
+    #[test]
+    fn backup_of_encrypted_database() {
+        let source_file = NamedTempFile::new().unwrap();
+        let backup_file = NamedTempFile::new().unwrap();
+        let options = || PagerOptions {
+            passphrase: Some("hunter2".to_string()),
+            ..Default::default()
+        };
+
+        let mut btree = BTree::with_options(source_file.path().to_str().unwrap(), options()).unwrap();
+        btree.create_tree("testing");
+        {
+            let mut cursor_handle = btree.open("testing").unwrap();
+            let mut cursor = cursor_handle.open_readwrite();
+            for i in 0..50u64 {
+                cursor.insert(i, format!("value {i}").into_bytes());
+            }
+        }
+
+        let mut backup = btree.backup(backup_file.path()).unwrap();
+        assert_eq!(BackupStatus::Done, backup.step(u32::MAX).unwrap());
+        assert_eq!(0, backup.restarts());
+
+        let copy = BTree::with_options(backup_file.path().to_str().unwrap(), options()).unwrap();
+        copy.verify().unwrap();
+        assert_eq!(read_all(&btree, "testing"), read_all(&copy, "testing"));
+    }
+}
@@ -1,3 +1,4 @@
+use std::cmp::max;
 use std::cell::{Ref, RefCell, RefMut};
 use std::io::Write;
 use std::sync::Arc;
@@ -12,8 +13,9 @@ use crate::storage::node::{NodePage, OverflowPage, SearchResult};
 use super::btree_verify::VerifyError;
 use super::cell::Value;
 use super::node::{self, InteriorNodePage};
-use super::pager::{self, Pager};
-use super::{btree_graph, btree_verify, CellReader};
+use super::pager::{self, PageError, Pager};
+use super::file::DiskFile;
+use super::{btree_graph, btree_verify, Backup, CellReader, DumpOptions, PageFile, Transaction};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct CursorState {
@@ -22,6 +24,23 @@ pub struct CursorState {
     /// key for the item pointed to by the cursor
     stack: Vec<InteriorNodeIterator>,
     leaf_iterator: Option<LeafNodeIterator>,
+
+    /// The row pointed to took the place of one that was removed, so `next` stays on it
+    skip_next: bool,
+
+    /// The sequence of an autoincrement tree, as this cursor last read or raised it. Sequences only
+    /// grow, so inserting a key no larger than this never has to look at the real one
+    sequence: Option<u64>,
+}
+
+/// Every key up to `u64::MAX` has been handed out, so there is no next rowid to give
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RowidsExhausted;
+
+impl std::fmt::Display for RowidsExhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no rowids are left to assign")
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -31,6 +50,11 @@ pub struct CursorHandle {
 }
 
 impl CursorHandle {
+    /// Whether the tree can't be written to, as with every table of a SQLite database
+    pub fn is_read_only(&self) -> bool {
+        self.pager.borrow().is_sqlite()
+    }
+
     pub fn open_readonly<'a>(&'a mut self) -> Cursor<'a, Ref<'a, Pager>> {
         let pager = RefCell::borrow(&self.pager);
         Cursor {
@@ -62,7 +86,8 @@ type LeafNodeIterator = (u32, usize);
 const NULL: serde_json::Value = serde_json::Value::Null;
 const CHUNK_THRESHOLD: usize = 55;
 
-/// Mutable cursor implementation
+/// Mutable cursor implementation. Unlike reads, writes don't return an error for a page which can't
+/// be read back, they panic rather than change a tree they can't see all of
 impl<'a, PagerRef> Cursor<'a, PagerRef>
 where
     PagerRef: DerefMut<Target = Pager>,
@@ -82,6 +107,8 @@ where
 
         let cell = Cell::new(key, first_part, continuation);
 
+        self.raise_sequence(key);
+
         // we maintain a stack of the nodes we decended through in case of needing to split them.
         // Starting at the root, we search to find:
         //   an empty place to put the new value
@@ -91,20 +118,22 @@ where
         let root_page = self
             .pager
             .get_root_page(&self.cursor_state.tree_name)
+            .unwrap()
             .unwrap();
         stack.push(root_page);
 
         loop {
             let top_page_idx = *stack.last().unwrap();
-            let mut top_page: NodePage = self.pager.get_and_decode(top_page_idx);
+            let mut top_page: NodePage = self.pager.get_and_decode(top_page_idx).unwrap();
             match top_page.search(&key) {
                 SearchResult::Found(insertion_index) => {
                     // We found the index in the node where an existing value for this key exists
                     // we need to replace it with our value
 
-                    top_page.set_item_at_index(insertion_index, cell);
+                    let old_cell = top_page.set_item_at_index(insertion_index, cell);
 
                     self.update_page(top_page, stack);
+                    self.free_continuation(&old_cell);
 
                     break;
                 }
@@ -123,6 +152,110 @@ where
                 }
             }
         }
+
+        self.pager.finish_operation();
+    }
+
+    /// Insert the value with a key one larger than any in the tree (or any ever in the tree, for
+    /// autoincrement trees), returning the key used. The cursor is left pointing at the new row.
+    /// Nothing is inserted once the largest possible key has been used.
+    pub fn insert_next_rowid(&mut self, value: Value) -> Result<u64, RowidsExhausted> {
+        self.last().unwrap();
+
+        let mut rowid = match self.row_key().unwrap() {
+            Some(max_key) => max_key.checked_add(1).ok_or(RowidsExhausted)?,
+            None => 1,
+        };
+
+        if self.cursor_state.sequence.is_some() {
+            // Read afresh, another cursor may have handed out rowids since
+            let tree_name = &self.cursor_state.tree_name;
+            let sequence = self.pager.get_sequence(tree_name).unwrap().unwrap_or(0);
+            rowid = max(rowid, sequence.checked_add(1).ok_or(RowidsExhausted)?);
+
+            self.pager.set_sequence(tree_name, rowid);
+            self.cursor_state.sequence = Some(rowid);
+        }
+
+        self.insert(rowid, value);
+        self.find(rowid).unwrap();
+
+        Ok(rowid)
+    }
+
+    /// Autoincrement trees remember the largest key they have ever held, so it is never handed out again.
+    /// A key past the sequence this cursor knows of is checked against the real one, which another
+    /// cursor may have raised further.
+    fn raise_sequence(&mut self, key: u64) {
+        let Some(known) = self.cursor_state.sequence else {
+            return;
+        };
+        if key <= known {
+            return;
+        }
+
+        let tree_name = &self.cursor_state.tree_name;
+        let sequence = self.pager.get_sequence(tree_name).unwrap().unwrap_or(0);
+        if key > sequence {
+            self.pager.set_sequence(tree_name, key);
+        }
+        self.cursor_state.sequence = Some(max(sequence, key));
+    }
+
+    /// Replace the value of the row the cursor points at, returning false if it isn't pointing at
+    /// one. The cursor is left pointing at the row, even if pages had to be split to make room.
+    pub fn replace(&mut self, value: Value) -> bool {
+        let Some(key) = self.row_key().unwrap() else {
+            return false;
+        };
+
+        self.insert(key, value);
+        self.find(key).unwrap();
+
+        true
+    }
+
+    /// Remove the row the cursor points at, returning false if it isn't pointing at one.
+    /// The cursor is left on the row after it, which `next` doesn't move past, so iterating while
+    /// removing rows carries on with the row it would have come to anyway.
+    ///
+    /// Leaves are not merged, one emptied of its rows stays in the tree for later inserts to use.
+    pub fn delete(&mut self) -> bool {
+        let Some(key) = self.row_key().unwrap() else {
+            return false;
+        };
+        let (leaf_page_idx, cell_idx) = self.cursor_state.leaf_iterator.unwrap();
+
+        let mut leaf_page: NodePage = self.pager.get_and_decode(leaf_page_idx).unwrap();
+        let cell = leaf_page.remove_item_at_index(cell_idx);
+        let path = self.path();
+        self.update_page(leaf_page, path);
+
+        self.free_continuation(&cell);
+
+        self.pager.finish_operation();
+
+        // Find where the key was, the next row is there unless it was the last in its leaf
+        self.find(key).unwrap();
+        if self.get_entry().unwrap().is_none() {
+            self.next().unwrap();
+        }
+        self.cursor_state.skip_next = true;
+
+        true
+    }
+
+    /// Free the overflow pages of a cell's value, which is no longer needed
+    fn free_continuation(&mut self, cell: &Cell) {
+        let mut continuation = cell.continuation();
+        while let Some(page_idx) = continuation {
+            let page: NodePage = self.pager.get_and_decode(page_idx).unwrap();
+            continuation = match page {
+                NodePage::OverflowPage(overflow_page) => overflow_page.continuation(),
+                _ => panic!("Continuations are always overflow pages"),
+            };
+            self.pager.dealocate(page_idx);
+        }
     }
 
     /// Updates a page with new content
@@ -170,7 +303,7 @@ where
 
             let parent_node_idx = stack.pop().unwrap();
 
-            let parent_node: NodePage = self.pager.get_and_decode(parent_node_idx);
+            let parent_node: NodePage = self.pager.get_and_decode(parent_node_idx).unwrap();
 
             let mut parent_interior_node = parent_node.interior().unwrap();
 
@@ -213,28 +346,37 @@ where
     /// Move the cursor to point at the first row in the btree
     /// This may result in the cursor not pointing to a row if there is no
     /// first row to point to
-    pub fn first(&mut self) {
+    pub fn first(&mut self) -> Result<(), PageError> {
         // Take the tree identified by the root page number, and find its left most node and
         // find its smallest entry
 
         let root_page = self
             .pager
-            .get_root_page(&self.cursor_state.tree_name)
+            .get_root_page(&self.cursor_state.tree_name)?
             .unwrap();
-        self.select_leftmost_of_idx(root_page)
+        self.cursor_state.stack.clear();
+        self.cursor_state.skip_next = false;
+        self.select_leftmost_of_idx(root_page)?;
+
+        // The leftmost leaf may have had all its rows removed
+        if self.get_entry()?.is_none() {
+            self.next()?;
+        }
+
+        Ok(())
     }
 
-    fn select_leftmost_of_idx(&mut self, page_idx: u32) {
+    fn select_leftmost_of_idx(&mut self, page_idx: u32) -> Result<(), PageError> {
         let mut page_idx = page_idx;
 
         loop {
-            let page: NodePage = self.pager.get_and_decode(page_idx);
+            let page: NodePage = self.pager.get_and_decode(page_idx)?;
             match page {
                 node::NodePage::Leaf(_l) => {
                     // We found the first leaf in the tree.
                     // TODO: Maybe store a readonly copy of this leaf node instead of this `leaf_iterator`
                     self.cursor_state.leaf_iterator = Some((page_idx, 0));
-                    return;
+                    return Ok(());
                 }
                 node::NodePage::Interior(i) => {
                     self.cursor_state.stack.push((page_idx, 0));
@@ -245,17 +387,17 @@ where
         }
     }
 
-    fn select_rightmost_of_idx(&mut self, page_idx: u32) {
+    fn select_rightmost_of_idx(&mut self, page_idx: u32) -> Result<(), PageError> {
         let mut page_idx = page_idx;
 
         loop {
-            let page: NodePage = self.pager.get_and_decode(page_idx);
+            let page: NodePage = self.pager.get_and_decode(page_idx)?;
             match page {
                 node::NodePage::Leaf(l) => {
-                    // We found the first leaf in the tree.
+                    // We found the last leaf in the tree, which is empty if its rows were removed
                     // TODO: Maybe store a readonly copy of this leaf node instead of this `leaf_iterator`
-                    self.cursor_state.leaf_iterator = Some((page_idx, l.num_items() - 1));
-                    return;
+                    self.cursor_state.leaf_iterator = Some((page_idx, l.num_items().saturating_sub(1)));
+                    return Ok(());
                 }
                 node::NodePage::Interior(i) => {
                     self.cursor_state.stack.push((page_idx, i.num_edges() - 1));
@@ -269,53 +411,49 @@ where
     /// Move the cursor to point at the last row in the btree
     /// This may result in the cursor not pointing to a row if there is no
     /// last row to point to
-    pub fn last(&mut self) {
+    pub fn last(&mut self) -> Result<(), PageError> {
         // Take the tree identified by the root page number, and find its right most node and
         // find its largest entry.
-        let root_page_idx = self
+        let root_page = self
             .pager
-            .get_root_page(&self.cursor_state.tree_name)
+            .get_root_page(&self.cursor_state.tree_name)?
             .unwrap();
-        let root_page: NodePage = self.pager.get_and_decode(root_page_idx);
+        self.cursor_state.stack.clear();
+        self.cursor_state.skip_next = false;
+        self.select_rightmost_of_idx(root_page)?;
 
-        let page = root_page;
-        let page_idx = root_page_idx;
-        loop {
-            match page {
-                node::NodePage::Leaf(l) => {
-                    // We found the first leaf in the tree.
-                    // TODO: Maybe store a readonly copy of this leaf node instead of this `leaf_iterator`
-                    self.cursor_state.leaf_iterator = Some((page_idx, l.num_items() - 1));
-                    return;
-                }
-                node::NodePage::Interior(_i) => todo!(),
-                node::NodePage::OverflowPage(_) => panic!(),
-            }
+        // The rightmost leaf may have had all its rows removed
+        if self.get_entry()?.is_none() {
+            self.prev()?;
         }
+
+        Ok(())
     }
 
     /// Move the cursor to point at the row in the btree identified by the given key
     /// This may result in the cursor not pointing to a row if there is no
     /// row found with that key to point to
-    pub fn find(&mut self, key: u64) {
+    pub fn find(&mut self, key: u64) -> Result<(), PageError> {
         let root_page_idx = self
             .pager
-            .get_root_page(&self.cursor_state.tree_name)
+            .get_root_page(&self.cursor_state.tree_name)?
             .unwrap();
         let mut page_idx = root_page_idx;
+        self.cursor_state.stack.clear();
+        self.cursor_state.skip_next = false;
 
         loop {
-            let page: NodePage = self.pager.get_and_decode(page_idx);
+            let page: NodePage = self.pager.get_and_decode(page_idx)?;
 
             match page.search(&key) {
                 SearchResult::Found(index) => {
                     self.cursor_state.leaf_iterator = Some((page_idx, index));
-                    return;
+                    return Ok(());
                 }
                 SearchResult::NotPresent(index) => {
                     self.cursor_state.leaf_iterator = Some((page_idx, index));
                     // TODO: does the caller need to know this isnt what they were looking for?
-                    return;
+                    return Ok(());
                 }
                 SearchResult::GoDown(c_idx, c) => {
                     self.cursor_state.stack.push((page_idx, c_idx));
@@ -326,20 +464,26 @@ where
         }
     }
 
-    fn row_key(&self) -> Option<u64> {
-        let cell = self.get_entry()?;
-
-        Some(cell.key())
+    fn row_key(&self) -> Result<Option<u64>, PageError> {
+        Ok(self.get_entry()?.map(|cell| cell.key()))
     }
 
-    pub fn get_entry<'b>(&'b self) -> Option<CellReader<'b>> {
-        let (leaf_page_number, entry_index) = self.cursor_state.leaf_iterator?;
+    /// The row the cursor points at, None if it isn't pointing at one
+    pub fn get_entry<'b>(&'b self) -> Result<Option<CellReader<'b>>, PageError> {
+        let Some((leaf_page_number, entry_index)) = self.cursor_state.leaf_iterator else {
+            return Ok(None);
+        };
 
         CellReader::new(&self.pager, leaf_page_number, entry_index)
     }
 
     /// Move the cursor to point at the next item in the btree
-    pub fn next(&mut self) {
+    pub fn next(&mut self) -> Result<(), PageError> {
+        // The cursor is already on the row after one it removed
+        if std::mem::take(&mut self.cursor_state.skip_next) {
+            return Ok(());
+        }
+
         // function takes a curent index and the number of indexes, and returns Some(idx) where idx is the next index to consider
         // or none if there are no more on this page
         let next_idx = |curent: usize, count| {
@@ -353,11 +497,13 @@ where
         // function to move the cursor to the next item to consider in subtree identified by page_idx in the given direction
         let select_first_in_direction = Self::select_leftmost_of_idx;
 
-        self.move_in_direction(next_idx, select_first_in_direction);
+        self.move_in_direction(next_idx, select_first_in_direction)
     }
 
-    /// Move the cursor to point at the next item in the btree
-    pub fn prev(&mut self) {
+    /// Move the cursor to point at the previous item in the btree
+    pub fn prev(&mut self) -> Result<(), PageError> {
+        self.cursor_state.skip_next = false;
+
         // function takes a curent index and the number of indexes, and returns Some(idx) where idx is the next index to consider
         // or none if there are no more on this page
         let next_idx = |curent: usize, _count| {
@@ -371,37 +517,37 @@ where
         // function to move the cursor to the next item to consider in subtree identified by page_idx in the given direction
         let select_first_in_direction = Self::select_rightmost_of_idx;
 
-        self.move_in_direction(next_idx, select_first_in_direction);
+        self.move_in_direction(next_idx, select_first_in_direction)
     }
 
     fn move_in_direction(
         &mut self,
         next_idx: impl Fn(usize, usize) -> Option<usize>,
-        select_first_in_direction: impl Fn(&mut Self, u32),
-    ) {
+        select_first_in_direction: impl Fn(&mut Self, u32) -> Result<(), PageError>,
+    ) -> Result<(), PageError> {
         if self.cursor_state.leaf_iterator.is_none() {
-            return;
+            return Ok(());
         }
         let (page_number, entry_index) = self.cursor_state.leaf_iterator.unwrap();
-        let page: NodePage = self.pager.get_and_decode(page_number);
+        let page: NodePage = self.pager.get_and_decode(page_number)?;
         let page = page
             .leaf()
             .expect("Values are always supposed to be in leaf pages");
         let num_items_in_leaf = page.num_items();
         if let Some(entry_index) = next_idx(entry_index, num_items_in_leaf) {
             self.cursor_state.leaf_iterator = Some((page_number, entry_index));
-            return;
+            return Ok(());
         }
         loop {
             // if the stack is empty then we have no more places to go
             if self.cursor_state.stack.is_empty() {
                 self.cursor_state.leaf_iterator = None;
-                return;
+                return Ok(());
             }
 
             let (curent_interior_idx, curent_edge) = self.cursor_state.stack.pop().unwrap();
 
-            let curent_interior: NodePage = self.pager.get_and_decode(curent_interior_idx);
+            let curent_interior: NodePage = self.pager.get_and_decode(curent_interior_idx)?;
 
             let curent_interior = curent_interior
                 .interior()
@@ -419,8 +565,13 @@ where
                 let curent_edge_idx = curent_interior.get_child_page_by_index(next_edge);
 
                 // then select the first item in the leftmost leaf of that subtree
-                select_first_in_direction(self, curent_edge_idx);
-                return;
+                select_first_in_direction(self, curent_edge_idx)?;
+
+                // unless it is a leaf with all its rows removed, which we carry on past
+                if self.get_entry()?.is_some() {
+                    return Ok(());
+                }
+                continue;
             }
 
             // if there are no more edges in this node:
@@ -436,6 +587,18 @@ where
     pub fn verify(&self) -> Result<(), VerifyError> {
         btree_verify::verify(&self.pager, &self.cursor_state.tree_name)
     }
+
+    /// Pages from the root down to the leaf the cursor is in, empty if it isn't positioned
+    pub fn path(&self) -> Vec<u32> {
+        let Some((leaf_idx, _)) = self.cursor_state.leaf_iterator else {
+            return vec![];
+        };
+
+        let mut path: Vec<u32> = self.cursor_state.stack.iter().map(|(page_idx, _)| *page_idx).collect();
+        path.push(leaf_idx);
+
+        path
+    }
 }
 
 fn split_and_store(pager: &mut Pager, mut rest: &[u8]) -> u32 {
@@ -479,6 +642,18 @@ fn split_and_store(pager: &mut Pager, mut rest: &[u8]) -> u32 {
     first_page_idx
 }
 
+fn create_tree(pager: &mut Pager, tree_name: &str) {
+    assert!(pager.get_root_page(tree_name).unwrap().is_none());
+    let idx = pager.allocate();
+    pager.set_root_page(tree_name, idx);
+    let empty_leaf_node = node::LeafNodePage::default();
+    let empty_root_node = node::NodePage::Leaf(empty_leaf_node);
+    // Encode and set the empty_root_node in the pager
+    pager.encode_and_set(idx, empty_root_node).unwrap();
+}
+
+/// A clone is another handle to the same database
+#[derive(Clone, Debug)]
 pub struct BTree {
     pager: Arc<RefCell<pager::Pager>>,
 }
@@ -490,15 +665,98 @@ impl BTree {
         }
     }
 
+    /// Open a btree whose pages are compressed, if the database is new.
+    /// Existing databases keep whichever compression they were created with.
+    pub fn with_compression(path: &str, compression: pager::Compression) -> BTree {
+        BTree {
+            pager: Arc::new(RefCell::new(Pager::with_compression(path, compression))),
+        }
+    }
+
+    /// Open a btree, supplying the passphrase if the database is (or should be created) encrypted
+    pub fn with_options(path: &str, options: pager::PagerOptions) -> Result<BTree, pager::OpenError> {
+        Ok(BTree {
+            pager: Arc::new(RefCell::new(Pager::with_options(path, options)?)),
+        })
+    }
+
+    /// Open a btree stored in something other than a file on disk, such as a `MemoryFile`
+    pub fn with_files(
+        file: Box<dyn PageFile>,
+        journal: Box<dyn PageFile>,
+        options: pager::PagerOptions,
+    ) -> Result<BTree, pager::OpenError> {
+        Ok(BTree {
+            pager: Arc::new(RefCell::new(Pager::with_files(file, journal, options)?)),
+        })
+    }
+
+    /// A btree in temporary files, for data which only lives as long as the btree, like spilled sort runs.
+    /// Nothing is synced, as nothing in it has to survive a crash.
+    ///
+    /// Rows of an encrypted database must not reach the disk in plaintext, so when `encrypted` the
+    /// pages are encrypted with a random key that is never stored.
+    pub fn temporary(encrypted: bool) -> std::io::Result<BTree> {
+        let options = pager::PagerOptions {
+            sync_mode: pager::SyncMode::Off,
+            ..Default::default()
+        };
+        let mut pager = Pager::with_files(
+            Box::new(DiskFile::temporary()?),
+            Box::new(DiskFile::temporary()?),
+            options,
+        )
+        .expect("an empty database to open");
+        if encrypted {
+            pager.use_ephemeral_key();
+        }
+
+        Ok(BTree {
+            pager: Arc::new(RefCell::new(pager)),
+        })
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.pager.borrow().is_encrypted()
+    }
+
+    /// SQLite databases are only ever read, nothing can be created or changed in them
+    pub fn is_read_only(&self) -> bool {
+        self.pager.borrow().is_sqlite()
+    }
+
+    /// The name and column names of every table, if this is a SQLite database
+    pub fn sqlite_tables(&self) -> Option<Vec<(String, Vec<String>)>> {
+        self.pager.borrow().sqlite_tables()
+    }
+
+    /// Start a transaction, every change made through this btree is part of it until it is committed
+    pub fn begin(&self) -> Transaction {
+        Transaction::begin(self.pager.clone())
+    }
+
+    /// Change the passphrase of an encrypted database, re-encrypting every page
+    pub fn rekey(&mut self, passphrase: &str) -> Result<(), pager::OpenError> {
+        self.pager.borrow_mut().rekey(passphrase)
+    }
+
+    /// A handle to the tree, None if there is no tree with that name. If the zero page can't be read
+    /// the handle is still given out, and the error comes back from its first read
     pub fn open(&self, tree_name: &str) -> Option<CursorHandle> {
         // Check if the root page actually exists, or return None
-        self.pager.borrow().get_root_page(tree_name)?;
+        let pager = self.pager.borrow();
+        if let Ok(None) = pager.get_root_page(tree_name) {
+            return None;
+        }
 
         let state = CursorState {
             stack: vec![],
             leaf_iterator: None,
+            skip_next: false,
             tree_name: tree_name.to_owned(),
+            sequence: pager.get_sequence(tree_name).ok().flatten(),
         };
+        drop(pager);
 
         Some(CursorHandle {
             pager: self.pager.clone(),
@@ -506,24 +764,36 @@ impl BTree {
         })
     }
 
+    /// Create a new tree whose generated rowids are never reused, even after the rows holding them are removed
+    pub fn create_autoincrement_tree(&mut self, tree_name: &str) {
+        let mut pager = self.pager.borrow_mut();
+        create_tree(&mut pager, tree_name);
+        pager.set_sequence(tree_name, 0);
+        pager.finish_operation();
+    }
+
     /// Create a new tree with the given name, tree must not already exist
     pub fn create_tree(&mut self, tree_name: &str) {
         let mut pager = self.pager.borrow_mut();
-
-        assert!(pager.get_root_page(tree_name).is_none());
-        let idx = pager.allocate();
-        pager.set_root_page(tree_name, idx);
-        let empty_leaf_node = node::LeafNodePage::default();
-        let empty_root_node = node::NodePage::Leaf(empty_leaf_node);
-        // Encode and set the empty_root_node in the pager
-        pager.encode_and_set(idx, empty_root_node).unwrap();
+        create_tree(&mut pager, tree_name);
+        pager.finish_operation();
     }
 
     pub fn debug(&self, message: &str) {
         self.pager.borrow().debug(message)
     }
 
-    pub fn dump_to_file(&self, output_path: &std::path::Path) -> std::io::Result<()> {
+    /// Write a graphviz dot graph of the trees to `output_path`
+    pub fn dump_to_file(&self, output_path: &std::path::Path, options: &DumpOptions) -> std::io::Result<()> {
+        if let Some(tree) = &options.tree {
+            if self.pager.borrow().get_root_page(tree)?.is_none() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("no tree named {tree:?}"),
+                ));
+            }
+        }
+
         let file = std::fs::OpenOptions::new()
             .create(true)
             .write(true)
@@ -531,18 +801,38 @@ impl BTree {
             .open(output_path)?;
         let mut writer = std::io::BufWriter::new(file);
 
-        write!(writer, "{}", self)?;
+        write!(writer, "{}", self.graph(options))?;
         Ok(())
     }
 
+    /// A graphviz dot graph of the trees, see `DumpOptions`
+    pub fn graph(&self, options: &DumpOptions) -> String {
+        let mut graph = String::new();
+        btree_graph::dump(&mut graph, &self.pager.borrow(), options).unwrap();
+
+        graph
+    }
+
     pub fn verify(&self) -> Result<(), VerifyError> {
         btree_verify::verify_all_trees(&self.pager.borrow())
     }
+
+    /// Start an online backup of the whole database into the file at `destination`
+    pub fn backup(&self, destination: &std::path::Path) -> std::io::Result<Backup> {
+        if self.pager.borrow().is_sqlite() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "SQLite databases can't be backed up, copy the file instead",
+            ));
+        }
+
+        Backup::new(self.pager.clone(), destination)
+    }
 }
 
 impl Display for BTree {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        btree_graph::dump(f, &self.pager.borrow())?;
+        btree_graph::dump(f, &self.pager.borrow(), &DumpOptions::default())?;
 
         Ok(())
     }
@@ -551,12 +841,15 @@ impl Display for BTree {
 #[cfg(test)]
 mod test {
     
+    use crate::storage::file::{FileEvent, MemoryFile};
+    use crate::storage::pager::{Compression, PagerOptions, SyncMode};
     use crate::test::TestDb;
     use proptest::prelude::*;
     use std::collections::BTreeMap;
     use std::io::Read;
+    use tempfile::NamedTempFile;
 
-    use super::BTree;
+    use super::{BTree, RowidsExhausted};
 
     #[test]
     fn test_create_blank() {
@@ -577,9 +870,9 @@ mod test {
         {
             let mut cursor_handle = btree.open("testing").unwrap();
             let mut cursor = cursor_handle.open_readonly();
-            cursor.first();
+            cursor.first().unwrap();
 
-            assert!(cursor.get_entry().is_none());
+            assert!(cursor.get_entry().unwrap().is_none());
         }
 
         // Test the new table is empty, when using a readwrite cursor
@@ -587,8 +880,8 @@ mod test {
             let mut cursor_handle = btree.open("testing").unwrap();
             let mut cursor = cursor_handle.open_readwrite();
 
-            cursor.first();
-            assert!(cursor.get_entry().is_none());
+            cursor.first().unwrap();
+            assert!(cursor.get_entry().unwrap().is_none());
         }
     }
 
@@ -613,9 +906,9 @@ mod test {
         {
             let mut cursor_handle = btree.open("testing").unwrap();
             let mut cursor = cursor_handle.open_readonly();
-            cursor.first();
+            cursor.first().unwrap();
             let mut buf = [0; 3];
-            cursor.get_entry().unwrap().read(&mut buf).unwrap();
+            cursor.get_entry().unwrap().unwrap().read(&mut buf).unwrap();
             assert_eq!(&buf, &[42, 255, 64]);
         }
 
@@ -647,12 +940,12 @@ mod test {
             let mut cursor_handle = btree.open("testing").unwrap();
             let mut cursor = cursor_handle.open_readonly();
 
-            cursor.first();
+            cursor.first().unwrap();
             for i in 1..10u64 {
                 let mut buf = [0; 8];
-                cursor.get_entry().unwrap().read(&mut buf).unwrap();
+                cursor.get_entry().unwrap().unwrap().read(&mut buf).unwrap();
                 assert_eq!(buf, i.to_be_bytes());
-                cursor.next();
+                cursor.next().unwrap();
             }
         }
 
@@ -685,13 +978,13 @@ mod test {
             let mut cursor_handle = btree.open("testing").unwrap();
             let mut cursor = cursor_handle.open_readonly();
 
-            cursor.find(7);
+            cursor.find(7).unwrap();
 
             for i in 7..10u64 {
                 let mut buf = [0; 8];
-                cursor.get_entry().unwrap().read(&mut buf).unwrap();
+                cursor.get_entry().unwrap().unwrap().read(&mut buf).unwrap();
                 assert_eq!(buf, i.to_be_bytes());
-                cursor.next();
+                cursor.next().unwrap();
             }
         }
 
@@ -717,17 +1010,17 @@ mod test {
         cursor.debug("");
         cursor.insert(11, long_string("C", 1));
 
-        cursor.first();
+        cursor.first().unwrap();
         cursor.debug("");
         cursor.verify().unwrap();
 
-        assert_eq!(1, cursor.row_key().unwrap());
-        cursor.next();
-        assert_eq!(10, cursor.row_key().unwrap());
-        cursor.next();
-        assert_eq!(11, cursor.row_key().unwrap());
-        cursor.next();
-        assert!(cursor.row_key().is_none());
+        assert_eq!(Ok(Some(1)), cursor.row_key());
+        cursor.next().unwrap();
+        assert_eq!(Ok(Some(10)), cursor.row_key());
+        cursor.next().unwrap();
+        assert_eq!(Ok(Some(11)), cursor.row_key());
+        cursor.next().unwrap();
+        assert_eq!(Ok(None), cursor.row_key());
 
         // Must close cursor or we cant print the btree below
         drop(cursor);
@@ -736,6 +1029,500 @@ mod test {
         println!("{btree}");
     }
 
+    #[test]
+    fn compressed_pages_are_transparent() {
+        let row = |i: u64| format!("[{i}, \"customer\", \"{{\\\"status\\\": \\\"active\\\"}}\"]");
+
+        let count_pages = |compression| {
+            let file = NamedTempFile::new().unwrap();
+            let mut btree = BTree::with_compression(file.path().to_str().unwrap(), compression);
+            btree.create_tree("testing");
+
+            let mut cursor_handle = btree.open("testing").unwrap();
+            let mut cursor = cursor_handle.open_readwrite();
+            for i in 0..200u64 {
+                cursor.insert(i, row(i).into_bytes());
+            }
+            cursor.verify().unwrap();
+
+            cursor.first().unwrap();
+            for i in 0..200u64 {
+                let mut buf = vec![];
+                cursor.get_entry().unwrap().unwrap().read_to_end(&mut buf).unwrap();
+                assert_eq!(row(i).into_bytes(), buf);
+                cursor.next().unwrap();
+            }
+            assert!(cursor.get_entry().unwrap().is_none());
+
+            cursor.pager.get_file_size_pages()
+        };
+
+        let uncompressed = count_pages(Compression::None);
+        let compressed = count_pages(Compression::Lz4);
+
+        assert!(compressed < uncompressed);
+    }
+
+    #[test]
+    fn encrypted_btree_reopen() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        let options = || PagerOptions {
+            compression: Compression::Lz4,
+            passphrase: Some("hunter2".to_string()),
+
+            ..Default::default()
+        };
+
+        let mut btree = BTree::with_options(path, options()).unwrap();
+        btree.create_tree("testing");
+        {
+            let mut cursor_handle = btree.open("testing").unwrap();
+            let mut cursor = cursor_handle.open_readwrite();
+            for i in 0..300u64 {
+                cursor.insert(i, format!("customer {i}").into_bytes());
+            }
+        }
+        btree.rekey("correct horse").unwrap();
+
+        let btree = BTree::with_options(
+            path,
+            PagerOptions {
+                passphrase: Some("correct horse".to_string()),
+                ..options()
+            },
+        )
+        .unwrap();
+        btree.verify().unwrap();
+
+        let mut cursor_handle = btree.open("testing").unwrap();
+        let mut cursor = cursor_handle.open_readonly();
+        cursor.first().unwrap();
+        for i in 0..300u64 {
+            let mut buf = vec![];
+            cursor.get_entry().unwrap().unwrap().read_to_end(&mut buf).unwrap();
+            assert_eq!(format!("customer {i}").into_bytes(), buf);
+            cursor.next().unwrap();
+        }
+        assert!(cursor.get_entry().unwrap().is_none());
+    }
+
+    #[test]
+    fn last_of_multi_level_tree() {
+        let mut db = TestDb::default();
+        db.btree.create_tree("testing");
+
+        let mut cursor_handle = db.btree.open("testing").unwrap();
+        let mut cursor = cursor_handle.open_readwrite();
+
+        cursor.last().unwrap();
+        assert!(cursor.get_entry().unwrap().is_none());
+
+        for i in 0..500u64 {
+            cursor.insert(i * 2, vec![b'x'; 40]);
+        }
+
+        cursor.last().unwrap();
+        assert_eq!(998, cursor.get_entry().unwrap().unwrap().key());
+        cursor.prev().unwrap();
+        assert_eq!(996, cursor.get_entry().unwrap().unwrap().key());
+        cursor.next().unwrap();
+        cursor.next().unwrap();
+        assert!(cursor.get_entry().unwrap().is_none());
+    }
+
+    #[test]
+    fn insert_next_rowid() {
+        let mut db = TestDb::default();
+        db.btree.create_tree("testing");
+
+        let mut cursor_handle = db.btree.open("testing").unwrap();
+        let mut cursor = cursor_handle.open_readwrite();
+
+        assert_eq!(1, cursor.insert_next_rowid(b"first".to_vec()).unwrap());
+        assert_eq!(2, cursor.insert_next_rowid(b"second".to_vec()).unwrap());
+        assert_eq!(2, cursor.get_entry().unwrap().unwrap().key());
+
+        cursor.insert(100, b"explicit".to_vec());
+        for i in 101..400 {
+            assert_eq!(i, cursor.insert_next_rowid(b"generated".to_vec()).unwrap());
+        }
+        cursor.verify().unwrap();
+
+        // Plain trees don't keep track of keys, and have no sequence
+        assert_eq!(Ok(None), cursor.pager.get_sequence("testing"));
+    }
+
+    #[test]
+    fn replace_while_iterating() {
+        let mut db = TestDb::default();
+        db.btree.create_tree("testing");
+
+        let mut cursor_handle = db.btree.open("testing").unwrap();
+        let mut cursor = cursor_handle.open_readwrite();
+        for i in 0..200 {
+            cursor.insert(i, b"small".to_vec());
+        }
+        assert!(!cursor.replace(b"nothing to replace".to_vec()));
+
+        // Larger values split pages under the cursor, which must still visit every row once
+        cursor.first().unwrap();
+        let mut keys = vec![];
+        while let Some(entry) = cursor.get_entry().unwrap() {
+            let key = entry.key();
+            keys.push(key);
+            assert!(cursor.replace(format!("a much larger value for row {key}").into_bytes()));
+            assert_eq!(key, cursor.get_entry().unwrap().unwrap().key());
+            cursor.next().unwrap();
+        }
+        assert_eq!(keys, (0..200).collect::<Vec<_>>());
+        cursor.verify().unwrap();
+
+        cursor.find(150).unwrap();
+        let mut value = String::new();
+        cursor.get_entry().unwrap().unwrap().read_to_string(&mut value).unwrap();
+        assert_eq!(value, "a much larger value for row 150");
+    }
+
+    #[test]
+    fn delete_while_iterating() {
+        let mut db = TestDb::default();
+        db.btree.create_tree("testing");
+
+        let mut cursor_handle = db.btree.open("testing").unwrap();
+        let mut cursor = cursor_handle.open_readwrite();
+        for i in 0..300 {
+            cursor.insert(i, format!("value {i}").into_bytes());
+        }
+        assert!(!cursor.delete());
+
+        // Removing every other row, and then all the rows of a few leaves
+        cursor.first().unwrap();
+        while let Some(entry) = cursor.get_entry().unwrap() {
+            let key = entry.key();
+            if key % 2 == 0 || (100..250).contains(&key) {
+                assert!(cursor.delete());
+            }
+            cursor.next().unwrap();
+        }
+        cursor.verify().unwrap();
+        drop(cursor);
+
+        let expected: Vec<u64> = (0..300).filter(|key| key % 2 == 1 && !(100..250).contains(key)).collect();
+        assert_eq!(read_keys(&db.btree, "testing"), expected);
+
+        // Going back after a delete finds the row before it
+        let mut cursor = cursor_handle.open_readwrite();
+        cursor.find(51).unwrap();
+        assert!(cursor.delete());
+        assert_eq!(53, cursor.get_entry().unwrap().unwrap().key());
+        cursor.prev().unwrap();
+        assert_eq!(49, cursor.get_entry().unwrap().unwrap().key());
+
+        // Iterating backwards skips the emptied leaves too
+        cursor.find(251).unwrap();
+        cursor.prev().unwrap();
+        assert_eq!(99, cursor.get_entry().unwrap().unwrap().key());
+
+        // Once every row is removed, the tree is empty
+        cursor.first().unwrap();
+        while cursor.get_entry().unwrap().is_some() {
+            assert!(cursor.delete());
+            cursor.next().unwrap();
+        }
+        cursor.first().unwrap();
+        assert!(cursor.get_entry().unwrap().is_none());
+        cursor.last().unwrap();
+        assert!(cursor.get_entry().unwrap().is_none());
+        cursor.verify().unwrap();
+
+        // And plain trees start their rowids again
+        assert_eq!(1, cursor.insert_next_rowid(b"again".to_vec()).unwrap());
+    }
+
+    #[test]
+    fn delete_frees_overflow_pages() {
+        let mut db = TestDb::default();
+        db.btree.create_tree("testing");
+
+        let mut cursor_handle = db.btree.open("testing").unwrap();
+        let mut cursor = cursor_handle.open_readwrite();
+        let large = vec![7; 1000];
+        for i in 0..10 {
+            cursor.insert(i, large.clone());
+        }
+        let pages = cursor.pager.get_file_size_pages();
+
+        cursor.first().unwrap();
+        while cursor.get_entry().unwrap().is_some() {
+            cursor.delete();
+            cursor.next().unwrap();
+        }
+
+        // The pages the values overflowed into are reused
+        for i in 0..10 {
+            cursor.insert(i, large.clone());
+        }
+        assert_eq!(pages, cursor.pager.get_file_size_pages());
+        cursor.find(3).unwrap();
+        let mut value = vec![];
+        cursor.get_entry().unwrap().unwrap().read_to_end(&mut value).unwrap();
+        assert_eq!(value, large);
+    }
+
+    #[test]
+    fn replace_frees_overflow_pages() {
+        let mut db = TestDb::default();
+        db.btree.create_tree("testing");
+
+        let mut cursor_handle = db.btree.open("testing").unwrap();
+        let mut cursor = cursor_handle.open_readwrite();
+        let large = vec![7; 2000];
+        cursor.insert(0, large.clone());
+
+        // The new value is stored before the old one is freed, after that the two take turns
+        cursor.first().unwrap();
+        assert!(cursor.replace(large.clone()));
+        let pages = cursor.pager.get_file_size_pages();
+        for n in 0..40 {
+            assert!(cursor.replace(vec![n; 2000]));
+        }
+        cursor.insert(0, large.clone());
+        assert_eq!(pages, cursor.pager.get_file_size_pages());
+
+        cursor.first().unwrap();
+        let mut value = vec![];
+        cursor.get_entry().unwrap().unwrap().read_to_end(&mut value).unwrap();
+        assert_eq!(value, large);
+    }
+
+    #[test]
+    fn autoincrement_rowids_not_reused_after_delete() {
+        let mut db = TestDb::default();
+        db.btree.create_autoincrement_tree("testing");
+
+        let mut cursor_handle = db.btree.open("testing").unwrap();
+        let mut cursor = cursor_handle.open_readwrite();
+        assert_eq!(1, cursor.insert_next_rowid(b"first".to_vec()).unwrap());
+        assert_eq!(2, cursor.insert_next_rowid(b"second".to_vec()).unwrap());
+        assert!(cursor.delete());
+        assert_eq!(3, cursor.insert_next_rowid(b"third".to_vec()).unwrap());
+    }
+
+    #[test]
+    fn autoincrement_sequence_persisted() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let mut btree = BTree::new(path);
+        btree.create_autoincrement_tree("testing");
+        {
+            let mut cursor_handle = btree.open("testing").unwrap();
+            let mut cursor = cursor_handle.open_readwrite();
+            assert_eq!(1, cursor.insert_next_rowid(b"first".to_vec()).unwrap());
+            cursor.insert(50, b"explicit".to_vec());
+            assert_eq!(51, cursor.insert_next_rowid(b"second".to_vec()).unwrap());
+        }
+
+        let btree = BTree::new(path);
+        let mut cursor_handle = btree.open("testing").unwrap();
+        let mut cursor = cursor_handle.open_readwrite();
+        assert_eq!(Ok(Some(51)), cursor.pager.get_sequence("testing"));
+
+        // The sequence wins even if it is ahead of the rows in the tree
+        cursor.pager.set_sequence("testing", 1000);
+        assert_eq!(1001, cursor.insert_next_rowid(b"third".to_vec()).unwrap());
+    }
+
+    #[test]
+    fn autoincrement_sequence_shared_between_cursors() {
+        let mut db = TestDb::default();
+        db.btree.create_autoincrement_tree("testing");
+        let mut first_handle = db.btree.open("testing").unwrap();
+        let mut second_handle = db.btree.open("testing").unwrap();
+
+        {
+            let mut second = second_handle.open_readwrite();
+            second.insert(100, b"explicit".to_vec());
+            second.find(100).unwrap();
+            assert!(second.delete());
+        }
+
+        // The first cursor opened before the sequence was raised, but neither lowers it nor reuses its rowids
+        let mut first = first_handle.open_readwrite();
+        first.insert(50, b"smaller".to_vec());
+        assert_eq!(Ok(Some(100)), first.pager.get_sequence("testing"));
+        assert_eq!(101, first.insert_next_rowid(b"next".to_vec()).unwrap());
+        first.insert(20, b"smallest".to_vec());
+        assert_eq!(Ok(Some(101)), first.pager.get_sequence("testing"));
+    }
+
+    #[test]
+    fn insert_next_rowid_exhausted() {
+        let mut db = TestDb::default();
+        db.btree.create_tree("plain");
+        db.btree.create_autoincrement_tree("auto");
+
+        let mut cursor_handle = db.btree.open("plain").unwrap();
+        let mut cursor = cursor_handle.open_readwrite();
+        cursor.insert(u64::MAX, b"last".to_vec());
+        assert_eq!(Err(RowidsExhausted), cursor.insert_next_rowid(b"more".to_vec()));
+        drop(cursor);
+        assert_eq!(vec![u64::MAX], read_keys(&db.btree, "plain"));
+
+        // The sequence remembers the largest key even once its row is gone
+        let mut cursor_handle = db.btree.open("auto").unwrap();
+        let mut cursor = cursor_handle.open_readwrite();
+        cursor.insert(u64::MAX, b"last".to_vec());
+        cursor.find(u64::MAX).unwrap();
+        assert!(cursor.delete());
+        assert_eq!(Err(RowidsExhausted), cursor.insert_next_rowid(b"more".to_vec()));
+        drop(cursor);
+        assert!(read_keys(&db.btree, "auto").is_empty());
+    }
+
+    fn read_keys(btree: &BTree, tree_name: &str) -> Vec<u64> {
+        let mut cursor_handle = btree.open(tree_name).unwrap();
+        let mut cursor = cursor_handle.open_readonly();
+        let mut keys = vec![];
+
+        cursor.first().unwrap();
+        while let Some(entry) = cursor.get_entry().unwrap() {
+            keys.push(entry.key());
+            cursor.next().unwrap();
+        }
+
+        keys
+    }
+
+    fn insert_keys(btree: &BTree, tree_name: &str, keys: std::ops::Range<u64>) {
+        let mut cursor_handle = btree.open(tree_name).unwrap();
+        let mut cursor = cursor_handle.open_readwrite();
+        for key in keys {
+            cursor.insert(key, format!("value {key}").into_bytes());
+        }
+    }
+
+    #[test]
+    fn transaction_across_trees() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let mut btree = BTree::new(path);
+        btree.create_tree("table");
+        btree.create_tree("index");
+        insert_keys(&btree, "table", 0..10);
+        insert_keys(&btree, "index", 0..10);
+
+        // Rolled back: neither tree changes, nor does the new tree exist
+        let transaction = btree.begin();
+        insert_keys(&btree, "table", 10..200);
+        insert_keys(&btree, "index", 10..200);
+        btree.create_tree("other");
+        assert_eq!(200, read_keys(&btree, "table").len());
+        transaction.rollback();
+
+        assert_eq!((0..10).collect::<Vec<_>>(), read_keys(&btree, "table"));
+        assert_eq!((0..10).collect::<Vec<_>>(), read_keys(&btree, "index"));
+        assert!(btree.open("other").is_none());
+        btree.verify().unwrap();
+
+        // Dropped without committing is also a rollback
+        {
+            let _transaction = btree.begin();
+            insert_keys(&btree, "table", 10..20);
+        }
+        assert_eq!(10, read_keys(&btree, "table").len());
+
+        let transaction = btree.begin();
+        insert_keys(&btree, "table", 10..200);
+        insert_keys(&btree, "index", 10..200);
+        transaction.commit();
+
+        let btree = BTree::new(path);
+        btree.verify().unwrap();
+        assert_eq!((0..200).collect::<Vec<_>>(), read_keys(&btree, "table"));
+        assert_eq!((0..200).collect::<Vec<_>>(), read_keys(&btree, "index"));
+    }
+
+    #[test]
+    fn transaction_savepoints() {
+        let mut db = TestDb::default();
+        db.btree.create_tree("table");
+
+        let mut transaction = db.btree.begin();
+        insert_keys(&db.btree, "table", 0..10);
+        let savepoint = transaction.savepoint();
+        insert_keys(&db.btree, "table", 10..100);
+        transaction.rollback_to(savepoint);
+        assert_eq!((0..10).collect::<Vec<_>>(), read_keys(&db.btree, "table"));
+
+        insert_keys(&db.btree, "table", 10..20);
+        transaction.release(savepoint);
+        transaction.commit();
+
+        assert_eq!((0..20).collect::<Vec<_>>(), read_keys(&db.btree, "table"));
+        db.btree.verify().unwrap();
+    }
+
+    fn insert_with_sync_mode(sync_mode: SyncMode) -> Vec<FileEvent> {
+        let file = MemoryFile::default();
+        let options = PagerOptions {
+            sync_mode,
+            ..Default::default()
+        };
+
+        let journal = file.sharing_log("journal");
+        let mut btree = BTree::with_files(Box::new(file.clone()), Box::new(journal), options).unwrap();
+        btree.create_tree("testing");
+
+        // Skip past the creation of the file, and insert enough for pages to split
+        file.clear_events();
+        let mut cursor_handle = btree.open("testing").unwrap();
+        let mut cursor = cursor_handle.open_readwrite();
+        for i in 0..100u64 {
+            cursor.insert(i, vec![b'x'; 200]);
+        }
+        cursor.verify().unwrap();
+
+        file.events()
+    }
+
+    #[test]
+    fn sync_off_never_syncs() {
+        let events = insert_with_sync_mode(SyncMode::Off);
+
+        assert!(!events.contains(&FileEvent::Sync));
+    }
+
+    #[test]
+    fn sync_normal_syncs_after_each_insert() {
+        let events = insert_with_sync_mode(SyncMode::Normal);
+
+        let syncs = events.iter().filter(|e| **e == FileEvent::Sync).count();
+        assert_eq!(100, syncs);
+        assert_eq!(Some(&FileEvent::Sync), events.last());
+    }
+
+    #[test]
+    fn sync_full_syncs_before_zero_page() {
+        let events = insert_with_sync_mode(SyncMode::Full);
+
+        let zero_page_writes: Vec<usize> = events
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| matches!(e, FileEvent::Write { offset: 0, .. }))
+            .map(|(i, _)| i)
+            .collect();
+
+        // splits and overflow pages allocate, which rewrites the zero page
+        assert!(!zero_page_writes.is_empty());
+        for i in zero_page_writes {
+            assert_eq!(FileEvent::Sync, events[i - 1]);
+        }
+        assert_eq!(Some(&FileEvent::Sync), events.last());
+    }
+
     fn do_test_ordering(
         elements: &[(u64, (char, usize))],
         my_btree: &mut BTree,
@@ -762,9 +1549,9 @@ mod test {
         // cursor.debug("Before order check");
 
         if ordering_forwards {
-            cursor.first();
+            cursor.first().unwrap();
         } else {
-            cursor.last();
+            cursor.last().unwrap();
         }
 
         let rust_btree_iter: Box<dyn Iterator<Item = _>> = if ordering_forwards {
@@ -776,13 +1563,13 @@ mod test {
         for (_key, actual_value) in rust_btree_iter {
             // println!("Key: {key} {my_value}");
             let mut buf = vec![];
-            cursor.get_entry().unwrap().read_to_end(&mut buf).unwrap();
+            cursor.get_entry().unwrap().unwrap().read_to_end(&mut buf).unwrap();
             assert_eq!(actual_value, &buf);
 
             if ordering_forwards {
-                cursor.next();
+                cursor.next().unwrap();
             } else {
-                cursor.prev();
+                cursor.prev().unwrap();
             }
         }
 
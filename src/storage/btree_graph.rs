@@ -5,6 +5,22 @@ use super::node;
 use super::node::NodePage;
 use super::pager::Pager;
 
+/// What to include in a graph dump
+#[derive(Debug, Clone, Default)]
+pub struct DumpOptions {
+    /// Only dump this tree, rather than every tree in the database
+    pub tree: Option<String>,
+
+    /// Stop descending below this depth, the root is at depth 0
+    pub max_depth: Option<usize>,
+
+    /// Show each leaf as its key range and number of entries, rather than every entry and value
+    pub summarise_leaves: bool,
+
+    /// Pages to highlight, from the root down, e.g. the path of a cursor
+    pub highlight: Vec<u32>,
+}
+
 /*
 Example of output text:
 
@@ -114,89 +130,233 @@ fn join<I: Iterator<Item = T>, T: std::fmt::Display>(iter: &mut I, sep: &str) ->
     }
 }
 
-fn to_json_string(value: &Vec<serde_json::Value>) -> String {
-    serde_json::Value::Array(value.clone()).to_string()
-}
-
-pub fn dump<W: Write>(output: &mut W, pager: &Pager) -> Result {
+pub fn dump<W: Write>(output: &mut W, pager: &Pager, options: &DumpOptions) -> Result {
     writeln!(output, "digraph Database {{")?;
 
     writeln!(output, "\tnode [ shape=record ]")?;
     writeln!(output, "\trankdir=\"LR\";")?;
 
-    for page_idx in 1..pager.get_file_size_pages() {
-        let page: NodePage = pager.get_and_decode(page_idx);
-
-        match page {
-            node::NodePage::Leaf(l) => {
-                // write!(output, "\t")?;
-                // node_name(output, page_idx)?;
-                // let mut label = (0..l.num_items()).map(|cell_idx| {
-                //     let cell = l.get_item_at_index(cell_idx).unwrap();
-                //     format!("<v_{}>{:?}", cell_idx, cell.key())
-                // });
-                // let label = join(&mut label, "|");
-                // let quoted_label = &label;
-                // writeln!(output, "[label=\"{quoted_label}\"]")?;
-
-                for _cell_idx in 0..l.num_items() {
-                    //write!(output, "\t")?;
-                    //value_edge(output, page_idx, cell_idx)?;
-                    //write!(output, " -> ")?;
-                    //value_node(output, page_idx, cell_idx)?;
-                    //writeln!(output, ";")?;
-
-                    //write!(output, "\t")?;
-                    //value_node(output, page_idx, cell_idx)?;
-                    //let value = &l.get_item_at_index(cell_idx).unwrap().value();
-                    //writeln!(output, "[label=\"{:?}\"]", value)?;
+    let tree_names = match &options.tree {
+        Some(tree) => Ok(vec![tree.clone()]),
+        None => pager.get_tree_names(),
+    };
+    let mut tree_names = match tree_names {
+        Ok(tree_names) => tree_names,
+        Err(e) => {
+            // No tree can be found without the zero page, so the graph only says why
+            writeln!(output, "\tlabel={};", quote(&e.to_string()))?;
+            return writeln!(output, "}}");
+        }
+    };
+    tree_names.sort();
+
+    for (tree_idx, tree_name) in tree_names.iter().enumerate() {
+        let Ok(Some(root_page)) = pager.get_root_page(tree_name) else {
+            continue;
+        };
+
+        writeln!(output, "\tsubgraph cluster_{tree_idx} {{")?;
+        writeln!(output, "\tlabel={};", quote(tree_name))?;
+        dump_page(output, pager, options, root_page, 0)?;
+        writeln!(output, "\t}}")?;
+        writeln!(output)?;
+    }
+
+    writeln!(output, "}}")?;
+
+    Ok(())
+}
+
+fn dump_page<W: Write>(output: &mut W, pager: &Pager, options: &DumpOptions, page_idx: u32, depth: usize) -> Result {
+    let page: NodePage = match pager.get_and_decode(page_idx) {
+        Ok(page) => page,
+        Err(e) => {
+            // Shown in place of the page, so the rest of the tree can still be seen
+            write!(output, "\t")?;
+            node_name(output, page_idx)?;
+            return writeln!(output, "[label={}]", quote(&e.to_string()));
+        }
+    };
+    let highlighted = options.highlight.contains(&page_idx);
+
+    match page {
+        node::NodePage::Leaf(l) if options.summarise_leaves => {
+            write!(output, "\t")?;
+            node_name(output, page_idx)?;
+            let label = match (l.get_item_at_index(0), l.num_items().checked_sub(1)) {
+                (Some(first), Some(last)) => {
+                    let last = l.get_item_at_index(last).unwrap();
+                    format!("keys {}..{}|{} entries", first.key(), last.key(), l.num_items())
                 }
-            }
-            node::NodePage::Interior(i) => {
+                _ => "empty".to_string(),
+            };
+            writeln!(output, "[label=\"{label}\"{}]", highlight_style(highlighted))?;
+        }
+        node::NodePage::Leaf(l) => {
+            write!(output, "\t")?;
+            node_name(output, page_idx)?;
+            let mut label = (0..l.num_items()).map(|cell_idx| {
+                let cell = l.get_item_at_index(cell_idx).unwrap();
+                format!("<v_{}>{:?}", cell_idx, cell.key())
+            });
+            let label = join(&mut label, "|");
+            writeln!(output, "[label=\"{label}\"{}]", highlight_style(highlighted))?;
+
+            for cell_idx in 0..l.num_items() {
                 write!(output, "\t")?;
-                node_name(output, page_idx)?;
-                let mut label = (1..i.num_edges()).map(|edge_index| {
-                    // Key | edge
-                    let key = i.get_key_by_index(edge_index - 1);
-                    format!("key={key:?}|<e_{edge_index}>.")
-                });
+                value_edge(output, page_idx, cell_idx)?;
+                write!(output, " -> ")?;
+                value_node(output, page_idx, cell_idx)?;
+                writeln!(output, ";")?;
+
+                write!(output, "\t")?;
+                value_node(output, page_idx, cell_idx)?;
+                let value = l.get_item_at_index(cell_idx).unwrap().value();
+                writeln!(output, "[label={}]", quote(&String::from_utf8_lossy(value)))?;
+            }
+        }
+        node::NodePage::Interior(i) => {
+            write!(output, "\t")?;
+            node_name(output, page_idx)?;
+            let mut label = (1..i.num_edges()).map(|edge_index| {
+                // Key | edge
+                let key = i.get_key_by_index(edge_index - 1);
+                format!("key={key:?}|<e_{edge_index}>.")
+            });
+
+            let label = join(&mut label, "|");
 
-                let label = join(&mut label, "|");
+            let label = format!("<e_0>.| {label}");
+            let quoted_label = &label;
+            writeln!(output, "[label=\"{quoted_label}\"{}]", highlight_style(highlighted))?;
 
-                let label = format!("<e_0>.| {label}");
-                let quoted_label = &label;
-                writeln!(output, "[label=\"{quoted_label}\"]")?;
+            let descend = options.max_depth.is_none_or(|max_depth| depth < max_depth);
+
+            for edge_index in 0..i.num_edges() {
+                let child_page_idx = i.get_child_page_by_index(edge_index);
+
+                write!(output, "\t")?;
+                interor_edge(output, page_idx, edge_index)?;
+                write!(output, " -> ")?;
+                node_name(output, child_page_idx)?;
+                if highlighted && options.highlight.contains(&child_page_idx) {
+                    write!(output, " [color=red penwidth=2]")?;
+                }
+                writeln!(output, ";")?;
 
-                for edge_index in 0..i.num_edges() {
+                if descend {
+                    dump_page(output, pager, options, child_page_idx, depth + 1)?;
+                } else {
+                    // Below the maximum depth, only show that the child exists
                     write!(output, "\t")?;
-                    interor_edge(output, page_idx, edge_index)?;
-                    write!(output, " -> ")?;
-                    let child_page_idx = i.get_child_page_by_index(edge_index);
                     node_name(output, child_page_idx)?;
-                    writeln!(output, ";")?;
+                    writeln!(output, "[label=\"...\"{}]", highlight_style(options.highlight.contains(&child_page_idx)))?;
                 }
             }
-            node::NodePage::OverflowPage(_o) => {
-                // write!(output, "\t")?;
-                // node_name(output, page_idx)?;
-                // if let Some(next_page_idx) = o.continuation() {
-                //     writeln!(output, "[label=\"<e_0>.\"]")?;
-
-                //     write!(output, "\t")?;
-                //     interor_edge(output, page_idx, 0)?;
-                //     write!(output, " -> ")?;
-                //     node_name(output, next_page_idx)?;
-                //     writeln!(output, ";")?;
-                // } else {
-                //     writeln!(output, "[label=\".\"]")?;
-                // }
+        }
+        node::NodePage::OverflowPage(_) => {
+            // Never the child of an interior page, their values are shown with the cell they belong to
+        }
+    }
+
+    Ok(())
+}
+
+fn highlight_style(highlighted: bool) -> &'static str {
+    if highlighted {
+        " style=filled fillcolor=yellow"
+    } else {
+        ""
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DumpOptions;
+    use crate::storage::BTree;
+    use crate::test::TestDb;
+
+    fn two_trees(btree: &mut BTree) {
+        for tree in ["first", "second"] {
+            btree.create_tree(tree);
+            let mut cursor_handle = btree.open(tree).unwrap();
+            let mut cursor = cursor_handle.open_readwrite();
+            for i in 0..500u64 {
+                cursor.insert(i, format!("{tree} {i:0>40}").into_bytes());
             }
         }
+    }
 
-        writeln!(output)?;
+    #[test]
+    fn single_tree() {
+        let test = TestDb::default();
+        let mut btree = test.btree;
+        two_trees(&mut btree);
+
+        let everything = btree.graph(&DumpOptions::default());
+        assert!(everything.contains("label=\"first\""));
+        assert!(everything.contains("label=\"second\""));
+
+        let options = DumpOptions {
+            tree: Some("second".to_string()),
+            ..Default::default()
+        };
+        let second = btree.graph(&options);
+        assert!(!second.contains("label=\"first\""));
+        assert!(second.contains("label=\"second\""));
+        assert!(second.contains("second 000"));
+        assert!(!second.contains("first 000"));
     }
 
-    writeln!(output, "}}")?;
+    #[test]
+    fn max_depth_and_summary() {
+        let test = TestDb::default();
+        let mut btree = test.btree;
+        two_trees(&mut btree);
+
+        let options = DumpOptions {
+            tree: Some("first".to_string()),
+            max_depth: Some(0),
+            ..Default::default()
+        };
+        let root_only = btree.graph(&options);
+        assert!(root_only.contains("[label=\"...\"]"));
+        assert!(!root_only.contains("first 000"));
+
+        let options = DumpOptions {
+            tree: Some("first".to_string()),
+            summarise_leaves: true,
+            ..Default::default()
+        };
+        let summary = btree.graph(&options);
+        assert!(summary.contains("label=\"keys 0.."));
+        assert!(summary.contains("..499|"));
+        assert!(!summary.contains("first 000"));
+    }
 
-    Ok(())
+    #[test]
+    fn highlight_cursor_path() {
+        let test = TestDb::default();
+        let mut btree = test.btree;
+        two_trees(&mut btree);
+
+        let mut cursor_handle = btree.open("first").unwrap();
+        let path = {
+            let mut cursor = cursor_handle.open_readonly();
+            cursor.find(250).unwrap();
+            cursor.path()
+        };
+        assert!(path.len() > 1);
+
+        let options = DumpOptions {
+            highlight: path.clone(),
+            ..Default::default()
+        };
+        let graph = btree.graph(&options);
+        for page_idx in &path {
+            assert!(graph.contains(&format!("node_{page_idx}[label=")));
+        }
+        assert_eq!(path.len(), graph.matches("fillcolor=yellow").count());
+        assert_eq!(path.len() - 1, graph.matches("color=red").count());
+    }
 }
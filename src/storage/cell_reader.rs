@@ -1,9 +1,7 @@
 
-use serde::Deserialize;
-
 use super::cell::Key;
 use super::node::NodePage;
-use super::pager::Pager;
+use super::pager::{PageError, Pager};
 
 // TODO: refactor to make this safer
 //       unsafe pointer dereference
@@ -29,7 +27,7 @@ impl<'a> std::io::Read for CellReader<'a> {
         match self.continuation {
             None => Ok(0),
             Some(continuation) => {
-                self.node = Box::new(self.pager.get_and_decode(continuation));
+                self.node = Box::new(self.pager.get_and_decode(continuation)?);
                 let overflow_page = match self.node.as_ref() {
                     NodePage::OverflowPage(p) => p,
                     _ => panic!(),
@@ -47,14 +45,17 @@ impl<'a> std::io::Read for CellReader<'a> {
 }
 
 impl<'a> CellReader<'a> {
-    pub fn new(pager: &'a Pager, leaf_page_idx: u32, cell_idx: usize) -> Option<CellReader<'a>> {
-        let node: Box<NodePage> = Box::new(pager.get_and_decode(leaf_page_idx));
+    /// A reader for the value of the cell, None if the leaf has no cell at that index
+    pub fn new(pager: &'a Pager, leaf_page_idx: u32, cell_idx: usize) -> Result<Option<CellReader<'a>>, PageError> {
+        let node: Box<NodePage> = Box::new(pager.get_and_decode(leaf_page_idx)?);
 
         let leaf_page = node
             .leaf()
             .expect("Values are always supposed to be in leaf pages");
 
-        let cell = leaf_page.get_item_at_index(cell_idx)?;
+        let Some(cell) = leaf_page.get_item_at_index(cell_idx) else {
+            return Ok(None);
+        };
         let key = cell.key();
         let continuation = cell.continuation();
         let value = cell.value();
@@ -62,22 +63,16 @@ impl<'a> CellReader<'a> {
         // TODO: factor the unsafe into seperate struct
         let buf = unsafe { std::slice::from_raw_parts(value.as_ptr(), value.len()) };
 
-        Some(CellReader {
+        Ok(Some(CellReader {
             pager,
             node,
             buf,
             key,
             continuation,
-        })
+        }))
     }
 
     pub fn key(&self) -> Key {
         self.key
     }
-
-    pub fn decode_as_json_array(&mut self) -> Vec<serde_json::Value> {
-        let mut deserializer = serde_json::Deserializer::from_reader(self);
-        let values = Vec::<serde_json::Value>::deserialize(&mut deserializer).unwrap();
-        values
-    }
 }
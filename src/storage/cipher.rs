@@ -0,0 +1,130 @@
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use sha2::Sha256;
+
+// Encrypted pages are laid out as: [nonce: 12 bytes][ciphertext][tag: 16 bytes]
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+
+/// Bytes of every encrypted page taken up by the cipher, and unavailable to page content
+pub const PAGE_OVERHEAD: usize = NONCE_SIZE + TAG_SIZE;
+
+pub const SALT_SIZE: usize = 16;
+
+// PBKDF2 is deliberately slow, which is a pain when every test opens a fresh database
+pub const KDF_ROUNDS: u32 = if cfg!(test) { 1_000 } else { 200_000 };
+
+#[derive(Debug)]
+pub struct AuthenticationError;
+
+/// Authenticated encryption of pages, keyed from a passphrase.
+///
+/// Every write uses a fresh random nonce, stored alongside the ciphertext. Nothing about it depends on what
+/// the file held before, so putting back older pages (as rolling back a journal does) can't lead to a nonce
+/// being used again, and at 96 bits a repeat by chance is vanishingly unlikely.
+pub struct PageCipher {
+    aead: ChaCha20Poly1305,
+    salt: Vec<u8>,
+    rounds: u32,
+}
+
+impl std::fmt::Debug for PageCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print the key
+        f.debug_struct("PageCipher")
+            .field("salt", &to_hex(&self.salt))
+            .field("rounds", &self.rounds)
+            .finish()
+    }
+}
+
+impl PageCipher {
+    pub fn new(passphrase: &str, salt: &[u8], rounds: u32) -> PageCipher {
+        let key: [u8; 32] = pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(passphrase.as_bytes(), salt, rounds);
+
+        PageCipher {
+            aead: ChaCha20Poly1305::new(Key::from_slice(&key)),
+            salt: salt.to_owned(),
+            rounds,
+        }
+    }
+
+    /// A random key which is never derived from anything, for data which doesn't outlive the process
+    pub fn ephemeral() -> PageCipher {
+        let key: [u8; 32] = rand::random();
+
+        PageCipher {
+            aead: ChaCha20Poly1305::new(Key::from_slice(&key)),
+            salt: vec![],
+            rounds: 0,
+        }
+    }
+
+    /// Derive a key for `passphrase` with a freshly generated salt
+    pub fn generate(passphrase: &str) -> PageCipher {
+        let salt: [u8; SALT_SIZE] = rand::random();
+        Self::new(passphrase, &salt, KDF_ROUNDS)
+    }
+
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    pub fn rounds(&self) -> u32 {
+        self.rounds
+    }
+
+    /// Encrypt `plaintext` under a new nonce, returning the nonce followed by the ciphertext and its tag.
+    /// `aad` is authenticated but not encrypted, and must be given again to open it
+    pub fn seal(&self, aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let nonce: [u8; NONCE_SIZE] = rand::random();
+        let payload = Payload { msg: plaintext, aad };
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(
+            self.aead
+                .encrypt(Nonce::from_slice(&nonce), payload)
+                .expect("page is within the cipher's size limit"),
+        );
+        sealed
+    }
+
+    pub fn open(&self, aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, AuthenticationError> {
+        if sealed.len() < PAGE_OVERHEAD {
+            return Err(AuthenticationError);
+        }
+
+        let (nonce, msg) = sealed.split_at(NONCE_SIZE);
+        let payload = Payload { msg, aad };
+
+        self.aead
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| AuthenticationError)
+    }
+
+    /// Encrypt a whole page, `page` must be `PAGE_OVERHEAD` bytes larger than `plaintext`.
+    /// The page number is authenticated, so a page can't be passed off as another
+    pub fn seal_page(&self, idx: u32, plaintext: &[u8], page: &mut [u8]) {
+        assert_eq!(plaintext.len() + PAGE_OVERHEAD, page.len());
+
+        page.copy_from_slice(&self.seal(&idx.to_le_bytes(), plaintext));
+    }
+
+    pub fn open_page(&self, idx: u32, page: &[u8]) -> Result<Vec<u8>, AuthenticationError> {
+        self.open(&idx.to_le_bytes(), page)
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// The bytes written out by `to_hex`, or None if the string isn't valid hex
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
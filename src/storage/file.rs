@@ -0,0 +1,275 @@
+#[cfg(test)]
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+use std::{
+    fmt::Debug,
+    fs::{File, OpenOptions},
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+};
+
+/// The file the pager reads and writes its pages from
+pub trait PageFile: Debug {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> std::io::Result<()>;
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> std::io::Result<()>;
+    fn size(&self) -> std::io::Result<u64>;
+    fn set_size(&mut self, len: u64) -> std::io::Result<()>;
+
+    /// Make every earlier write durable
+    fn sync(&mut self) -> std::io::Result<()>;
+}
+
+#[derive(Debug)]
+pub struct DiskFile {
+    file: File,
+}
+
+impl DiskFile {
+    pub fn open(path: &Path) -> std::io::Result<DiskFile> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        Ok(DiskFile { file })
+    }
+
+    /// An anonymous file, removed by the operating system once it is closed
+    pub fn temporary() -> std::io::Result<DiskFile> {
+        let file = tempfile::tempfile()?;
+
+        Ok(DiskFile { file })
+    }
+}
+
+impl PageFile for DiskFile {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        self.file.read_exact_at(buf, offset)
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> std::io::Result<()> {
+        self.file.write_all_at(buf, offset)
+    }
+
+    fn size(&self) -> std::io::Result<u64> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    fn set_size(&mut self, len: u64) -> std::io::Result<()> {
+        self.file.set_len(len)
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.file.sync_data()
+    }
+}
+
+/// A file on disk which only exists while it has content: it is created by the first write, and removed
+/// again when truncated to nothing. Used for the journal, so it is only ever left behind by a crash.
+#[derive(Debug)]
+pub struct TransientFile {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl TransientFile {
+    pub fn open(path: &Path) -> std::io::Result<TransientFile> {
+        let file = match OpenOptions::new().read(true).write(true).open(path) {
+            Ok(file) => Some(file),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+
+        Ok(TransientFile {
+            path: path.to_owned(),
+            file,
+        })
+    }
+
+    fn file(&mut self) -> std::io::Result<&mut File> {
+        if self.file.is_none() {
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&self.path)?;
+            self.file = Some(file);
+        }
+
+        Ok(self.file.as_mut().unwrap())
+    }
+}
+
+impl PageFile for TransientFile {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        match &self.file {
+            Some(file) => file.read_exact_at(buf, offset),
+            None => Err(std::io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> std::io::Result<()> {
+        self.file()?.write_all_at(buf, offset)
+    }
+
+    fn size(&self) -> std::io::Result<u64> {
+        match &self.file {
+            Some(file) => Ok(file.metadata()?.len()),
+            None => Ok(0),
+        }
+    }
+
+    fn set_size(&mut self, len: u64) -> std::io::Result<()> {
+        if len > 0 {
+            return self.file()?.set_len(len);
+        }
+
+        if self.file.take().is_some() {
+            std::fs::remove_file(&self.path)?;
+        }
+
+        Ok(())
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        match &self.file {
+            Some(file) => file.sync_data(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Everything that happened to a `MemoryFile`, in order
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileEvent {
+    Write { offset: u64, len: usize },
+    SetLen(u64),
+    Sync,
+}
+
+/// Events of every file sharing a log, tagged with the name of the file
+#[cfg(test)]
+type EventLog = Rc<RefCell<Vec<(&'static str, FileEvent)>>>;
+
+/// A file kept in memory, which records every write and sync made to it.
+///
+/// Clones share the same content, so a clone can be kept to inspect a file handed to a pager.
+/// Files created with `sharing_log` record into the same log, to check the order of events across files.
+#[cfg(test)]
+#[derive(Debug, Default, Clone)]
+pub struct MemoryFile {
+    name: &'static str,
+    data: Rc<RefCell<Vec<u8>>>,
+    log: EventLog,
+
+    // how many more writes succeed before every write fails, None for no limit
+    writes_left: Rc<Cell<Option<usize>>>,
+}
+
+#[cfg(test)]
+impl MemoryFile {
+    pub fn named(name: &'static str) -> MemoryFile {
+        MemoryFile {
+            name,
+            ..Default::default()
+        }
+    }
+
+    /// A new empty file, whose events are recorded in the same log as this one
+    pub fn sharing_log(&self, name: &'static str) -> MemoryFile {
+        MemoryFile {
+            name,
+            data: Default::default(),
+            log: self.log.clone(),
+            writes_left: Default::default(),
+        }
+    }
+
+    /// Fail every write after the next `writes`, as if the machine crashed part way through.
+    /// None lets writes succeed again, as they would once it restarts
+    pub fn crash_after_writes(&self, writes: Option<usize>) {
+        self.writes_left.set(writes);
+    }
+
+    /// Events of this file only
+    pub fn events(&self) -> Vec<FileEvent> {
+        self.log
+            .borrow()
+            .iter()
+            .filter(|(name, _)| *name == self.name)
+            .map(|(_, event)| event.clone())
+            .collect()
+    }
+
+    /// Events of every file sharing this file's log
+    pub fn all_events(&self) -> Vec<(&'static str, FileEvent)> {
+        self.log.borrow().clone()
+    }
+
+    pub fn clear_events(&self) {
+        self.log.borrow_mut().clear();
+    }
+
+    fn record(&self, event: FileEvent) {
+        self.log.borrow_mut().push((self.name, event));
+    }
+}
+
+#[cfg(test)]
+impl PageFile for MemoryFile {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        let data = self.data.borrow();
+        let start = offset as usize;
+
+        match data.get(start..start + buf.len()) {
+            Some(data) => {
+                buf.copy_from_slice(data);
+                Ok(())
+            }
+            None => Err(std::io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> std::io::Result<()> {
+        match self.writes_left.get() {
+            Some(0) => return Err(std::io::ErrorKind::Other.into()),
+            Some(writes) => self.writes_left.set(Some(writes - 1)),
+            None => {}
+        }
+
+        {
+            let mut data = self.data.borrow_mut();
+            let start = offset as usize;
+
+            if data.len() < start + buf.len() {
+                data.resize(start + buf.len(), 0);
+            }
+            data[start..start + buf.len()].copy_from_slice(buf);
+        }
+
+        self.record(FileEvent::Write {
+            offset,
+            len: buf.len(),
+        });
+
+        Ok(())
+    }
+
+    fn size(&self) -> std::io::Result<u64> {
+        Ok(self.data.borrow().len() as u64)
+    }
+
+    fn set_size(&mut self, len: u64) -> std::io::Result<()> {
+        self.data.borrow_mut().resize(len as usize, 0);
+        self.record(FileEvent::SetLen(len));
+
+        Ok(())
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.record(FileEvent::Sync);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use sha2::{Digest, Sha256};
+
+use super::file::PageFile;
+use super::pager::{SyncMode, PAGE_SIZE};
+
+// The rollback journal holds the original content of every page a commit is about to overwrite:
+//   header: [magic: 8 bytes][original size in pages: u32 LE][number of entries: u32 LE][nonce: 8 bytes]
+//           [checksum: 8 bytes]
+//   entries: [page number: u32 LE][page content: PAGE_SIZE bytes][checksum: 8 bytes]
+// An empty journal (or one with a zeroed header) means there is nothing to roll back.
+//
+// Every checksum covers the journal's nonce, which is random for each commit, so a torn write or
+// content left over from an earlier journal never passes for part of this one.
+const MAGIC: &[u8; 8] = b"dbjournl";
+const HEADER_SIZE: u64 = 32;
+const CHECKSUM_SIZE: usize = 8;
+const ENTRY_SIZE: u64 = 4 + PAGE_SIZE + CHECKSUM_SIZE as u64;
+
+/// Page numbers and their original content
+pub type JournalPages = Vec<(u32, Vec<u8>)>;
+
+fn checksum(nonce: &[u8], parts: &[&[u8]]) -> [u8; CHECKSUM_SIZE] {
+    let mut hasher = Sha256::new();
+    hasher.update(nonce);
+    for part in parts {
+        hasher.update(part);
+    }
+
+    hasher.finalize()[..CHECKSUM_SIZE].try_into().unwrap()
+}
+
+/// Write out the original pages, so they are durable before the database file is touched.
+///
+/// In `SyncMode::Full` the header is only written once the entries are on disk, so a journal which
+/// looks valid is never missing entries. Otherwise a crash can leave the header on disk without some of
+/// the entries, which their checksums catch.
+pub fn write(journal: &mut dyn PageFile, original_size_pages: u32, pages: &[(u32, &[u8])], sync_mode: SyncMode) {
+    journal.set_size(0).unwrap();
+
+    let nonce: [u8; 8] = rand::random();
+
+    for (i, (idx, content)) in pages.iter().enumerate() {
+        let offset = HEADER_SIZE + i as u64 * ENTRY_SIZE;
+        let idx = idx.to_le_bytes();
+        journal.write_at(offset, &idx).unwrap();
+        journal.write_at(offset + 4, content).unwrap();
+        journal
+            .write_at(offset + 4 + PAGE_SIZE, &checksum(&nonce, &[&idx, content]))
+            .unwrap();
+    }
+
+    if sync_mode == SyncMode::Full {
+        journal.sync().unwrap();
+    }
+
+    let mut header = [0; HEADER_SIZE as usize];
+    header[..8].copy_from_slice(MAGIC);
+    header[8..12].copy_from_slice(&original_size_pages.to_le_bytes());
+    header[12..16].copy_from_slice(&(pages.len() as u32).to_le_bytes());
+    header[16..24].copy_from_slice(&nonce);
+    let header_checksum = checksum(&nonce, &[&header[..16]]);
+    header[24..32].copy_from_slice(&header_checksum);
+    journal.write_at(0, &header).unwrap();
+
+    if sync_mode != SyncMode::Off {
+        journal.sync().unwrap();
+    }
+}
+
+/// Mark the journal as no longer needed, once the database file holds the committed pages
+pub fn clear(journal: &mut dyn PageFile, sync_mode: SyncMode) {
+    journal.set_size(0).unwrap();
+
+    if sync_mode != SyncMode::Off {
+        journal.sync().unwrap();
+    }
+}
+
+/// The original size and pages recorded in a journal left behind by an interrupted commit, if any.
+///
+/// A journal with a header or entry that fails its checksum was never completely written, so the commit
+/// never got as far as touching the database, and there is nothing to roll back.
+pub fn read_hot(journal: &dyn PageFile) -> Option<(u32, JournalPages)> {
+    let size = journal.size().unwrap();
+    if size < HEADER_SIZE {
+        return None;
+    }
+
+    let mut header = [0; HEADER_SIZE as usize];
+    journal.read_at(0, &mut header).unwrap();
+    if &header[..8] != MAGIC {
+        return None;
+    }
+
+    let nonce = &header[16..24];
+    if checksum(nonce, &[&header[..16]]) != header[24..32] {
+        return None;
+    }
+
+    let original_size_pages = u32::from_le_bytes(header[8..12].try_into().unwrap());
+    let count = u32::from_le_bytes(header[12..16].try_into().unwrap());
+    if size < HEADER_SIZE + count as u64 * ENTRY_SIZE {
+        // The commit never got as far as touching the database
+        return None;
+    }
+
+    let mut pages = vec![];
+    for i in 0..count as u64 {
+        let offset = HEADER_SIZE + i * ENTRY_SIZE;
+        let mut idx = [0; 4];
+        journal.read_at(offset, &mut idx).unwrap();
+        let mut content = vec![0; PAGE_SIZE as usize];
+        journal.read_at(offset + 4, &mut content).unwrap();
+        let mut stored = [0; CHECKSUM_SIZE];
+        journal.read_at(offset + 4 + PAGE_SIZE, &mut stored).unwrap();
+
+        if checksum(nonce, &[&idx, &content]) != stored {
+            return None;
+        }
+
+        pages.push((u32::from_le_bytes(idx), content));
+    }
+
+    Some((original_size_pages, pages))
+}
@@ -32,14 +32,19 @@ impl NodePage {
     }
 
     // TODO: setting an item into an interior page doesn't make sense, interior pages dont store values!
-    pub fn set_item_at_index(&mut self, item_idx: usize, cell: Cell) {
+    pub fn set_item_at_index(&mut self, item_idx: usize, cell: Cell) -> Cell {
         match self {
-            NodePage::Leaf(l) => {
-                l.set_item_at_index(item_idx, cell);
-            }
+            NodePage::Leaf(l) => l.set_item_at_index(item_idx, cell),
             NodePage::Interior(_) => todo!(),
             _ => panic!(),
-        };
+        }
+    }
+
+    pub fn remove_item_at_index(&mut self, item_idx: usize) -> Cell {
+        match self {
+            NodePage::Leaf(l) => l.remove_item_at_index(item_idx),
+            _ => panic!(),
+        }
     }
 
     pub fn split(self) -> (Self, Self) {
@@ -125,14 +130,19 @@ impl LeafNodePage {
         SearchResult::NotPresent(self.cells.len())
     }
 
-    pub fn set_item_at_index(&mut self, index: usize, cell: Cell) {
-        self.cells[index] = cell;
+    /// Replace the cell at the index, returning the one it replaced
+    pub fn set_item_at_index(&mut self, index: usize, cell: Cell) -> Cell {
+        std::mem::replace(&mut self.cells[index], cell)
     }
 
     pub fn insert_item_at_index(&mut self, index: usize, cell: Cell) {
         self.cells.insert(index, cell);
     }
 
+    pub fn remove_item_at_index(&mut self, index: usize) -> Cell {
+        self.cells.remove(index)
+    }
+
     pub fn get_item_at_index<'a>(&'a self, entry_index: usize) -> Option<&'a Cell> {
         self.cells.get(entry_index)
     }
@@ -403,7 +413,9 @@ mod test {
                 let cell = Cell::new(key, value, None);
                 let result = page.search(&key);
                 match result {
-                    SearchResult::Found(idx) => page.set_item_at_index(idx, cell),
+                    SearchResult::Found(idx) => {
+                        page.set_item_at_index(idx, cell);
+                    }
                     SearchResult::NotPresent(idx) => page.insert_item_at_index(idx, cell),
                     SearchResult::GoDown(_, _) => panic!(),
                 };
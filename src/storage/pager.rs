@@ -1,19 +1,24 @@
-use std::{
-    borrow::Borrow,
-    collections::HashMap,
-    fs::{File, OpenOptions},
-    io::{BufReader, Read, Seek, Write},
-    os::unix::prelude::MetadataExt,
-    path::Path,
-};
+use std::{borrow::Borrow, collections::HashMap, io::BufReader, path::Path};
 
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
+use super::cipher::{self, PageCipher};
+use super::file::{DiskFile, PageFile, TransientFile};
+use super::journal;
+use super::sqlite::{self, SqliteError, SqliteFile};
+
+#[derive(Clone)]
 pub struct Page {
     // TODO: maybe share an existing open page
     content: [u8; PAGE_SIZE as usize],
 }
 
+impl Page {
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+}
+
 impl Default for Page {
     fn default() -> Self {
         Self {
@@ -31,6 +36,19 @@ pub struct ZeroPage {
 
     // contains the root pages for the given entities
     root_pages: HashMap<String, u32>,
+
+    // largest key ever used by each autoincrement tree
+    #[serde(default)]
+    sequences: HashMap<String, u64>,
+
+    // how every other page in the file is compressed, the zero page itself is never compressed
+    #[serde(default)]
+    compression: Compression,
+
+    // present if the database is encrypted, everything except the compression and this header
+    // is then kept in the sealed part of the header rather than in plaintext
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encryption: Option<EncryptionHeader>,
 }
 
 impl Default for ZeroPage {
@@ -38,16 +56,163 @@ impl Default for ZeroPage {
         Self {
             free_page_list: Default::default(),
             root_pages: Default::default(),
+            sequences: Default::default(),
+            compression: Default::default(),
+            encryption: None,
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+struct EncryptionHeader {
+    // key derivation parameters, in hex
+    salt: String,
+    rounds: u32,
+
+    // the encrypted json of the rest of the zero page, with the nonce it was sealed with, in hex.
+    // A passphrase which can open this is the right one
+    sealed: String,
+}
+
+impl EncryptionHeader {
+    // The plaintext parts of the zero page are authenticated along with the sealed content, so no part
+    // of it can be changed without the zero page failing to open
+    fn aad(compression: Compression, salt: &str, rounds: u32) -> Vec<u8> {
+        serde_json::to_vec(&(0u32, compression, salt, rounds)).unwrap()
+    }
+}
+
+/// Compression applied to the encoded content of each page (other than the zero page)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Lz4,
+}
+
+/// Settings used when opening a pager
+#[derive(Default, Clone)]
+pub struct PagerOptions {
+    /// Compression of a newly created database, existing databases keep their own
+    pub compression: Compression,
+
+    /// Passphrase used to encrypt a newly created database, and required to open an encrypted one
+    pub passphrase: Option<String>,
+
+    pub sync_mode: SyncMode,
+}
+
+/// When writes are forced out to disk with fsync
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Never sync, leave it to the operating system. A crash can lose or reorder any recent writes
+    Off,
+    /// Sync once each operation has written all its pages.
+    /// Committing a transaction syncs the journal before the database is written, and the database
+    /// before the journal is cleared
+    #[default]
+    Normal,
+    /// Also sync before the zero page is rewritten, so it never refers to pages which are not on disk yet,
+    /// and sync the journal entries before the journal header which makes them valid
+    Full,
+}
+
+#[derive(Debug)]
+pub enum OpenError {
+    /// The database is encrypted, but no passphrase was given
+    PassphraseRequired,
+    /// The passphrase is not the one the database was encrypted with, or the zero page has been modified
+    WrongPassphrase,
+    /// A passphrase was given, but the database is not encrypted
+    NotEncrypted,
+    /// The file is a SQLite database which can't be read
+    Sqlite(SqliteError),
+    /// The zero page can't be decoded, the file isn't a database or it has been damaged
+    Corrupt,
+}
+
+impl std::fmt::Display for OpenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpenError::PassphraseRequired => write!(f, "the database is encrypted, a passphrase is required"),
+            OpenError::WrongPassphrase => write!(f, "wrong passphrase"),
+            OpenError::NotEncrypted => write!(f, "the database is not encrypted"),
+            OpenError::Sqlite(e) => write!(f, "{e}"),
+            OpenError::Corrupt => write!(f, "the file is not a database, or it is corrupt"),
+        }
+    }
+}
+
+/// A page which can't be read back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageError {
+    /// The page of an encrypted database failed authentication, it has been modified
+    Tampered(u32),
+    /// The content of the page can't be decoded
+    Corrupt(u32),
+}
+
+impl std::fmt::Display for PageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PageError::Tampered(idx) => write!(f, "page {idx} failed authentication, it has been modified"),
+            PageError::Corrupt(idx) => write!(f, "page {idx} is corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for PageError {}
+
+impl From<PageError> for std::io::Error {
+    fn from(e: PageError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+    }
+}
+
+// Pages changed by the transaction in progress, held in memory until it is committed
+#[derive(Clone)]
+struct PendingTransaction {
+    dirty: HashMap<u32, Page>,
+    size_pages: u32,
+
+    // what to go back to for each open savepoint, innermost last
+    savepoints: Vec<(HashMap<u32, Page>, u32)>,
+}
+
+impl std::fmt::Debug for PendingTransaction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingTransaction")
+            .field("dirty", &self.dirty.keys())
+            .field("size_pages", &self.size_pages)
+            .field("savepoints", &self.savepoints.len())
+            .finish()
+    }
+}
+
 #[derive(Debug)]
 pub struct Pager {
-    path: String,
+    file: Box<dyn PageFile>,
+    journal: Box<dyn PageFile>,
+    transaction: Option<PendingTransaction>,
+    sync_mode: SyncMode,
+    compression: Compression,
+    cipher: Option<PageCipher>,
+
+    // set when the file is a SQLite database, which is read through this rather than as our own pages
+    sqlite: Option<SqliteFile>,
+
+    // bumped on every page write, lets a backup notice the database changing underneath it
+    generation: u64,
 }
 
-const PAGE_SIZE: u64 = 2 << 11;
+pub const PAGE_SIZE: u64 = 2 << 11;
+
+// Compressed pages start with a small frame: [format: u8][length: u32 LE][payload]
+// the payload is either the lz4 block (with the decoded size prepended) or, when compressing
+// didn't make the page any smaller, the plain json encoding
+const FRAME_HEADER_SIZE: usize = 5;
+const FRAME_STORED: u8 = 0;
+const FRAME_LZ4: u8 = 1;
 
 #[derive(Debug)]
 pub enum EncodingError {
@@ -56,99 +221,514 @@ pub enum EncodingError {
 
 impl Pager {
     pub fn new(path: &str) -> Pager {
-        Pager {
-            path: path.to_owned(),
+        Self::with_compression(path, Compression::None)
+    }
+
+    /// Open the pager, using `compression` if the database is new.
+    /// An existing database always keeps the compression stored in its zero page.
+    pub fn with_compression(path: &str, compression: Compression) -> Pager {
+        let options = PagerOptions {
+            compression,
+            ..Default::default()
+        };
+
+        Self::with_options(path, options).unwrap()
+    }
+
+    /// Open the pager, checking the passphrase against the one stored if the database is encrypted.
+    /// The rollback journal is kept next to the database, with `-journal` appended to its name.
+    pub fn with_options(path: &str, options: PagerOptions) -> Result<Pager, OpenError> {
+        let file = DiskFile::open(Path::new(path)).expect("database file to be openable");
+        let journal_path = format!("{path}-journal");
+        let journal = TransientFile::open(Path::new(&journal_path)).expect("journal file to be openable");
+
+        Self::with_files(Box::new(file), Box::new(journal), options)
+    }
+
+    /// Open a pager over any kind of file, see `with_options`.
+    /// A journal left behind by an interrupted commit is rolled back first.
+    ///
+    /// SQLite database files are detected by their header and opened read-only, see `SqliteFile`.
+    pub fn with_files(
+        file: Box<dyn PageFile>,
+        journal: Box<dyn PageFile>,
+        options: PagerOptions,
+    ) -> Result<Pager, OpenError> {
+        if sqlite::is_sqlite_file(file.as_ref()) {
+            return Self::with_sqlite_file(file, journal, options);
+        }
+
+        let mut pager = Pager {
+            file,
+            journal,
+            transaction: None,
+            sync_mode: options.sync_mode,
+            compression: options.compression,
+            cipher: None,
+            sqlite: None,
+            generation: 0,
+        };
+
+        pager.recover_journal();
+
+        if pager.get_file_size_pages() < 1 {
+            // New database, the encryption settings get written out with the zero page
+            pager.cipher = options.passphrase.as_deref().map(PageCipher::generate);
+            return Ok(pager);
+        }
+
+        let zero: ZeroPage = pager.get_and_decode(0).map_err(|_| OpenError::Corrupt)?;
+        pager.compression = zero.compression;
+
+        match (zero.encryption, options.passphrase) {
+            (None, None) => {}
+            (None, Some(_)) => return Err(OpenError::NotEncrypted),
+            (Some(_), None) => return Err(OpenError::PassphraseRequired),
+            (Some(header), Some(passphrase)) => {
+                let salt = cipher::from_hex(&header.salt).ok_or(OpenError::WrongPassphrase)?;
+                let sealed = cipher::from_hex(&header.sealed).ok_or(OpenError::WrongPassphrase)?;
+                let cipher = PageCipher::new(&passphrase, &salt, header.rounds);
+                let aad = EncryptionHeader::aad(zero.compression, &header.salt, header.rounds);
+                cipher.open(&aad, &sealed).map_err(|_| OpenError::WrongPassphrase)?;
+                pager.cipher = Some(cipher);
+            }
+        }
+
+        Ok(pager)
+    }
+
+    fn with_sqlite_file(
+        file: Box<dyn PageFile>,
+        journal: Box<dyn PageFile>,
+        options: PagerOptions,
+    ) -> Result<Pager, OpenError> {
+        if options.passphrase.is_some() {
+            return Err(OpenError::NotEncrypted);
+        }
+
+        // SQLite names its rollback journal the same way we do
+        if journal.size().unwrap() > 0 {
+            return Err(OpenError::Sqlite(SqliteError::Unsupported(
+                "a hot journal, open the database with sqlite first to roll it back".to_string(),
+            )));
+        }
+
+        let sqlite = SqliteFile::open(file.as_ref()).map_err(OpenError::Sqlite)?;
+
+        Ok(Pager {
+            file,
+            journal,
+            transaction: None,
+            sync_mode: options.sync_mode,
+            compression: Compression::None,
+            cipher: None,
+            sqlite: Some(sqlite),
+            generation: 0,
+        })
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Encrypt a new database with a random key which is forgotten once the pager is dropped, for
+    /// temporary files holding rows of an encrypted database
+    pub fn use_ephemeral_key(&mut self) {
+        assert_eq!(0, self.get_file_size_pages(), "only a new database can be given an ephemeral key");
+
+        self.cipher = Some(PageCipher::ephemeral());
+    }
+
+    /// Whether this is a SQLite database, which can only be read
+    pub fn is_sqlite(&self) -> bool {
+        self.sqlite.is_some()
+    }
+
+    /// The name and column names of every table of a SQLite database, from its `sqlite_schema`
+    pub fn sqlite_tables(&self) -> Option<Vec<(String, Vec<String>)>> {
+        self.sqlite.as_ref().map(SqliteFile::table_columns)
+    }
+
+    /// Number of page writes made to the database file, changes whenever the committed content does
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Re-encrypt every page with a key derived from `passphrase`.
+    ///
+    /// Every page is rewritten in one transaction, so if this is interrupted the next open rolls the
+    /// database back to the old key.
+    pub fn rekey(&mut self, passphrase: &str) -> Result<(), OpenError> {
+        assert!(self.transaction.is_none(), "cannot rekey during a transaction");
+
+        if self.cipher.is_none() {
+            return Err(OpenError::NotEncrypted);
+        }
+
+        let zero = self.get_zero_page().map_err(|_| OpenError::Corrupt)?.unwrap();
+        let new_cipher = PageCipher::generate(passphrase);
+
+        self.begin();
+
+        for idx in 1..self.get_file_size_pages() {
+            let page = self.get(idx);
+
+            // Allocated but never written, there is nothing to re-encrypt
+            if page.content.iter().all(|b| *b == 0) {
+                continue;
+            }
+
+            // Nothing is re-encrypted if any page is damaged
+            let Ok(content) = self.open_content(idx, &page) else {
+                self.rollback();
+                return Err(OpenError::Corrupt);
+            };
+
+            let mut page = Page::default();
+            new_cipher.seal_page(idx, &content, &mut page.content);
+            self.set(idx, page);
         }
+
+        self.cipher = Some(new_cipher);
+        self.set_zero_page(zero);
+        self.commit();
+
+        Ok(())
     }
 
+    /// Size of the database, including pages added by the transaction in progress
     pub fn get_file_size_pages(&self) -> u32 {
-        let path = Path::new(&self.path);
-        let file = OpenOptions::new()
-            .read(true)
-            .write(false)
-            .open(path)
-            .unwrap();
-        let file_size_bytes = file.metadata().unwrap().size();
+        match &self.transaction {
+            Some(transaction) => transaction.size_pages,
+            None => self.get_committed_size_pages(),
+        }
+    }
+
+    /// Size of the database file, as committed
+    pub fn get_committed_size_pages(&self) -> u32 {
+        if let Some(sqlite) = &self.sqlite {
+            // SQLite numbers its pages from 1, page 0 stands in for our zero page
+            return sqlite.page_count() + 1;
+        }
+
+        let file_size_bytes = self.file.size().unwrap();
         let num_pages = file_size_bytes / PAGE_SIZE;
 
         num_pages as u32
     }
 
-    pub fn set_file_size_pages(&self, num_pages: u32) {
-        let path = Path::new(&self.path);
-        let file = OpenOptions::new()
-            .read(false)
-            .write(true)
-            .open(path)
+    pub fn set_file_size_pages(&mut self, num_pages: u32) {
+        assert!(self.sqlite.is_none(), "SQLite databases are read-only");
+
+        match &mut self.transaction {
+            Some(transaction) => transaction.size_pages = num_pages,
+            None => self.file.set_size(PAGE_SIZE * num_pages as u64).unwrap(),
+        }
+    }
+
+    /// Called once an operation has written all of its pages, to make them durable.
+    /// Inside a transaction the pages only become durable once it commits.
+    pub fn finish_operation(&mut self) {
+        if self.transaction.is_none() && self.sync_mode != SyncMode::Off {
+            self.file.sync().unwrap();
+        }
+    }
+
+    /// Start holding page writes in memory, until `commit` or `rollback`
+    pub fn begin(&mut self) {
+        assert!(
+            self.transaction.is_none(),
+            "transactions can't be nested, use a savepoint instead"
+        );
+
+        self.transaction = Some(PendingTransaction {
+            dirty: HashMap::new(),
+            size_pages: self.get_committed_size_pages(),
+            savepoints: vec![],
+        });
+    }
+
+    /// Write out every page changed by the transaction.
+    ///
+    /// The original content of the pages is journaled first, so if the commit is interrupted the next
+    /// open of the database rolls it back.
+    pub fn commit(&mut self) {
+        let transaction = self.transaction.take().expect("a transaction to be in progress");
+        if transaction.dirty.is_empty() && transaction.size_pages == self.get_committed_size_pages() {
+            return;
+        }
+
+        let original_size_pages = self.get_committed_size_pages();
+
+        let mut dirty: Vec<(u32, Page)> = transaction.dirty.into_iter().collect();
+        dirty.sort_by_key(|(idx, _)| *idx);
+
+        let originals: Vec<(u32, Page)> = dirty
+            .iter()
+            .filter(|(idx, _)| *idx < original_size_pages)
+            .map(|(idx, _)| (*idx, self.get_committed(*idx)))
+            .collect();
+        let originals: Vec<(u32, &[u8])> = originals
+            .iter()
+            .map(|(idx, page)| (*idx, page.content()))
+            .collect();
+        journal::write(self.journal.as_mut(), original_size_pages, &originals, self.sync_mode);
+
+        self.file
+            .set_size(PAGE_SIZE * transaction.size_pages as u64)
             .unwrap();
+        for (idx, page) in dirty {
+            self.write_committed(idx, &page);
+        }
+        if self.sync_mode != SyncMode::Off {
+            self.file.sync().unwrap();
+        }
+
+        journal::clear(self.journal.as_mut(), self.sync_mode);
+    }
+
+    /// Throw away every change made by the transaction
+    pub fn rollback(&mut self) {
+        self.transaction.take().expect("a transaction to be in progress");
+    }
+
+    /// Remember the current state of the transaction, returning the depth of the new savepoint
+    pub fn savepoint(&mut self) -> usize {
+        let transaction = self.transaction.as_mut().expect("a transaction to be in progress");
+
+        let state = (transaction.dirty.clone(), transaction.size_pages);
+        transaction.savepoints.push(state);
+
+        transaction.savepoints.len() - 1
+    }
+
+    /// Forget the savepoint at `depth` and any inside it, keeping their changes
+    pub fn release_savepoint(&mut self, depth: usize) {
+        let transaction = self.transaction.as_mut().expect("a transaction to be in progress");
+        assert!(depth < transaction.savepoints.len(), "savepoint already released");
 
-        file.set_len(PAGE_SIZE * num_pages as u64).unwrap();
+        transaction.savepoints.truncate(depth);
     }
 
-    fn get_zero_page(&self) -> Option<ZeroPage> {
+    /// Undo every change since the savepoint at `depth` was made, the savepoint itself stays open
+    pub fn rollback_to_savepoint(&mut self, depth: usize) {
+        let transaction = self.transaction.as_mut().expect("a transaction to be in progress");
+        assert!(depth < transaction.savepoints.len(), "savepoint already released");
+
+        transaction.savepoints.truncate(depth + 1);
+        let (dirty, size_pages) = transaction.savepoints[depth].clone();
+        transaction.dirty = dirty;
+        transaction.size_pages = size_pages;
+    }
+
+    // Put back the original pages from a commit that never finished
+    fn recover_journal(&mut self) {
+        let Some((original_size_pages, pages)) = journal::read_hot(self.journal.as_ref()) else {
+            return;
+        };
+
+        for (idx, content) in pages {
+            self.file.write_at(PAGE_SIZE * idx as u64, &content).unwrap();
+        }
+        self.file
+            .set_size(PAGE_SIZE * original_size_pages as u64)
+            .unwrap();
+        self.file.sync().unwrap();
+
+        journal::clear(self.journal.as_mut(), SyncMode::Full);
+    }
+
+    fn get_zero_page(&self) -> Result<Option<ZeroPage>, PageError> {
         if self.get_file_size_pages() < 1 {
-            None
-        } else {
-            Some(self.get_and_decode(0))
+            return Ok(None);
+        }
+
+        let zero: ZeroPage = self.get_and_decode(0)?;
+
+        let Some(header) = zero.encryption else {
+            return Ok(Some(zero));
+        };
+
+        let cipher = self.cipher.as_ref().expect("encrypted database to have a key");
+        let aad = EncryptionHeader::aad(zero.compression, &header.salt, header.rounds);
+        let json = cipher::from_hex(&header.sealed)
+            .and_then(|sealed| cipher.open(&aad, &sealed).ok())
+            .ok_or(PageError::Tampered(0))?;
+
+        let mut zero: ZeroPage = serde_json::from_slice(&json).map_err(|_| PageError::Corrupt(0))?;
+        zero.encryption = Some(EncryptionHeader {
+            sealed: String::new(),
+            ..header
+        });
+
+        Ok(Some(zero))
+    }
+
+    // The zero page of a database with pages, to be changed and written back. Writes only go ahead
+    // once the zero page has been read
+    fn zero_page_to_change(&self) -> ZeroPage {
+        match self.get_zero_page() {
+            Ok(zero) => zero.expect("database to have a zero page"),
+            Err(e) => panic!("{e}"),
         }
     }
 
-    fn set_zero_page(&mut self, zero: ZeroPage) {
+    fn set_zero_page(&mut self, mut zero: ZeroPage) {
+        zero.encryption = None;
+
+        if let Some(cipher) = &self.cipher {
+            let json = serde_json::to_vec(&zero).unwrap();
+            let salt = cipher::to_hex(cipher.salt());
+            let aad = EncryptionHeader::aad(zero.compression, &salt, cipher.rounds());
+
+            zero = ZeroPage {
+                compression: zero.compression,
+                encryption: Some(EncryptionHeader {
+                    salt,
+                    rounds: cipher.rounds(),
+                    sealed: cipher::to_hex(&cipher.seal(&aad, &json)),
+                }),
+                ..Default::default()
+            };
+        }
+
+        if self.sync_mode == SyncMode::Full && self.transaction.is_none() {
+            // The pages the new zero page refers to must be on disk before it is
+            self.file.sync().unwrap();
+        }
+
         self.encode_and_set(0, zero).unwrap();
     }
 
-    fn file_at_page_readonly(&self, idx: u32) -> File {
-        let path = Path::new(&self.path);
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(false)
-            .open(path)
-            .unwrap();
-        let seek = PAGE_SIZE * idx as u64;
-        file.seek(std::io::SeekFrom::Start(seek)).unwrap();
+    // Space available to the encoded content of a page
+    fn content_size(&self, idx: u32) -> usize {
+        if idx != 0 && self.cipher.is_some() {
+            PAGE_SIZE as usize - cipher::PAGE_OVERHEAD
+        } else {
+            PAGE_SIZE as usize
+        }
+    }
 
-        file
+    // The encoded content of the page, decrypted if needed. The zero page is never encrypted as a whole
+    fn open_content(&self, idx: u32, page: &Page) -> Result<Vec<u8>, PageError> {
+        match &self.cipher {
+            Some(cipher) if idx != 0 => cipher
+                .open_page(idx, &page.content)
+                .map_err(|_| PageError::Tampered(idx)),
+            _ => Ok(page.content.to_vec()),
+        }
     }
 
-    fn file_at_page_write(&mut self, idx: u32) -> File {
-        let path = Path::new(&self.path);
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(path)
-            .unwrap();
-        let seek = PAGE_SIZE * idx as u64;
-        file.seek(std::io::SeekFrom::Start(seek)).unwrap();
+    fn seal_content(&mut self, idx: u32, content: &[u8]) {
+        let mut page = Page::default();
+
+        match &self.cipher {
+            Some(cipher) if idx != 0 => cipher.seal_page(idx, content, &mut page.content),
+            _ => page.content[..content.len()].copy_from_slice(content),
+        }
 
-        file
+        self.set(idx, page);
     }
 
     pub fn get<PageNo: Borrow<u32>>(&self, idx: PageNo) -> Page {
         // println!("Reading page {}", idx.borrow());
+        let idx = *idx.borrow();
+
+        if let Some(transaction) = &self.transaction {
+            if let Some(page) = transaction.dirty.get(&idx) {
+                return page.clone();
+            }
+            if idx >= self.get_committed_size_pages() {
+                // Allocated by the transaction but not written yet
+                return Page::default();
+            }
+        }
+
+        self.get_committed(idx)
+    }
+
+    /// The page as it is in the database file, ignoring the transaction in progress
+    pub fn get_committed(&self, idx: u32) -> Page {
         let mut p = Page::default();
 
         let content = p.content.as_mut_slice();
+        let offset = PAGE_SIZE * idx as u64;
 
-        let mut file = self.file_at_page_readonly(idx.borrow().clone());
-        file.read_exact(content).unwrap();
+        self.file.read_at(offset, content).unwrap();
 
         p
     }
 
+    /// The page decoded, or an error if it has been modified or damaged since it was written
     pub fn get_and_decode<P: Borrow<P> + DeserializeOwned, PageNo: Borrow<u32>>(
         &self,
         idx: PageNo,
-    ) -> P {
-        let p = self.get(idx);
-        let reader = BufReader::new(p.content.as_slice());
-        let mut deserializer = serde_json::Deserializer::from_reader(reader);
-        P::deserialize(&mut deserializer).unwrap()
+    ) -> Result<P, PageError> {
+        let idx = *idx.borrow();
+        let corrupt = |_| PageError::Corrupt(idx);
+
+        if let Some(sqlite) = &self.sqlite {
+            let value = if idx == 0 {
+                let zero = ZeroPage {
+                    root_pages: sqlite.table_root_pages(),
+                    ..Default::default()
+                };
+                serde_json::to_value(zero)
+            } else {
+                let node = sqlite
+                    .read_node(self.file.as_ref(), idx)
+                    .map_err(|_| PageError::Corrupt(idx))?;
+                serde_json::to_value(node)
+            };
+            return serde_json::from_value(value.unwrap()).map_err(corrupt);
+        }
+
+        let content = self.open_content(idx, &self.get(idx))?;
+
+        if idx == 0 || self.compression == Compression::None {
+            let reader = BufReader::new(content.as_slice());
+            let mut deserializer = serde_json::Deserializer::from_reader(reader);
+            return P::deserialize(&mut deserializer).map_err(corrupt);
+        }
+
+        let format = content[0];
+        let length = u32::from_le_bytes(content[1..FRAME_HEADER_SIZE].try_into().unwrap()) as usize;
+        let payload = content
+            .get(FRAME_HEADER_SIZE..FRAME_HEADER_SIZE.saturating_add(length))
+            .ok_or(PageError::Corrupt(idx))?;
+
+        match format {
+            FRAME_STORED => serde_json::from_slice(payload).map_err(corrupt),
+            FRAME_LZ4 => {
+                let json = lz4_flex::decompress_size_prepended(payload).map_err(|_| PageError::Corrupt(idx))?;
+                serde_json::from_slice(&json).map_err(corrupt)
+            }
+            _ => Err(PageError::Corrupt(idx)),
+        }
     }
 
     pub fn set<P: Borrow<Page>, PageNo: Borrow<u32>>(&mut self, idx: PageNo, page: P) {
         // println!("Writing page {}", idx.borrow());
-        let mut file = self.file_at_page_write(idx.borrow().clone());
-        file.write_all(&page.borrow().content).unwrap();
+        let idx = *idx.borrow();
+        assert!(self.sqlite.is_none(), "SQLite databases are read-only");
+
+        match &mut self.transaction {
+            Some(transaction) => {
+                transaction.dirty.insert(idx, page.borrow().clone());
+            }
+            None => self.write_committed(idx, page.borrow()),
+        }
+    }
+
+    fn write_committed(&mut self, idx: u32, page: &Page) {
+        let offset = PAGE_SIZE * idx as u64;
+
+        self.file.write_at(offset, &page.content).unwrap();
+        self.generation += 1;
     }
 
     pub fn encode_and_set<P: Borrow<P> + Serialize, PageNo: Borrow<u32>>(
@@ -156,8 +736,33 @@ impl Pager {
         idx: PageNo,
         v: P,
     ) -> Result<(), EncodingError> {
-        let mut page = Page::default();
-        let result = serde_json::to_writer(page.content.as_mut_slice(), v.borrow());
+        let idx = *idx.borrow();
+        let mut content = vec![0; self.content_size(idx)];
+
+        if idx != 0 && self.compression == Compression::Lz4 {
+            let json = serde_json::to_vec(v.borrow()).unwrap();
+            let compressed = lz4_flex::compress_prepend_size(&json);
+
+            let (format, payload) = if compressed.len() < json.len() {
+                (FRAME_LZ4, compressed)
+            } else {
+                (FRAME_STORED, json)
+            };
+
+            if FRAME_HEADER_SIZE + payload.len() > content.len() {
+                return Err(EncodingError::NotEnoughSpaceInPage);
+            }
+
+            content[0] = format;
+            content[1..FRAME_HEADER_SIZE].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+            content[FRAME_HEADER_SIZE..FRAME_HEADER_SIZE + payload.len()].copy_from_slice(&payload);
+
+            self.seal_content(idx, &content);
+
+            return Ok(());
+        }
+
+        let result = serde_json::to_writer(content.as_mut_slice(), v.borrow());
 
         match result {
             Err(e) => match e.classify() {
@@ -171,7 +776,7 @@ impl Pager {
             _ => {}
         };
 
-        self.set(idx, page);
+        self.seal_content(idx, &content);
 
         Ok(())
     }
@@ -185,14 +790,17 @@ impl Pager {
             self.set_file_size_pages(2);
 
             // Write out new zero page
-            let zero = ZeroPage::default();
+            let zero = ZeroPage {
+                compression: self.compression,
+                ..Default::default()
+            };
             self.set_zero_page(zero);
             // New page is the first page
             1
         } else {
             // We need to find the page allocation table in the first page and get a page from its free list
 
-            let mut zero = self.get_zero_page().unwrap();
+            let mut zero = self.zero_page_to_change();
             let page_no = zero.free_page_list.pop();
 
             self.set_zero_page(zero);
@@ -214,7 +822,7 @@ impl Pager {
             panic!("Cant dealloc page zero");
         }
 
-        let mut zero = self.get_zero_page().unwrap();
+        let mut zero = self.zero_page_to_change();
 
         if zero.free_page_list.contains(&idx) {
             panic!("Free list already contains this page!");
@@ -225,36 +833,56 @@ impl Pager {
         self.set_zero_page(zero);
     }
 
-    pub fn get_root_page(&self, root_name: &str) -> Option<u32> {
-        let zero = self.get_zero_page()?;
+    pub fn get_root_page(&self, root_name: &str) -> Result<Option<u32>, PageError> {
+        let Some(zero) = self.get_zero_page()? else {
+            return Ok(None);
+        };
 
-        zero.root_pages.get(&root_name.to_string()).copied()
+        Ok(zero.root_pages.get(&root_name.to_string()).copied())
     }
 
     pub fn set_root_page(&mut self, root_name: &str, idx: u32) {
-        let mut zero = self.get_zero_page().unwrap();
+        let mut zero = self.zero_page_to_change();
 
         zero.root_pages.insert(root_name.to_string(), idx);
 
         self.set_zero_page(zero);
     }
 
+    /// The largest key ever inserted into the tree, if it is an autoincrement tree
+    pub fn get_sequence(&self, root_name: &str) -> Result<Option<u64>, PageError> {
+        let Some(zero) = self.get_zero_page()? else {
+            return Ok(None);
+        };
+
+        Ok(zero.sequences.get(root_name).copied())
+    }
+
+    pub fn set_sequence(&mut self, root_name: &str, sequence: u64) {
+        let mut zero = self.zero_page_to_change();
+
+        zero.sequences.insert(root_name.to_string(), sequence);
+
+        self.set_zero_page(zero);
+    }
+
     pub fn debug(&self, message: &str) {
         for i in 0..self.get_file_size_pages() {
-            let page: serde_json::Value = self.get_and_decode(i);
-
-            println!("{message}: Page {i} : {page}");
+            match self.get_and_decode::<serde_json::Value, _>(i) {
+                Ok(page) => println!("{message}: Page {i} : {page}"),
+                Err(e) => println!("{message}: Page {i} : {e}"),
+            }
         }
     }
 
-    pub fn get_tree_names(&self) -> Vec<String> {
-        let zp = self.get_zero_page();
+    pub fn get_tree_names(&self) -> Result<Vec<String>, PageError> {
+        let zp = self.get_zero_page()?;
         if zp.is_none() {
-            return vec![];
+            return Ok(vec![]);
         }
         let zp = zp.unwrap();
 
-        zp.root_pages.keys().cloned().collect()
+        Ok(zp.root_pages.keys().cloned().collect())
     }
 }
 
@@ -262,7 +890,29 @@ impl Pager {
 mod test {
     use tempfile::NamedTempFile;
 
-    use super::Pager;
+    use super::{Compression, OpenError, PageError, Pager, PagerOptions, SyncMode};
+    use crate::storage::file::{FileEvent, MemoryFile, PageFile};
+    use crate::storage::journal;
+
+    fn memory_pager(sync_mode: SyncMode) -> (Pager, MemoryFile, MemoryFile) {
+        let file = MemoryFile::named("db");
+        let journal = file.sharing_log("journal");
+        let options = PagerOptions {
+            sync_mode,
+            ..Default::default()
+        };
+
+        let pager = Pager::with_files(Box::new(file.clone()), Box::new(journal.clone()), options).unwrap();
+
+        (pager, file, journal)
+    }
+
+    fn with_passphrase(passphrase: &str) -> PagerOptions {
+        PagerOptions {
+            passphrase: Some(passphrase.to_string()),
+            ..Default::default()
+        }
+    }
 
     #[test]
     fn simple() {
@@ -344,4 +994,491 @@ mod test {
         // more pages allocated
         assert_eq!(max_size + 1, pager.get_file_size_pages());
     }
+
+    #[test]
+    fn compressed_round_trip() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let mut pager = Pager::with_compression(path, Compression::Lz4);
+
+        let idx = pager.allocate();
+
+        // Repetitive content which is too large for a page unless compressed
+        let value = vec!["{\"name\": \"alice\", \"age\": 42}".to_string(); 500];
+        pager.encode_and_set(idx, &value).unwrap();
+
+        let decoded: Vec<String> = pager.get_and_decode(idx).unwrap();
+        assert_eq!(value, decoded);
+
+        // The compression setting is stored in the file, and wins over the one requested
+        let pager = Pager::with_compression(path, Compression::None);
+        assert_eq!(Compression::Lz4, pager.compression());
+
+        let decoded: Vec<String> = pager.get_and_decode(idx).unwrap();
+        assert_eq!(value, decoded);
+    }
+
+    #[test]
+    fn uncompressed_page_too_large() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let mut pager = Pager::new(path);
+        let idx = pager.allocate();
+
+        let value = vec!["{\"name\": \"alice\", \"age\": 42}".to_string(); 500];
+        assert!(pager.encode_and_set(idx, &value).is_err());
+    }
+
+    #[test]
+    fn incompressible_page_stored() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let mut pager = Pager::with_compression(path, Compression::Lz4);
+        let idx = pager.allocate();
+
+        let value: Vec<u64> = (0..10).map(|i| i * 7919 % 1013).collect();
+        pager.encode_and_set(idx, &value).unwrap();
+
+        let decoded: Vec<u64> = pager.get_and_decode(idx).unwrap();
+        assert_eq!(value, decoded);
+    }
+
+    #[test]
+    fn encrypted_round_trip() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let mut pager = Pager::with_options(path, with_passphrase("hunter2")).unwrap();
+        assert!(pager.is_encrypted());
+
+        let idx = pager.allocate();
+        pager.set_root_page("customers", idx);
+        pager.encode_and_set(idx, "top secret customer").unwrap();
+
+        // Neither page content nor tree names are stored in plaintext
+        let raw = std::fs::read(path).unwrap();
+        let raw = String::from_utf8_lossy(&raw);
+        assert!(!raw.contains("top secret"));
+        assert!(!raw.contains("customers"));
+
+        let pager = Pager::with_options(path, with_passphrase("hunter2")).unwrap();
+        let decoded: String = pager.get_and_decode(idx).unwrap();
+        assert_eq!("top secret customer", decoded);
+        assert_eq!(Some(idx), pager.get_root_page("customers").unwrap());
+
+        assert!(matches!(
+            Pager::with_options(path, with_passphrase("hunter3")),
+            Err(OpenError::WrongPassphrase)
+        ));
+        assert!(matches!(
+            Pager::with_options(path, PagerOptions::default()),
+            Err(OpenError::PassphraseRequired)
+        ));
+    }
+
+    #[test]
+    fn ephemeral_key() {
+        let (mut pager, file, _) = memory_pager(SyncMode::Off);
+        pager.use_ephemeral_key();
+        assert!(pager.is_encrypted());
+
+        let idx = pager.allocate();
+        pager.set_root_page("run0", idx);
+        pager.encode_and_set(idx, "top secret customer").unwrap();
+
+        let decoded: String = pager.get_and_decode(idx).unwrap();
+        assert_eq!("top secret customer", decoded);
+
+        let mut raw = vec![0; file.size().unwrap() as usize];
+        file.read_at(0, &mut raw).unwrap();
+        let raw = String::from_utf8_lossy(&raw);
+        assert!(!raw.contains("top secret"));
+        assert!(!raw.contains("run0"));
+    }
+
+    #[test]
+    fn passphrase_for_plaintext_database() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let mut pager = Pager::new(path);
+        pager.allocate();
+
+        assert!(matches!(
+            Pager::with_options(path, with_passphrase("hunter2")),
+            Err(OpenError::NotEncrypted)
+        ));
+    }
+
+    #[test]
+    fn rewritten_pages_use_new_nonce() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let mut pager = Pager::with_options(path, with_passphrase("hunter2")).unwrap();
+        let idx = pager.allocate();
+
+        pager.encode_and_set(idx, "same content").unwrap();
+        let first = pager.get(idx);
+        pager.encode_and_set(idx, "same content").unwrap();
+        let second = pager.get(idx);
+
+        assert_ne!(first.content, second.content);
+    }
+
+    #[test]
+    fn nonces_not_reused_after_recovery() {
+        let file = MemoryFile::named("db");
+        let journal_file = file.sharing_log("journal");
+        let open = || {
+            Pager::with_files(Box::new(file.clone()), Box::new(journal_file.clone()), with_passphrase("hunter2")).unwrap()
+        };
+
+        let mut pager = open();
+        let a = pager.allocate();
+        pager.encode_and_set(a, "original").unwrap();
+        let size = pager.get_file_size_pages();
+
+        // Crash part way through a commit, after page a was rewritten
+        let original = pager.get(a);
+        journal::write(&mut journal_file.clone(), size, &[(a, original.content())], SyncMode::Full);
+        pager.encode_and_set(a, "half written").unwrap();
+        let interrupted = pager.get(a);
+        drop(pager);
+
+        // The page goes back to what it was, writing it again must not use the nonce it had when interrupted
+        let mut pager = open();
+        assert_eq!(original.content(), pager.get(a).content());
+        pager.encode_and_set(a, "half written").unwrap();
+        let rewritten = pager.get(a);
+        assert_ne!(interrupted.content()[..12], rewritten.content()[..12]);
+        assert_ne!(interrupted.content(), rewritten.content());
+    }
+
+    #[test]
+    fn tampered_page_detected() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let mut pager = Pager::with_options(path, with_passphrase("hunter2")).unwrap();
+        let idx = pager.allocate();
+        pager.encode_and_set(idx, "top secret customer").unwrap();
+        pager.set_root_page("customers", idx);
+
+        let mut page = pager.get(idx);
+        page.content[100] ^= 1;
+        pager.set(idx, page);
+
+        assert_eq!(Err(PageError::Tampered(idx)), pager.get_and_decode::<String, _>(idx));
+
+        // The zero page is checked again whenever it is read, not only when the database is opened
+        let mut zero = pager.get(0);
+        let sealed_at = zero.content.windows(10).position(|w| w == b"\"sealed\":\"").unwrap() + 30;
+        zero.content[sealed_at] = if zero.content[sealed_at] == b'0' { b'1' } else { b'0' };
+        pager.set(0, zero);
+
+        assert_eq!(Err(PageError::Tampered(0)), pager.get_root_page("customers"));
+    }
+
+    #[test]
+    fn unreadable_zero_page() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        std::fs::write(path, vec![0xff; super::PAGE_SIZE as usize]).unwrap();
+
+        assert!(matches!(Pager::with_options(path, PagerOptions::default()), Err(OpenError::Corrupt)));
+    }
+
+    #[test]
+    fn tampered_zero_page_detected() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let mut pager = Pager::with_options(path, with_passphrase("hunter2")).unwrap();
+        let idx = pager.allocate();
+        pager.set_root_page("customers", idx);
+        drop(pager);
+
+        let raw = std::fs::read(path).unwrap();
+        let zero = String::from_utf8_lossy(&raw[..super::PAGE_SIZE as usize]).into_owned();
+        let tamper = |from: &str, to: &str| {
+            assert!(zero.contains(from));
+            let mut raw = raw.clone();
+            let zero = zero.replacen(from, to, 1);
+            raw[..zero.len()].copy_from_slice(zero.as_bytes());
+            std::fs::write(path, raw).unwrap();
+
+            Pager::with_options(path, with_passphrase("hunter2"))
+        };
+
+        // Both the plaintext settings and the sealed content are authenticated
+        assert!(matches!(tamper("\"None\"", "\"Lz4\""), Err(OpenError::WrongPassphrase)));
+        assert!(matches!(tamper("\"rounds\":1000", "\"rounds\":1001"), Err(OpenError::WrongPassphrase)));
+        let sealed_at = zero.find("\"sealed\":\"").unwrap() + 40;
+        let flipped = if &zero[sealed_at..sealed_at + 1] == "0" { "1" } else { "0" };
+        assert!(matches!(
+            tamper(&zero[sealed_at - 30..sealed_at + 1], &format!("{}{flipped}", &zero[sealed_at - 30..sealed_at])),
+            Err(OpenError::WrongPassphrase)
+        ));
+
+        std::fs::write(path, &raw).unwrap();
+        let pager = Pager::with_options(path, with_passphrase("hunter2")).unwrap();
+        assert_eq!(Some(idx), pager.get_root_page("customers").unwrap());
+    }
+
+    #[test]
+    fn rekey() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let options = PagerOptions {
+            compression: Compression::Lz4,
+            passphrase: Some("hunter2".to_string()),
+
+            ..Default::default()
+        };
+        let mut pager = Pager::with_options(path, options).unwrap();
+
+        let a = pager.allocate();
+        let b = pager.allocate();
+        pager.encode_and_set(a, "first").unwrap();
+        pager.encode_and_set(b, "second").unwrap();
+        pager.set_root_page("customers", a);
+
+        pager.rekey("correct horse").unwrap();
+
+        assert!(matches!(
+            Pager::with_options(path, with_passphrase("hunter2")),
+            Err(OpenError::WrongPassphrase)
+        ));
+
+        let pager = Pager::with_options(path, with_passphrase("correct horse")).unwrap();
+        assert_eq!(Compression::Lz4, pager.compression());
+        assert_eq!(Some(a), pager.get_root_page("customers").unwrap());
+
+        let decoded: String = pager.get_and_decode(a).unwrap();
+        assert_eq!("first", decoded);
+        let decoded: String = pager.get_and_decode(b).unwrap();
+        assert_eq!("second", decoded);
+    }
+
+    #[test]
+    fn interrupted_rekey_rolled_back_on_open() {
+        let file = MemoryFile::named("db");
+        let journal_file = file.sharing_log("journal");
+        let open = |passphrase| {
+            Pager::with_files(Box::new(file.clone()), Box::new(journal_file.clone()), with_passphrase(passphrase))
+        };
+
+        let mut pager = open("hunter2").unwrap();
+        let pages: Vec<u32> = (0..5).map(|_| pager.allocate()).collect();
+        for idx in &pages {
+            pager.encode_and_set(idx, format!("page {idx}")).unwrap();
+        }
+
+        // Crash once some of the pages have been written with the new key
+        file.crash_after_writes(Some(3));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| pager.rekey("correct horse")));
+        assert!(result.is_err());
+        drop(pager);
+        file.crash_after_writes(None);
+
+        assert!(matches!(open("correct horse"), Err(OpenError::WrongPassphrase)));
+        let pager = open("hunter2").unwrap();
+        for idx in &pages {
+            let decoded: String = pager.get_and_decode(idx).unwrap();
+            assert_eq!(format!("page {idx}"), decoded);
+        }
+    }
+
+    #[test]
+    fn rekey_plaintext_database() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let mut pager = Pager::new(path);
+        pager.allocate();
+
+        assert!(matches!(pager.rekey("hunter2"), Err(OpenError::NotEncrypted)));
+    }
+
+    #[test]
+    fn transaction_rollback() {
+        let (mut pager, file, _) = memory_pager(SyncMode::Normal);
+        let a = pager.allocate();
+        pager.encode_and_set(a, "original").unwrap();
+        let size = pager.get_file_size_pages();
+
+        file.clear_events();
+        pager.begin();
+        pager.encode_and_set(a, "changed").unwrap();
+        let b = pager.allocate();
+        pager.encode_and_set(b, "new").unwrap();
+
+        let decoded: String = pager.get_and_decode(a).unwrap();
+        assert_eq!("changed", decoded);
+        assert_eq!(size + 1, pager.get_file_size_pages());
+
+        pager.rollback();
+
+        // Nothing reached the file
+        assert!(file.all_events().is_empty());
+        let decoded: String = pager.get_and_decode(a).unwrap();
+        assert_eq!("original", decoded);
+        assert_eq!(size, pager.get_file_size_pages());
+    }
+
+    #[test]
+    fn transaction_commit() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let mut pager = Pager::new(path);
+        let a = pager.allocate();
+        pager.encode_and_set(a, "original").unwrap();
+
+        pager.begin();
+        pager.encode_and_set(a, "changed").unwrap();
+        let b = pager.allocate();
+        pager.encode_and_set(b, "new").unwrap();
+        pager.commit();
+
+        let pager = Pager::new(path);
+        let decoded: String = pager.get_and_decode(a).unwrap();
+        assert_eq!("changed", decoded);
+        let decoded: String = pager.get_and_decode(b).unwrap();
+        assert_eq!("new", decoded);
+
+        // The journal only exists while committing
+        assert!(!std::path::Path::new(&format!("{path}-journal")).exists());
+    }
+
+    #[test]
+    fn nested_savepoints() {
+        let (mut pager, _, _) = memory_pager(SyncMode::Normal);
+        let a = pager.allocate();
+        pager.encode_and_set(a, "zero").unwrap();
+
+        pager.begin();
+        pager.encode_and_set(a, "one").unwrap();
+        let outer = pager.savepoint();
+        pager.encode_and_set(a, "two").unwrap();
+        let inner = pager.savepoint();
+        pager.encode_and_set(a, "three").unwrap();
+
+        pager.rollback_to_savepoint(inner);
+        let decoded: String = pager.get_and_decode(a).unwrap();
+        assert_eq!("two", decoded);
+
+        // The savepoint stays open after rolling back to it
+        pager.encode_and_set(a, "four").unwrap();
+        pager.rollback_to_savepoint(inner);
+        let decoded: String = pager.get_and_decode(a).unwrap();
+        assert_eq!("two", decoded);
+
+        pager.release_savepoint(inner);
+        pager.rollback_to_savepoint(outer);
+        let decoded: String = pager.get_and_decode(a).unwrap();
+        assert_eq!("one", decoded);
+
+        pager.release_savepoint(outer);
+        pager.commit();
+        let decoded: String = pager.get_and_decode(a).unwrap();
+        assert_eq!("one", decoded);
+    }
+
+    #[test]
+    fn commit_sync_ordering() {
+        let (mut pager, file, _) = memory_pager(SyncMode::Normal);
+        let a = pager.allocate();
+        pager.encode_and_set(a, "original").unwrap();
+
+        pager.begin();
+        pager.encode_and_set(a, "changed").unwrap();
+        file.clear_events();
+        pager.commit();
+
+        // Collapse runs of the same kind of event on the same file
+        let mut events: Vec<(&str, &str)> = file
+            .all_events()
+            .into_iter()
+            .map(|(name, event)| match event {
+                FileEvent::Write { .. } => (name, "write"),
+                FileEvent::SetLen(_) => (name, "set_len"),
+                FileEvent::Sync => (name, "sync"),
+            })
+            .collect();
+        events.dedup();
+
+        assert_eq!(
+            vec![
+                ("journal", "set_len"),
+                ("journal", "write"),
+                ("journal", "sync"),
+                ("db", "set_len"),
+                ("db", "write"),
+                ("db", "sync"),
+                ("journal", "set_len"),
+                ("journal", "sync"),
+            ],
+            events
+        );
+    }
+
+    #[test]
+    fn interrupted_commit_rolled_back_on_open() {
+        let (mut pager, file, mut journal_file) = memory_pager(SyncMode::Normal);
+        let a = pager.allocate();
+        pager.encode_and_set(a, "original").unwrap();
+        let size = pager.get_file_size_pages();
+
+        // Crash part way through a commit: the journal is written, and so is some of the database
+        let original = pager.get(a);
+        journal::write(&mut journal_file, size, &[(a, original.content())], SyncMode::Full);
+        pager.encode_and_set(a, "half written").unwrap();
+        let b = pager.allocate();
+        pager.encode_and_set(b, "new").unwrap();
+        drop(pager);
+
+        let pager = Pager::with_files(Box::new(file), Box::new(journal_file.clone()), PagerOptions::default()).unwrap();
+        let decoded: String = pager.get_and_decode(a).unwrap();
+        assert_eq!("original", decoded);
+        assert_eq!(size, pager.get_file_size_pages());
+        assert!(journal::read_hot(&journal_file).is_none());
+    }
+
+    #[test]
+    fn torn_journal_ignored_on_open() {
+        let (mut pager, file, mut journal_file) = memory_pager(SyncMode::Normal);
+        let a = pager.allocate();
+        pager.encode_and_set(a, "original").unwrap();
+        let b = pager.allocate();
+        pager.encode_and_set(b, "other").unwrap();
+        let size = pager.get_file_size_pages();
+        let (page_a, page_b) = (pager.get(a), pager.get(b));
+        drop(pager);
+
+        // An entry from an earlier journal, with a valid checksum for that journal
+        journal::write(&mut journal_file, size, &[(a, page_b.content())], SyncMode::Normal);
+        let mut stale = vec![0; journal_file.size().unwrap() as usize - 32];
+        journal_file.read_at(32, &mut stale).unwrap();
+
+        // Crash while writing the journal: the header made it to disk, but not all of the entry did
+        journal::write(&mut journal_file, size, &[(a, page_a.content())], SyncMode::Normal);
+        assert!(journal::read_hot(&journal_file).is_some());
+        journal_file.write_at(32 + 4, &[0; 200]).unwrap();
+        assert!(journal::read_hot(&journal_file).is_none());
+
+        journal_file.write_at(32, &stale).unwrap();
+        assert!(journal::read_hot(&journal_file).is_none());
+
+        // The database was never touched, and isn't now
+        let pager = Pager::with_files(Box::new(file), Box::new(journal_file), PagerOptions::default()).unwrap();
+        let decoded: String = pager.get_and_decode(a).unwrap();
+        assert_eq!("original", decoded);
+        assert_eq!(size, pager.get_file_size_pages());
+    }
 }
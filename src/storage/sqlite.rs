@@ -0,0 +1,648 @@
+use std::collections::HashMap;
+
+use super::cell::Cell;
+use crate::record::{read_varint, Record};
+use crate::scalarvalue::ScalarValue;
+use super::file::PageFile;
+use super::node::{InteriorNodePage, LeafNodePage, NodePage, OverflowPage};
+
+/// Read-only access to a genuine SQLite 3 database file, see https://www.sqlite.org/fileformat.html
+///
+/// Holds what was parsed from the header and `sqlite_schema`, pages are read from the file passed in.
+/// Table b-tree pages are translated into our own `NodePage`s, using the SQLite page numbers as page indexes,
+/// so a `Cursor` can walk the tables unchanged. Every payload is read eagerly (including its overflow pages)
+/// and passed through as is, as our rows use the same record format.
+///
+/// Rowids are used as keys, which can't be negative, so a database with a table holding a negative rowid
+/// is refused. Columns which are an alias for the rowid (`INTEGER PRIMARY KEY`) are stored by SQLite as
+/// NULL, and read back that way.
+/// Names of tables and columns are lowercased, as our parser lowercases the names in a query.
+/// Column types aren't looked at, so a whole number in a REAL column (which SQLite stores as an integer)
+/// is read as an integer. Blobs are passed through, but can't be read as a value.
+/// Index b-trees are keyed by records rather than rowids, so they can't be walked by a `Cursor`, and their
+/// pages appear as opaque overflow pages. For the same reason WITHOUT ROWID tables are left out.
+#[derive(Debug)]
+pub struct SqliteFile {
+    page_size: u32,
+    usable_size: u32,
+    page_count: u32,
+
+    // every table in `sqlite_schema`
+    tables: Vec<SqliteTable>,
+}
+
+#[derive(Debug, Clone)]
+struct SqliteTable {
+    name: String,
+    root_page: u32,
+    columns: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum SqliteError {
+    NotASqliteFile,
+    Unsupported(String),
+    Corrupt(String),
+}
+
+impl std::fmt::Display for SqliteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SqliteError::NotASqliteFile => write!(f, "not a SQLite database"),
+            SqliteError::Unsupported(what) => write!(f, "unsupported SQLite feature: {what}"),
+            SqliteError::Corrupt(what) => write!(f, "corrupt SQLite database: {what}"),
+        }
+    }
+}
+
+pub const MAGIC: &[u8; 16] = b"SQLite format 3\0";
+const HEADER_SIZE: usize = 100;
+
+const INTERIOR_INDEX: u8 = 2;
+const INTERIOR_TABLE: u8 = 5;
+const LEAF_INDEX: u8 = 10;
+const LEAF_TABLE: u8 = 13;
+
+/// The cells of a b-tree page, in the order they appear
+enum BTreePage {
+    TableLeaf(Vec<(i64, Vec<u8>)>),
+    /// (left child, largest rowid in the left child), then the right-most child
+    TableInterior(Vec<(u32, i64)>, u32),
+    /// Either kind of index page, whose cells aren't read
+    Index,
+}
+
+/// Does the file start with the SQLite header
+pub fn is_sqlite_file(file: &dyn PageFile) -> bool {
+    let mut magic = [0; MAGIC.len()];
+    file.read_at(0, &mut magic).is_ok() && &magic == MAGIC
+}
+
+impl SqliteFile {
+    pub fn open(file: &dyn PageFile) -> Result<SqliteFile, SqliteError> {
+        let mut header = [0; HEADER_SIZE];
+        file.read_at(0, &mut header)
+            .map_err(|_| SqliteError::NotASqliteFile)?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(SqliteError::NotASqliteFile);
+        }
+
+        let page_size = match u16::from_be_bytes([header[16], header[17]]) {
+            1 => 65536,
+            size if size >= 512 && size.is_power_of_two() => size as u32,
+            size => return Err(SqliteError::Corrupt(format!("page size {size}"))),
+        };
+
+        // Write version 2 is WAL mode, whose most recent pages may not be in the main file
+        if header[18] != 1 || header[19] != 1 {
+            return Err(SqliteError::Unsupported(
+                "WAL mode databases, checkpoint and switch to journal_mode=delete first".to_string(),
+            ));
+        }
+
+        let text_encoding = u32::from_be_bytes(header[56..60].try_into().unwrap());
+        if text_encoding > 1 {
+            return Err(SqliteError::Unsupported("UTF-16 text encoding".to_string()));
+        }
+
+        let usable_size = page_size - header[20] as u32;
+
+        // The in-header page count is only valid if the change counter matches the version-valid-for number
+        let page_count = if header[24..28] == header[92..96] {
+            u32::from_be_bytes(header[28..32].try_into().unwrap())
+        } else {
+            (file.size().map_err(|e| SqliteError::Corrupt(e.to_string()))? / page_size as u64) as u32
+        };
+
+        let mut sqlite = SqliteFile {
+            page_size,
+            usable_size,
+            page_count,
+            tables: vec![],
+        };
+        sqlite.tables = sqlite.read_schema(file)?;
+
+        for table in &sqlite.tables {
+            if sqlite.smallest_rowid(file, table.root_page)?.is_some_and(|rowid| rowid < 0) {
+                return Err(negative_rowids(&table.name));
+            }
+        }
+
+        Ok(sqlite)
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.page_count
+    }
+
+    /// Root pages of every table, including `sqlite_schema` itself
+    pub fn table_root_pages(&self) -> HashMap<String, u32> {
+        let mut roots: HashMap<String, u32> =
+            self.tables.iter().map(|table| (table.name.clone(), table.root_page)).collect();
+        roots.insert("sqlite_schema".to_string(), 1);
+
+        roots
+    }
+
+    /// The name and column names of every table, in the order of `sqlite_schema`, which comes last
+    pub fn table_columns(&self) -> Vec<(String, Vec<String>)> {
+        let schema_columns = ["type", "name", "tbl_name", "rootpage", "sql"];
+
+        self.tables
+            .iter()
+            .map(|table| (table.name.clone(), table.columns.clone()))
+            .chain([(
+                "sqlite_schema".to_string(),
+                schema_columns.iter().map(|column| column.to_string()).collect(),
+            )])
+            .collect()
+    }
+
+    /// The page translated into the node our btree would have stored
+    pub fn read_node(&self, file: &dyn PageFile, page_no: u32) -> Result<NodePage, SqliteError> {
+        let node = match self.read_btree_page(file, page_no)? {
+            Some(BTreePage::TableLeaf(rows)) => {
+                let mut leaf = LeafNodePage::default();
+                for (idx, (rowid, payload)) in rows.into_iter().enumerate() {
+                    leaf.insert_item_at_index(idx, Cell::new(key(rowid)?, payload, None));
+                }
+                NodePage::Leaf(leaf)
+            }
+            Some(BTreePage::TableInterior(cells, right_most)) => {
+                // Our interior keys are the smallest key of the child to their right,
+                // SQLite's are the largest key of the child to their left
+                let mut edges = cells.iter().map(|(child, _)| *child);
+                let mut interior = match (edges.next(), cells.first()) {
+                    (Some(first), Some((_, rowid))) => {
+                        let second = cells.get(1).map_or(right_most, |(child, _)| *child);
+                        InteriorNodePage::new(first, key(*rowid)? + 1, second)
+                    }
+                    _ => return Err(SqliteError::Corrupt(format!("interior page {page_no} has no cells"))),
+                };
+                for (i, (_, rowid)) in cells.iter().enumerate().skip(1) {
+                    let child = cells.get(i + 1).map_or(right_most, |(child, _)| *child);
+                    interior.insert_child_page(key(*rowid)? + 1, child);
+                }
+                NodePage::Interior(interior)
+            }
+            // Not part of a table b-tree
+            Some(BTreePage::Index) | None => {
+                NodePage::OverflowPage(OverflowPage::new(vec![], None))
+            }
+        };
+
+        Ok(node)
+    }
+
+    fn read_page(&self, file: &dyn PageFile, page_no: u32) -> Result<Vec<u8>, SqliteError> {
+        if page_no == 0 || page_no > self.page_count {
+            return Err(SqliteError::Corrupt(format!("page {page_no} out of range")));
+        }
+
+        let mut page = vec![0; self.page_size as usize];
+        file.read_at((page_no - 1) as u64 * self.page_size as u64, &mut page)
+            .map_err(|e| SqliteError::Corrupt(format!("reading page {page_no}: {e}")))?;
+
+        Ok(page)
+    }
+
+    fn read_btree_page(&self, file: &dyn PageFile, page_no: u32) -> Result<Option<BTreePage>, SqliteError> {
+        let page = self.read_page(file, page_no)?;
+        let corrupt = || SqliteError::Corrupt(format!("b-tree page {page_no}"));
+
+        // The first page also holds the database header
+        let header_start = if page_no == 1 { HEADER_SIZE } else { 0 };
+        let header = &page[header_start..];
+
+        let page_type = header[0];
+        let cell_count = u16::from_be_bytes([header[3], header[4]]) as usize;
+        let (right_most, pointers_start) = match page_type {
+            INTERIOR_INDEX | LEAF_INDEX => return Ok(Some(BTreePage::Index)),
+            INTERIOR_TABLE => (
+                u32::from_be_bytes(header[8..12].try_into().unwrap()),
+                header_start + 12,
+            ),
+            LEAF_TABLE => (0, header_start + 8),
+            _ => return Ok(None),
+        };
+
+        let cell_offsets = (0..cell_count).map(|i| {
+            let pointer = pointers_start + i * 2;
+            page.get(pointer..pointer + 2)
+                .map(|p| u16::from_be_bytes([p[0], p[1]]) as usize)
+                .ok_or_else(corrupt)
+        });
+
+        let mut table_leaf = vec![];
+        let mut table_interior = vec![];
+
+        for offset in cell_offsets {
+            let cell = page.get(offset?..).ok_or_else(corrupt)?;
+
+            match page_type {
+                LEAF_TABLE => {
+                    let (payload_size, n) = read_varint(cell).ok_or_else(corrupt)?;
+                    let (rowid, m) = read_varint(&cell[n..]).ok_or_else(corrupt)?;
+                    let payload = self.read_payload(file, &cell[n + m..], payload_size, self.max_local_table())?;
+                    table_leaf.push((rowid as i64, payload));
+                }
+                _ => {
+                    let child = u32::from_be_bytes(cell.get(..4).ok_or_else(corrupt)?.try_into().unwrap());
+                    let (rowid, _) = read_varint(&cell[4..]).ok_or_else(corrupt)?;
+                    table_interior.push((child, rowid as i64));
+                }
+            }
+        }
+
+        Ok(Some(match page_type {
+            LEAF_TABLE => BTreePage::TableLeaf(table_leaf),
+            _ => BTreePage::TableInterior(table_interior, right_most),
+        }))
+    }
+
+    fn max_local_table(&self) -> usize {
+        self.usable_size as usize - 35
+    }
+
+    /// The whole payload of a cell starting at `cell`, following the overflow chain if it didn't fit
+    fn read_payload(&self, file: &dyn PageFile, cell: &[u8], payload_size: u64, max_local: usize) -> Result<Vec<u8>, SqliteError> {
+        let payload_size = payload_size as usize;
+        let corrupt = || SqliteError::Corrupt("cell payload".to_string());
+
+        if payload_size <= max_local {
+            return Ok(cell.get(..payload_size).ok_or_else(corrupt)?.to_vec());
+        }
+
+        let usable = self.usable_size as usize;
+        let min_local = ((usable - 12) * 32 / 255) - 23;
+        let k = min_local + ((payload_size - min_local) % (usable - 4));
+        let local = if k <= max_local { k } else { min_local };
+
+        let mut payload = cell.get(..local).ok_or_else(corrupt)?.to_vec();
+        let next = cell.get(local..local + 4).ok_or_else(corrupt)?;
+        let mut next = u32::from_be_bytes(next.try_into().unwrap());
+
+        while payload.len() < payload_size {
+            if next == 0 {
+                return Err(corrupt());
+            }
+
+            let page = self.read_page(file, next)?;
+            next = u32::from_be_bytes(page[..4].try_into().unwrap());
+
+            let wanted = (payload_size - payload.len()).min(usable - 4);
+            payload.extend_from_slice(&page[4..4 + wanted]);
+        }
+
+        Ok(payload)
+    }
+
+    /// Every row of the table b-tree rooted at `root_page`, in rowid order
+    fn read_table(&self, file: &dyn PageFile, root_page: u32) -> Result<Vec<(i64, Vec<u8>)>, SqliteError> {
+        let mut rows = vec![];
+        let mut pages = vec![root_page];
+
+        // Children are pushed in reverse, so the left-most is visited first
+        while let Some(page_no) = pages.pop() {
+            match self.read_btree_page(file, page_no)? {
+                Some(BTreePage::TableLeaf(mut page_rows)) => rows.append(&mut page_rows),
+                Some(BTreePage::TableInterior(cells, right_most)) => {
+                    pages.push(right_most);
+                    pages.extend(cells.iter().rev().map(|(child, _)| *child));
+                }
+                _ => return Err(SqliteError::Corrupt(format!("page {page_no} is not a table page"))),
+            }
+        }
+
+        Ok(rows)
+    }
+
+    /// The rowid of the first row of the table b-tree rooted at `root_page`, None if it is empty
+    fn smallest_rowid(&self, file: &dyn PageFile, root_page: u32) -> Result<Option<i64>, SqliteError> {
+        let mut page_no = root_page;
+
+        loop {
+            match self.read_btree_page(file, page_no)? {
+                Some(BTreePage::TableLeaf(rows)) => return Ok(rows.first().map(|(rowid, _)| *rowid)),
+                Some(BTreePage::TableInterior(cells, right_most)) => {
+                    page_no = cells.first().map_or(right_most, |(child, _)| *child);
+                }
+                _ => return Err(SqliteError::Corrupt(format!("page {page_no} is not a table page"))),
+            }
+        }
+    }
+
+    // Rows of sqlite_schema are (type, name, tbl_name, rootpage, sql)
+    fn read_schema(&self, file: &dyn PageFile) -> Result<Vec<SqliteTable>, SqliteError> {
+        let corrupt = || SqliteError::Corrupt("sqlite_schema".to_string());
+        let mut tables = vec![];
+
+        for (_, payload) in self.read_table(file, 1)? {
+            let record = Record::parse(&payload).map_err(|_| corrupt())?;
+
+            match (record.column(0), record.column(1), record.column(3), record.column(4)) {
+                (Ok(ScalarValue::String(tipe)), _, _, _) if tipe != "table" => {}
+                // Virtual tables have no b-tree, and WITHOUT ROWID tables are stored in an index b-tree
+                (_, _, Ok(ScalarValue::Integer(0)), _) => {}
+                (_, _, _, Ok(ScalarValue::String(sql))) if without_rowid(&sql) => {}
+                (
+                    Ok(_),
+                    Ok(ScalarValue::String(name)),
+                    Ok(ScalarValue::Integer(root_page)),
+                    Ok(ScalarValue::String(sql)),
+                ) => {
+                    tables.push(SqliteTable {
+                        name: name.to_lowercase(),
+                        root_page: root_page as u32,
+                        columns: column_names(&sql).ok_or_else(corrupt)?,
+                    });
+                }
+                _ => return Err(corrupt()),
+            }
+        }
+
+        Ok(tables)
+    }
+}
+
+/// The key of a row in our btree, which is unsigned
+fn key(rowid: i64) -> Result<u64, SqliteError> {
+    u64::try_from(rowid).map_err(|_| negative_rowids("a table"))
+}
+
+fn negative_rowids(table: &str) -> SqliteError {
+    SqliteError::Unsupported(format!("negative rowids, which {table} has"))
+}
+
+fn without_rowid(sql: &str) -> bool {
+    sql.rfind(')')
+        .is_some_and(|end| sql[end..].to_lowercase().contains("without"))
+}
+
+/// The names of the columns a `CREATE TABLE` statement defines, skipping its table constraints.
+/// This only looks at the first word of each definition, so it accepts anything SQLite does.
+fn column_names(sql: &str) -> Option<Vec<String>> {
+    let start = sql.find('(')? + 1;
+
+    // Split the definitions at the commas which aren't inside brackets or quotes
+    let mut definitions = vec![];
+    let mut depth = 0;
+    let mut quote = None;
+    let mut definition_start = start;
+    for (i, c) in sql[start..].char_indices().map(|(i, c)| (i + start, c)) {
+        match (quote, c) {
+            (Some(close), c) if c == close => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '[') => quote = Some(']'),
+            (None, '(') => depth += 1,
+            (None, ')') if depth == 0 => {
+                definitions.push(&sql[definition_start..i]);
+                break;
+            }
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                definitions.push(&sql[definition_start..i]);
+                definition_start = i + 1;
+            }
+            (None, _) => {}
+        }
+    }
+
+    let constraints = ["constraint", "primary", "unique", "check", "foreign"];
+    let mut columns = vec![];
+    for definition in definitions {
+        let definition = definition.trim_start();
+        let name = match definition.chars().next()? {
+            open @ ('"' | '`' | '[') => {
+                let close = if open == '[' { ']' } else { open };
+                let rest = &definition[1..];
+                &rest[..rest.find(close)?]
+            }
+            _ => {
+                let end = definition
+                    .find(|c: char| c.is_whitespace() || c == '(')
+                    .unwrap_or(definition.len());
+                let word = &definition[..end];
+                if constraints.contains(&word.to_lowercase().as_str()) {
+                    continue;
+                }
+                word
+            }
+        };
+        columns.push(name.to_lowercase());
+    }
+
+    Some(columns)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use crate::engine::Engine;
+    use crate::record::Record;
+    use crate::scalarvalue::ScalarValue;
+    use crate::storage::file::MemoryFile;
+    use crate::storage::pager::OpenError;
+    use crate::storage::{BTree, PageFile, PagerOptions};
+
+    use super::SqliteError;
+
+    // Made with sqlite 3.40 using a page size of 512, so there are interior and overflow pages:
+    //   create table people(id integer primary key, name text, age integer, score real, data blob)
+    //   create index people_name on people(name)
+    //   create table notes(body text)
+    // with people 1..=500 ("person {i}", i % 90 - 5 or NULL every 7th, i / 4, [i % 256, 1, 2])
+    // and notes "x" * 3000 and "short"
+    const PEOPLE: &[u8] = include_bytes!("testdata/people.sqlite");
+
+    fn open_people() -> BTree {
+        let mut file = MemoryFile::default();
+        file.write_at(0, PEOPLE).unwrap();
+
+        BTree::with_files(Box::new(file), Box::new(MemoryFile::default()), PagerOptions::default()).unwrap()
+    }
+
+    fn read_rows(btree: &BTree, table: &str) -> Vec<(u64, Vec<u8>)> {
+        let mut cursor_handle = btree.open(table).unwrap();
+        let mut cursor = cursor_handle.open_readonly();
+        let mut rows = vec![];
+
+        cursor.first().unwrap();
+        while let Some(mut entry) = cursor.get_entry().unwrap() {
+            let mut buf = vec![];
+            entry.read_to_end(&mut buf).unwrap();
+            rows.push((entry.key(), buf));
+            cursor.next().unwrap();
+        }
+
+        rows
+    }
+
+    // The first `n` columns of the row
+    fn columns(row: &[u8], n: usize) -> Vec<ScalarValue> {
+        let record = Record::parse(row).unwrap();
+
+        (0..n).map(|idx| record.column(idx).unwrap()).collect()
+    }
+
+    fn text(s: &str) -> ScalarValue {
+        ScalarValue::String(s.to_string())
+    }
+
+    #[test]
+    fn read_tables() {
+        let btree = open_people();
+
+        let people = read_rows(&btree, "people");
+        assert_eq!(500, people.len());
+        assert_eq!((1..=500).collect::<Vec<u64>>(), people.iter().map(|(k, _)| *k).collect::<Vec<_>>());
+        // The rowid alias is stored as NULL
+        assert_eq!(
+            vec![ScalarValue::Null, text("person 3"), ScalarValue::Integer(-2), ScalarValue::Floating(0.75)],
+            columns(&people[2].1, 4)
+        );
+        assert_eq!(
+            vec![ScalarValue::Null, text("person 7"), ScalarValue::Null, ScalarValue::Floating(1.75)],
+            columns(&people[6].1, 4)
+        );
+        // SQLite stores whole REAL values as integers
+        assert_eq!(
+            vec![ScalarValue::Null, text("person 500"), ScalarValue::Integer(45), ScalarValue::Integer(125)],
+            columns(&people[499].1, 4)
+        );
+        let record = Record::parse(&people[299].1).unwrap();
+        assert_eq!(ScalarValue::Blob(vec![44, 1, 2]), record.column(4).unwrap());
+
+        let notes = read_rows(&btree, "notes");
+        assert_eq!(vec![text(&"x".repeat(3000))], columns(&notes[0].1, 1));
+        assert_eq!(vec![text("short")], columns(&notes[1].1, 1));
+
+        let schema = read_rows(&btree, "sqlite_schema");
+        let names: Vec<_> = schema.iter().map(|(_, row)| columns(row, 2)[1].clone()).collect();
+        assert_eq!(vec![text("people"), text("notes"), text("people_name")], names);
+
+        btree.verify().unwrap();
+        assert!(format!("{btree}").contains("digraph"));
+    }
+
+    #[test]
+    fn find_by_rowid() {
+        let btree = open_people();
+        let mut cursor_handle = btree.open("people").unwrap();
+        let mut cursor = cursor_handle.open_readonly();
+
+        for rowid in [1, 123, 256, 500] {
+            cursor.find(rowid).unwrap();
+            let mut entry = cursor.get_entry().unwrap().unwrap();
+            let mut buf = vec![];
+            entry.read_to_end(&mut buf).unwrap();
+
+            assert_eq!(rowid, entry.key());
+            assert_eq!(text(&format!("person {rowid}")), columns(&buf, 2)[1]);
+        }
+    }
+
+    #[test]
+    fn sql_queries() {
+        let btree = open_people();
+        let schema = crate::catalog::load(&btree).unwrap();
+        let query = |sql| {
+            let plan = crate::planner::plan(crate::frontend::parse(sql).unwrap(), &schema).unwrap();
+            let program = crate::compiler::compile(&plan);
+            Engine::with_program(program.operations(), program.num_registers(), btree.clone())
+                .run()
+                .unwrap()
+        };
+
+        assert_eq!(
+            vec![vec![text("person 3"), ScalarValue::Integer(-2)]],
+            query("select name, age from people where rowid = 3")
+        );
+        assert_eq!(vec![vec![ScalarValue::Integer(500)]], query("select count(*) from people where id is null"));
+        assert_eq!(vec![vec![text("short")]], query("select body from notes where rowid = 2"));
+        assert_eq!(
+            vec![vec![ScalarValue::Blob(vec![3, 1, 2])], vec![ScalarValue::Blob(vec![0, 1, 2])]],
+            query("select data from people where rowid = 3 or rowid = 256")
+        );
+        assert_eq!(
+            vec![vec![text("people")], vec![text("notes")]],
+            query("select name from sqlite_schema where type = 'table'")
+        );
+    }
+
+    #[test]
+    fn column_names() {
+        let names = |sql| super::column_names(sql).unwrap();
+
+        assert_eq!(
+            vec!["id", "name", "age", "score", "data"],
+            names("create table people(id integer primary key, name text, age integer, score real, data blob)")
+        );
+        assert_eq!(
+            vec!["a", "b c", "price", "d"],
+            names(
+                "CREATE TABLE t (A INT NOT NULL DEFAULT (1, 2), \"b c\" TEXT CHECK (x IN ('a,b', ')')), \
+                 [Price] DECIMAL(10, 2), `d`, PRIMARY KEY (A, [Price]), CONSTRAINT u UNIQUE (d))"
+            )
+        );
+        assert!(super::without_rowid("CREATE TABLE t (a PRIMARY KEY) WITHOUT ROWID"));
+        assert!(!super::without_rowid("CREATE TABLE t (without_rowid)"));
+    }
+
+    #[test]
+    fn negative_rowids_refused() {
+        // Made with sqlite 3.40:
+        //   create table t(a)
+        //   insert into t(rowid, a) values (-1, 'minus one'), (1, 'one'), (2, 'two')
+        let mut file = MemoryFile::default();
+        file.write_at(0, include_bytes!("testdata/negative.sqlite")).unwrap();
+
+        let result = BTree::with_files(Box::new(file), Box::new(MemoryFile::default()), PagerOptions::default());
+        assert!(matches!(
+            result,
+            Err(OpenError::Sqlite(SqliteError::Unsupported(what))) if what.contains("negative rowids, which t has")
+        ));
+    }
+
+    #[test]
+    fn sql_writes_rejected() {
+        use crate::catalog::{self, CatalogError};
+        use crate::engine::ErrorKind;
+        use crate::frontend::{ast, parse};
+
+        let mut btree = open_people();
+        let mut schema = catalog::load(&btree).unwrap();
+
+        let ast::Statement::CreateTable(create) = parse("create table x (a)").unwrap() else {
+            panic!("expected a CREATE TABLE");
+        };
+        assert_eq!(Err(CatalogError::ReadOnly), catalog::create_table(&mut btree, &mut schema, &create));
+        assert!(schema.get_table("x").is_none());
+
+        // Deleting from people reads its blobs before getting as far as a write
+        for sql in [
+            "insert into notes values ('more')",
+            "delete from notes",
+            "update notes set body = 'new'",
+            "delete from people where age > 10",
+        ] {
+            let plan = crate::planner::plan(parse(sql).unwrap(), &schema).unwrap();
+            let program = crate::compiler::compile(&plan);
+            let error = Engine::with_program(program.operations(), program.num_registers(), btree.clone())
+                .run()
+                .unwrap_err();
+            assert!(matches!(error.kind, ErrorKind::ReadOnly(_)), "{sql}: {error}");
+        }
+        assert_eq!(2, read_rows(&btree, "notes").len());
+    }
+
+    #[test]
+    #[should_panic(expected = "SQLite databases are read-only")]
+    fn writes_rejected() {
+        let mut btree = open_people();
+
+        btree.create_tree("testing");
+    }
+}
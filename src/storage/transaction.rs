@@ -0,0 +1,65 @@
+use std::{cell::RefCell, sync::Arc};
+
+use super::pager::Pager;
+
+/// Groups every change made through a `BTree`, across any number of trees, so they are written out together.
+///
+/// Until `commit` the changes only exist in memory, `rollback` (or dropping the transaction) throws them away.
+/// Cursors positioned before a rollback may point at pages which no longer exist and should be repositioned.
+#[derive(Debug)]
+pub struct Transaction {
+    pager: Arc<RefCell<Pager>>,
+    finished: bool,
+}
+
+/// A point in a transaction which can be rolled back to, without abandoning the whole transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Savepoint {
+    depth: usize,
+}
+
+impl Transaction {
+    pub(super) fn begin(pager: Arc<RefCell<Pager>>) -> Transaction {
+        pager.borrow_mut().begin();
+
+        Transaction {
+            pager,
+            finished: false,
+        }
+    }
+
+    /// Savepoints nest, releasing or rolling back to one also releases every savepoint made after it
+    pub fn savepoint(&mut self) -> Savepoint {
+        let depth = self.pager.borrow_mut().savepoint();
+
+        Savepoint { depth }
+    }
+
+    /// Keep the changes made since the savepoint, as part of the enclosing savepoint or transaction
+    pub fn release(&mut self, savepoint: Savepoint) {
+        self.pager.borrow_mut().release_savepoint(savepoint.depth);
+    }
+
+    /// Undo the changes made since the savepoint, which stays open
+    pub fn rollback_to(&mut self, savepoint: Savepoint) {
+        self.pager.borrow_mut().rollback_to_savepoint(savepoint.depth);
+    }
+
+    pub fn commit(mut self) {
+        self.finished = true;
+        self.pager.borrow_mut().commit();
+    }
+
+    pub fn rollback(mut self) {
+        self.finished = true;
+        self.pager.borrow_mut().rollback();
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.pager.borrow_mut().rollback();
+        }
+    }
+}
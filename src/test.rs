@@ -1,9 +1,20 @@
 use tempfile::NamedTempFile;
 
-use crate::storage::BTree;
+use crate::{
+    compiler::{compile, CompiledProgram},
+    engine::{sorter, Engine, EngineError},
+    frontend::parse,
+    planner::{plan, schema},
+    record,
+    scalarvalue::ScalarValue,
+    storage::BTree,
+};
 
 pub struct TestDb {
     pub btree: BTree,
+    /// Bytes of rows a query's sorters hold in memory, lower it to make them spill
+    pub sort_budget: usize,
+    schema: schema::Schema,
     _file: NamedTempFile,
 }
 
@@ -13,7 +24,99 @@ impl Default for TestDb {
         let path = file.path().to_str().unwrap();
         Self {
             btree: BTree::new(path),
+            sort_budget: sorter::DEFAULT_BUDGET,
+            schema: schema::Schema { tables: vec![] },
             _file: file,
         }
     }
 }
+
+impl TestDb {
+    /// Users, and the orders some of them made. One order has no user
+    pub fn users_and_orders() -> TestDb {
+        let mut db = TestDb::default();
+        db.create_table("users", &["id", "name"], &[vec![i(1), s("ann")], vec![i(2), s("bob")], vec![i(3), s("cid")]]);
+        db.create_table(
+            "orders",
+            &["id", "user_id", "total"],
+            &[
+                vec![i(10), i(2), i(5)],
+                vec![i(11), i(1), i(7)],
+                vec![i(12), i(2), i(9)],
+                vec![i(13), ScalarValue::Null, i(1)],
+            ],
+        );
+        db
+    }
+
+    /// A table `t` of values `v` in groups `g`, with NULLs among both
+    pub fn groups() -> TestDb {
+        let mut db = TestDb::default();
+        db.create_table(
+            "t",
+            &["g", "v"],
+            &[
+                vec![s("b"), i(10)],
+                vec![s("a"), i(1)],
+                vec![ScalarValue::Null, i(7)],
+                vec![s("b"), ScalarValue::Null],
+                vec![s("a"), i(4)],
+                vec![ScalarValue::Null, i(3)],
+                vec![s("b"), i(20)],
+            ],
+        );
+        db
+    }
+
+    /// Create a table that queries can read, each row is keyed by its position
+    pub fn create_table(&mut self, name: &str, columns: &[&str], rows: &[Vec<ScalarValue>]) {
+        self.btree.create_tree(name);
+
+        let mut cursor = self.btree.open(name).unwrap();
+        let mut cursor = cursor.open_readwrite();
+        for (key, row) in rows.iter().enumerate() {
+            cursor.insert(key as u64, record::encode(row));
+        }
+
+        self.schema.tables.push(schema::Table {
+            name: name.to_string(),
+            columns: columns
+                .iter()
+                .map(|name| schema::Column { name: name.to_string() })
+                .collect(),
+        });
+    }
+
+    /// Parse, plan and compile a query against the tables made with `create_table`
+    pub fn compile(&self, sql: &str) -> CompiledProgram {
+        let statement = parse(sql).unwrap();
+        let plan = plan(statement, &self.schema).unwrap();
+        compile(&plan)
+    }
+
+    /// Parse, plan, compile and run a query against the tables made with `create_table`
+    pub fn query(&self, sql: &str) -> Result<Vec<Vec<ScalarValue>>, EngineError> {
+        let program = self.compile(sql);
+
+        let mut engine = Engine::with_program(program.operations(), program.num_registers(), self.btree.clone());
+        engine.set_sort_budget(self.sort_budget);
+        engine.run()
+    }
+}
+
+// Shorthands for the values test rows are made of
+pub fn i(value: i64) -> ScalarValue {
+    ScalarValue::Integer(value)
+}
+
+pub fn f(value: f64) -> ScalarValue {
+    ScalarValue::Floating(value)
+}
+
+pub fn s(value: &str) -> ScalarValue {
+    ScalarValue::String(value.to_string())
+}
+
+pub fn b(value: bool) -> ScalarValue {
+    ScalarValue::Boolean(value)
+}